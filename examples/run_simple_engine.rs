@@ -1,6 +1,10 @@
 use sett_rs::{
     chx, fluid, hhx, regen,
-    types::{ConvergenceTolerance, LoopTolerance, MaxIters, OdeTolerance, RunInputs, RunSettings},
+    types::{
+        Budget, ConvergenceTolerance, Discretization, LoopTolerance, MaxIters, OdeTolerance,
+        OutputGrid, PressureModel, PropertyEvaluation, RunInputs, RunSettings, SteadyStateMethod,
+        DEFAULT_MOTION_TOL,
+    },
     ws::{self, sinusoidal_drive::Geometry, Parasitics, ThermalResistance},
     Components, Engine, LuSolver,
 };
@@ -25,10 +29,13 @@ fn main() {
                 },
                 thermal_resistance: ThermalResistance::default(),
                 parasitics: Parasitics::default(),
+                appendix_gap: None,
+                wall_heat: None,
             }),
             chx: Box::<chx::FixedApproach>::default(),
             regen: Box::<regen::FixedApproach>::default(),
             hhx: Box::<hhx::FixedApproach>::default(),
+            buffer: None,
         };
         let fluid = fluid::IdealGas::hydrogen();
         let inputs = RunInputs {
@@ -38,15 +45,21 @@ fn main() {
         };
         let settings = RunSettings {
             resolution: 30,
+            grid: OutputGrid::Uniform,
+            discretization: Discretization::default(),
+            pressure_model: PressureModel::Uniform,
+            properties: PropertyEvaluation::Tabulated,
             loop_tol: LoopTolerance {
                 inner: ConvergenceTolerance {
                     abs: 1e-2,
                     rel: 1e-4,
                 },
+                motion: DEFAULT_MOTION_TOL,
                 outer: ConvergenceTolerance {
                     abs: 1e-2,
                     rel: 1e-4,
-                },
+                }
+                .into(),
             },
             ode_tol: OdeTolerance {
                 abs: 1e-6,
@@ -56,9 +69,12 @@ fn main() {
                 inner: 20,
                 outer: 20,
             },
+            steady_state: SteadyStateMethod::TimeMarching,
+            budget: Budget::default(),
+            cancel: None,
         };
 
-        let engine = Engine::run::<LuSolver>(components, fluid, inputs, settings)
+        let engine = Engine::run::<LuSolver>(components, fluid, inputs, settings.clone())
             .expect("engine should converge");
 
         println!("-----------------");
@@ -1,17 +1,32 @@
+mod bracket;
+mod operating_point;
+mod sensitivity;
+mod target;
+
 use crate::{
     config::Config,
-    fluid::{self, Fluid},
-    performance::Performance,
-    state_equations::LuSolver,
-    types::RunError,
-    Engine,
+    engine::{Components, ComponentsConfig, RunOptions, StateSnapshot},
+    fluid::{self, Fluid, IdealGas},
+    performance::{CrankLoads, Performance},
+    types::{
+        ConvergenceReport, MetalTemperatures, RunError, RunInputs, RunSettings, SolverConfig,
+        SolverStats, ThermalMasses,
+    },
+    ws, Engine, Transient,
+};
+
+pub use operating_point::{find_operating_point, LoadCurve, OperatingPoint, OperatingPointError};
+pub use sensitivity::{
+    sensitivities, Metrics, Parameter, Sensitivities, Sensitivity, SensitivityError, Step,
 };
+pub use target::{find_target, Goal, TargetError, Targeted, Variable};
 
 /// The main interface for running an engine
 ///
 /// # Errors
 ///
-/// Will return `Err` if the inner loop or outer loop convergence fails.
+/// Will return `Err` if the config is invalid or if the inner loop or outer
+/// loop convergence fails.
 ///
 /// # Panics
 ///
@@ -19,7 +34,105 @@ use crate::{
 /// github.com/ isentropic-dev/sett-rs/issues/63> is resolved, the function
 /// will no longer panic.
 pub fn run_engine(config: Config) -> Result<RunResults, RunError> {
-    let fluid = match config.engine.fluid {
+    let engine = start(config, None)?;
+    Ok(RunResults::from(engine))
+}
+
+/// Run the engine described by `config` through consecutive cycles while the
+/// metal of its heat exchangers warms up or cools down
+///
+/// The metal starts at `metal` and changes with the lumped `thermal` masses.
+/// `conditions` returns the inputs for the cycle that starts at a time (s),
+/// which take the place of the config's conditions so that the sink, source,
+/// and charge pressure can change during the run.  The results hold a summary
+/// of each of the `cycles`.
+///
+/// # Errors
+///
+/// Will return `Err` if the config is invalid or if any cycle cannot be
+/// integrated.
+///
+/// # Panics
+///
+/// Will panic if an unsupported fluid model is provided.
+pub fn run_transient(
+    config: Config,
+    thermal: ThermalMasses,
+    metal: MetalTemperatures,
+    cycles: usize,
+    mut conditions: impl FnMut(f64) -> RunInputs,
+) -> Result<Vec<CycleResults>, RunError> {
+    if cycles == 0 {
+        return Ok(Vec::new());
+    }
+    let decomposition = config.solver.decomposition;
+    let settings = settings(config.solver)?;
+    let mut transient = Transient::start(
+        decomposition,
+        components(config.engine.components)?,
+        fluid(config.engine.fluid),
+        conditions(0.0),
+        settings,
+        thermal,
+        metal,
+    )?;
+    let mut results = Vec::with_capacity(cycles);
+    results.push(CycleResults::from(&transient));
+    for _ in 1..cycles {
+        transient.step(conditions(transient.time()))?;
+        results.push(CycleResults::from(&transient));
+    }
+    Ok(results)
+}
+
+/// Run the engine described by `config`, starting from `hint` if provided
+///
+/// # Panics
+///
+/// Will panic if an unsupported fluid model is provided.
+fn start(config: Config, hint: Option<StateSnapshot>) -> Result<Engine<IdealGas>, RunError> {
+    let decomposition = config.solver.decomposition;
+    let components = components(config.engine.components)?;
+    let fluid = fluid(config.engine.fluid);
+    let inputs = config.conditions.into();
+    let settings = settings(config.solver)?;
+    let options = RunOptions {
+        decomposition,
+        hint,
+        ..RunOptions::default()
+    };
+    Engine::run_with(components, fluid, inputs, settings, options)
+}
+
+/// Return the run settings described by `config`
+///
+/// # Errors
+///
+/// Will return `RunError::InvalidConfig` if the settings are invalid.
+fn settings(config: SolverConfig) -> Result<RunSettings, RunError> {
+    config
+        .try_into()
+        .map_err(|err: anyhow::Error| RunError::InvalidConfig(err.to_string()))
+}
+
+/// Return the engine components described by `config`
+///
+/// # Errors
+///
+/// Will return `RunError::InvalidConfig` if a component is invalid.
+fn components(config: ComponentsConfig) -> Result<Components, RunError> {
+    config
+        .try_into()
+        .map_err(|err: anyhow::Error| RunError::InvalidConfig(err.to_string()))
+}
+
+/// Return the fluid described by `config`
+///
+/// # Panics
+///
+/// Will panic if an unsupported fluid model is provided.
+fn fluid(config: fluid::Config) -> IdealGas {
+    match config {
         fluid::Config::Hydrogen(model) => match model {
             fluid::ModelConfig::IdealGas => fluid::IdealGas::hydrogen(),
             fluid::ModelConfig::RefProp => todo!(),
@@ -32,16 +145,7 @@ pub fn run_engine(config: Config) -> Result<RunResults, RunError> {
             fluid::ModelConfig::Fit => todo!(),
             fluid::ModelConfig::Custom => todo!(),
         },
-    };
-
-    let engine = Engine::run::<LuSolver>(
-        config.engine.components.into(),
-        fluid,
-        config.conditions.into(),
-        config.solver.into(),
-    )?;
-
-    Ok(RunResults::from(engine))
+    }
 }
 
 /// The results of an engine run
@@ -62,17 +166,77 @@ pub struct RunResults {
     /// Engine pressure (Pa)
     pub pressure: Pressure,
 
-    /// Regenerator approach temperature imbalance (K)
+    /// Operating frequency (Hz)
+    pub frequency: f64,
+
+    /// Piston and displacer strokes of a free-piston engine (m)
+    pub stroke: Option<Stroke>,
+
+    /// Parasitic power in the working spaces (W)
+    ///
+    /// Alternator copper losses are electrical parasitics of the compression
+    /// space.
+    pub ws_parasitics: ws::Parasitics,
+
+    /// Regenerator approach temperature imbalance (K), found from an energy
+    /// balance on the regenerator enthalpy flows
     pub regen_imbalance: f64,
 
     /// Shaft torque (N-m)
     pub shaft_torque: f64,
 
+    /// Loads on the crank over the cycle, unless the pistons are free
+    pub crank: Option<Crank>,
+
     /// Engine temperatures (K)
     pub temperature: Temperature,
 
     /// Time-discretized values over one engine cycle
     pub values: Values,
+
+    /// Statistics collected while solving the state equations
+    pub solver_stats: SolverStats,
+
+    /// Quantities that had not converged after each outer loop iteration
+    pub convergence: ConvergenceReport,
+}
+
+/// Summary results of one cycle of a transient run
+#[derive(Debug)]
+pub struct CycleResults {
+    /// Time at the end of the cycle (s)
+    pub time: f64,
+
+    /// Metal temperatures at the end of the cycle (K)
+    pub metal: MetalTemperatures,
+
+    /// Engine efficiency over the cycle (-)
+    pub efficiency: Efficiency,
+
+    /// Average heat flow rates over the cycle (W)
+    pub heat_flow: HeatFlow,
+
+    /// Average mass flow rates through the heat exchangers (kg/s)
+    pub mass_flow: MassFlow,
+
+    /// Engine power over the cycle (W)
+    pub power: Power,
+
+    /// Engine pressure over the cycle (Pa)
+    pub pressure: Pressure,
+
+    /// Regenerator approach temperature imbalance (K), found from the
+    /// regenerator metal temperature at the start of the cycle
+    pub regen_imbalance: f64,
+
+    /// Shaft torque (N-m)
+    pub shaft_torque: f64,
+
+    /// Engine temperatures during the cycle (K)
+    ///
+    /// The sink and source are the conditions of the cycle, while the heat
+    /// exchanger temperatures follow from the metal at the start of it.
+    pub temperature: Temperature,
 }
 
 /// Different characterizations of engine efficiency
@@ -82,6 +246,10 @@ pub struct Efficiency {
     pub mechanical: f64,
 
     /// Overal efficiency, which includes electrical parasitics
+    ///
+    /// This is the electrical output over the heat input for engines with an
+    /// alternator.  Otherwise it is the net power less the electrical
+    /// parasitics, which are driven from it, over the heat input.
     pub overall: f64,
 }
 
@@ -105,11 +273,15 @@ pub struct HeatFlow {
 }
 
 /// Average mass flow rates through the heat exchangers (kg/s)
+///
+/// `seal` is the average leakage through the piston seal into the buffer,
+/// which is zero without a buffer.
 #[derive(Debug)]
 pub struct MassFlow {
     pub chx: f64,
     pub regen: f64,
     pub hhx: f64,
+    pub seal: f64,
 }
 
 /// Different characterizations of engine power
@@ -132,6 +304,20 @@ pub struct Power {
     /// Net power is defined as the shaft power less any mechanical parasitics
     /// in the heat exchangers.
     pub net: f64,
+
+    /// Electrical output power (W)
+    ///
+    /// Electrical output is defined as the power generated by a linear
+    /// alternator less its copper losses and any other electrical parasitics.
+    /// It is zero for engines without an alternator.
+    pub electrical: f64,
+
+    /// Power lost to leakage through the piston seal (W)
+    ///
+    /// This is the work of throttling the leakage between the compression
+    /// space and the buffer, which is already reflected in the indicated
+    /// power.  It is zero without a buffer.
+    pub seal_leakage: f64,
 }
 
 /// Engine pressure (Pa)
@@ -143,6 +329,53 @@ pub struct Pressure {
     pub t_zero: f64,
 }
 
+/// Peak-to-peak strokes of a free-piston engine (m)
+#[derive(Debug)]
+pub struct Stroke {
+    pub piston: f64,
+    pub displacer: f64,
+}
+
+/// Loads on the crank over one engine cycle
+///
+/// The loads come from the gas pressures alone, with the buffer pressure
+/// `P_b` behind the pistons, and share the index of `Values`.
+#[derive(Debug)]
+pub struct Crank {
+    /// Instantaneous shaft torque (N-m)
+    ///
+    /// Mechanical parasitics are not included, so the torque averages to the
+    /// indicated power over the angular velocity.
+    pub torque: Vec<f64>,
+
+    /// Forces on the pistons and rods, when the kinematics of the drive are
+    /// known
+    pub forces: Option<Forces>,
+
+    /// Largest swing in the work done on the crank by the torque less its
+    /// mean over the cycle (J)
+    pub energy_fluctuation: f64,
+}
+
+/// Gas forces on the pistons and the rods that drive them (N)
+///
+/// Piston forces are positive toward the expansion space and rod loads are
+/// positive in compression.
+#[derive(Debug)]
+pub struct Forces {
+    /// Net gas force on the power piston
+    pub piston: Vec<f64>,
+
+    /// Net gas force on the displacer
+    pub displacer: Vec<f64>,
+
+    /// Load in each connecting rod of the power piston
+    pub piston_rod: Vec<f64>,
+
+    /// Load in each connecting rod of the displacer
+    pub displacer_rod: Vec<f64>,
+}
+
 /// Engine temperature (K)
 #[derive(Debug)]
 pub struct Temperature {
@@ -171,6 +404,9 @@ pub struct Values {
     /// Time in the cycle for each discrete point (s)
     pub time: Vec<f64>,
 
+    /// Crank angle for each discrete point, from 0 to 360 over the cycle (deg)
+    pub crank_angle: Vec<f64>,
+
     /// Pressure in all volumes, assuming no hxr pressure drop (Pa)
     pub P: Vec<f64>,
 
@@ -212,6 +448,51 @@ pub struct Values {
     ///
     /// Positive values represent heat flow from the heat exchanger to the fluid.
     pub Q_dot_l: Vec<f64>,
+
+    /// Heat flow from the working fluid to the compression space wall (W)
+    ///
+    /// Only heat through walls with a time-varying conductance is included;
+    /// it is zero for constant thermal resistances.
+    pub Q_dot_c: Vec<f64>,
+
+    /// Heat flow from the expansion space wall to the working fluid (W)
+    ///
+    /// Only heat through walls with a time-varying conductance is included;
+    /// it is zero for constant thermal resistances.
+    pub Q_dot_e: Vec<f64>,
+
+    /// Compression space volume (m^3)
+    pub V_c: Vec<f64>,
+
+    /// Expansion space volume (m^3)
+    pub V_e: Vec<f64>,
+
+    /// Pressure in the buffer behind the pistons (Pa)
+    ///
+    /// Without a buffer, the pistons are backed by the mean cycle pressure.
+    pub P_b: Vec<f64>,
+
+    /// Mass flow rate through the piston seal from the compression space to
+    /// the buffer (kg/s)
+    pub m_dot_leak: Vec<f64>,
+
+    /// Mass flow rate across each control volume interface, from the
+    /// compression space to the expansion space (kg/s)
+    ///
+    /// Each entry is the flow across one interface over the cycle.  With one
+    /// control volume per heat exchanger these match `m_dot_ck`, `m_dot_kr`,
+    /// `m_dot_rl`, and `m_dot_le`.
+    pub m_dot: Vec<Vec<f64>>,
+
+    /// Heat flow for each control volume, from the cold heat exchanger to the
+    /// hot heat exchanger (W)
+    ///
+    /// Each entry is the heat flow in one control volume over the cycle, with
+    /// the sign convention of its component total.  Regenerator volumes sit on
+    /// an imposed linear matrix temperature profile, so their heat flows show
+    /// how the load is shared along the regenerator but the profile itself is
+    /// not solved for.
+    pub Q_dot: Vec<Vec<f64>>,
 }
 
 impl<T: Fluid> From<Engine<T>> for RunResults {
@@ -219,48 +500,21 @@ impl<T: Fluid> From<Engine<T>> for RunResults {
         let performance = Performance::from(&engine);
 
         Self {
-            efficiency: Efficiency {
-                mechanical: performance.efficiency,
-                // TODO: How do we calculate this?
-                overall: 0.,
-            },
-            heat_flow: HeatFlow {
-                input: performance.heat.input,
-                rejection: performance.heat.rejected,
-                chx: engine.state.heat_flow.chx,
-                regen: engine.state.heat_flow.regen,
-                hhx: engine.state.heat_flow.hhx,
-            },
-            mass_flow: MassFlow {
-                chx: engine.state.mass_flow.chx,
-                regen: engine.state.mass_flow.regen,
-                hhx: engine.state.mass_flow.hhx,
-            },
-            power: Power {
-                ideal_indicated: performance.power.indicated_zero_dP,
-                indicated: performance.power.indicated,
-                shaft: performance.power.shaft,
-                net: performance.power.net,
-            },
-            pressure: Pressure {
-                avg: engine.state.pres.avg,
-                max: engine.state.pres.max,
-                min: engine.state.pres.min,
-                t_zero: engine.state.pres.t_zero,
-            },
+            efficiency: Efficiency::from(&performance),
+            heat_flow: HeatFlow::new(&engine, &performance),
+            mass_flow: MassFlow::new(&engine, &performance),
+            power: Power::from(&performance),
+            pressure: Pressure::from(&engine),
+            frequency: performance.frequency,
+            stroke: Stroke::new(&engine),
+            ws_parasitics: performance.ws_parasitics,
             regen_imbalance: engine.state.regen_imbalance.0,
             shaft_torque: performance.shaft_torque,
-            temperature: Temperature {
-                sink: engine.state.temp.sink,
-                chx: engine.state.temp.chx,
-                regen_cold: engine.state.temp.regen.cold,
-                regen_avg: engine.state.temp.regen.avg,
-                regen_hot: engine.state.temp.regen.hot,
-                hhx: engine.state.temp.hhx,
-                source: engine.state.temp.source,
-            },
+            crank: performance.crank.map(Crank::from),
+            temperature: Temperature::from(&engine),
             values: Values {
                 time: engine.values.time,
+                crank_angle: engine.values.crank_angle,
                 P: engine.values.P,
                 P_c: performance.pressures_with_drops.P_c.data.into(),
                 P_e: performance.pressures_with_drops.P_e.data.into(),
@@ -273,7 +527,301 @@ impl<T: Fluid> From<Engine<T>> for RunResults {
                 Q_dot_k: engine.values.Q_dot_k,
                 Q_dot_r: engine.values.Q_dot_r,
                 Q_dot_l: engine.values.Q_dot_l,
+                Q_dot_c: engine.values.Q_dot_c,
+                Q_dot_e: engine.values.Q_dot_e,
+                V_c: engine.values.V_c,
+                V_e: engine.values.V_e,
+                P_b: engine.values.P_b,
+                m_dot_leak: engine.values.m_dot_leak,
+                m_dot: engine.values.m_dot,
+                Q_dot: engine.values.Q_dot,
+            },
+            solver_stats: engine.stats,
+            convergence: engine.convergence,
+        }
+    }
+}
+
+impl<T: Fluid> From<&Transient<T>> for CycleResults {
+    fn from(transient: &Transient<T>) -> Self {
+        let engine = transient.engine();
+        let performance = Performance::from(engine);
+        let inputs = transient.inputs();
+
+        Self {
+            time: transient.time(),
+            metal: transient.metal(),
+            efficiency: Efficiency::from(&performance),
+            heat_flow: HeatFlow::new(engine, &performance),
+            mass_flow: MassFlow::new(engine, &performance),
+            power: Power::from(&performance),
+            pressure: Pressure::from(engine),
+            regen_imbalance: engine.state.regen_imbalance.0,
+            shaft_torque: performance.shaft_torque,
+            temperature: Temperature {
+                sink: inputs.temp_sink,
+                source: inputs.temp_source,
+                ..Temperature::from(engine)
+            },
+        }
+    }
+}
+
+impl From<&Performance> for Efficiency {
+    fn from(performance: &Performance) -> Self {
+        Self {
+            mechanical: performance.efficiency,
+            overall: performance.power.output / performance.heat.input,
+        }
+    }
+}
+
+impl HeatFlow {
+    fn new<T: Fluid>(engine: &Engine<T>, performance: &Performance) -> Self {
+        Self {
+            input: performance.heat.input,
+            rejection: performance.heat.rejected,
+            chx: engine.state.heat_flow.chx,
+            regen: engine.state.heat_flow.regen,
+            hhx: engine.state.heat_flow.hhx,
+        }
+    }
+}
+
+impl MassFlow {
+    fn new<T: Fluid>(engine: &Engine<T>, performance: &Performance) -> Self {
+        Self {
+            chx: engine.state.mass_flow.chx,
+            regen: engine.state.mass_flow.regen,
+            hhx: engine.state.mass_flow.hhx,
+            seal: performance.seal.mass_flow,
+        }
+    }
+}
+
+impl From<&Performance> for Power {
+    fn from(performance: &Performance) -> Self {
+        Self {
+            ideal_indicated: performance.power.indicated_zero_dP,
+            indicated: performance.power.indicated,
+            shaft: performance.power.shaft,
+            net: performance.power.net,
+            electrical: performance.power.electrical,
+            seal_leakage: performance.seal.power,
+        }
+    }
+}
+
+impl<T: Fluid> From<&Engine<T>> for Pressure {
+    fn from(engine: &Engine<T>) -> Self {
+        Self {
+            avg: engine.state.pres.avg,
+            max: engine.state.pres.max,
+            min: engine.state.pres.min,
+            t_zero: engine.state.pres.t_zero,
+        }
+    }
+}
+
+impl Stroke {
+    /// Return the strokes over the cycle if `engine` has free pistons
+    fn new<T: Fluid>(engine: &Engine<T>) -> Option<Self> {
+        engine.components.ws.dynamics(&engine.state.ws())?;
+        let range = |values: &[f64]| {
+            let (min, max) = values
+                .iter()
+                .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), &x| {
+                    (min.min(x), max.max(x))
+                });
+            max - min
+        };
+        Some(Self {
+            piston: range(&engine.values.x_p),
+            displacer: range(&engine.values.x_d),
+        })
+    }
+}
+
+impl RunResults {
+    /// Return the flywheel moment of inertia (kg-m^2) that holds the speed
+    /// within a fluctuation `coefficient` (-)
+    ///
+    /// The coefficient is the difference between the highest and lowest
+    /// speeds over the mean speed.  `None` is returned for free pistons,
+    /// which have no crank.
+    #[must_use]
+    pub fn flywheel_inertia(&self, coefficient: f64) -> Option<f64> {
+        let crank = self.crank.as_ref()?;
+        let omega = 2. * std::f64::consts::PI * self.frequency;
+        Some(crank.energy_fluctuation / (coefficient * omega.powi(2)))
+    }
+}
+
+impl From<CrankLoads> for Crank {
+    fn from(loads: CrankLoads) -> Self {
+        Self {
+            torque: loads.torque.data.into(),
+            forces: loads.forces.map(|forces| Forces {
+                piston: forces.iter().map(|f| f.piston).collect(),
+                displacer: forces.iter().map(|f| f.displacer).collect(),
+                piston_rod: forces.iter().map(|f| f.piston_rod).collect(),
+                displacer_rod: forces.iter().map(|f| f.displacer_rod).collect(),
+            }),
+            energy_fluctuation: loads.energy_fluctuation,
+        }
+    }
+}
+
+impl<T: Fluid> From<&Engine<T>> for Temperature {
+    fn from(engine: &Engine<T>) -> Self {
+        Self {
+            sink: engine.state.temp.sink,
+            chx: engine.state.temp.chx,
+            regen_cold: engine.state.temp.regen.cold,
+            regen_avg: engine.state.temp.regen.avg,
+            regen_hot: engine.state.temp.regen.hot,
+            hhx: engine.state.temp.hhx,
+            source: engine.state.temp.source,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::iter;
+
+    use super::*;
+
+    /// Return the config of a simple engine shared by the api tests
+    pub(super) fn config() -> Config {
+        config::Config::builder()
+            .add_source(config::File::from_str(
+                r#"
+                [engine.fluid.hydrogen]
+                model = "ideal_gas"
+
+                [engine.components.chx.fixed_approach]
+                vol = 4e-5
+                DT = 40
+                R_hyd = 0
+                W_parasitic = 0
+
+                [engine.components.hhx.fixed_approach]
+                vol = 1e-4
+                DT = 100
+                R_hyd = 0
+                W_parasitic = 0
+                Q_parasitic = 0
+
+                [engine.components.regen.fixed_approach]
+                vol = 1e-4
+                DT = 10
+                R_hyd = 0
+                Q_parasitic = 0
+
+                [engine.components.ws.sinusoidal]
+                frequency = 66.6667
+                phase_angle = 90
+                V_swept_c = 1.128e-4
+                V_clearance_c = 4.68e-5
+                R_c = inf
+                W_parasitic_c = 0
+                V_swept_e = 1.128e-4
+                V_clearance_e = 1.68e-5
+                R_e = inf
+                W_parasitic_e = 0
+                Q_parasitic_e = 0
+
+                [solver.inner_loop]
+                tolerance = { abs = 1e-3, rel = 1e-6 }
+                max_iterations = 20
+
+                [solver.outer_loop]
+                tolerance = { abs = 1e-3, rel = 1e-6 }
+                max_iterations = 20
+
+                [solver.ode]
+                tolerance = { abs = 1e-8, rel = 1e-8 }
+                num_timesteps = 30
+
+                [conditions]
+                temp_sink = 300
+                temp_source = 900
+                pres_zero = 10e6
+                "#,
+                config::FileFormat::Toml,
+            ))
+            .build()
+            .unwrap()
+            .try_deserialize()
+            .unwrap()
+    }
+
+    #[test]
+    fn transient_results_track_the_metal() {
+        use approx::assert_relative_eq;
+
+        use crate::types::ThermalMass;
+
+        let thermal = ThermalMasses {
+            chx: ThermalMass {
+                capacitance: 500.0,
+                conductance: 1e4,
+            },
+            regen: 2000.0,
+            hhx: ThermalMass {
+                capacitance: 500.0,
+                conductance: 1e4,
             },
+        };
+        let metal = MetalTemperatures {
+            chx: 300.0,
+            regen: 550.0,
+            hhx: 900.0,
+        };
+
+        // The source is raised after the first cycle
+        let conditions = |time: f64| RunInputs {
+            pres_zero: 10e6,
+            temp_sink: 300.0,
+            temp_source: if time > 0.0 { 950.0 } else { 900.0 },
+        };
+        let results = run_transient(config(), thermal, metal, 3, conditions)
+            .expect("cycles should integrate");
+        assert_eq!(results.len(), 3);
+        assert!(run_transient(config(), thermal, metal, 0, conditions)
+            .unwrap()
+            .is_empty());
+
+        // Each cycle starts from the metal at the end of the one before it,
+        // offset by the approach temperatures of the heat exchangers
+        let starts = iter::once(metal).chain(results.iter().map(|cycle| cycle.metal));
+        for (i, (cycle, start)) in results.iter().zip(starts).enumerate() {
+            assert_relative_eq!(cycle.time, (i + 1) as f64 / 66.6667, max_relative = 1e-9);
+            assert_relative_eq!(
+                cycle.temperature.chx,
+                start.chx + 40.0,
+                max_relative = 1e-12
+            );
+            assert_relative_eq!(
+                cycle.temperature.hhx,
+                start.hhx - 100.0,
+                max_relative = 1e-12
+            );
+            assert_relative_eq!(
+                cycle.temperature.regen_avg,
+                start.regen,
+                max_relative = 1e-12
+            );
+            assert_eq!(cycle.temperature.sink, 300.0);
         }
+        assert_eq!(results[0].temperature.source, 900.0);
+        assert_eq!(results[2].temperature.source, 950.0);
+
+        // Heat rejection warms the chx metal and heat input cools the hhx
+        // metal, which then moves toward the hotter source
+        assert!(results[0].metal.chx > metal.chx);
+        assert!(results[0].metal.hhx < metal.hhx);
+        assert!(results[2].metal.hhx > results[1].metal.hhx);
     }
 }
@@ -0,0 +1,181 @@
+// Most false position iterations used to find a crossing
+const MAX_ITERS: usize = 50;
+
+/// Where a residual crosses zero, along with what was found there
+pub(super) struct Crossing<T> {
+    pub x: f64,
+    pub found: T,
+
+    /// Slope of the residual through the crossing, from the nearest inputs
+    /// on either side of it
+    pub slope: f64,
+}
+
+/// Tolerances on the input and the residual that end the search for a
+/// crossing once either is met
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Tolerance {
+    pub x: f64,
+    pub residual: f64,
+}
+
+/// An error that can occur while finding a crossing
+pub(super) enum CrossingError<E> {
+    /// The residual has the same sign at both ends of the range, or is NaN at
+    /// either end, where `low` and `high` are the residuals at either end
+    NotBracketed { low: f64, high: f64 },
+
+    /// The crossing was not found within the iteration limit
+    DidNotConverge,
+
+    /// An evaluation failed
+    Eval(E),
+}
+
+/// Find where a residual crosses zero between `low` and `high`
+///
+/// The ends of the range can be given in either order.  `eval` returns what
+/// is found at an input along with the residual there.
+/// The crossing is found by false position with the Illinois modification,
+/// which keeps the crossing bracketed while converging superlinearly.  When
+/// the residual crosses zero more than once within the range, any one of the
+/// crossings may be found, and it may cross in the opposite direction to the
+/// residuals at the ends of the range.
+pub(super) fn find_crossing<T, E>(
+    (mut low, mut high): (f64, f64),
+    tol: Tolerance,
+    mut eval: impl FnMut(f64) -> Result<(T, f64), E>,
+) -> Result<Crossing<T>, CrossingError<E>> {
+    if low > high {
+        (low, high) = (high, low);
+    }
+    let (low_found, mut low_residual) = eval(low).map_err(CrossingError::Eval)?;
+    let (high_found, mut high_residual) = eval(high).map_err(CrossingError::Eval)?;
+    if low_residual.is_nan() || high_residual.is_nan() || low_residual * high_residual > 0.0 {
+        return Err(CrossingError::NotBracketed {
+            low: low_residual,
+            high: high_residual,
+        });
+    }
+
+    let slope = |(x0, residual0): (f64, f64), (x1, residual1): (f64, f64)| {
+        (residual1 - residual0) / (x1 - x0)
+    };
+    if low_residual.abs() <= tol.residual || high_residual.abs() <= tol.residual {
+        let slope = slope((low, low_residual), (high, high_residual));
+        let (x, found) = if low_residual.abs() <= tol.residual {
+            (low, low_found)
+        } else {
+            (high, high_found)
+        };
+        return Ok(Crossing { x, found, slope });
+    }
+
+    // The residual at an end that is kept twice in a row is halved, so that
+    // both ends of the bracket close in on the crossing.  The residuals at
+    // the ends themselves are kept for the slope.
+    let (mut low_end, mut high_end) = ((low, low_residual), (high, high_residual));
+    let mut last = None;
+    let mut kept_high = None;
+    for _ in 0..MAX_ITERS {
+        let x = (low * high_residual - high * low_residual) / (high_residual - low_residual);
+        let (found, residual) = eval(x).map_err(CrossingError::Eval)?;
+        let converged = residual.abs() <= tol.residual
+            || last.is_some_and(|last: f64| (x - last).abs() < tol.x)
+            || high - low < tol.x;
+        if converged {
+            // Take the slope over whichever side of `x` still brackets the
+            // crossing, or over the whole bracket if `x` is on it
+            let slope = if residual * low_residual > 0.0 {
+                slope((x, residual), high_end)
+            } else if residual * high_residual > 0.0 {
+                slope(low_end, (x, residual))
+            } else {
+                slope(low_end, high_end)
+            };
+            return Ok(Crossing { x, found, slope });
+        }
+        if residual * low_residual > 0.0 {
+            low_end = (x, residual);
+            (low, low_residual) = (x, residual);
+            if kept_high == Some(true) {
+                high_residual *= 0.5;
+            }
+            kept_high = Some(true);
+        } else {
+            high_end = (x, residual);
+            (high, high_residual) = (x, residual);
+            if kept_high == Some(false) {
+                low_residual *= 0.5;
+            }
+            kept_high = Some(false);
+        }
+        last = Some(x);
+    }
+
+    Err(CrossingError::DidNotConverge)
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+
+    fn find(
+        range: (f64, f64),
+        residual: fn(f64) -> f64,
+    ) -> Result<Crossing<usize>, CrossingError<()>> {
+        let mut evals = 0;
+        let tol = Tolerance {
+            x: 1e-12,
+            residual: 0.0,
+        };
+        find_crossing(range, tol, |x| {
+            evals += 1;
+            Ok((evals, residual(x)))
+        })
+    }
+
+    #[test]
+    fn finds_crossings_and_their_slopes() {
+        let Ok(crossing) = find((0.0, 4.0), |x| 2.0 - x * x) else {
+            panic!("crossing should be found");
+        };
+        assert_relative_eq!(crossing.x, 2f64.sqrt(), max_relative = 1e-12);
+        assert_relative_eq!(crossing.slope, -2.0 * 2f64.sqrt(), max_relative = 1e-2);
+        assert!(crossing.found < 20, "took {} evaluations", crossing.found);
+
+        let Ok(crossing) = find((0.0, 4.0), |x| x.powi(3) - 1.0) else {
+            panic!("crossing should be found");
+        };
+        assert_relative_eq!(crossing.x, 1.0, max_relative = 1e-12);
+        assert_relative_eq!(crossing.slope, 3.0, max_relative = 1e-2);
+    }
+
+    #[test]
+    fn rejects_ranges_without_a_crossing() {
+        assert!(matches!(
+            find((0.0, 1.0), |x| x + 1.0),
+            Err(CrossingError::NotBracketed { low, high }) if low == 1.0 && high == 2.0
+        ));
+        assert!(matches!(
+            find((0.0, 1.0), |x| if x > 0.5 { f64::NAN } else { -1.0 }),
+            Err(CrossingError::NotBracketed { low, high }) if low == -1.0 && high.is_nan()
+        ));
+    }
+
+    #[test]
+    fn finds_crossings_in_reversed_ranges() {
+        // Without an input tolerance, only the residual tolerance ends the search
+        let tol = Tolerance {
+            x: 0.0,
+            residual: 1e-9,
+        };
+        let Ok(crossing) = find_crossing((4.0, 0.0), tol, |x| Ok::<_, ()>(((), 2.0 - x * x)))
+        else {
+            panic!("crossing should be found");
+        };
+        assert!((2.0 - crossing.x * crossing.x).abs() <= 1e-9);
+    }
+}
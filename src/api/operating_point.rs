@@ -0,0 +1,227 @@
+use crate::{config::Config, engine::StateSnapshot, types::RunError};
+
+use super::{
+    bracket::{find_crossing, CrossingError, Tolerance},
+    start, RunResults,
+};
+
+/// Load torque (N-m) as a function of frequency (Hz)
+///
+/// The torque is interpolated linearly between points, and the operating
+/// frequency is only searched for within the range of the points.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoadCurve {
+    points: Vec<(f64, f64)>,
+}
+
+/// An operating point where the shaft torque matches the load
+#[derive(Debug)]
+pub struct OperatingPoint {
+    /// Operating frequency (Hz)
+    pub frequency: f64,
+
+    /// Load torque at the operating frequency (N-m)
+    pub load_torque: f64,
+
+    /// Whether the engine returns to the operating point after a small
+    /// change in speed
+    ///
+    /// An operating point is stable when the shaft torque falls below the
+    /// load as the speed increases, so that the excess torque slows the
+    /// engine back down.  This is judged from the slope of the excess torque
+    /// between the nearest runs on either side of the operating frequency.
+    pub stable: bool,
+
+    /// The results of a run at the operating frequency
+    pub results: RunResults,
+}
+
+/// An error that can occur while finding an operating point
+#[derive(Debug, Clone)]
+pub enum OperatingPointError {
+    /// The load curve has fewer than two points, or its frequencies do not
+    /// increase
+    InvalidLoadCurve,
+
+    /// The working spaces do not take frequency as an input
+    NoFrequencyInput,
+
+    /// The shaft torque does not cross the load curve within its range,
+    /// where `low` and `high` are the excess of shaft torque over the load
+    /// (N-m) at either end of the range
+    NotBracketed { low: f64, high: f64 },
+
+    /// The operating frequency was not found within the iteration limit
+    DidNotConverge,
+
+    /// A run failed at `frequency` (Hz)
+    Run { frequency: f64, error: RunError },
+}
+
+impl LoadCurve {
+    /// Create a load curve from `(frequency, torque)` points
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if there are fewer than two points or if the
+    /// frequencies do not strictly increase.
+    pub fn new(points: Vec<(f64, f64)>) -> Result<Self, OperatingPointError> {
+        let increasing = points.windows(2).all(|pair| pair[0].0 < pair[1].0);
+        if points.len() < 2 || !increasing {
+            return Err(OperatingPointError::InvalidLoadCurve);
+        }
+        Ok(Self { points })
+    }
+
+    /// Return the load torque (N-m) at `frequency` (Hz)
+    ///
+    /// The torque is held constant beyond the ends of the curve.
+    #[must_use]
+    pub fn torque(&self, frequency: f64) -> f64 {
+        let end = self
+            .points
+            .partition_point(|&(point, _)| point <= frequency)
+            .clamp(1, self.points.len() - 1);
+        let (f0, torque0) = self.points[end - 1];
+        let (f1, torque1) = self.points[end];
+        let fraction = ((frequency - f0) / (f1 - f0)).clamp(0.0, 1.0);
+        torque0 + fraction * (torque1 - torque0)
+    }
+
+    /// Return the lowest and highest frequencies (Hz) of the curve
+    fn range(&self) -> (f64, f64) {
+        (self.points[0].0, self.points[self.points.len() - 1].0)
+    }
+}
+
+/// Find the frequency at which the shaft torque matches a load
+///
+/// The frequency input of the working spaces is varied over the range of the
+/// load curve, and the crossing of the shaft torque and the load is found by
+/// false position to within `tol` (Hz).  Each run is warm started from the
+/// previous one.  When the curves cross more than once within the range, any
+/// one of the crossings may be found.
+///
+/// # Errors
+///
+/// Will return `Err` if the working spaces have no frequency input, if the
+/// shaft torque does not cross the load within the range of the curve, if
+/// the crossing is not found within the iteration limit, or if any run fails
+/// to converge.
+///
+/// # Panics
+///
+/// Will panic if an unsupported fluid model is provided.
+pub fn find_operating_point(
+    config: &Config,
+    load: &LoadCurve,
+    tol: f64,
+) -> Result<OperatingPoint, OperatingPointError> {
+    if config
+        .engine
+        .components
+        .ws
+        .clone()
+        .frequency_mut()
+        .is_none()
+    {
+        return Err(OperatingPointError::NoFrequencyInput);
+    }
+
+    // Return the results at `frequency` along with the excess torque
+    let mut snapshot: Option<StateSnapshot> = None;
+    let run = |frequency: f64| {
+        let mut config = config.clone();
+        if let Some(value) = config.engine.components.ws.frequency_mut() {
+            *value = frequency;
+        }
+        let engine = start(config, snapshot)
+            .map_err(|error| OperatingPointError::Run { frequency, error })?;
+        snapshot = Some(engine.snapshot());
+        let results = RunResults::from(engine);
+        let excess = results.shaft_torque - load.torque(frequency);
+        Ok((results, excess))
+    };
+
+    let tol = Tolerance {
+        x: tol,
+        residual: 0.0,
+    };
+    match find_crossing(load.range(), tol, run) {
+        Ok(crossing) => Ok(OperatingPoint {
+            frequency: crossing.x,
+            load_torque: load.torque(crossing.x),
+            stable: crossing.slope < 0.0,
+            results: crossing.found,
+        }),
+        Err(CrossingError::NotBracketed { low, high }) => {
+            Err(OperatingPointError::NotBracketed { low, high })
+        }
+        Err(CrossingError::DidNotConverge) => Err(OperatingPointError::DidNotConverge),
+        Err(CrossingError::Eval(err)) => Err(err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use crate::api::{run_engine, tests::config};
+
+    use super::*;
+
+    #[test]
+    fn load_curve_interpolates_between_points() {
+        let load = LoadCurve::new(vec![(10.0, 1.0), (20.0, 3.0), (40.0, 4.0)]).unwrap();
+        assert_eq!(load.torque(10.0), 1.0);
+        assert_eq!(load.torque(15.0), 2.0);
+        assert_eq!(load.torque(30.0), 3.5);
+        assert_eq!(load.torque(5.0), 1.0);
+        assert_eq!(load.torque(50.0), 4.0);
+
+        assert!(LoadCurve::new(vec![(10.0, 1.0)]).is_err());
+        assert!(LoadCurve::new(vec![(10.0, 1.0), (10.0, 2.0)]).is_err());
+    }
+
+    #[test]
+    fn finds_stable_operating_point() {
+        // A load that rises with speed through the torque at the configured
+        // frequency
+        let torque = run_engine(config()).unwrap().shaft_torque;
+        let load = LoadCurve::new(vec![(40.0, 0.5 * torque), (100.0, 1.5 * torque)]).unwrap();
+        let point = find_operating_point(&config(), &load, 1e-3).expect("point should be found");
+        assert!(point.stable);
+        assert!(point.frequency > 40.0 && point.frequency < 100.0);
+        assert_relative_eq!(
+            point.results.frequency,
+            point.frequency,
+            max_relative = 1e-9
+        );
+        assert_relative_eq!(
+            point.results.shaft_torque,
+            point.load_torque,
+            max_relative = 1e-4
+        );
+    }
+
+    #[test]
+    fn reports_unstable_operating_point() {
+        // A load that falls with speed faster than the shaft torque
+        let torque = run_engine(config()).unwrap().shaft_torque;
+        let load = LoadCurve::new(vec![(40.0, 1.5 * torque), (100.0, 0.5 * torque)]).unwrap();
+        let point = find_operating_point(&config(), &load, 1e-3).expect("point should be found");
+        assert!(!point.stable);
+        assert_relative_eq!(
+            point.results.shaft_torque,
+            point.load_torque,
+            max_relative = 1e-4
+        );
+
+        // A load that never meets the shaft torque has no operating point
+        let load = LoadCurve::new(vec![(40.0, 2.0 * torque), (100.0, 3.0 * torque)]).unwrap();
+        assert!(matches!(
+            find_operating_point(&config(), &load, 1e-3),
+            Err(OperatingPointError::NotBracketed { low, high }) if low < 0.0 && high < 0.0
+        ));
+    }
+}
@@ -0,0 +1,782 @@
+use crate::{config::Config, types::RunError};
+
+use super::{start, RunResults};
+
+/// A config parameter that the results are differentiated with respect to
+pub struct Parameter {
+    /// Name that identifies the parameter in the `Sensitivities`
+    pub name: String,
+
+    /// Step used for the central differences
+    pub step: Step,
+
+    /// Return the parameter within a config
+    ///
+    /// `None` is returned when the parameter does not apply to the config,
+    /// such as a heat exchanger parameter when a different heat exchanger
+    /// model is configured.  Component parameters are found by matching on
+    /// the configured model, such as `|config| match &mut
+    /// config.engine.components.hhx { hhx::Config::FixedConductance(hhx) =>
+    /// Some(&mut hhx.UA), _ => None }`.
+    pub value: fn(&mut Config) -> Option<&mut f64>,
+}
+
+/// The step used to perturb a `Parameter`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Step {
+    /// A step in the units of the parameter
+    Absolute(f64),
+
+    /// A step as a fraction of the parameter's value
+    Relative(f64),
+}
+
+/// Scalar results of an engine run that sensitivities are found for
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Metrics {
+    /// Net power (W)
+    pub net_power: f64,
+
+    /// Mechanical efficiency (-)
+    pub efficiency: f64,
+
+    /// Total heat input to the engine (W)
+    pub heat_input: f64,
+}
+
+/// The sensitivity of the `Metrics` to a single `Parameter`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sensitivity {
+    /// Name of the parameter
+    pub name: String,
+
+    /// Value of the parameter in the baseline config
+    pub value: f64,
+
+    /// Step used for the central differences, in the units of the parameter
+    pub step: f64,
+
+    /// Derivatives of the metrics with respect to the parameter
+    ///
+    /// Each derivative is in the units of the metric per unit of the
+    /// parameter, such as W/m^3 for net power with respect to a volume.
+    pub derivative: Metrics,
+}
+
+/// The results of a baseline run and their sensitivities to each `Parameter`
+#[derive(Debug)]
+pub struct Sensitivities {
+    pub baseline: RunResults,
+    pub parameters: Vec<Sensitivity>,
+}
+
+/// An error that can occur while finding sensitivities
+#[derive(Debug, Clone)]
+pub enum SensitivityError {
+    /// The named parameter does not apply to the config
+    NotApplicable(String),
+
+    /// The step for the named parameter is zero
+    ZeroStep(String),
+
+    /// A run failed, where `parameter` is `None` for the baseline run
+    Run {
+        parameter: Option<String>,
+        error: RunError,
+    },
+}
+
+/// Find the sensitivities of the results to config parameters
+///
+/// Every parameter is perturbed by its step in both directions, and the
+/// derivatives of the `Metrics` are found by central differences.  Each
+/// perturbed run is warm started from the converged baseline, so it only
+/// needs a few outer loop iterations.  Since every run only converges to
+/// within the solver tolerances, the tolerances must be tight enough that
+/// the changes in the metrics over a step are not swamped by them.
+///
+/// # Errors
+///
+/// Will return `Err` if a parameter does not apply to the config or has a
+/// zero step, or if any run fails to converge.
+///
+/// # Panics
+///
+/// Will panic if an unsupported fluid model is provided.
+pub fn sensitivities(
+    config: &Config,
+    parameters: &[Parameter],
+) -> Result<Sensitivities, SensitivityError> {
+    // Check every parameter before running anything
+    let steps = parameters
+        .iter()
+        .map(|parameter| {
+            let value = *(parameter.value)(&mut config.clone())
+                .ok_or_else(|| SensitivityError::NotApplicable(parameter.name.clone()))?;
+            let step = match parameter.step {
+                Step::Absolute(step) => step.abs(),
+                Step::Relative(fraction) => (fraction * value).abs(),
+            };
+            if step == 0.0 {
+                return Err(SensitivityError::ZeroStep(parameter.name.clone()));
+            }
+            Ok((value, step))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let engine = start(config.clone(), None).map_err(|error| SensitivityError::Run {
+        parameter: None,
+        error,
+    })?;
+    let snapshot = engine.snapshot();
+    let baseline = RunResults::from(engine);
+
+    let parameters = parameters
+        .iter()
+        .zip(steps)
+        .map(|(parameter, (value, step))| {
+            let run = |delta: f64| {
+                let mut config = config.clone();
+                if let Some(value) = (parameter.value)(&mut config) {
+                    *value += delta;
+                }
+                start(config, Some(snapshot))
+                    .map(|engine| Metrics::from(&RunResults::from(engine)))
+                    .map_err(|error| SensitivityError::Run {
+                        parameter: Some(parameter.name.clone()),
+                        error,
+                    })
+            };
+            let above = run(step)?;
+            let below = run(-step)?;
+            let slope =
+                |metric: fn(&Metrics) -> f64| (metric(&above) - metric(&below)) / (2.0 * step);
+            Ok(Sensitivity {
+                name: parameter.name.clone(),
+                value,
+                step,
+                derivative: Metrics {
+                    net_power: slope(|metrics| metrics.net_power),
+                    efficiency: slope(|metrics| metrics.efficiency),
+                    heat_input: slope(|metrics| metrics.heat_input),
+                },
+            })
+        })
+        .collect::<Result<_, _>>()?;
+
+    Ok(Sensitivities {
+        baseline,
+        parameters,
+    })
+}
+
+impl From<&RunResults> for Metrics {
+    fn from(results: &RunResults) -> Self {
+        Self {
+            net_power: results.power.net,
+            efficiency: results.efficiency.mechanical,
+            heat_input: results.heat_flow.input,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use crate::{
+        api::tests::config,
+        chx, hhx, regen,
+        ws::{self, crank_drive, wall_heat},
+    };
+
+    use super::*;
+
+    fn hhx_approach() -> Parameter {
+        Parameter {
+            name: "hhx approach".into(),
+            step: Step::Absolute(5.0),
+            value: |config| match &mut config.engine.components.hhx {
+                hhx::Config::FixedApproach(hhx) => Some(&mut hhx.DT),
+                _ => None,
+            },
+        }
+    }
+
+    fn source_temp() -> Parameter {
+        Parameter {
+            name: "source temperature".into(),
+            step: Step::Absolute(5.0),
+            value: |config| Some(&mut config.conditions.temp_source),
+        }
+    }
+
+    fn exp_clearance() -> Parameter {
+        Parameter {
+            name: "expansion clearance volume".into(),
+            step: Step::Relative(0.05),
+            value: |config| match &mut config.engine.components.ws {
+                ws::Config::Sinusoidal(ws) => Some(&mut ws.V_clearance_e),
+                _ => None,
+            },
+        }
+    }
+
+    #[test]
+    fn finds_sensitivities_by_central_differences() {
+        let results = sensitivities(&config(), &[hhx_approach(), source_temp(), exp_clearance()])
+            .expect("engine should converge");
+        let [approach, source, clearance] = &results.parameters[..] else {
+            panic!("expected three sensitivities");
+        };
+
+        // The hhx temperature depends only on the source temperature less the
+        // fixed approach, so their sensitivities must cancel
+        assert_eq!(approach.value, 100.0);
+        assert_eq!(source.value, 900.0);
+        assert!(approach.derivative.net_power < 0.0);
+        assert_relative_eq!(
+            approach.derivative.net_power,
+            -source.derivative.net_power,
+            max_relative = 5e-3
+        );
+        assert_relative_eq!(
+            approach.derivative.efficiency,
+            -source.derivative.efficiency,
+            max_relative = 5e-3
+        );
+        assert_relative_eq!(
+            approach.derivative.heat_input,
+            -source.derivative.heat_input,
+            max_relative = 5e-3
+        );
+
+        // Dead volume reduces the pressure swing, and with it the power
+        assert_relative_eq!(clearance.step, 0.05 * 1.68e-5);
+        assert!(clearance.derivative.net_power < 0.0);
+    }
+
+    #[test]
+    fn finds_sensitivities_to_component_parameters() {
+        let mut config = config();
+        config.engine.components.hhx =
+            hhx::Config::FixedConductance(hhx::fixed_conductance::Config {
+                UA: 5000.0,
+                ..Default::default()
+            });
+        let hhx_conductance = Parameter {
+            name: "hhx conductance".into(),
+            step: Step::Relative(0.05),
+            value: |config| match &mut config.engine.components.hhx {
+                hhx::Config::FixedConductance(hhx) => Some(&mut hhx.UA),
+                _ => None,
+            },
+        };
+        let results = sensitivities(&config, &[hhx_conductance]).expect("engine should converge");
+
+        // A better hhx heats the gas closer to the source temperature
+        let [conductance] = &results.parameters[..] else {
+            panic!("expected one sensitivity");
+        };
+        assert_eq!(conductance.value, 5000.0);
+        assert_relative_eq!(conductance.step, 250.0);
+        assert!(conductance.derivative.net_power > 0.0);
+    }
+
+    #[test]
+    fn rejects_parameters_that_do_not_apply() {
+        let mut config = config();
+        config.engine.components.hhx = hhx::Config::FixedConductance(Default::default());
+        let result = sensitivities(&config, &[source_temp(), hhx_approach()]);
+        assert!(matches!(
+            result,
+            Err(SensitivityError::NotApplicable(name)) if name == "hhx approach"
+        ));
+    }
+
+    fn crank_config() -> Config {
+        let mut config = config();
+        config.engine.components.ws = ws::Config::Crank(crank_drive::Config {
+            frequency: 50.0,
+            layout: crank_drive::Layout::Alpha {
+                D_c: 0.06,
+                D_e: 0.05,
+            },
+            drive: crank_drive::Drive::SliderCrank {
+                stroke_c: 0.04,
+                stroke_e: 0.03,
+                L_conn_c: 0.12,
+                L_conn_e: 0.1,
+                phase_angle: 90.0,
+            },
+            V_clearance_c: 4e-5,
+            R_c: f64::INFINITY,
+            W_parasitic_c: 0.0,
+            V_clearance_e: 2e-5,
+            R_e: f64::INFINITY,
+            W_parasitic_e: 0.0,
+            Q_parasitic_e: 0.0,
+            appendix_gap: None,
+            wall_heat: Some(wall_heat::Config {
+                correlation: wall_heat::Correlation::Annand { a: 0.6, b: 0.7 },
+                k_gas: 0.2,
+                mu_gas: 2e-5,
+                bore_c: None,
+                bore_e: None,
+            }),
+        });
+        config
+    }
+
+    #[test]
+    fn reaches_nested_component_parameters() {
+        let bore: fn(&mut Config) -> Option<&mut f64> =
+            |config| match &mut config.engine.components.ws {
+                ws::Config::Crank(crank_drive::Config {
+                    layout: crank_drive::Layout::Alpha { D_c, .. },
+                    ..
+                }) => Some(D_c),
+                _ => None,
+            };
+        let conn_rod: fn(&mut Config) -> Option<&mut f64> =
+            |config| match &mut config.engine.components.ws {
+                ws::Config::Crank(crank_drive::Config {
+                    drive: crank_drive::Drive::SliderCrank { L_conn_c, .. },
+                    ..
+                }) => Some(L_conn_c),
+                _ => None,
+            };
+        let annand: fn(&mut Config) -> Option<&mut f64> =
+            |config| match &mut config.engine.components.ws {
+                ws::Config::Crank(crank_drive::Config {
+                    wall_heat:
+                        Some(wall_heat::Config {
+                            correlation: wall_heat::Correlation::Annand { a, .. },
+                            ..
+                        }),
+                    ..
+                }) => Some(a),
+                _ => None,
+            };
+
+        let mut crank = crank_config();
+        assert_eq!(bore(&mut crank), Some(&mut 0.06));
+        assert_eq!(conn_rod(&mut crank), Some(&mut 0.12));
+        assert_eq!(annand(&mut crank), Some(&mut 0.6));
+
+        let mut sinusoidal = config();
+        assert!(bore(&mut sinusoidal).is_none());
+        assert!(conn_rod(&mut sinusoidal).is_none());
+        assert!(annand(&mut sinusoidal).is_none());
+    }
+
+    /// Lists every field of the component configs from outside their
+    /// modules, so this stops compiling if a field is added that a
+    /// `Parameter` cannot reach
+    #[test]
+    #[allow(clippy::too_many_lines)]
+    fn every_component_field_is_public() {
+        let crate::engine::ComponentsConfig {
+            chx,
+            hhx,
+            regen,
+            ws,
+            buffer,
+        } = crank_config().engine.components;
+
+        match chx {
+            chx::Config::FixedApproach(chx::fixed_approach::Config {
+                vol: _,
+                DT: _,
+                R_hyd: _,
+                W_parasitic: _,
+            })
+            | chx::Config::FixedConductance(chx::fixed_conductance::Config {
+                vol: _,
+                UA: _,
+                R_hyd: _,
+                W_parasitic: _,
+            })
+            | chx::Config::GPU3(chx::gpu3::Config {
+                length_total: _,
+                length_ht: _,
+                D_inner: _,
+                D_outer: _,
+                N_total: _,
+                N_shell: _,
+                D_sh: _,
+                Ac_d: _,
+                roughness: _,
+                vol_h: _,
+                m_dot_w: _,
+                coolant: _,
+                m_dot_a: _,
+                UA_a: _,
+                W_parasitic: _,
+            })
+            | chx::Config::Mod2(chx::mod2::Config {
+                geometry:
+                    chx::mod2::Geometry {
+                        tubes:
+                            chx::mod2::Tubes {
+                                length: _,
+                                length_ht: _,
+                                D_outer: _,
+                                D_inner: _,
+                                N_total: _,
+                                roughness: _,
+                                material: _,
+                            },
+                        shell:
+                            chx::mod2::Shell {
+                                R_inner: _,
+                                V_header: _,
+                                Ac_header: _,
+                            },
+                    },
+                m_dot_p_fs: _,
+                W_dot_p_fs: _,
+                n_fs: _,
+                fluid: _,
+                correlation: _,
+            }) => {}
+        }
+
+        match hhx {
+            hhx::Config::FixedApproach(hhx::fixed_approach::Config {
+                vol: _,
+                DT: _,
+                R_hyd: _,
+                W_parasitic: _,
+                Q_parasitic: _,
+            })
+            | hhx::Config::FixedConductance(hhx::fixed_conductance::Config {
+                vol: _,
+                UA: _,
+                R_hyd: _,
+                W_parasitic: _,
+                Q_parasitic: _,
+            })
+            | hhx::Config::GPU3(hhx::gpu3::Config {
+                L_total: _,
+                L_htr: _,
+                R_tc: _,
+                D_outer: _,
+                D_inner: _,
+                roughness: _,
+                N_total: _,
+                vol_h: _,
+                eta_comb: _,
+                R_c_loss: _,
+                W_parasitic: _,
+            })
+            | hhx::Config::Mod2(hhx::mod2::Config {
+                geometry:
+                    hhx::mod2::Geometry {
+                        tubes:
+                            hhx::mod2::Tubes {
+                                L_front: _,
+                                L_rear: _,
+                                L_inactive: _,
+                                D_outer: _,
+                                D_inner: _,
+                                roughness: _,
+                                N_total: _,
+                                materialtube: _,
+                                materialfin: _,
+                            },
+                        shell:
+                            hhx::mod2::Shell {
+                                R_outer: _,
+                                R_inner: _,
+                                V_header: _,
+                            },
+                        fins:
+                            hhx::mod2::Fins {
+                                thickness: _,
+                                pitch: _,
+                                L_fin: _,
+                            },
+                    },
+                correlation: _,
+            })
+            | hhx::Config::GPU3NI(hhx::ni_gpu3::Config {
+                R_f: _,
+                L_f: _,
+                R_regen: _,
+                D_outer: _,
+                D_inner: _,
+                k_f: _,
+                roughness: _,
+                N_total: _,
+                vol_h: _,
+                R_ins: _,
+                W_parasitic: _,
+            })
+            | hhx::Config::Mod2NI(hhx::ni_mod2::Config {
+                R_f: _,
+                L_f: _,
+                R_regen: _,
+                D_outer: _,
+                D_inner: _,
+                k_f: _,
+                roughness: _,
+                N_total: _,
+                vol_h: _,
+                R_ins: _,
+                W_parasitic: _,
+            }) => {}
+        }
+
+        match regen {
+            regen::Config::FixedApproach(regen::fixed_approach::Config {
+                vol: _,
+                DT: _,
+                R_hyd: _,
+                Q_parasitic: _,
+            })
+            | regen::Config::FixedConductance(regen::fixed_conductance::Config {
+                vol: _,
+                UA: _,
+                R_hyd: _,
+                Q_parasitic: _,
+            })
+            | regen::Config::GPU3(regen::gpu3::Config {
+                geometry:
+                    regen::gpu3::Geometry {
+                        vol_h: _,
+                        mesh:
+                            regen::gpu3::Mesh {
+                                material: _,
+                                D_wire: _,
+                                pitch: _,
+                            },
+                        shell:
+                            regen::gpu3::Shell {
+                                diameter: _,
+                                length: _,
+                                number: _,
+                            },
+                    },
+                Q_parasitic: _,
+                bypass: _,
+                correlationf: _,
+                correlationj: _,
+            })
+            | regen::Config::Mod2(regen::mod2::Config {
+                geometry:
+                    regen::mod2::Geometry {
+                        mesh:
+                            regen::mod2::Mesh {
+                                material: _,
+                                D_wire: _,
+                                pitch: _,
+                            },
+                        shell:
+                            regen::mod2::Shell {
+                                material: _,
+                                R_sh: _,
+                                th_sh_cold: _,
+                                th_sh_hot: _,
+                                length: _,
+                            },
+                    },
+                correlationtype: _,
+                correlationf: _,
+                correlationj: _,
+            }) => {}
+        }
+
+        let (appendix_gap, wall_heat) = match ws {
+            ws::Config::Sinusoidal(ws::sinusoidal_drive::Config {
+                frequency: _,
+                phase_angle: _,
+                V_swept_c: _,
+                V_clearance_c: _,
+                R_c: _,
+                W_parasitic_c: _,
+                V_swept_e: _,
+                V_clearance_e: _,
+                R_e: _,
+                W_parasitic_e: _,
+                Q_parasitic_e: _,
+                appendix_gap,
+                wall_heat,
+            })
+            | ws::Config::Rhombic(ws::rhombic_drive::Config {
+                frequency: _,
+                V_clearance_c: _,
+                R_c: _,
+                W_parasitic_c: _,
+                V_clearance_e: _,
+                R_e: _,
+                W_parasitic_e: _,
+                Q_parasitic_e: _,
+                r_crank: _,
+                L_conn: _,
+                eccentricity: _,
+                D_p: _,
+                D_d: _,
+                appendix_gap,
+                wall_heat,
+            }) => (appendix_gap, wall_heat),
+            ws::Config::GPU3(ws::gpu3::Config {
+                frequency: _,
+                V_clearance_c: _,
+                R_c: _,
+                V_clearance_e: _,
+                R_e: _,
+                r_crank: _,
+                L_conn: _,
+                eccentricity: _,
+                D: _,
+                D_dr: _,
+                L: _,
+                h: _,
+            })
+            | ws::Config::Mod2(ws::mod2::Config {
+                frequency: _,
+                phaseAngle: _,
+                D: _,
+                h: _,
+                L: _,
+                stroke: _,
+                V_clearance_c: _,
+                R_c: _,
+                V_clearance_e: _,
+                R_e: _,
+                material_p: _,
+                material_c: _,
+                th_pw: _,
+                th_cw: _,
+                L_cond: _,
+                e: _,
+            }) => (None, None),
+            ws::Config::FreePiston(ws::free_piston::Config {
+                frequency_hint: _,
+                stroke_hint: _,
+                V_mid_c: _,
+                R_c: _,
+                W_parasitic_c: _,
+                V_mid_e: _,
+                R_e: _,
+                W_parasitic_e: _,
+                Q_parasitic_e: _,
+                D_p: _,
+                m_p: _,
+                k_p: _,
+                c_p: _,
+                c2_p: _,
+                D_d: _,
+                D_rod: _,
+                m_d: _,
+                k_d: _,
+                c_d: _,
+                alternator,
+            }) => {
+                if let Some(ws::free_piston::AlternatorConfig {
+                    K_m: _,
+                    R_coil: _,
+                    L_coil: _,
+                    R_load: _,
+                }) = alternator
+                {}
+                (None, None)
+            }
+            ws::Config::Profile(ws::volume_profile::Config {
+                frequency: _,
+                comp,
+                R_c: _,
+                W_parasitic_c: _,
+                exp,
+                R_e: _,
+                W_parasitic_e: _,
+                Q_parasitic_e: _,
+                wall_heat,
+            }) => {
+                for profile in [comp, exp] {
+                    match profile {
+                        ws::volume_profile::ProfileConfig::Table {
+                            crank_angle: _,
+                            volume: _,
+                        }
+                        | ws::volume_profile::ProfileConfig::Fourier {
+                            mean: _,
+                            cos: _,
+                            sin: _,
+                        } => {}
+                    }
+                }
+                (None, wall_heat)
+            }
+            ws::Config::Crank(crank_drive::Config {
+                frequency: _,
+                layout,
+                drive,
+                V_clearance_c: _,
+                R_c: _,
+                W_parasitic_c: _,
+                V_clearance_e: _,
+                R_e: _,
+                W_parasitic_e: _,
+                Q_parasitic_e: _,
+                appendix_gap,
+                wall_heat,
+            }) => {
+                match layout {
+                    crank_drive::Layout::Alpha { D_c: _, D_e: _ }
+                    | crank_drive::Layout::Beta { D: _, D_rod: _ }
+                    | crank_drive::Layout::Gamma {
+                        D_c: _,
+                        D_e: _,
+                        D_rod: _,
+                    } => {}
+                }
+                match drive {
+                    crank_drive::Drive::SliderCrank {
+                        stroke_c: _,
+                        stroke_e: _,
+                        L_conn_c: _,
+                        L_conn_e: _,
+                        phase_angle: _,
+                    }
+                    | crank_drive::Drive::ScotchYoke {
+                        stroke_c: _,
+                        stroke_e: _,
+                        phase_angle: _,
+                    }
+                    | crank_drive::Drive::RossYoke {
+                        r_crank: _,
+                        a_yoke: _,
+                        b_yoke: _,
+                        L_conn: _,
+                    } => {}
+                }
+                (appendix_gap, wall_heat)
+            }
+        };
+
+        if let Some(ws::appendix_gap::Config {
+            width: _,
+            length: _,
+            stroke: _,
+            k_gas: _,
+        }) = appendix_gap
+        {}
+        if let Some(wall_heat::Config {
+            correlation,
+            k_gas: _,
+            mu_gas: _,
+            bore_c: _,
+            bore_e: _,
+        }) = wall_heat
+        {
+            match correlation {
+                wall_heat::Correlation::Annand { a: _, b: _ }
+                | wall_heat::Correlation::Woschni { c1: _ }
+                | wall_heat::Correlation::KornhauserSmith => {}
+            }
+        }
+        if let Some(crate::buffer::Config { vol: _, R_seal: _ }) = buffer {}
+    }
+}
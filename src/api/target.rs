@@ -0,0 +1,193 @@
+use crate::{config::Config, engine::StateSnapshot, types::RunError};
+
+use super::{
+    bracket::{find_crossing, CrossingError, Tolerance},
+    start, RunResults,
+};
+
+/// A config input that is adjusted to reach a `Goal`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Variable {
+    /// Pressure at time zero (Pa), which sets the charge of working fluid
+    ChargePressure,
+
+    /// Source temperature (K)
+    SourceTemperature,
+
+    /// Frequency (Hz) of working spaces that take it as an input
+    Frequency,
+}
+
+/// A result of an engine run to be reached
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Goal {
+    /// Net power (W)
+    NetPower(f64),
+
+    /// Shaft torque (N-m)
+    ShaftTorque(f64),
+
+    /// Total heat input to the engine (W)
+    HeatInput(f64),
+}
+
+/// The value of a `Variable` that reaches a `Goal`
+#[derive(Debug)]
+pub struct Targeted {
+    /// Value of the variable
+    pub value: f64,
+
+    /// The results of a run at that value
+    pub results: RunResults,
+}
+
+/// An error that can occur while reaching a goal
+#[derive(Debug, Clone)]
+pub enum TargetError {
+    /// The variable does not apply to the config, such as frequency for
+    /// free pistons
+    NotApplicable,
+
+    /// The goal is not reached within the range, where `low` and `high` are
+    /// the excess of the result over the goal at either end of the range
+    NotBracketed { low: f64, high: f64 },
+
+    /// The goal was not reached within the iteration limit
+    DidNotConverge,
+
+    /// A run failed with the variable at `value`
+    Run { value: f64, error: RunError },
+}
+
+impl Variable {
+    /// Return the variable within a config
+    fn value(self, config: &mut Config) -> Option<&mut f64> {
+        match self {
+            Variable::ChargePressure => Some(&mut config.conditions.pres_zero),
+            Variable::SourceTemperature => Some(&mut config.conditions.temp_source),
+            Variable::Frequency => config.engine.components.ws.frequency_mut(),
+        }
+    }
+}
+
+impl Goal {
+    /// Return the excess of `results` over the goal
+    fn excess(self, results: &RunResults) -> f64 {
+        match self {
+            Goal::NetPower(power) => results.power.net - power,
+            Goal::ShaftTorque(torque) => results.shaft_torque - torque,
+            Goal::HeatInput(heat) => results.heat_flow.input - heat,
+        }
+    }
+}
+
+/// Find the value of `variable` within `range` at which a run reaches `goal`
+///
+/// The goal is bracketed by the ends of the range, and is found by false
+/// position to within `tol`, which is in the units of the goal.  Each run is
+/// warm started from the previous one.  Since every run only converges to
+/// within the solver tolerances, `tol` must be loose enough that it is not
+/// swamped by them.
+///
+/// # Errors
+///
+/// Will return `Err` if the variable does not apply to the config, if the
+/// goal is not reached within the range or the iteration limit, or if any
+/// run fails to converge.
+///
+/// # Panics
+///
+/// Will panic if an unsupported fluid model is provided.
+pub fn find_target(
+    config: &Config,
+    variable: Variable,
+    range: (f64, f64),
+    goal: Goal,
+    tol: f64,
+) -> Result<Targeted, TargetError> {
+    if variable.value(&mut config.clone()).is_none() {
+        return Err(TargetError::NotApplicable);
+    }
+
+    // Return the results at `value` along with their excess over the goal
+    let mut snapshot: Option<StateSnapshot> = None;
+    let run = |value: f64| {
+        let mut config = config.clone();
+        if let Some(variable) = variable.value(&mut config) {
+            *variable = value;
+        }
+        let engine = start(config, snapshot).map_err(|error| TargetError::Run { value, error })?;
+        snapshot = Some(engine.snapshot());
+        let results = RunResults::from(engine);
+        let excess = goal.excess(&results);
+        Ok((results, excess))
+    };
+
+    let tol = Tolerance {
+        x: 0.0,
+        residual: tol,
+    };
+    match find_crossing(range, tol, run) {
+        Ok(crossing) => Ok(Targeted {
+            value: crossing.x,
+            results: crossing.found,
+        }),
+        Err(CrossingError::NotBracketed { low, high }) => {
+            Err(TargetError::NotBracketed { low, high })
+        }
+        Err(CrossingError::DidNotConverge) => Err(TargetError::DidNotConverge),
+        Err(CrossingError::Eval(err)) => Err(err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api::{run_engine, tests::config};
+
+    use super::*;
+
+    #[test]
+    fn charge_pressure_reaches_net_power() {
+        let power = run_engine(config()).unwrap().power.net;
+        let target = find_target(
+            &config(),
+            Variable::ChargePressure,
+            (5e6, 15e6),
+            Goal::NetPower(0.8 * power),
+            1e-3 * power,
+        )
+        .expect("goal should be reached");
+
+        // Less charge gives less power
+        assert!(target.value > 5e6 && target.value < 10e6);
+        assert!((target.results.power.net - 0.8 * power).abs() <= 1e-3 * power);
+        assert_eq!(target.results.pressure.t_zero, target.value);
+    }
+
+    #[test]
+    fn source_temperature_reaches_heat_input() {
+        let heat = run_engine(config()).unwrap().heat_flow.input;
+        let target = find_target(
+            &config(),
+            Variable::SourceTemperature,
+            (700.0, 1200.0),
+            Goal::HeatInput(1.05 * heat),
+            1e-3 * heat,
+        )
+        .expect("goal should be reached");
+        assert!(target.value > 900.0 && target.value < 1200.0);
+        assert!((target.results.heat_flow.input - 1.05 * heat).abs() <= 1e-3 * heat);
+
+        // A goal beyond the range is not bracketed
+        assert!(matches!(
+            find_target(
+                &config(),
+                Variable::ChargePressure,
+                (5e6, 15e6),
+                Goal::HeatInput(10.0 * heat),
+                1e-3 * heat,
+            ),
+            Err(TargetError::NotBracketed { low, high }) if low < 0.0 && high < 0.0
+        ));
+    }
+}
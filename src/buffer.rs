@@ -0,0 +1,80 @@
+use anyhow::ensure;
+use serde::Deserialize;
+
+#[allow(non_snake_case)]
+/// A buffer space behind the pistons
+///
+/// The buffer fills the rest of the engine housing, so it gives up whatever
+/// volume the working spaces gain.  Its gas is held at the sink temperature
+/// and is connected to the compression space through the piston seal.  The
+/// gas that leaks through the seal stays in the buffer, so its pressure
+/// settles where no gas leaks over a cycle.  A perfect seal keeps the charge
+/// the buffer starts with, at the mean cycle pressure when the working spaces
+/// are at their mean volume.
+#[derive(Debug, Clone, Copy)]
+pub struct Buffer {
+    volume: f64,
+    R_seal: f64,
+}
+
+#[allow(non_snake_case)]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+/// Configuration for a buffer space
+pub struct Config {
+    pub vol: f64,
+    pub R_seal: f64,
+}
+
+#[allow(non_snake_case)]
+impl Buffer {
+    /// Create a buffer space.
+    ///
+    /// # Arguments
+    ///
+    /// * `volume` - the mean volume of the buffer space (in m^3), which must
+    ///   exceed the most the working spaces gain over their mean volume.
+    /// * `R_seal` - the hydraulic resistance of the piston seal (in Pa-s/m^3).
+    ///   A resistance of `f64::INFINITY` models a perfect seal.
+    ///
+    #[must_use]
+    pub fn new(volume: f64, R_seal: f64) -> Self {
+        Self { volume, R_seal }
+    }
+
+    /// Returns the mean volume (m^3) of the buffer space
+    #[must_use]
+    pub fn volume(&self) -> f64 {
+        self.volume
+    }
+
+    /// Returns the hydraulic resistance (Pa-s/m^3) of the piston seal
+    #[must_use]
+    pub fn R_seal(&self) -> f64 {
+        self.R_seal
+    }
+
+    /// Returns the volumetric flow rate (m^3/s) through the piston seal from
+    /// the compression space into the buffer
+    #[must_use]
+    pub fn seal_flow(&self, pres_comp: f64, pres_buffer: f64) -> f64 {
+        (pres_comp - pres_buffer) / self.R_seal
+    }
+}
+
+impl TryFrom<Config> for Buffer {
+    type Error = anyhow::Error;
+
+    fn try_from(config: Config) -> Result<Self, Self::Error> {
+        ensure!(
+            config.vol > 0.0 && config.vol.is_finite(),
+            "buffer volume must be positive, not {}",
+            config.vol
+        );
+        ensure!(
+            config.R_seal > 0.0,
+            "seal resistance must be positive, not {}",
+            config.R_seal
+        );
+        Ok(Self::new(config.vol, config.R_seal))
+    }
+}
@@ -1,7 +1,7 @@
-mod fixed_approach;
-mod fixed_conductance;
-mod gpu3;
-mod mod2;
+pub mod fixed_approach;
+pub mod fixed_conductance;
+pub mod gpu3;
+pub mod mod2;
 
 // Export all available cold heat exchanger components
 pub use fixed_approach::FixedApproach;
@@ -47,7 +47,7 @@ pub struct State {
 }
 
 #[allow(non_snake_case)]
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum Config {
     FixedApproach(fixed_approach::Config),
@@ -19,7 +19,7 @@ pub struct FixedApproach {
 }
 
 #[allow(non_snake_case)]
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
 /// Configuration for a fixed approach cold heat exchanger.
 pub struct Config {
     pub vol: f64,
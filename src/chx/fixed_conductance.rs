@@ -21,13 +21,13 @@ pub struct FixedConductance {
 }
 
 #[allow(non_snake_case)]
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
 /// Configuration for a fixed conductance cold heat exchanger.
 pub struct Config {
-    vol: f64,
-    UA: f64,
-    R_hyd: f64,
-    W_parasitic: f64,
+    pub vol: f64,
+    pub UA: f64,
+    pub R_hyd: f64,
+    pub W_parasitic: f64,
 }
 
 #[allow(non_snake_case)]
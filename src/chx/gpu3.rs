@@ -7,27 +7,27 @@ use super::{ColdHeatExchanger, State};
 pub struct GPU3 {}
 
 #[allow(non_snake_case)]
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
 /// Configuration for a GPU3 cold heat exchanger.
 pub struct Config {
-    length_total: f64,
-    length_ht: f64,
-    D_inner: f64,
-    D_outer: f64,
-    N_total: u32,
-    N_shell: u32,
-    D_sh: f64,
-    Ac_d: f64,
-    roughness: f64,
-    vol_h: f64,
-    m_dot_w: f64,
-    coolant: Coolant,
-    m_dot_a: f64,
-    UA_a: f64,
-    W_parasitic: f64,
+    pub length_total: f64,
+    pub length_ht: f64,
+    pub D_inner: f64,
+    pub D_outer: f64,
+    pub N_total: u32,
+    pub N_shell: u32,
+    pub D_sh: f64,
+    pub Ac_d: f64,
+    pub roughness: f64,
+    pub vol_h: f64,
+    pub m_dot_w: f64,
+    pub coolant: Coolant,
+    pub m_dot_a: f64,
+    pub UA_a: f64,
+    pub W_parasitic: f64,
 }
 
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
 pub enum Coolant {
     Water,
 }
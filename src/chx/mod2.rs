@@ -7,50 +7,50 @@ use super::{ColdHeatExchanger, State};
 pub struct Mod2 {}
 
 #[allow(non_snake_case)]
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
 /// Configuration for a Mod II/I cold heat exchanger.
 pub struct Config {
-    geometry: Geometry,
-    m_dot_p_fs: f64,
-    W_dot_p_fs: f64,
-    n_fs: f64,
-    fluid: Fluid,
-    correlation: Correlation,
+    pub geometry: Geometry,
+    pub m_dot_p_fs: f64,
+    pub W_dot_p_fs: f64,
+    pub n_fs: f64,
+    pub fluid: Fluid,
+    pub correlation: Correlation,
 }
 
 #[allow(non_snake_case)]
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
 pub struct Geometry {
-    tubes: Tubes,
-    shell: Shell,
+    pub tubes: Tubes,
+    pub shell: Shell,
 }
 
 #[allow(non_snake_case)]
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
 pub struct Tubes {
-    length: f64,
-    length_ht: f64,
-    D_outer: f64,
-    D_inner: f64,
-    N_total: u32,
-    roughness: f64,
-    material: Material,
+    pub length: f64,
+    pub length_ht: f64,
+    pub D_outer: f64,
+    pub D_inner: f64,
+    pub N_total: u32,
+    pub roughness: f64,
+    pub material: Material,
 }
 
 #[allow(non_snake_case)]
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
 pub struct Shell {
-    R_inner: f64,
-    V_header: f64,
-    Ac_header: f64,
+    pub R_inner: f64,
+    pub V_header: f64,
+    pub Ac_header: f64,
 }
 
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
 pub enum Fluid {
     Water,
 }
 
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
 pub enum Correlation {
     Oscillating,
     Steady,
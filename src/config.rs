@@ -6,7 +6,7 @@ use crate::{
     ws,
 };
 
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
 pub struct Config {
     pub engine: engine::Config,
     pub solver: SolverConfig,
@@ -34,6 +34,7 @@ impl From<Legacy> for Config {
                     hhx: legacy_config.hhx.into(),
                     regen: legacy_config.regen.into(),
                     ws: legacy_config.ws.into(),
+                    buffer: None,
                 },
             },
             solver: legacy_config.solver.into(),
@@ -45,10 +46,11 @@ impl From<Legacy> for Config {
 #[cfg(test)]
 mod test {
     use crate::{
-        chx, engine, fluid, hhx, regen,
+        buffer, chx, engine, fluid, hhx, regen,
         types::{
-            ConditionsConfig, InnerLoopConfig, OdeConfig, OuterLoopConfig, SolverConfig,
-            ToleranceConfig, DEFAULT_MAX_ITERS,
+            BudgetConfig, ConditionsConfig, Decomposition, Discretization, InnerLoopConfig,
+            OdeConfig, OuterLoopConfig, OutputGrid, PressureModel, PropertyEvaluation, RunSettings,
+            SolverConfig, SteadyStateMethod, ToleranceConfig, DEFAULT_MAX_ITERS,
         },
         ws,
     };
@@ -118,18 +120,38 @@ mod test {
             R_e = inf
             W_parasitic_e = 0
             Q_parasitic_e = 0
-            
+
+            [engine.components.buffer]
+            vol = 2e-3
+            R_seal = 1e12
+
+            [solver]
+            decomposition = "robust"
+            pressure_model = "momentum"
+            properties = "direct"
+            steady_state = { harmonic_balance = { harmonics = 12 } }
+
+            [solver.discretization]
+            regen = 8
+
+            [solver.budget]
+            wall_time = 60
+            rhs_evals = 1000000
+
             [solver.inner_loop]
             tolerance = { abs = 1e-6, rel = 1e-6 }
             max_iterations = 10
+            motion = { abs = 1e-3, rel = 1e-4 }
 
             [solver.outer_loop]
             tolerance = { abs = 1e-8, rel = 1e-8 }
             max_iterations = 10
+            heat_flow = { abs = 1e-2, rel = 1e-4 }
 
             [solver.ode]
             tolerance = { abs = 1e-8, rel = 1e-8 }
             num_timesteps = 20
+            output_grid = { refined_at_flow_reversal = { extra_points = 4 } }
 
             [conditions]
             temp_sink = 20
@@ -144,6 +166,10 @@ mod test {
                         hhx: hhx::Config::FixedApproach(Default::default()),
                         regen: regen::Config::FixedApproach(Default::default()),
                         ws: ws::Config::Sinusoidal(Default::default()),
+                        buffer: Some(buffer::Config {
+                            vol: 2e-3,
+                            R_seal: 1e12,
+                        }),
                     },
                 },
                 solver: SolverConfig {
@@ -153,6 +179,10 @@ mod test {
                             rel: 1e-6,
                         },
                         max_iterations: 10,
+                        motion: Some(ToleranceConfig {
+                            abs: 1e-3,
+                            rel: 1e-4,
+                        }),
                     },
                     outer_loop: OuterLoopConfig {
                         tolerance: ToleranceConfig {
@@ -160,6 +190,12 @@ mod test {
                             rel: 1e-8,
                         },
                         max_iterations: 10,
+                        pressure: None,
+                        mass_flow: None,
+                        heat_flow: Some(ToleranceConfig {
+                            abs: 1e-2,
+                            rel: 1e-4,
+                        }),
                     },
                     ode: OdeConfig {
                         tolerance: ToleranceConfig {
@@ -167,6 +203,21 @@ mod test {
                             rel: 1e-8,
                         },
                         num_timesteps: 20,
+                        output_grid: OutputGrid::RefinedAtFlowReversal { extra_points: 4 },
+                    },
+                    decomposition: Decomposition::Robust,
+                    discretization: Discretization {
+                        chx: 1,
+                        regen: 8,
+                        hhx: 1,
+                    },
+                    pressure_model: PressureModel::Momentum,
+                    properties: PropertyEvaluation::Direct,
+                    steady_state: SteadyStateMethod::HarmonicBalance { harmonics: 12 },
+                    budget: BudgetConfig {
+                        wall_time: Some(60.),
+                        rhs_evals: Some(1_000_000),
+                        steps: None,
                     },
                 },
                 conditions: ConditionsConfig {
@@ -178,6 +229,70 @@ mod test {
         )
     }
 
+    #[test]
+    fn rejects_heat_exchangers_without_control_volumes() {
+        let solver = |regen: u32| {
+            let toml_str = format!(
+                r#"
+                inner_loop = {{ tolerance = {{ abs = 1e-2, rel = 1e-4 }}, max_iterations = 10 }}
+                outer_loop = {{ tolerance = {{ abs = 1e-2, rel = 1e-4 }}, max_iterations = 10 }}
+                ode = {{ tolerance = {{ abs = 1e-6, rel = 1e-6 }}, num_timesteps = 20 }}
+                discretization = {{ regen = {regen} }}
+                "#
+            );
+            config::Config::builder()
+                .add_source(config::File::from_str(&toml_str, config::FileFormat::Toml))
+                .build()
+                .unwrap()
+                .try_deserialize::<SolverConfig>()
+                .unwrap()
+        };
+        assert!(RunSettings::try_from(solver(4)).is_ok());
+        assert!(RunSettings::try_from(solver(0)).is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_wall_time_budget() {
+        let solver = |wall_time: &str| {
+            let toml_str = format!(
+                r#"
+                inner_loop = {{ tolerance = {{ abs = 1e-2, rel = 1e-4 }}, max_iterations = 10 }}
+                outer_loop = {{ tolerance = {{ abs = 1e-2, rel = 1e-4 }}, max_iterations = 10 }}
+                ode = {{ tolerance = {{ abs = 1e-6, rel = 1e-6 }}, num_timesteps = 20 }}
+                budget = {{ wall_time = {wall_time} }}
+                "#
+            );
+            config::Config::builder()
+                .add_source(config::File::from_str(&toml_str, config::FileFormat::Toml))
+                .build()
+                .unwrap()
+                .try_deserialize::<SolverConfig>()
+                .unwrap()
+        };
+        assert!(RunSettings::try_from(solver("60")).is_ok());
+        assert!(RunSettings::try_from(solver("-1")).is_err());
+        assert!(RunSettings::try_from(solver("nan")).is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_buffer() {
+        let buffer = |vol: &str, resistance: &str| {
+            let toml_str = format!("vol = {vol}\nR_seal = {resistance}");
+            config::Config::builder()
+                .add_source(config::File::from_str(&toml_str, config::FileFormat::Toml))
+                .build()
+                .unwrap()
+                .try_deserialize::<buffer::Config>()
+                .unwrap()
+        };
+        assert!(buffer::Buffer::try_from(buffer("2e-3", "1e12")).is_ok());
+        assert!(buffer::Buffer::try_from(buffer("2e-3", "inf")).is_ok());
+        assert!(buffer::Buffer::try_from(buffer("2e-3", "0")).is_err());
+        assert!(buffer::Buffer::try_from(buffer("2e-3", "-1e12")).is_err());
+        assert!(buffer::Buffer::try_from(buffer("2e-3", "nan")).is_err());
+        assert!(buffer::Buffer::try_from(buffer("0", "1e12")).is_err());
+    }
+
     #[test]
     fn deserialize_legacy_config() {
         check_legacy_config(
@@ -269,6 +384,7 @@ mod test {
                             R_e: 1e300,
                             ..Default::default()
                         }),
+                        buffer: None,
                     },
                 },
                 solver: SolverConfig {
@@ -278,6 +394,7 @@ mod test {
                             rel: 1e-6,
                         },
                         max_iterations: DEFAULT_MAX_ITERS,
+                        motion: None,
                     },
                     outer_loop: OuterLoopConfig {
                         tolerance: ToleranceConfig {
@@ -285,6 +402,9 @@ mod test {
                             rel: 1e-8,
                         },
                         max_iterations: DEFAULT_MAX_ITERS,
+                        pressure: None,
+                        mass_flow: None,
+                        heat_flow: None,
                     },
                     ode: OdeConfig {
                         tolerance: ToleranceConfig {
@@ -292,7 +412,14 @@ mod test {
                             rel: 1e-8,
                         },
                         num_timesteps: 20,
+                        output_grid: OutputGrid::Uniform,
                     },
+                    decomposition: Decomposition::Lu,
+                    discretization: Discretization::default(),
+                    pressure_model: PressureModel::Uniform,
+                    properties: PropertyEvaluation::Tabulated,
+                    steady_state: SteadyStateMethod::TimeMarching,
+                    budget: BudgetConfig::default(),
                 },
                 conditions: ConditionsConfig {
                     temp_sink: 20.,
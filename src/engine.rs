@@ -1,24 +1,61 @@
 mod run;
 pub(crate) mod state;
+mod transient;
+
+use std::time::Instant;
 
 use serde::Deserialize;
 
 use crate::{
-    chx,
+    buffer, chx,
     fluid::{self, Fluid},
-    hhx, regen,
-    state_equations::{Cycle, MatrixDecomposition, SteadyStateInputs},
-    types::{RunError, RunInputs, RunSettings},
+    hhx,
+    observer::Observer,
+    regen,
+    state_equations::{
+        Cycle, DenseOutput, EliminationSolver, Interrupted, Limits, LuSolver, MatrixDecomposition,
+        QrSolver, RobustSolver, SteadyStateInputs, SvdDefaultSolver,
+    },
+    types::{
+        ConvergenceReport, Decomposition, Interruption, PartialRun, RunError, RunInputs,
+        RunSettings, SolverStats,
+    },
     ws,
 };
 
-pub use state::Pressure;
+pub use state::{Pressure, State, StateSnapshot};
+pub use transient::Transient;
 
 /// Represents a Stirling engine running at cyclic steady state
 pub struct Engine<T: Fluid> {
     pub components: Components,
-    pub state: state::State<T>,
+    pub state: State<T>,
     pub values: state::Values,
+    pub stats: SolverStats,
+    pub convergence: ConvergenceReport,
+    dense_output: DenseOutput,
+    settings: RunSettings,
+}
+
+/// Options for an engine run beyond its inputs and settings
+///
+/// The run starts from `hint` when one is provided, and reports its progress
+/// to `observer`.  The default options use an LU decomposition, start from
+/// the components' initial approaches, and observe nothing.
+pub struct RunOptions<'a, T: Fluid> {
+    pub decomposition: Decomposition,
+    pub hint: Option<StateSnapshot>,
+    pub observer: Option<&'a mut dyn Observer<T>>,
+}
+
+impl<T: Fluid> Default for RunOptions<'_, T> {
+    fn default() -> Self {
+        Self {
+            decomposition: Decomposition::default(),
+            hint: None,
+            observer: None,
+        }
+    }
 }
 
 /// The components of a Stirling engine
@@ -27,6 +64,7 @@ pub struct Components {
     pub chx: Box<dyn chx::ColdHeatExchanger>,
     pub regen: Box<dyn regen::Regenerator>,
     pub hhx: Box<dyn hhx::HotHeatExchanger>,
+    pub buffer: Option<buffer::Buffer>,
 }
 
 impl<T: Fluid> Engine<T> {
@@ -43,61 +81,237 @@ impl<T: Fluid> Engine<T> {
         inputs: RunInputs,
         settings: RunSettings,
     ) -> Result<Self, RunError> {
-        let mut state = state::State::new_hint(&components, fluid, inputs);
-        for _ in 0..settings.max_iters.outer {
-            let run: run::Run<T, U> = run::Run::new(&components, &state);
-            let values = run
-                .find_steady_state(SteadyStateInputs {
-                    pres_zero: state.pres.t_zero,
-                    temp_comp_hint: state.temp.chx,
-                    temp_exp_hint: state.temp.hhx,
-                    num_points: settings.resolution,
-                    ode_tol: settings.ode_tol,
-                    conv_tol: settings.loop_tol.inner,
-                    max_iters: settings.max_iters.inner,
-                })
-                .map_err(|_| RunError::InnerLoop)?;
-            let values = values.into(); // convert state equation values to engine values
-            match state.update(&components, &values, settings.loop_tol.outer) {
-                Ok(new_state) => {
-                    state = new_state;
-                }
-                Err(state) => {
-                    return Ok(Engine {
-                        components,
-                        state,
-                        values,
-                    });
-                }
+        Self::start::<U>(components, fluid, inputs, settings, None, &mut ())
+    }
+
+    /// Attempt to create a running `Engine` with `RunOptions`
+    ///
+    /// The options choose the matrix decomposition at runtime, and can start
+    /// the run from a `StateSnapshot` or report its progress to an `Observer`.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err<RunError>` if a converged engine cannot be created,
+    /// including `RunError::Interrupted` if the observer breaks out of the run.
+    pub fn run_with(
+        components: Components,
+        fluid: T,
+        inputs: RunInputs,
+        settings: RunSettings,
+        options: RunOptions<T>,
+    ) -> Result<Self, RunError> {
+        let RunOptions {
+            decomposition,
+            hint,
+            observer,
+        } = options;
+        let mut unobserved = ();
+        let observer: &mut dyn Observer<T> = match observer {
+            Some(observer) => observer,
+            None => &mut unobserved,
+        };
+        let start = match decomposition {
+            Decomposition::Lu => Self::start::<LuSolver>,
+            Decomposition::Qr => Self::start::<QrSolver>,
+            Decomposition::Svd => Self::start::<SvdDefaultSolver>,
+            Decomposition::Robust => Self::start::<RobustSolver>,
+            Decomposition::Elimination => Self::start::<EliminationSolver>,
+        };
+        start(components, fluid, inputs, settings, hint, observer)
+    }
+
+    /// Return a `StateSnapshot` of the converged state
+    pub fn snapshot(&self) -> StateSnapshot {
+        StateSnapshot::new(&self.state, &self.values)
+    }
+
+    /// Run the outer loop from an optional `StateSnapshot`
+    fn start<U: MatrixDecomposition>(
+        components: Components,
+        fluid: T,
+        inputs: RunInputs,
+        settings: RunSettings,
+        hint: Option<StateSnapshot>,
+        observer: &mut dyn Observer<T>,
+    ) -> Result<Self, RunError> {
+        // Each inner loop starts where the last one converged, so that the
+        // outer loop only perturbs a converged cycle, and a snapshot also
+        // provides the start of the first
+        let (mut state, ic_hint) = match hint {
+            Some(snapshot) => (
+                State::from_snapshot(fluid, inputs, snapshot),
+                Some((snapshot.temp_comp_zero, snapshot.temp_exp_zero)),
+            ),
+            None => (State::new_hint(&components, fluid, inputs), None),
+        };
+        run::check_buffer(&components, &state, None)?;
+        let mut temp_zero = ic_hint.unwrap_or((state.temp.chx, state.temp.hhx));
+        let mut motion_hint = None;
+        let mut limits = Limits {
+            budget: settings.budget,
+            cancel: settings.cancel.clone(),
+            start: Instant::now(),
+            spent: SolverStats::default(),
+        };
+        let mut convergence = ConvergenceReport::default();
+        for iteration in 0..settings.max_iters.outer {
+            let stop = if observer.outer_iteration_start(iteration, &state).is_break() {
+                Some(Interruption::Cancelled)
+            } else {
+                limits.check(SolverStats::default())
             };
+            if let Some(reason) = stop {
+                return Err(interrupted(
+                    reason,
+                    &state,
+                    temp_zero,
+                    limits.spent,
+                    convergence,
+                ));
+            }
+            let run: run::Run<T, U> = run::Run::new(&components, &state, &settings);
+            let (temp_comp_hint, temp_exp_hint) = temp_zero;
+            let steady_state = run
+                .find_steady_state(
+                    SteadyStateInputs {
+                        pres_zero: run.pres_zero(),
+                        temp_comp_hint,
+                        temp_exp_hint,
+                        motion_hint,
+                        num_points: settings.resolution,
+                        grid: settings.grid,
+                        ode_tol: settings.ode_tol,
+                        conv_tol: settings.loop_tol.inner,
+                        motion_tol: settings.loop_tol.motion,
+                        max_iters: settings.max_iters.inner,
+                        method: settings.steady_state,
+                        limits: limits.clone(),
+                    },
+                    &mut *observer,
+                )
+                .map_err(|err| match err.downcast_ref::<Interrupted>() {
+                    Some(&Interrupted { reason, stats }) => {
+                        let mut spent = limits.spent;
+                        spent += stats;
+                        interrupted(reason, &state, temp_zero, spent, convergence.clone())
+                    }
+                    None => RunError::InnerLoop,
+                })?;
+            limits.spent += steady_state.stats;
+            let dense_output = steady_state.dense_output;
+
+            // Free pistons start the next inner loop where this one converged
+            motion_hint = Some(dense_output.initial_conditions().motion);
+            let values: state::Values = steady_state.values.into(); // convert state equation values to engine values
+            run::check_buffer(&components, &state, Some(&values))?;
+            temp_zero = (values.T_c[0], values.T_e[0]);
+            let (new_state, unconverged, converged) =
+                match state.update(&components, &values, settings.loop_tol.outer) {
+                    Ok((new_state, unconverged)) => (new_state, unconverged, false),
+                    Err(state) => (state, Vec::new(), true),
+                };
+            state = new_state;
+            let flow = observer.outer_iteration_end(iteration, &state, &unconverged);
+            convergence.unconverged.push(unconverged);
+            if flow.is_break() {
+                return Err(interrupted(
+                    Interruption::Cancelled,
+                    &state,
+                    temp_zero,
+                    limits.spent,
+                    convergence,
+                ));
+            }
+            if converged {
+                return Ok(Engine {
+                    components,
+                    state,
+                    values,
+                    stats: limits.spent,
+                    convergence,
+                    dense_output,
+                    settings,
+                });
+            }
         }
 
         Err(RunError::OuterLoop)
     }
+
+    /// Return the settings used for this run
+    pub fn settings(&self) -> &RunSettings {
+        &self.settings
+    }
+
+    /// Calculate state values at arbitrary crank angles (degrees)
+    ///
+    /// The values come from the dense output of the converged cycle, so the
+    /// state equations are not integrated again.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err<RunError>` if the state equations cannot be solved at
+    /// any of the crank angles.
+    pub fn values_at<U: MatrixDecomposition>(
+        &self,
+        crank_angles: &[f64],
+    ) -> Result<state::Values, RunError> {
+        let run: run::Run<T, U> = run::Run::new(&self.components, &self.state, &self.settings);
+        let (values, _) = self
+            .dense_output
+            .values_at_angles(&run, crank_angles)
+            .map_err(|_| RunError::InnerLoop)?;
+        Ok(values.into())
+    }
+}
+
+/// Return the `RunError` for a run that was interrupted at `state`
+fn interrupted<T: Fluid>(
+    reason: Interruption,
+    state: &State<T>,
+    temp_zero: (f64, f64),
+    spent: SolverStats,
+    convergence: ConvergenceReport,
+) -> RunError {
+    RunError::Interrupted(Box::new(PartialRun {
+        reason,
+        state: StateSnapshot::with_temp_zero(state, temp_zero),
+        stats: spent,
+        convergence,
+    }))
 }
 
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
 pub struct Config {
     pub fluid: fluid::Config,
     pub components: ComponentsConfig,
 }
 
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
 pub struct ComponentsConfig {
     pub chx: chx::Config,
     pub hhx: hhx::Config,
     pub regen: regen::Config,
     pub ws: ws::Config,
+    #[serde(default)]
+    pub buffer: Option<buffer::Config>,
 }
 
-impl From<ComponentsConfig> for Components {
-    fn from(config: ComponentsConfig) -> Self {
-        Self {
+impl TryFrom<ComponentsConfig> for Components {
+    type Error = anyhow::Error;
+
+    fn try_from(config: ComponentsConfig) -> Result<Self, Self::Error> {
+        Ok(Self {
             ws: match config.ws {
-                ws::Config::Sinusoidal(config) => Box::<ws::SinusoidalDrive>::new(config.into()),
+                ws::Config::Sinusoidal(config) => {
+                    Box::<ws::SinusoidalDrive>::new(config.try_into()?)
+                }
                 ws::Config::Rhombic(config) => Box::<ws::RhombicDrive>::new(config.into()),
                 ws::Config::GPU3(config) => Box::<ws::GPU3>::new(config.into()),
                 ws::Config::Mod2(config) => Box::<ws::Mod2>::new(config.into()),
+                ws::Config::FreePiston(config) => Box::<ws::FreePiston>::new(config.try_into()?),
+                ws::Config::Profile(config) => Box::<ws::VolumeProfile>::new(config.try_into()?),
+                ws::Config::Crank(config) => Box::<ws::CrankDrive>::new(config.try_into()?),
             },
             chx: match config.chx {
                 chx::Config::FixedApproach(config) => Box::<chx::FixedApproach>::new(config.into()),
@@ -127,17 +341,33 @@ impl From<ComponentsConfig> for Components {
                 hhx::Config::GPU3NI(config) => Box::<hhx::NuclearIsomerGPU3>::new(config.into()),
                 hhx::Config::Mod2NI(config) => Box::<hhx::NuclearIsomerMod2>::new(config.into()),
             },
-        }
+            buffer: config.buffer.map(TryInto::try_into).transpose()?,
+        })
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::ops::ControlFlow;
+
+    use approx::assert_relative_eq;
+
     use crate::{
         fluid::IdealGas,
+        observer::{CycleObserver, Residuals},
+        performance::{Performance, PressuresWithDrops},
         state_equations::LuSolver,
-        types::{ConvergenceTolerance, LoopTolerance, MaxIters, OdeTolerance},
-        ws::{sinusoidal_drive::Geometry, Parasitics, ThermalResistance},
+        types::{
+            Budget, CancelToken, ConvergenceTolerance, Discretization, LoopTolerance, MaxIters,
+            MetalTemperatures, OdeTolerance, OuterTolerance, OutputGrid, ParasiticPower,
+            PressureModel, PropertyEvaluation, Quantity, SteadyStateMethod, ThermalMass,
+            ThermalMasses, DEFAULT_MOTION_TOL,
+        },
+        ws::{
+            free_piston::{Alternator, Displacer, Piston},
+            sinusoidal_drive::Geometry,
+            wall_heat, Parasitics, ThermalResistance,
+        },
     };
 
     use super::*;
@@ -168,34 +398,78 @@ mod tests {
             },
             thermal_resistance: ThermalResistance::default(),
             parasitics: Parasitics::default(),
+            appendix_gap: None,
+            wall_heat: None,
         })
     }
 
-    #[test]
-    fn run_simple_engine() {
-        let components = Components {
+    fn ws_free_piston() -> Box<ws::FreePiston> {
+        Box::new(ws::FreePiston {
+            frequency_hint: 60.0,
+            stroke_hint: 0.02,
+            comp_volume: 2e-4,
+            exp_volume: 1e-4,
+            piston: Piston {
+                mass: 5.0,
+                area: 3e-3,
+                stiffness: 5e5,
+                damping: 0.0,
+                quadratic_damping: 50.0,
+            },
+            displacer: Displacer {
+                mass: 0.5,
+                area: 3e-3,
+                rod_area: 3e-4,
+                stiffness: 1e5,
+                damping: 200.0,
+            },
+            alternator: Some(Alternator {
+                motor_constant: 20.0,
+                coil_resistance: 0.5,
+                coil_inductance: 0.01,
+                load_resistance: 9.5,
+            }),
+            thermal_resistance: ThermalResistance::default(),
+            parasitics: Parasitics::default(),
+        })
+    }
+
+    fn components() -> Components {
+        Components {
             ws: ws_sinusoidal(),
             chx: chx_fixed_approach(),
             regen: regen_fixed_approach(),
             hhx: hhx_fixed_approach(),
-        };
-        let fluid = IdealGas::hydrogen();
-        let inputs = RunInputs {
+            buffer: None,
+        }
+    }
+
+    fn inputs() -> RunInputs {
+        RunInputs {
             pres_zero: 10e6,
             temp_sink: 300.,
             temp_source: 900.,
-        };
-        let settings = RunSettings {
+        }
+    }
+
+    fn settings() -> RunSettings {
+        RunSettings {
             resolution: 30,
+            grid: OutputGrid::Uniform,
+            discretization: Discretization::default(),
+            pressure_model: PressureModel::Uniform,
+            properties: PropertyEvaluation::Tabulated,
             loop_tol: LoopTolerance {
                 inner: ConvergenceTolerance {
                     abs: 1e-3,
                     rel: 1e-6,
                 },
+                motion: DEFAULT_MOTION_TOL,
                 outer: ConvergenceTolerance {
                     abs: 1e-3,
                     rel: 1e-6,
-                },
+                }
+                .into(),
             },
             ode_tol: OdeTolerance {
                 abs: 1e-6,
@@ -205,8 +479,911 @@ mod tests {
                 inner: 20,
                 outer: 20,
             },
+            steady_state: SteadyStateMethod::TimeMarching,
+            budget: Budget::default(),
+            cancel: None,
+        }
+    }
+
+    #[test]
+    fn run_simple_engine() {
+        let fluid = IdealGas::hydrogen();
+        let _engine = Engine::run::<LuSolver>(components(), fluid, inputs(), settings())
+            .expect("engine should converge");
+    }
+
+    #[test]
+    fn run_with_robust_decomposition() {
+        let fluid = IdealGas::hydrogen();
+        let options = RunOptions {
+            decomposition: Decomposition::Robust,
+            ..RunOptions::default()
+        };
+        let engine = Engine::run_with(components(), fluid, inputs(), settings(), options)
+            .expect("engine should converge");
+        assert_eq!(
+            engine.stats.matrix_fallbacks, 0,
+            "a well-posed engine should not need fallbacks"
+        );
+    }
+
+    #[test]
+    fn overall_efficiency_without_alternator() {
+        let parasitics = Parasitics {
+            comp: ParasiticPower {
+                electrical: 10.0,
+                ..ParasiticPower::default()
+            },
+            exp: ParasiticPower {
+                electrical: 5.0,
+                ..ParasiticPower::default()
+            },
+        };
+        let components = Components {
+            ws: Box::new(ws::SinusoidalDrive {
+                parasitics,
+                ..*ws_sinusoidal()
+            }),
+            ..components()
+        };
+        let engine =
+            Engine::run::<LuSolver>(components, IdealGas::hydrogen(), inputs(), settings())
+                .expect("engine should converge");
+        let results = crate::api::RunResults::from(engine);
+
+        // A kinematic engine drives its electrical parasitics from its net
+        // power, and has no electrical output of its own
+        assert_eq!(results.power.electrical, 0.0);
+        assert!(results.efficiency.overall > 0.0);
+        assert_relative_eq!(
+            results.efficiency.overall,
+            (results.power.net - 15.0) / results.heat_flow.input,
+            max_relative = 1e-12
+        );
+        assert!(results.efficiency.overall < results.efficiency.mechanical);
+    }
+
+    #[test]
+    fn crank_torque_averages_to_indicated_power() {
+        let components = Components {
+            ws: Box::<ws::RhombicDrive>::default(),
+            ..components()
+        };
+        let engine =
+            Engine::run::<LuSolver>(components, IdealGas::hydrogen(), inputs(), settings())
+                .expect("engine should converge");
+        let performance = Performance::from(&engine);
+        let crank = performance.crank.expect("a rhombic drive has a crank");
+        let forces = crank.forces.expect("a rhombic drive has kinematics");
+        assert_eq!(forces.len(), engine.values.time.len());
+
+        // The torque averages to the indicated power, and the mean pressure
+        // of the buffer does no work over the cycle
+        let time = &engine.values.time;
+        let work: f64 = (1..time.len())
+            .map(|i| 0.5 * (crank.torque[i] + crank.torque[i - 1]) * (time[i] - time[i - 1]))
+            .sum();
+        let omega = 2. * std::f64::consts::PI * performance.frequency;
+        assert_relative_eq!(
+            work * omega * performance.frequency,
+            performance.power.indicated,
+            max_relative = 1e-9
+        );
+        assert!(crank.energy_fluctuation > 0.0);
+        assert!(forces.iter().any(|f| f.piston_rod > 0.0));
+
+        // A flywheel holding the speed within 2% absorbs the fluctuation
+        let inertia = crate::api::RunResults::from(engine)
+            .flywheel_inertia(0.02)
+            .expect("a rhombic drive has a crank");
+        assert_relative_eq!(
+            0.02 * inertia * omega.powi(2),
+            crank.energy_fluctuation,
+            max_relative = 1e-12
+        );
+    }
+
+    #[test]
+    fn rejects_buffer_smaller_than_swing() {
+        // The working spaces swing by about 3.5e-4 m^3 about their mean
+        let buffer = |vol| Components {
+            buffer: Some(buffer::Buffer::new(vol, f64::INFINITY)),
+            ..components()
+        };
+        let Err(RunError::InvalidConfig(message)) =
+            Engine::run::<LuSolver>(buffer(3e-4), IdealGas::hydrogen(), inputs(), settings())
+        else {
+            panic!("a buffer smaller than the swing should be rejected");
+        };
+        assert!(message.contains("buffer volume"));
+        assert!(
+            Engine::run::<LuSolver>(buffer(4e-4), IdealGas::hydrogen(), inputs(), settings())
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn seal_leaks_into_buffer() {
+        let buffer = |resistance| Components {
+            buffer: Some(buffer::Buffer::new(5e-3, resistance)),
+            ..components()
+        };
+        let sealed = Engine::run::<LuSolver>(
+            buffer(f64::INFINITY),
+            IdealGas::hydrogen(),
+            inputs(),
+            settings(),
+        )
+        .expect("engine should converge");
+        let fine = RunSettings {
+            resolution: 361,
+            ..settings()
+        };
+        let leaky = Engine::run::<LuSolver>(buffer(1e12), IdealGas::hydrogen(), inputs(), fine)
+            .expect("engine should converge");
+
+        // The buffer is compressed as the working spaces expand
+        let values = &sealed.values;
+        let (i_max, _) = values
+            .V_c
+            .iter()
+            .zip(&values.V_e)
+            .map(|(comp, exp)| comp + exp)
+            .enumerate()
+            .fold(
+                (0, 0.0),
+                |max, (i, vol)| if vol > max.1 { (i, vol) } else { max },
+            );
+        assert!(values.P_b[i_max] > sealed.state.pres.avg);
+        assert!(values.m_dot_leak.iter().all(|&m_dot| m_dot == 0.0));
+
+        // Gas leaks both ways through the seal, and the buffer keeps what
+        // leaks in, so that its mass follows the leak over the cycle
+        let values = &leaky.values;
+        let m_dot_leak = &values.m_dot_leak;
+        assert!(m_dot_leak.iter().any(|&m_dot| m_dot > 0.0));
+        assert!(m_dot_leak.iter().any(|&m_dot| m_dot < 0.0));
+        let vol_ws: Vec<f64> = values
+            .V_c
+            .iter()
+            .zip(&values.V_e)
+            .map(|(c, e)| c + e)
+            .collect();
+        let vol_ws_mean = vol_ws[1..].iter().sum::<f64>() / (vol_ws.len() - 1) as f64;
+        let temp_sink = leaky.state.temp.sink;
+        let mass_buffer: Vec<f64> = vol_ws
+            .iter()
+            .zip(&values.P_b)
+            .map(|(vol, &pres)| {
+                (5e-3 + vol_ws_mean - vol) * leaky.state.fluid.dens(temp_sink, pres)
+            })
+            .collect();
+        let leaky_performance = Performance::from(&leaky);
+        let period = values.final_time();
+        let leak_per_cycle = leaky_performance.seal.mass_flow * period;
+        let mut leaked = 0.0;
+        for i in 1..m_dot_leak.len() {
+            leaked +=
+                0.5 * (m_dot_leak[i] + m_dot_leak[i - 1]) * (values.time[i] - values.time[i - 1]);
+            assert_relative_eq!(
+                mass_buffer[i] - mass_buffer[0],
+                leaked,
+                epsilon = 1e-4 * leak_per_cycle
+            );
+        }
+
+        // The buffer settles where no gas leaks over the cycle
+        let initial = leaky.dense_output.initial_conditions().m_b;
+        let net = leaky.dense_output.final_conditions().m_b - initial;
+        assert!(net.abs() < 1e-6 * leak_per_cycle);
+
+        // Throttling through the seal costs power
+        let sealed_performance = Performance::from(&sealed);
+        assert_eq!(sealed_performance.seal.power, 0.0);
+        assert!(leaky_performance.seal.power > 0.0);
+        assert!(leaky_performance.power.indicated < sealed_performance.power.indicated);
+    }
+
+    #[test]
+    fn wall_heat_enters_the_energy_balance() {
+        // The sinusoidal drive of `ws_sinusoidal` with 8 cm bores
+        let config = ws::sinusoidal_drive::Config {
+            frequency: 4000. / 60.,
+            phase_angle: 90.0,
+            V_swept_c: 5e-4,
+            V_clearance_c: 2e-5,
+            V_swept_e: 5e-4,
+            V_clearance_e: 2e-5,
+            wall_heat: Some(wall_heat::Config {
+                correlation: wall_heat::Correlation::KornhauserSmith,
+                k_gas: 0.2,
+                mu_gas: 9e-6,
+                bore_c: Some(0.08),
+                bore_e: Some(0.08),
+            }),
+            ..ws::sinusoidal_drive::Config::default()
+        };
+        let walls = Components {
+            ws: Box::new(ws::SinusoidalDrive::try_from(config).expect("config is valid")),
+            ..components()
+        };
+        let engine = Engine::run::<LuSolver>(walls, IdealGas::hydrogen(), inputs(), settings())
+            .expect("engine should converge");
+        let adiabatic =
+            Engine::run::<LuSolver>(components(), IdealGas::hydrogen(), inputs(), settings())
+                .expect("engine should converge");
+
+        // Heat flows both ways through the walls as the gas is compressed
+        // and expanded
+        let values = &engine.values;
+        for heat in [&values.Q_dot_c, &values.Q_dot_e] {
+            assert!(heat.iter().any(|&heat| heat > 0.0));
+            assert!(heat.iter().any(|&heat| heat < 0.0));
+        }
+        assert!(adiabatic.values.Q_dot_c.iter().all(|&heat| heat == 0.0));
+
+        // The wall heat is part of the heat exchanger heat flows, which
+        // agree with the heat input and rejection in the performance
+        let time = &values.time;
+        let mean = |heat: &[f64]| {
+            (1..time.len())
+                .map(|i| 0.5 * (heat[i] + heat[i - 1]) * (time[i] - time[i - 1]))
+                .sum::<f64>()
+                / values.final_time()
+        };
+        let heat_flow = &engine.state.heat_flow;
+        let performance = Performance::from(&engine);
+        assert!(mean(&values.Q_dot_e).abs() > 0.01 * heat_flow.hhx);
+        assert_relative_eq!(performance.heat.input, heat_flow.hhx, max_relative = 1e-3);
+        assert_relative_eq!(
+            performance.heat.rejected,
+            heat_flow.chx,
+            max_relative = 1e-3
+        );
+        assert!(performance.power.indicated != Performance::from(&adiabatic).power.indicated);
+    }
+
+    #[test]
+    fn values_at_crank_angles() {
+        let fluid = IdealGas::hydrogen();
+        let engine = Engine::run::<LuSolver>(components(), fluid, inputs(), settings())
+            .expect("engine should converge");
+
+        // Points on the uniform grid should be reproduced
+        let step = 360.0 / 29.0;
+        let angles: Vec<_> = (0..30).map(|i| f64::from(i) * step).collect();
+        let values = engine
+            .values_at::<LuSolver>(&angles)
+            .expect("values should be calculated");
+        assert_eq!(values.crank_angle.len(), 30);
+        for i in 0..30 {
+            assert_relative_eq!(
+                values.crank_angle[i],
+                engine.values.crank_angle[i],
+                epsilon = 1e-9
+            );
+            assert_relative_eq!(values.P[i], engine.values.P[i], max_relative = 1e-12);
+            assert_relative_eq!(
+                values.m_dot_kr[i],
+                engine.values.m_dot_kr[i],
+                epsilon = 1e-9
+            );
+        }
+
+        // Angles off the grid should fall within the range of nearby values
+        let values = engine
+            .values_at::<LuSolver>(&[0.5 * step])
+            .expect("values should be calculated");
+        let (low, high) = (engine.values.P[0], engine.values.P[1]);
+        assert!(values.P[0] > low.min(high) && values.P[0] < low.max(high));
+    }
+
+    #[test]
+    fn refines_grid_at_flow_reversal() {
+        let fluid = IdealGas::hydrogen();
+        let settings = RunSettings {
+            grid: OutputGrid::RefinedAtFlowReversal { extra_points: 3 },
+            ..settings()
+        };
+        let engine = Engine::run::<LuSolver>(components(), fluid, inputs(), settings)
+            .expect("engine should converge");
+        let values = &engine.values;
+        let reversals = values
+            .m_dot_ck
+            .windows(2)
+            .filter(|pair| pair[0] * pair[1] < 0.0)
+            .count();
+        assert!(reversals >= 2, "flow should reverse during a cycle");
+        assert!(values.time.len() > 30 && (values.time.len() - 30) % 3 == 0);
+        assert!(values.time.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_relative_eq!(*values.crank_angle.last().unwrap(), 360.0);
+    }
+
+    #[test]
+    fn run_with_discretized_heat_exchangers() {
+        let lumped =
+            Engine::run::<LuSolver>(components(), IdealGas::hydrogen(), inputs(), settings())
+                .expect("engine should converge");
+        let settings = RunSettings {
+            discretization: Discretization {
+                chx: 2,
+                regen: 8,
+                hhx: 2,
+            },
+            ..settings()
+        };
+        let discretized = Engine::run::<EliminationSolver>(
+            components(),
+            IdealGas::hydrogen(),
+            inputs(),
+            settings,
+        )
+        .expect("engine should converge");
+
+        // Every interface and control volume is reported, and they sum to the totals
+        let values = &discretized.values;
+        assert_eq!(values.m_dot.len(), 2 + 8 + 2 + 1);
+        assert_eq!(values.Q_dot.len(), 2 + 8 + 2);
+        assert_eq!(values.m_dot[0], values.m_dot_ck);
+        assert_eq!(values.m_dot[12], values.m_dot_le);
+        for (i, total) in values.Q_dot_r.iter().enumerate() {
+            let sum: f64 = values.Q_dot[2..10].iter().map(|heat| heat[i]).sum();
+            assert_relative_eq!(sum, total, max_relative = 1e-9, epsilon = 1e-9);
+        }
+
+        // Isothermal heat exchangers and a linear regenerator profile give a similar cycle
+        let (lumped, discretized) = (&lumped.state, &discretized.state);
+        assert_relative_eq!(lumped.pres.avg, discretized.pres.avg, max_relative = 0.05);
+        assert_relative_eq!(
+            lumped.heat_flow.hhx,
+            discretized.heat_flow.hhx,
+            max_relative = 0.05
+        );
+    }
+
+    #[test]
+    fn run_with_momentum_pressures() {
+        let components = || Components {
+            regen: Box::new(regen::FixedApproach::new(
+                1.0e-4,
+                5e6,
+                10.,
+                ParasiticPower::default(),
+            )),
+            ..components()
+        };
+        let uniform =
+            Engine::run::<LuSolver>(components(), IdealGas::hydrogen(), inputs(), settings())
+                .expect("engine should converge");
+        assert!(uniform.values.P_drop.iter().all(|&drop| drop == 0.0));
+
+        let settings = || RunSettings {
+            pressure_model: PressureModel::Momentum,
+            ..settings()
+        };
+        let elimination = Engine::run::<EliminationSolver>(
+            components(),
+            IdealGas::hydrogen(),
+            inputs(),
+            settings(),
+        );
+        assert!(
+            elimination.is_err(),
+            "elimination only supports a uniform pressure"
+        );
+        let momentum =
+            Engine::run::<LuSolver>(components(), IdealGas::hydrogen(), inputs(), settings())
+                .expect("engine should converge");
+
+        // The pressure drop from the integration is similar to the one found
+        // after the fact from a uniform pressure cycle
+        let max_drop = |values: &[f64]| values.iter().fold(0.0_f64, |max, v| max.max(v.abs()));
+        let uniform_drop = PressuresWithDrops::new(&uniform);
+        let uniform_drop = max_drop((uniform_drop.P_c - uniform_drop.P_e).as_slice());
+        let momentum_drop = max_drop(&momentum.values.P_drop);
+        assert!(
+            momentum_drop > 1e4,
+            "regenerator should cause a pressure drop"
+        );
+        assert_relative_eq!(momentum_drop, uniform_drop, max_relative = 0.05);
+        assert_relative_eq!(
+            momentum.state.heat_flow.hhx,
+            uniform.state.heat_flow.hhx,
+            max_relative = 0.05
+        );
+    }
+
+    #[test]
+    fn reports_outer_loop_convergence() {
+        let temp_only =
+            Engine::run::<LuSolver>(components(), IdealGas::hydrogen(), inputs(), settings())
+                .expect("engine should converge");
+        let report = &temp_only.convergence;
+        assert_eq!(report.unconverged.last(), Some(&Vec::new()));
+        assert_eq!(report.limiting(), [Quantity::Temperature]);
+
+        // Checking more quantities can only take more iterations
+        let mut settings = settings();
+        settings.loop_tol.outer = OuterTolerance {
+            pres: Some(ConvergenceTolerance::new(100.0, 1e-5)),
+            mass_flow: Some(ConvergenceTolerance::new(1e-5, 1e-4)),
+            heat_flow: Some(ConvergenceTolerance::new(10.0, 1e-2)),
+            ..settings.loop_tol.outer
+        };
+        let all = Engine::run::<LuSolver>(components(), IdealGas::hydrogen(), inputs(), settings)
+            .expect("engine should converge");
+        let report = &all.convergence;
+        assert!(report.iterations() >= temp_only.convergence.iterations());
+        assert_eq!(report.unconverged.last(), Some(&Vec::new()));
+        assert!(!report.limiting().is_empty());
+        assert!(report.unconverged[0].contains(&Quantity::MassFlow));
+    }
+
+    #[test]
+    fn warm_starts_from_snapshot() {
+        let engine =
+            Engine::run::<LuSolver>(components(), IdealGas::hydrogen(), inputs(), settings())
+                .expect("engine should converge");
+
+        // A snapshot survives serialization
+        let snapshot = engine.snapshot();
+        let json = serde_json::to_string(&snapshot).expect("snapshot should serialize");
+        let snapshot: StateSnapshot =
+            serde_json::from_str(&json).expect("snapshot should deserialize");
+        let original = engine.snapshot();
+        assert_relative_eq!(snapshot.pres.avg, original.pres.avg, max_relative = 1e-12);
+        assert_relative_eq!(
+            snapshot.temp.regen.avg,
+            original.temp.regen.avg,
+            max_relative = 1e-12
+        );
+        assert_relative_eq!(
+            snapshot.temp_exp_zero,
+            original.temp_exp_zero,
+            max_relative = 1e-12
+        );
+
+        // Starting from a converged state at the same conditions is already converged
+        let warm = Engine::run_with(
+            components(),
+            IdealGas::hydrogen(),
+            inputs(),
+            settings(),
+            RunOptions {
+                hint: Some(snapshot),
+                ..RunOptions::default()
+            },
+        )
+        .expect("engine should converge");
+        assert_eq!(warm.convergence.iterations(), 1);
+
+        // A nearby point converges faster from the snapshot
+        let nearby = RunInputs {
+            temp_source: inputs().temp_source + 5.0,
+            pres_zero: inputs().pres_zero * 1.01,
+            ..inputs()
+        };
+        let cold = Engine::run::<LuSolver>(components(), IdealGas::hydrogen(), nearby, settings())
+            .expect("engine should converge");
+        let warm = Engine::run_with(
+            components(),
+            IdealGas::hydrogen(),
+            nearby,
+            settings(),
+            RunOptions {
+                hint: Some(snapshot),
+                ..RunOptions::default()
+            },
+        )
+        .expect("engine should converge");
+        assert!(warm.convergence.iterations() < cold.convergence.iterations());
+        assert_relative_eq!(warm.state.temp.hhx, cold.state.temp.hhx, epsilon = 1e-2);
+        assert_relative_eq!(
+            warm.state.heat_flow.hhx,
+            cold.state.heat_flow.hhx,
+            max_relative = 1e-4
+        );
+    }
+
+    #[derive(Default)]
+    struct Recorder {
+        outer_starts: usize,
+        outer_ends: Vec<Vec<Quantity>>,
+        residuals: Vec<Residuals>,
+        stats: SolverStats,
+        newton_stats: SolverStats,
+        abort_after: Option<usize>,
+    }
+
+    impl CycleObserver for Recorder {
+        fn inner_iteration(&mut self, _iteration: usize, residuals: Residuals) -> ControlFlow<()> {
+            self.residuals.push(residuals);
+            ControlFlow::Continue(())
+        }
+
+        fn integration(&mut self, stats: SolverStats) -> ControlFlow<()> {
+            self.stats += stats;
+            ControlFlow::Continue(())
+        }
+
+        fn newton_iteration(&mut self, stats: SolverStats) -> ControlFlow<()> {
+            self.newton_stats += stats;
+            ControlFlow::Continue(())
+        }
+    }
+
+    impl Observer<IdealGas> for Recorder {
+        fn outer_iteration_start(
+            &mut self,
+            _iteration: usize,
+            _state: &State<IdealGas>,
+        ) -> ControlFlow<()> {
+            self.outer_starts += 1;
+            ControlFlow::Continue(())
+        }
+
+        fn outer_iteration_end(
+            &mut self,
+            iteration: usize,
+            _state: &State<IdealGas>,
+            unconverged: &[Quantity],
+        ) -> ControlFlow<()> {
+            self.outer_ends.push(unconverged.to_vec());
+            if self.abort_after == Some(iteration) {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        }
+    }
+
+    #[test]
+    fn observes_engine_run() {
+        let mut recorder = Recorder::default();
+        let engine = Engine::run_with(
+            components(),
+            IdealGas::hydrogen(),
+            inputs(),
+            settings(),
+            RunOptions {
+                observer: Some(&mut recorder),
+                ..RunOptions::default()
+            },
+        )
+        .expect("engine should converge");
+        assert_eq!(recorder.outer_starts, engine.convergence.iterations());
+        assert_eq!(recorder.outer_ends, engine.convergence.unconverged);
+
+        // The last inner iteration of each outer iteration is converged
+        let last = recorder.residuals.last().expect("should have residuals");
+        assert!(last.temp_comp.abs() < 1e-3 && last.temp_exp.abs() < 1e-3);
+
+        // Integration statistics exclude evaluating the converged cycle on the grid
+        assert!(recorder.stats.accepted_steps > 0);
+        assert!(recorder.stats.rhs_evals > recorder.stats.accepted_steps);
+        assert_eq!(recorder.stats.accepted_steps, engine.stats.accepted_steps);
+        assert!(recorder.stats.flow_updates <= engine.stats.flow_updates);
+    }
+
+    #[test]
+    fn observes_newton_iterations() {
+        let mut recorder = Recorder::default();
+        let settings = RunSettings {
+            steady_state: SteadyStateMethod::HarmonicBalance { harmonics: 5 },
+            ..settings()
+        };
+        let engine = Engine::run_with(
+            components(),
+            IdealGas::hydrogen(),
+            inputs(),
+            settings,
+            RunOptions {
+                observer: Some(&mut recorder),
+                ..RunOptions::default()
+            },
+        )
+        .expect("engine should converge");
+
+        // Harmonic balance does not integrate the cycle
+        assert_eq!(recorder.stats, SolverStats::default());
+        assert_eq!(
+            recorder.newton_stats.accepted_steps,
+            recorder.residuals.len()
+        );
+        assert_eq!(
+            recorder.newton_stats.accepted_steps,
+            engine.stats.accepted_steps
+        );
+    }
+
+    #[test]
+    fn observer_aborts_run() {
+        let mut recorder = Recorder {
+            abort_after: Some(0),
+            ..Recorder::default()
+        };
+        let result = Engine::run_with(
+            components(),
+            IdealGas::hydrogen(),
+            inputs(),
+            settings(),
+            RunOptions {
+                observer: Some(&mut recorder),
+                ..RunOptions::default()
+            },
+        );
+        let Err(RunError::Interrupted(partial)) = result else {
+            panic!("run should be interrupted");
+        };
+        assert_eq!(partial.reason, Interruption::Cancelled);
+        assert_eq!(partial.convergence.iterations(), 1);
+        assert_eq!(recorder.outer_starts, 1);
+    }
+
+    #[test]
+    fn cancel_token_stops_run() {
+        let cancel = CancelToken::new();
+        cancel.clone().cancel();
+        let settings = RunSettings {
+            cancel: Some(cancel),
+            ..settings()
+        };
+        let result =
+            Engine::run::<LuSolver>(components(), IdealGas::hydrogen(), inputs(), settings);
+        let Err(RunError::Interrupted(partial)) = result else {
+            panic!("run should be interrupted");
+        };
+        assert_eq!(partial.reason, Interruption::Cancelled);
+        assert_eq!(partial.stats, SolverStats::default());
+    }
+
+    #[test]
+    fn budget_stops_run_with_partial_state() {
+        let steps = 500;
+        let limited = RunSettings {
+            budget: Budget {
+                steps: Some(steps),
+                ..Budget::default()
+            },
+            ..settings()
+        };
+        let result = Engine::run::<LuSolver>(components(), IdealGas::hydrogen(), inputs(), limited);
+        let Err(RunError::Interrupted(partial)) = result else {
+            panic!("run should be interrupted");
+        };
+        assert_eq!(partial.reason, Interruption::Steps);
+        let stats = partial.stats;
+        assert!(stats.accepted_steps + stats.rejected_steps > steps);
+        assert!(stats.rhs_evals > stats.accepted_steps);
+
+        // The partial state can be used to finish the run
+        let engine = Engine::run_with(
+            components(),
+            IdealGas::hydrogen(),
+            inputs(),
+            settings(),
+            RunOptions {
+                hint: Some(partial.state),
+                ..RunOptions::default()
+            },
+        )
+        .expect("engine should converge");
+        assert_eq!(engine.convergence.unconverged.last(), Some(&Vec::new()));
+
+        // Limits are checked while integrating, not only between cycles
+        let limited = RunSettings {
+            budget: Budget {
+                rhs_evals: Some(10),
+                ..Budget::default()
+            },
+            ..settings()
+        };
+        let result = Engine::run::<LuSolver>(components(), IdealGas::hydrogen(), inputs(), limited);
+        let Err(RunError::Interrupted(partial)) = result else {
+            panic!("run should be interrupted");
+        };
+        assert_eq!(partial.reason, Interruption::RhsEvals);
+        assert!(partial.stats.rhs_evals < 20);
+    }
+
+    #[test]
+    fn harmonic_balance_matches_time_marching() {
+        let marching =
+            Engine::run::<LuSolver>(components(), IdealGas::hydrogen(), inputs(), settings())
+                .expect("engine should converge");
+        let balance = |harmonics| {
+            let settings = RunSettings {
+                steady_state: SteadyStateMethod::HarmonicBalance { harmonics },
+                ..settings()
+            };
+            Engine::run::<LuSolver>(components(), IdealGas::hydrogen(), inputs(), settings)
+                .expect("engine should converge")
+        };
+        let (coarse, fine) = (balance(5), balance(15));
+        assert_relative_eq!(
+            fine.state.pres.avg,
+            marching.state.pres.avg,
+            max_relative = 1e-3
+        );
+        assert_relative_eq!(
+            fine.state.heat_flow.hhx,
+            marching.state.heat_flow.hhx,
+            max_relative = 5e-3
+        );
+        assert_relative_eq!(fine.values.T_e[0], marching.values.T_e[0], epsilon = 1.0);
+
+        // More harmonics get closer to the time-marching result
+        let error = |engine: &Engine<IdealGas>| {
+            (engine.state.heat_flow.hhx - marching.state.heat_flow.hhx).abs()
+        };
+        assert!(error(&fine) < error(&coarse));
+        assert!(fine.stats.rhs_evals < marching.stats.rhs_evals);
+    }
+
+    /// Return metal that is well coupled to the sink and source
+    fn thermal_masses() -> ThermalMasses {
+        ThermalMasses {
+            chx: ThermalMass {
+                capacitance: 500.0,
+                conductance: 1e4,
+            },
+            regen: 2000.0,
+            hhx: ThermalMass {
+                capacitance: 500.0,
+                conductance: 1e4,
+            },
+        }
+    }
+
+    /// Start a transient with the regenerator matrix close to its steady
+    /// temperature, so that the cycles settle quickly
+    fn start_transient(
+        settings: RunSettings,
+        thermal: ThermalMasses,
+    ) -> Result<Transient<IdealGas>, RunError> {
+        let metal = MetalTemperatures {
+            chx: 300.,
+            regen: 550.,
+            hhx: 900.,
+        };
+        Transient::start(
+            Decomposition::Lu,
+            components(),
+            IdealGas::hydrogen(),
+            inputs(),
+            settings,
+            thermal,
+            metal,
+        )
+    }
+
+    #[test]
+    fn transient_settles_to_steady_state() {
+        let mut transient =
+            start_transient(settings(), thermal_masses()).expect("cycle should integrate");
+        for _ in 1..30 {
+            transient.step(inputs()).expect("cycle should integrate");
+        }
+        assert_relative_eq!(transient.time(), 30. * 60. / 4000., max_relative = 1e-12);
+
+        // Heat rejection warms the chx metal above the sink and heat input
+        // cools the hhx metal below the source
+        let metal = transient.metal();
+        assert!(metal.chx > 300.);
+        assert!(metal.hhx < 900.);
+
+        // Once settled, the cycle matches a steady run with the metal in place
+        // of the sink and source
+        let steady = Engine::run::<LuSolver>(
+            components(),
+            IdealGas::hydrogen(),
+            RunInputs {
+                temp_sink: metal.chx,
+                temp_source: metal.hhx,
+                ..inputs()
+            },
+            settings(),
+        )
+        .expect("engine should converge");
+        let engine = transient.engine();
+        assert_relative_eq!(
+            engine.state.heat_flow.hhx,
+            steady.state.heat_flow.hhx,
+            max_relative = 1e-2
+        );
+
+        // The regenerator matrix is still settling, but only slowly
+        assert!(engine.state.heat_flow.regen.abs() < 0.05 * engine.state.heat_flow.hhx);
+    }
+
+    #[test]
+    fn transient_rejects_zero_heat_capacity() {
+        let thermal = ThermalMasses {
+            regen: 0.0,
+            ..thermal_masses()
+        };
+        let Err(RunError::InvalidConfig(_)) = start_transient(settings(), thermal) else {
+            panic!("zero heat capacity should be rejected");
+        };
+    }
+
+    #[test]
+    fn failed_transient_step_keeps_previous_cycle() {
+        let first = start_transient(settings(), thermal_masses()).expect("cycle should integrate");
+
+        // A budget that only covers the first cycle interrupts the second
+        let settings = RunSettings {
+            budget: Budget {
+                rhs_evals: Some(first.stats().rhs_evals + 1),
+                ..Budget::default()
+            },
+            ..settings()
+        };
+        let mut transient =
+            start_transient(settings, thermal_masses()).expect("cycle should integrate");
+        let temp = transient.engine().state.temp;
+        let Err(RunError::Interrupted(_)) = transient.step(RunInputs {
+            temp_source: 950.,
+            ..inputs()
+        }) else {
+            panic!("second cycle should be interrupted");
+        };
+        assert_eq!(transient.engine().state.temp, temp);
+        assert_eq!(transient.metal(), first.metal());
+        assert_eq!(transient.time(), first.time());
+        assert_eq!(transient.inputs().temp_source, inputs().temp_source);
+    }
+
+    #[test]
+    fn free_pistons_find_their_own_frequency_and_stroke() {
+        let components = Components {
+            ws: ws_free_piston(),
+            ..components()
+        };
+        let settings = settings();
+        let settings = RunSettings {
+            max_iters: MaxIters {
+                inner: 50,
+                outer: 20,
+            },
+            ..settings
         };
-        let _engine = Engine::run::<LuSolver>(components, fluid, inputs, settings)
+        let engine = Engine::run::<LuSolver>(components, IdealGas::hydrogen(), inputs(), settings)
             .expect("engine should converge");
+
+        // The cycle ends with the power piston back at mid-stroke
+        let x_p = &engine.values.x_p;
+        assert_relative_eq!(x_p[x_p.len() - 1], 0.0, epsilon = 1e-12);
+
+        // The frequency and stroke settle away from their hints, and the
+        // engine produces power that the load absorbs
+        let results = crate::api::RunResults::from(engine);
+        let period = results.values.time[results.values.time.len() - 1];
+        assert_relative_eq!(results.frequency, 1.0 / period);
+        assert!((results.frequency - 60.0).abs() > 1.0);
+        let stroke = results.stroke.expect("free pistons have a stroke");
+        assert!(stroke.piston > 0.01 && (stroke.piston - 0.02).abs() > 1e-4);
+        assert!(stroke.displacer > 0.0);
+        assert!(results.power.indicated > 0.0);
+
+        // The alternator delivers some of that power to its load, after
+        // losing some to the resistance of its coil, which carries the same
+        // current as the load
+        let copper = results.ws_parasitics.comp.electrical;
+        assert!(results.power.electrical < results.power.indicated);
+        assert_relative_eq!(
+            copper / results.power.electrical,
+            0.5 / 9.5,
+            max_relative = 1e-2
+        );
+        assert_relative_eq!(
+            results.efficiency.overall,
+            results.power.electrical / results.heat_flow.input
+        );
+
+        // The volumes follow the piston motion rather than time
+        let vol = &results.values.V_c;
+        assert_relative_eq!(vol[0], vol[vol.len() - 1], max_relative = 1e-3);
+        assert!(vol.iter().any(|&v| (v - vol[0]).abs() > 1e-5));
     }
 }
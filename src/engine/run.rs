@@ -1,64 +1,179 @@
+mod cache;
+
 use std::marker::PhantomData;
 
 use crate::{
+    buffer::Buffer,
     fluid::Fluid,
     state_equations::{
         Conditions, Cycle, HeatExchangerInputs, Inputs as StateEquationInputs, MatrixDecomposition,
-        RegeneratorInputs, WorkingSpaceInputs,
+        RegeneratorInputs, Solution, WorkingSpaceInputs,
     },
+    types::{Discretization, PressureModel, RunError, RunSettings},
     ws,
 };
 
+use self::cache::{Enthalpy, Properties, PropertyTable};
+
 use super::{
-    state::{Pressure, State, Temperatures},
+    state::{Pressure, State, Temperatures, Values},
     Components,
 };
 
+// Number of points used to find the mean and largest working space volumes
+const MEAN_VOLUME_POINTS: u32 = 360;
+
+// Newton iterations and relative tolerance used to find the buffer pressure
+// that gives its gas the density of its mass in its volume
+const BUFFER_PRES_ITERS: usize = 20;
+const BUFFER_PRES_TOL: f64 = 1e-12;
+
 /// Information needed to implement `Cycle`
 pub(super) struct Run<'a, T: Fluid, U: MatrixDecomposition> {
+    buffer: Option<BufferSpace>,
     enth_norm: f64,
     fluid: &'a T,
+    hyd_res: HydraulicResistances,
     period: f64,
     pres: Pressure,
+    pressure_drops: bool,
+    props: HeatExchangerProperties,
     solver: PhantomData<U>,
-    temp: Temperatures,
-    vol_chx: f64,
-    vol_hhx: f64,
-    vol_regen: f64,
+    vol_chx: f64,   // per control volume
+    vol_hhx: f64,   // per control volume
+    vol_regen: f64, // per control volume
+    walls: Option<Walls>,
+    ws_dynamics: Option<Box<dyn ws::Dynamics>>,
     ws_parasitics: ws::Parasitics,
     ws_vol_fn: Box<dyn Fn(f64) -> (ws::CompVolume, ws::ExpVolume)>,
 }
 
+/// A buffer space behind the pistons
+///
+/// The buffer is at the sink temperature `temp` (K), and its volume is found
+/// from how far the working spaces are from their mean volume `vol_ws_mean`
+/// (m^3).
+struct BufferSpace {
+    buffer: Buffer,
+    temp: f64,
+    vol_ws_mean: f64,
+}
+
+/// Working space walls with a conductance that varies over the cycle
+///
+/// Each wall is at the temperature (K) of the heat exchanger next to its
+/// space.
+struct Walls {
+    conductance: Box<dyn ws::WallConductance>,
+    temp_chx: f64,
+    temp_hhx: f64,
+}
+
+/// Hydraulic resistance of each heat exchanger control volume in Pa-s/m^3
+struct HydraulicResistances {
+    chx: f64,
+    regen: f64,
+    hhx: f64,
+}
+
+/// Cached fluid properties at the constant heat exchanger temperatures
+///
+/// The heat exchangers are isothermal, so all of their control volumes share
+/// properties.  The regenerator has properties for each control volume and
+/// for each face between them, ordered from cold to hot.
+struct HeatExchangerProperties {
+    num_chx: usize,
+    num_hhx: usize,
+    chx: PropertyTable<Properties>,
+    regen: Vec<PropertyTable<Properties>>,
+    regen_faces: Vec<PropertyTable<Enthalpy>>,
+    hhx: PropertyTable<Properties>,
+}
+
 impl<'a, T: Fluid, U: MatrixDecomposition> Run<'a, T, U> {
     /// Create an `Run` for a specific matrix solver
     #[allow(clippy::similar_names)]
-    pub(super) fn new(components: &'a Components, state: &'a State<T>) -> Self {
+    pub(super) fn new(
+        components: &'a Components,
+        state: &'a State<T>,
+        settings: &RunSettings,
+    ) -> Self {
         // Calculate an average enthalpy using the sink and source temperatures
         let h_sink = state.fluid.enth(state.temp.sink, state.pres.avg);
         let h_source = state.fluid.enth(state.temp.source, state.pres.avg);
         let enth_norm = 0.5 * (h_sink + h_source);
 
         // Ask heat exchanger components for their volumes
-        let vol_chx = components.chx.volume();
-        let vol_regen = components.regen.volume();
-        let vol_hhx = components.hhx.volume();
+        let Discretization { chx, regen, hhx } = settings.discretization;
+        let vol_chx = components.chx.volume() / f64::from(chx);
+        let vol_regen = components.regen.volume() / f64::from(regen);
+        let vol_hhx = components.hhx.volume() / f64::from(hhx);
+
+        // Pressure drops are only part of the state equations with `Momentum`
+        let hyd_res = match settings.pressure_model {
+            PressureModel::Uniform => HydraulicResistances {
+                chx: 0.0,
+                regen: 0.0,
+                hhx: 0.0,
+            },
+            PressureModel::Momentum => HydraulicResistances {
+                chx: components.chx.hydraulic_resistance(&state.chx()) / f64::from(chx),
+                regen: components.regen.hydraulic_resistance(&state.regen()) / f64::from(regen),
+                hhx: components.hhx.hydraulic_resistance(&state.hhx()) / f64::from(hhx),
+            },
+        };
 
         // Ask working spaces component for its properties
         let ws_state = state.ws();
         let period = 1.0 / components.ws.frequency(&ws_state);
         let ws_vol_fn = components.ws.volumes(&ws_state);
+        let ws_dynamics = components.ws.dynamics(&ws_state);
         let ws_parasitics = components.ws.parasitics(&ws_state);
+        let walls = components
+            .ws
+            .wall_conductance(&ws_state)
+            .map(|conductance| Walls {
+                conductance,
+                temp_chx: state.temp.chx,
+                temp_hhx: state.temp.hhx,
+            });
+        let buffer = components.buffer.map(|buffer| BufferSpace {
+            buffer,
+            temp: state.temp.sink,
+            vol_ws_mean: ws_volumes(&ws_vol_fn, ws_dynamics.as_deref(), period).0,
+        });
+
+        // Heat exchanger temperatures are constant so their properties can be cached
+        let (pres_avg, evaluation) = (state.pres.avg, settings.properties);
+        let table = |temp| PropertyTable::new(temp, pres_avg, evaluation);
+        let (regen_temps, regen_face_temps) = regen_temps(&state.temp, regen);
+        let props = HeatExchangerProperties {
+            num_chx: chx as usize,
+            num_hhx: hhx as usize,
+            chx: table(state.temp.chx),
+            regen: regen_temps.into_iter().map(table).collect(),
+            regen_faces: regen_face_temps
+                .into_iter()
+                .map(|temp| PropertyTable::new(temp, pres_avg, evaluation))
+                .collect(),
+            hhx: table(state.temp.hhx),
+        };
 
         Self {
+            buffer,
             enth_norm,
             fluid: &state.fluid,
+            hyd_res,
             period,
             pres: state.pres,
+            pressure_drops: settings.pressure_model == PressureModel::Momentum,
+            props,
             solver: PhantomData,
-            temp: state.temp,
             vol_chx,
             vol_hhx,
             vol_regen,
+            walls,
+            ws_dynamics,
             ws_parasitics,
             ws_vol_fn,
         }
@@ -76,44 +191,139 @@ impl<'a, T: Fluid, U: MatrixDecomposition> Run<'a, T, U> {
             du_dT_P: self.fluid.du_dT_P(temp, pres),
             dV_dt: vol.deriv,
             Q_dot: self.ws_parasitics.comp.thermal,
+            Q_dot_wall: 0.0,
+            m_dot_leak: 0.0,
+            enth_leak: 0.0,
         }
     }
 
-    fn chx_inputs(&self, pres: f64) -> HeatExchangerInputs {
-        let temp = self.temp.chx;
-        HeatExchangerInputs {
-            vol: self.vol_chx,
-            dens: self.fluid.dens(temp, pres),
-            inte: self.fluid.inte(temp, pres),
-            enth: self.fluid.enth(temp, pres),
-            dd_dP_T: self.fluid.dd_dP_T(temp, pres),
-            du_dP_T: self.fluid.du_dP_T(temp, pres),
-        }
+    /// Return the buffer pressure along with the mass flow rate and specific
+    /// enthalpy of the seal leakage from the compression space
+    ///
+    /// Without a buffer, the pistons are backed by the mean cycle pressure and
+    /// the seal is perfect.  The buffer pressure is found from the buffer
+    /// mass `mass_buffer` (kg) and the total volume `vol_ws` (m^3) of the
+    /// working spaces.  The seal sees the pressure in the conditions, and
+    /// leakage carries the properties of the side it leaves.
+    fn seal(&self, vol_ws: f64, temp_comp: f64, pres: f64, mass_buffer: f64) -> (f64, f64, f64) {
+        let Some(space) = &self.buffer else {
+            return (self.pres.avg, 0.0, 0.0);
+        };
+        let vol_buffer = space.buffer.volume() + space.vol_ws_mean - vol_ws;
+        let pres_buffer = self.buffer_pres(space.temp, mass_buffer / vol_buffer);
+        let flow = space.buffer.seal_flow(pres, pres_buffer);
+        let (temp, pres_upstream) = if flow >= 0.0 {
+            (temp_comp, pres)
+        } else {
+            (space.temp, pres_buffer)
+        };
+        (
+            pres_buffer,
+            flow * self.fluid.dens(temp, pres_upstream),
+            self.fluid.enth(temp, pres_upstream),
+        )
     }
 
-    fn regen_inputs(&self, pres: f64) -> RegeneratorInputs {
-        let temp = self.temp.regen.avg;
-        RegeneratorInputs {
-            vol: self.vol_regen,
-            dens: self.fluid.dens(temp, pres),
-            inte: self.fluid.inte(temp, pres),
-            dd_dP_T: self.fluid.dd_dP_T(temp, pres),
-            du_dP_T: self.fluid.du_dP_T(temp, pres),
-            enth_cold: self.fluid.enth(self.temp.regen.cold, pres),
-            enth_hot: self.fluid.enth(self.temp.regen.hot, pres),
+    /// Return the pressure (Pa) at which the buffer gas at `temp` (K) has the
+    /// density `dens` (kg/m^3)
+    ///
+    /// Newton's method starts from the mean cycle pressure, and needs a
+    /// single step for an ideal gas.
+    fn buffer_pres(&self, temp: f64, dens: f64) -> f64 {
+        let mut pres = self.pres.avg;
+        for _ in 0..BUFFER_PRES_ITERS {
+            let step = (dens - self.fluid.dens(temp, pres)) / self.fluid.dd_dP_T(temp, pres);
+            pres += step;
+            if step.abs() <= BUFFER_PRES_TOL * pres.abs() {
+                break;
+            }
         }
+        pres
+    }
+
+    /// Return the heat (W) leaving the gas through the walls of the
+    /// compression and expansion spaces
+    ///
+    /// The working spaces are adiabatic unless they have a wall conductance.
+    fn wall_heat(
+        &self,
+        (comp, temp_comp, pres_comp): (&WorkingSpaceInputs, f64, f64),
+        (exp, temp_exp, pres_exp): (&WorkingSpaceInputs, f64, f64),
+    ) -> (f64, f64) {
+        let Some(walls) = &self.walls else {
+            return (0.0, 0.0);
+        };
+        let conditions = |space: &WorkingSpaceInputs, temp, pres| ws::SpaceConditions {
+            vol: space.vol,
+            dV_dt: space.dV_dt,
+            dens: space.dens,
+            cp: self.fluid.cp(temp, pres),
+        };
+        let (comp_ua, exp_ua) = walls.conductance.conductances(
+            conditions(comp, temp_comp, pres_comp),
+            conditions(exp, temp_exp, pres_exp),
+        );
+        (
+            comp_ua * (temp_comp - walls.temp_chx),
+            exp_ua * (temp_exp - walls.temp_hhx),
+        )
     }
 
-    fn hhx_inputs(&self, pres: f64) -> HeatExchangerInputs {
-        let temp = self.temp.hhx;
-        HeatExchangerInputs {
+    fn chx_inputs(&self, pres: f64, chx: &mut Vec<HeatExchangerInputs>) {
+        let props = self.props.chx.get(self.fluid, pres);
+        let inputs = HeatExchangerInputs {
+            vol: self.vol_chx,
+            dens: props.dens,
+            inte: props.inte,
+            enth: props.enth,
+            dd_dP_T: props.dd_dP_T,
+            du_dP_T: props.du_dP_T,
+            hyd_res: self.hyd_res.chx,
+        };
+        chx.clear();
+        chx.resize(self.props.num_chx, inputs);
+    }
+
+    fn regen_inputs(&self, pres: f64, regen: &mut Vec<RegeneratorInputs>) {
+        let Enthalpy(mut enth_cold) = self.props.regen_faces[0].get(self.fluid, pres);
+        regen.clear();
+        regen.extend(
+            self.props
+                .regen
+                .iter()
+                .zip(&self.props.regen_faces[1..])
+                .map(|(table, hot_face)| {
+                    let props = table.get(self.fluid, pres);
+                    let Enthalpy(enth_hot) = hot_face.get(self.fluid, pres);
+                    let inputs = RegeneratorInputs {
+                        vol: self.vol_regen,
+                        dens: props.dens,
+                        inte: props.inte,
+                        dd_dP_T: props.dd_dP_T,
+                        du_dP_T: props.du_dP_T,
+                        hyd_res: self.hyd_res.regen,
+                        enth_cold,
+                        enth_hot,
+                    };
+                    enth_cold = enth_hot;
+                    inputs
+                }),
+        );
+    }
+
+    fn hhx_inputs(&self, pres: f64, hhx: &mut Vec<HeatExchangerInputs>) {
+        let props = self.props.hhx.get(self.fluid, pres);
+        let inputs = HeatExchangerInputs {
             vol: self.vol_hhx,
-            dens: self.fluid.dens(temp, pres),
-            inte: self.fluid.inte(temp, pres),
-            enth: self.fluid.enth(temp, pres),
-            dd_dP_T: self.fluid.dd_dP_T(temp, pres),
-            du_dP_T: self.fluid.du_dP_T(temp, pres),
-        }
+            dens: props.dens,
+            inte: props.inte,
+            enth: props.enth,
+            dd_dP_T: props.dd_dP_T,
+            du_dP_T: props.du_dP_T,
+            hyd_res: self.hyd_res.hhx,
+        };
+        hhx.clear();
+        hhx.resize(self.props.num_hhx, inputs);
     }
 
     fn exp_inputs(&self, vol: ws::ExpVolume, temp: f64, pres: f64) -> WorkingSpaceInputs {
@@ -128,25 +338,154 @@ impl<'a, T: Fluid, U: MatrixDecomposition> Run<'a, T, U> {
             du_dT_P: self.fluid.du_dT_P(temp, pres),
             dV_dt: vol.deriv,
             Q_dot: self.ws_parasitics.exp.thermal,
+            Q_dot_wall: 0.0,
+            m_dot_leak: 0.0,
+            enth_leak: 0.0,
         }
     }
 }
 
+/// Check that the buffer holds more than the working spaces gain over their
+/// mean volume
+///
+/// Kinematic working spaces are checked over a cycle of their volumes, while
+/// the swing of free pistons is only known from the integrated `values`.
+///
+/// # Errors
+///
+/// Will return `RunError::InvalidConfig` if the working spaces gain as much
+/// volume as the buffer holds, which would leave it without any volume.
+pub(super) fn check_buffer<T: Fluid>(
+    components: &Components,
+    state: &State<T>,
+    values: Option<&Values>,
+) -> Result<(), RunError> {
+    let Some(buffer) = components.buffer else {
+        return Ok(());
+    };
+    let ws_state = state.ws();
+    let period = 1.0 / components.ws.frequency(&ws_state);
+    let vol_fn = components.ws.volumes(&ws_state);
+    let dynamics = components.ws.dynamics(&ws_state);
+    let (mean, max) = ws_volumes(&vol_fn, dynamics.as_deref(), period);
+    let max = values.map_or(max, |values| {
+        values
+            .V_c
+            .iter()
+            .zip(&values.V_e)
+            .map(|(comp, exp)| comp + exp)
+            .fold(max, f64::max)
+    });
+    let swing = max - mean;
+    if swing >= buffer.volume() {
+        return Err(RunError::InvalidConfig(format!(
+            "buffer volume of {} m^3 must exceed the working space swing of {swing} m^3",
+            buffer.volume()
+        )));
+    }
+    Ok(())
+}
+
+/// Return the mean and largest total volumes (m^3) of the working spaces over
+/// a cycle
+///
+/// Free pistons oscillate about mid-stroke, where they start each cycle, so
+/// their mean volume is found there.  Their largest volume is not known until
+/// the cycle is integrated, so it is taken as the mean.
+fn ws_volumes(
+    vol_fn: &dyn Fn(f64) -> (ws::CompVolume, ws::ExpVolume),
+    dynamics: Option<&dyn ws::Dynamics>,
+    period: f64,
+) -> (f64, f64) {
+    let total = |(comp, exp): (ws::CompVolume, ws::ExpVolume)| comp.value + exp.value;
+    if let Some(dynamics) = dynamics {
+        let mean = total(dynamics.volumes(dynamics.initial_motion()));
+        return (mean, mean);
+    }
+    let step = period / f64::from(MEAN_VOLUME_POINTS);
+    let volumes: Vec<f64> = (0..MEAN_VOLUME_POINTS)
+        .map(|i| total(vol_fn(f64::from(i) * step)))
+        .collect();
+    let mean = volumes.iter().sum::<f64>() / f64::from(MEAN_VOLUME_POINTS);
+    (mean, volumes.into_iter().fold(f64::NEG_INFINITY, f64::max))
+}
+
+/// Return the regenerator control volume and face temperatures, from cold to hot
+///
+/// The faces are evenly spaced between the cold and hot regenerator
+/// temperatures.  Each control volume uses the log mean temperature over its
+/// share of the profile that defines `RegenTemp::avg`, so a single volume is at
+/// the average regenerator temperature.
+fn regen_temps(temp: &Temperatures, num_volumes: u32) -> (Vec<f64>, Vec<f64>) {
+    let lerp = |cold: f64, hot: f64, i: u32| {
+        (cold * f64::from(num_volumes - i) + hot * f64::from(i)) / f64::from(num_volumes)
+    };
+    let log_mean = |a: f64, b: f64| {
+        let ratio = b / a;
+        if (ratio - 1.0).abs() < 1e-12 {
+            0.5 * (a + b)
+        } else {
+            (b - a) / ratio.ln()
+        }
+    };
+
+    let (cold, hot) = (temp.regen.cold, temp.regen.hot);
+    let faces = (0..=num_volumes).map(|i| lerp(cold, hot, i)).collect();
+
+    let cold_end = 0.5 * (temp.chx + cold);
+    let hot_end = 0.5 * (temp.hhx + hot);
+    let volumes = (0..num_volumes)
+        .map(|i| log_mean(lerp(cold_end, hot_end, i), lerp(cold_end, hot_end, i + 1)))
+        .collect();
+
+    (volumes, faces)
+}
+
 impl<T: Fluid, U: MatrixDecomposition> Cycle for Run<'_, T, U> {
     type Solver = U;
 
-    fn calculate_inputs(&self, time: f64, conditions: Conditions) -> StateEquationInputs {
-        let Conditions { P, T_c, T_e } = conditions;
-        let (comp_vol, exp_vol) = (self.ws_vol_fn)(time);
-        StateEquationInputs {
-            pres: P,
-            enth_norm: self.enth_norm,
-            comp: self.comp_inputs(comp_vol, T_c, P),
-            chx: self.chx_inputs(P),
-            regen: self.regen_inputs(P),
-            hhx: self.hhx_inputs(P),
-            exp: self.exp_inputs(exp_vol, T_e, P),
-        }
+    #[allow(clippy::similar_names)]
+    fn calculate_inputs(
+        &self,
+        time: f64,
+        conditions: Conditions,
+        inputs: &mut StateEquationInputs,
+    ) {
+        let Conditions {
+            P,
+            T_c,
+            T_e,
+            motion,
+            m_b,
+            P_drop,
+        } = conditions;
+        let [pres_chx, pres_regen, pres_hhx, pres_exp] = P_drop.map(|drop| P - drop);
+        let (comp_vol, exp_vol) = match &self.ws_dynamics {
+            Some(dynamics) => dynamics.volumes(motion),
+            None => (self.ws_vol_fn)(time),
+        };
+        let (pres_buffer, m_dot_leak, enth_leak) =
+            self.seal(comp_vol.value + exp_vol.value, T_c, P, m_b);
+        let comp = self.comp_inputs(comp_vol, T_c, P);
+        let exp = self.exp_inputs(exp_vol, T_e, pres_exp);
+        let (wall_comp, wall_exp) = self.wall_heat((&comp, T_c, P), (&exp, T_e, pres_exp));
+        inputs.pres = P;
+        inputs.pres_drop = P_drop;
+        inputs.pres_buffer = pres_buffer;
+        inputs.enth_norm = self.enth_norm;
+        inputs.comp = WorkingSpaceInputs {
+            Q_dot_wall: wall_comp,
+            m_dot_leak,
+            enth_leak,
+            ..comp
+        };
+        self.chx_inputs(pres_chx, &mut inputs.chx);
+        self.regen_inputs(pres_regen, &mut inputs.regen);
+        self.hhx_inputs(pres_hhx, &mut inputs.hhx);
+        inputs.exp = WorkingSpaceInputs {
+            Q_dot_wall: wall_exp,
+            ..exp
+        };
     }
 
     fn pres_zero(&self) -> f64 {
@@ -156,4 +495,103 @@ impl<T: Fluid, U: MatrixDecomposition> Cycle for Run<'_, T, U> {
     fn period(&self) -> f64 {
         self.period
     }
+
+    fn initial_motion(&self) -> Option<ws::Motion> {
+        self.ws_dynamics
+            .as_ref()
+            .map(|dynamics| dynamics.initial_motion())
+    }
+
+    /// Return the time derivatives of the piston motion
+    ///
+    /// Each piston face sees the pressure of its own space.
+    fn motion_derivatives(&self, conditions: Conditions, _solution: &Solution) -> ws::Motion {
+        let Some(dynamics) = &self.ws_dynamics else {
+            return ws::Motion::default();
+        };
+        let pres_exp = conditions.P - conditions.P_drop[3];
+        dynamics.derivatives(conditions.motion, conditions.P, pres_exp)
+    }
+
+    fn has_pressure_drops(&self) -> bool {
+        self.pressure_drops
+    }
+
+    /// Return the buffer mass at the start of the cycle
+    ///
+    /// The buffer starts out charged to the mean cycle pressure when the
+    /// working spaces are at their mean volume.
+    fn initial_buffer_mass(&self) -> Option<f64> {
+        let space = self.buffer.as_ref()?;
+        Some(space.buffer.volume() * self.fluid.dens(space.temp, self.pres.avg))
+    }
+
+    /// Return the buffer mass to start the next cycle from
+    ///
+    /// The buffer mass relaxes toward its periodic value with the time
+    /// constant of the seal and buffer, which can span many cycles.  Over one
+    /// cycle the mass covers `1 - exp(-period / time_constant)` of the
+    /// remaining way, so the next cycle starts from where that leads, which
+    /// is the periodic value for a linear seal.  The leak carries gas from
+    /// the compression space and the buffer in turn, so the time constant
+    /// uses the mean of their densities.  A perfect seal never changes the
+    /// buffer charge.
+    fn next_buffer_mass(&self, start: Conditions, end: Conditions) -> f64 {
+        let Some(space) = &self.buffer else {
+            return end.m_b;
+        };
+        let (start, end, temp_comp) = (start.m_b, end.m_b, end.T_c);
+        let vol = space.buffer.volume();
+        let pres = self.buffer_pres(space.temp, start / vol);
+        let dens = 0.5 * (self.fluid.dens(space.temp, pres) + self.fluid.dens(temp_comp, pres));
+        let compressibility = self.fluid.dd_dP_T(space.temp, pres);
+        let time_constant = space.buffer.R_seal() * vol * compressibility / dens;
+        if !time_constant.is_finite() {
+            return start;
+        }
+        let settled = -(-self.period / time_constant).exp_m1();
+        start + (end - start) / settled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use crate::engine::state::RegenImbalance;
+
+    use super::*;
+
+    fn temperatures(regen_imbalance: f64) -> Temperatures {
+        let (chx, hhx) = (320.0, 880.0);
+        Temperatures {
+            sink: 300.0,
+            chx,
+            regen: RegenImbalance(regen_imbalance).regen_temp(chx, hhx, 15.0),
+            hhx,
+            source: 900.0,
+        }
+    }
+
+    #[test]
+    fn single_regen_volume_is_at_average_temperature() {
+        for imbalance in [-20.0, 0.0, 20.0] {
+            let temp = temperatures(imbalance);
+            let (volumes, faces) = regen_temps(&temp, 1);
+            assert_relative_eq!(volumes[0], temp.regen.avg, max_relative = 1e-12);
+            assert_eq!(faces, vec![temp.regen.cold, temp.regen.hot]);
+        }
+    }
+
+    #[test]
+    fn regen_temperatures_increase_from_cold_to_hot() {
+        let temp = temperatures(10.0);
+        let (volumes, faces) = regen_temps(&temp, 8);
+        assert_eq!(volumes.len(), 8);
+        assert_eq!(faces.len(), 9);
+        assert_eq!(faces[0], temp.regen.cold);
+        assert_eq!(faces[8], temp.regen.hot);
+        assert!(volumes.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(faces.windows(2).all(|pair| pair[0] < pair[1]));
+    }
 }
@@ -0,0 +1,285 @@
+use std::cell::RefCell;
+
+use crate::{fluid::Fluid, types::PropertyEvaluation};
+
+// The number of pressure nodes per average cycle pressure
+const NODES_PER_PRES: u32 = 32;
+
+/// Fluid properties that can be stored in a `PropertyTable`
+pub(super) trait Tabulated: Copy {
+    /// Evaluate the properties directly from a fluid
+    fn evaluate<T: Fluid>(fluid: &T, temp: f64, pres: f64) -> Self;
+
+    /// Linearly interpolate between `self` and `other`
+    fn lerp(self, other: Self, frac: f64) -> Self;
+}
+
+/// Fluid properties needed by the state equations at a fixed temperature
+#[allow(non_snake_case)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) struct Properties {
+    pub dens: f64,
+    pub inte: f64,
+    pub enth: f64,
+    pub dd_dP_T: f64,
+    pub du_dP_T: f64,
+}
+
+/// Specific enthalpy, which is all that is needed at a regenerator face
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) struct Enthalpy(pub f64);
+
+/// A lazily filled table of properties over pressure at a fixed temperature
+///
+/// During a `Run` the heat exchanger temperatures are constant and only the
+/// pressure varies, so properties are evaluated at evenly spaced pressure
+/// nodes and linearly interpolated between them.  The nodes run from zero to
+/// twice the average cycle pressure and are only evaluated when first needed.
+/// Pressures outside them, which a trial step of the integrator can reach,
+/// are evaluated directly.
+///
+/// Linear interpolation is exact for an ideal gas, where density is linear in
+/// pressure and all other properties are independent of it.  For a real gas
+/// the relative error in density is of order `(Z - 1) / NODES_PER_PRES^2`,
+/// and `PropertyEvaluation::Direct` skips the table entirely.
+pub(super) struct PropertyTable<P: Tabulated> {
+    temp: f64,
+    pres_step: f64,
+    evaluation: PropertyEvaluation,
+    nodes: RefCell<Vec<Option<P>>>,
+}
+
+impl Tabulated for Properties {
+    fn evaluate<T: Fluid>(fluid: &T, temp: f64, pres: f64) -> Self {
+        Self {
+            dens: fluid.dens(temp, pres),
+            inte: fluid.inte(temp, pres),
+            enth: fluid.enth(temp, pres),
+            dd_dP_T: fluid.dd_dP_T(temp, pres),
+            du_dP_T: fluid.du_dP_T(temp, pres),
+        }
+    }
+
+    fn lerp(self, other: Self, frac: f64) -> Self {
+        Self {
+            dens: lerp(self.dens, other.dens, frac),
+            inte: lerp(self.inte, other.inte, frac),
+            enth: lerp(self.enth, other.enth, frac),
+            dd_dP_T: lerp(self.dd_dP_T, other.dd_dP_T, frac),
+            du_dP_T: lerp(self.du_dP_T, other.du_dP_T, frac),
+        }
+    }
+}
+
+impl Tabulated for Enthalpy {
+    fn evaluate<T: Fluid>(fluid: &T, temp: f64, pres: f64) -> Self {
+        Self(fluid.enth(temp, pres))
+    }
+
+    fn lerp(self, other: Self, frac: f64) -> Self {
+        Self(lerp(self.0, other.0, frac))
+    }
+}
+
+fn lerp(a: f64, b: f64, frac: f64) -> f64 {
+    a + frac * (b - a)
+}
+
+impl<P: Tabulated> PropertyTable<P> {
+    /// Create a table at `temp` with nodes spaced relative to `pres_avg`
+    pub(super) fn new(temp: f64, pres_avg: f64, evaluation: PropertyEvaluation) -> Self {
+        Self {
+            temp,
+            pres_step: pres_avg / f64::from(NODES_PER_PRES),
+            evaluation,
+            nodes: RefCell::new(vec![None; 2 * NODES_PER_PRES as usize + 1]),
+        }
+    }
+
+    /// Return the properties at `pres`
+    ///
+    /// Pressures that are not finite, or that are outside the nodes or below
+    /// the first positive node, are evaluated directly.
+    pub(super) fn get<T: Fluid>(&self, fluid: &T, pres: f64) -> P {
+        let position = pres / self.pres_step;
+        let lower = position.floor();
+        let tabulated = match self.evaluation {
+            PropertyEvaluation::Tabulated => lower >= 1.0 && lower < f64::from(2 * NODES_PER_PRES),
+            PropertyEvaluation::Direct => false,
+        };
+        if !tabulated {
+            return P::evaluate(fluid, self.temp, pres);
+        }
+
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let index = lower as usize;
+        let lower_props = self.node(fluid, index);
+        let upper_props = self.node(fluid, index + 1);
+        lower_props.lerp(upper_props, position - lower)
+    }
+
+    /// Return the properties at a node, evaluating them if needed
+    fn node<T: Fluid>(&self, fluid: &T, index: usize) -> P {
+        *self.nodes.borrow_mut()[index].get_or_insert_with(|| {
+            #[allow(clippy::cast_precision_loss)]
+            let pres = self.pres_step * index as f64;
+            P::evaluate(fluid, self.temp, pres)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use crate::fluid::IdealGas;
+
+    use super::*;
+
+    /// A hydrogen-like gas with a temperature dependent second virial
+    /// coefficient, `v = R T / P + b - a / T`, so that density is nonlinear
+    /// in pressure
+    struct VirialGas;
+
+    impl VirialGas {
+        const R: f64 = 4124.;
+        const CV: f64 = 10_180.;
+        const A: f64 = 0.5;
+        const B: f64 = 7e-3;
+
+        fn spec_vol(temp: f64, pres: f64) -> f64 {
+            Self::R * temp / pres + Self::B - Self::A / temp
+        }
+    }
+
+    #[allow(non_snake_case)]
+    impl Fluid for VirialGas {
+        fn dens(&self, temp: f64, pres: f64) -> f64 {
+            1. / Self::spec_vol(temp, pres)
+        }
+
+        fn inte(&self, temp: f64, pres: f64) -> f64 {
+            Self::CV * temp - Self::A * pres / temp
+        }
+
+        fn enth(&self, temp: f64, pres: f64) -> f64 {
+            self.inte(temp, pres) + pres * Self::spec_vol(temp, pres)
+        }
+
+        fn cp(&self, temp: f64, pres: f64) -> f64 {
+            Self::CV + Self::R + 2. * Self::A * pres / temp.powi(2)
+        }
+
+        fn dd_dP_T(&self, temp: f64, pres: f64) -> f64 {
+            Self::R * temp / (pres * Self::spec_vol(temp, pres)).powi(2)
+        }
+
+        fn dd_dT_P(&self, temp: f64, pres: f64) -> f64 {
+            -(Self::R / pres + Self::A / temp.powi(2)) / Self::spec_vol(temp, pres).powi(2)
+        }
+
+        fn du_dP_T(&self, temp: f64, _pres: f64) -> f64 {
+            -Self::A / temp
+        }
+
+        fn du_dT_P(&self, temp: f64, pres: f64) -> f64 {
+            Self::CV + Self::A * pres / temp.powi(2)
+        }
+    }
+
+    const PRESSURES: [f64; 6] = [4.1e6, 7.3e6, 10e6, 11.99e6, 16.7e6, 23.9e6];
+
+    #[test]
+    fn matches_ideal_gas_properties() {
+        let fluid = IdealGas::hydrogen();
+        let temp = 650.0;
+        let table = PropertyTable::new(temp, 10e6, PropertyEvaluation::Tabulated);
+        for pres in PRESSURES {
+            let expected = Properties::evaluate(&fluid, temp, pres);
+            let actual: Properties = table.get(&fluid, pres);
+            assert_relative_eq!(expected.dens, actual.dens, max_relative = 1e-12);
+            assert_relative_eq!(expected.inte, actual.inte, max_relative = 1e-12);
+            assert_relative_eq!(expected.enth, actual.enth, max_relative = 1e-12);
+            assert_relative_eq!(expected.dd_dP_T, actual.dd_dP_T, max_relative = 1e-12);
+            assert_eq!(expected.du_dP_T, actual.du_dP_T);
+        }
+    }
+
+    #[test]
+    fn interpolates_real_gas_properties() {
+        let fluid = VirialGas;
+        let temp = 300.0;
+        let table = PropertyTable::new(temp, 10e6, PropertyEvaluation::Tabulated);
+        let faces = PropertyTable::new(temp, 10e6, PropertyEvaluation::Tabulated);
+        for pres in [4.1e6, 7.3e6, 11.99e6, 16.7e6, 18.3e6] {
+            let expected = Properties::evaluate(&fluid, temp, pres);
+            let actual: Properties = table.get(&fluid, pres);
+            assert_ne!(
+                expected.dens, actual.dens,
+                "density is nonlinear between nodes"
+            );
+            assert_relative_eq!(expected.dens, actual.dens, max_relative = 1e-4);
+            assert_relative_eq!(expected.dd_dP_T, actual.dd_dP_T, max_relative = 1e-3);
+            assert_relative_eq!(expected.inte, actual.inte, max_relative = 1e-12);
+            assert_relative_eq!(expected.enth, actual.enth, max_relative = 1e-12);
+            assert_relative_eq!(expected.du_dP_T, actual.du_dP_T, max_relative = 1e-12);
+            let Enthalpy(enth) = faces.get(&fluid, pres);
+            assert_relative_eq!(expected.enth, enth, max_relative = 1e-12);
+        }
+    }
+
+    #[test]
+    fn direct_evaluation_skips_table() {
+        let fluid = VirialGas;
+        let temp = 300.0;
+        let table = PropertyTable::new(temp, 10e6, PropertyEvaluation::Direct);
+        for pres in PRESSURES {
+            assert_eq!(
+                Properties::evaluate(&fluid, temp, pres),
+                table.get(&fluid, pres)
+            );
+        }
+        assert!(table.nodes.borrow().iter().all(Option::is_none));
+    }
+
+    #[test]
+    fn evaluates_nodes_lazily() {
+        let fluid = IdealGas::helium();
+        let temp = 300.0;
+        let table = PropertyTable::new(temp, 1e6, PropertyEvaluation::Tabulated);
+        let evaluated = || {
+            table
+                .nodes
+                .borrow()
+                .iter()
+                .filter(|node| node.is_some())
+                .count()
+        };
+        assert_eq!(
+            Properties::evaluate(&fluid, temp, -1.0),
+            table.get(&fluid, -1.0),
+            "negative pressures should be evaluated directly"
+        );
+        assert_eq!(evaluated(), 0);
+
+        // Only the nodes on either side of a pressure are evaluated
+        let _: Properties = table.get(&fluid, 1.99e6);
+        assert!(table.nodes.borrow()[63].is_some() && table.nodes.borrow()[64].is_some());
+        let _: Properties = table.get(&fluid, 0.5e6);
+        assert_eq!(evaluated(), 4);
+    }
+
+    #[test]
+    fn evaluates_distant_pressures_directly() {
+        let fluid = VirialGas;
+        let temp = 300.0;
+        let table = PropertyTable::new(temp, 10e6, PropertyEvaluation::Tabulated);
+        for pres in [20.01e6, 1e12, 1e300, f64::INFINITY, f64::NAN, 1e3] {
+            let expected = Properties::evaluate(&fluid, temp, pres);
+            let actual: Properties = table.get(&fluid, pres);
+            assert_eq!(expected.dens.to_bits(), actual.dens.to_bits());
+        }
+        assert!(table.nodes.borrow().iter().all(Option::is_none));
+        assert_eq!(table.nodes.borrow().len(), 65, "the table should not grow");
+    }
+}
@@ -1,15 +1,26 @@
 use itertools::Itertools;
+use serde::{Deserialize, Serialize};
 
 use crate::{
     chx,
     fluid::Fluid,
     hhx, regen, state_equations,
-    types::{ConvergenceTolerance, HeatExchanger, RunInputs},
+    types::{
+        ConvergenceTolerance, HeatExchanger, MetalTemperatures, OuterTolerance, Quantity, RunInputs,
+    },
     ws,
 };
 
 use super::Components;
 
+// Bisection iterations used to find a `RegenImbalance`, which narrow its
+// range by a factor of about 1e15
+const BISECTION_ITERS: usize = 50;
+
+// Fraction of a change in the regenerator imbalance that is taken when it
+// reverses the last change
+const IMBALANCE_DAMPING: f64 = 0.5;
+
 /// The state of a running Stirling engine
 pub struct State<T: Fluid> {
     pub fluid: T,
@@ -18,10 +29,11 @@ pub struct State<T: Fluid> {
     pub mass_flow: MassFlows,
     pub heat_flow: HeatFlows,
     pub regen_imbalance: RegenImbalance,
+    imbalance_step: f64,
 }
 
 /// Engine pressure over the cycle in Pa
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct Pressure {
     pub avg: f64,
     pub max: f64,
@@ -30,7 +42,7 @@ pub struct Pressure {
 }
 
 /// Constant engine temperatures in K
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct Temperatures {
     pub sink: f64,
     pub chx: f64,
@@ -40,7 +52,7 @@ pub struct Temperatures {
 }
 
 /// Temperatures associated with the regenerator
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct RegenTemp {
     pub cold: f64, // T_r_cold
     pub avg: f64,  // T_r
@@ -48,7 +60,7 @@ pub struct RegenTemp {
 }
 
 /// Average mass flow rates through the heat exchangers in kg/s
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct MassFlows {
     pub chx: f64,
     pub regen: f64,
@@ -56,7 +68,7 @@ pub struct MassFlows {
 }
 
 /// Average heat flow rates through the heat exhangers in W
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct HeatFlows {
     pub chx: f64,
     pub regen: f64,
@@ -83,14 +95,40 @@ pub struct HeatFlows {
 ///    `T_r_cold = T_k + (approach - imbalance)`
 ///    `T_r_hot = T_l - approach`
 ///
-#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
 pub struct RegenImbalance(pub f64);
 
+/// A snapshot of an engine state
+///
+/// Snapshots are taken of converged runs and of runs that were interrupted.
+/// A snapshot can be used as the starting point of another run, which lets
+/// a run at nearby conditions converge in fewer iterations.  Along with the
+/// engine state, it holds the working space temperatures at the start of
+/// the latest cycle, which are the initial conditions of the inner loop.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct StateSnapshot {
+    pub temp: Temperatures,
+    pub pres: Pressure,
+    pub mass_flow: MassFlows,
+    pub heat_flow: HeatFlows,
+    pub regen_imbalance: RegenImbalance,
+    pub temp_comp_zero: f64,
+    pub temp_exp_zero: f64,
+}
+
 /// Time-discretized state values within a Stirling engine
+///
+/// `P` is the compression space pressure and `P_drop` is the pressure drop
+/// from it to the expansion space, which is zero with a uniform pressure.
+///
+/// `m_dot` holds the mass flow rate across each control volume interface and
+/// `Q_dot` the heat flow for each control volume, both ordered as in a
+/// `Solution` and each over time.
 #[allow(non_snake_case)]
 #[derive(Debug, Default)]
 pub struct Values {
     pub time: Vec<f64>,
+    pub crank_angle: Vec<f64>,
     pub P: Vec<f64>,
     pub T_c: Vec<f64>,
     pub T_e: Vec<f64>,
@@ -101,6 +139,22 @@ pub struct Values {
     pub Q_dot_k: Vec<f64>,
     pub Q_dot_r: Vec<f64>,
     pub Q_dot_l: Vec<f64>,
+    pub P_drop: Vec<f64>,
+    pub V_c: Vec<f64>,
+    pub V_e: Vec<f64>,
+    pub dVc_dt: Vec<f64>,
+    pub dVe_dt: Vec<f64>,
+    pub x_p: Vec<f64>,
+    pub v_p: Vec<f64>,
+    pub x_d: Vec<f64>,
+    pub v_d: Vec<f64>,
+    pub current: Vec<f64>,
+    pub P_b: Vec<f64>,
+    pub m_dot_leak: Vec<f64>,
+    pub Q_dot_c: Vec<f64>,
+    pub Q_dot_e: Vec<f64>,
+    pub m_dot: Vec<Vec<f64>>,
+    pub Q_dot: Vec<Vec<f64>>,
 }
 
 #[derive(Default, Clone, Copy)]
@@ -113,29 +167,33 @@ struct Approach {
 impl<T: Fluid> State<T> {
     /// Return `self` updated from new `state_equations::Values`
     ///
-    /// The updated `State` is returned as `Ok(self)`.  If the provided
-    /// `values` do not change the `State` within `tol`, then the original
-    /// `State` is returned as `Err(self)`.
+    /// The updated `State` is returned as `Ok(self)`, along with the
+    /// quantities that have not converged.  If the provided `values` do not
+    /// change any checked quantity within `tol`, then the original `State` is
+    /// returned as `Err(self)`, with the pressure and flows of `values`, so
+    /// that they describe the same cycle as the temperatures that produced it.
     #[allow(clippy::result_large_err, clippy::similar_names)]
-    pub fn update(
+    pub(super) fn update(
         self,
         components: &Components,
         values: &Values,
-        tol: ConvergenceTolerance,
-    ) -> Result<Self, Self> {
-        // Calculate actual pressure
-        let pres = Pressure::from_values(values);
+        tol: OuterTolerance,
+    ) -> Result<(Self, Vec<Quantity>), Self> {
+        let (pres, mass_flow, heat_flow) = self.flows(components, values);
 
-        // Calculate actual mass flow rates
-        let mass_flow = MassFlows::from_values(values);
-
-        // Calculate actual heat flow rates
-        let ws_state = self.ws();
-        let thermal_res = components.ws.thermal_resistance(&ws_state);
-        let heat_flow = HeatFlows::from_values(values, self.temp.chx, self.temp.hhx, thermal_res);
-
-        // TODO: Determine a better regen_imbalance using an energy balance and math
-        let regen_imbalance = self.regen_imbalance;
+        // Shift the regenerator imbalance to balance its enthalpy flows.  The
+        // cycle is only integrated to within the ODE tolerance, so near the
+        // balance the shift can keep reversing.  Only part of a shift that
+        // reverses the last one is taken, so that the imbalance settles
+        // instead of oscillating.
+        let balanced = self
+            .regen_imbalance
+            .balance(&self.fluid, &self.temp, values);
+        let mut imbalance_step = balanced.0 - self.regen_imbalance.0;
+        if imbalance_step * self.imbalance_step < 0. {
+            imbalance_step *= IMBALANCE_DAMPING;
+        }
+        let regen_imbalance = RegenImbalance(self.regen_imbalance.0 + imbalance_step);
 
         // Generate the new state we will provide to the components
         let new_state = State {
@@ -145,6 +203,7 @@ impl<T: Fluid> State<T> {
             mass_flow,
             heat_flow,
             regen_imbalance,
+            imbalance_step,
         };
 
         // Calculate engine temperatures from updated approach temperatures
@@ -160,26 +219,114 @@ impl<T: Fluid> State<T> {
             regen_imbalance,
         );
 
-        // Check for temperature convergence
-        // TODO: Do we want to check `pres`, `mass_flow`, and `heat_flow` for convergence as well?
-        if self.temp.is_converged(new_temp, tol) {
-            // Temperatures are converged and we return `self` unchanged
+        // Check each quantity for convergence, where temperatures include the
+        // regenerator imbalance through the cold and hot regenerator temperatures
+        let checks = [
+            (
+                Quantity::Temperature,
+                Some(self.temp.is_converged(new_temp, tol.temp)),
+            ),
+            (
+                Quantity::Pressure,
+                tol.pres.map(|tol| self.pres.is_converged(pres, tol)),
+            ),
+            (
+                Quantity::MassFlow,
+                tol.mass_flow
+                    .map(|tol| self.mass_flow.is_converged(mass_flow, tol)),
+            ),
+            (
+                Quantity::HeatFlow,
+                tol.heat_flow
+                    .map(|tol| self.heat_flow.is_converged(heat_flow, tol)),
+            ),
+        ];
+        let unconverged: Vec<_> = checks
+            .into_iter()
+            .filter_map(|(quantity, converged)| (converged == Some(false)).then_some(quantity))
+            .collect();
+
+        if unconverged.is_empty() {
+            // Everything is converged and we return `self` with the flows of
+            // its final cycle
             Err(Self {
                 fluid: new_state.fluid,
+                pres,
+                mass_flow,
+                heat_flow,
                 ..self
             })
         } else {
-            // Approach temperatures have changed and we return the new `State`
-            Ok(Self {
-                temp: new_temp,
-                ..new_state
-            })
+            // Something has changed and we return the new `State`
+            Ok((
+                Self {
+                    temp: new_temp,
+                    ..new_state
+                },
+                unconverged,
+            ))
         }
     }
 
+    /// Update the pressure and flows from the `values` of a single cycle
+    ///
+    /// This is used by transient runs, where the temperatures are set by the
+    /// metal temperatures instead of converging with the flows.
+    pub(super) fn update_flows(&mut self, components: &Components, values: &Values) {
+        (self.pres, self.mass_flow, self.heat_flow) = self.flows(components, values);
+    }
+
+    /// Update the temperatures from the metal temperatures of a transient run
+    ///
+    /// The cold and hot heat exchanger metal take the place of the sink and
+    /// source, so the components' approach temperatures are relative to the
+    /// metal.  The regenerator imbalance is chosen so that the average
+    /// regenerator temperature matches its matrix.  The pressures are scaled
+    /// to match `pres_zero`.
+    pub(super) fn update_temperatures(
+        &mut self,
+        components: &Components,
+        metal: MetalTemperatures,
+        pres_zero: f64,
+    ) {
+        let approach = Approach {
+            chx: components.chx.approach(&self.chx()),
+            regen: components.regen.approach(&self.regen()),
+            hhx: components.hhx.approach(&self.hhx()),
+        };
+        let (chx, hhx) = (metal.chx + approach.chx, metal.hhx - approach.hhx);
+        let regen_imbalance = RegenImbalance::with_average(chx, hhx, approach.regen, metal.regen);
+        self.pres = self.pres.scaled_to(pres_zero);
+        self.temp = Temperatures::from_approach(metal.chx, metal.hhx, approach, regen_imbalance);
+        self.regen_imbalance = regen_imbalance;
+    }
+
+    /// Return the pressure, mass flows, and heat flows found from `values`
+    fn flows(&self, components: &Components, values: &Values) -> (Pressure, MassFlows, HeatFlows) {
+        // Calculate actual pressure
+        let pres = Pressure::from_values(values);
+
+        // Calculate actual mass flow rates
+        let mass_flow = MassFlows::from_values(values);
+
+        // Calculate actual heat flow rates
+        let ws_state = self.ws();
+        let thermal_res = components.ws.thermal_resistance(&ws_state);
+        let heat_flow = HeatFlows::from_values(values, self.temp.chx, self.temp.hhx, thermal_res);
+
+        (pres, mass_flow, heat_flow)
+    }
+
     /// Return the `ws::State` that corresponds to this `engine::State`
     pub fn ws(&self) -> ws::State {
-        ws::State { pres: self.pres }
+        let temp_mean = 0.5 * (self.temp.chx + self.temp.hhx);
+        ws::State {
+            pres: self.pres,
+            temp_chx: self.temp.chx,
+            temp_hhx: self.temp.hhx,
+            dens: self.fluid.dens(temp_mean, self.pres.avg),
+            cp: self.fluid.cp(temp_mean, self.pres.avg),
+        }
     }
 
     /// Return the `chx::State` that corresponds to this `engine::State`
@@ -228,6 +375,34 @@ impl<T: Fluid> State<T> {
         }
     }
 
+    /// Create an initial `State` hint from a `StateSnapshot`
+    ///
+    /// The snapshot approach temperatures are applied to the sink and source
+    /// temperatures from `inputs`, and the snapshot pressures are scaled to
+    /// match the new `pres_zero`.
+    pub(super) fn from_snapshot(fluid: T, inputs: RunInputs, snapshot: StateSnapshot) -> Self {
+        let temp = snapshot.temp;
+        let approach = Approach {
+            chx: temp.chx - temp.sink,
+            regen: (temp.regen.cold - temp.chx).min(temp.hhx - temp.regen.hot),
+            hhx: temp.source - temp.hhx,
+        };
+        Self {
+            fluid,
+            pres: snapshot.pres.scaled_to(inputs.pres_zero),
+            temp: Temperatures::from_approach(
+                inputs.temp_sink,
+                inputs.temp_source,
+                approach,
+                snapshot.regen_imbalance,
+            ),
+            mass_flow: snapshot.mass_flow,
+            heat_flow: snapshot.heat_flow,
+            regen_imbalance: snapshot.regen_imbalance,
+            imbalance_step: 0.,
+        }
+    }
+
     /// Create an initial `State` hint
     #[allow(clippy::similar_names)]
     pub(super) fn new_hint(components: &Components, fluid: T, inputs: RunInputs) -> Self {
@@ -244,7 +419,7 @@ impl<T: Fluid> State<T> {
             hhx: components.hhx.initial_approach(),
         };
 
-        // Assume regenerator is balanced
+        // Assume regenerator is balanced until a cycle has been solved
         let regen_imbalance = RegenImbalance::default();
 
         Self {
@@ -254,6 +429,28 @@ impl<T: Fluid> State<T> {
             mass_flow: MassFlows::constant(0.),
             heat_flow: HeatFlows::constant(0.),
             regen_imbalance,
+            imbalance_step: 0.,
+        }
+    }
+}
+
+impl StateSnapshot {
+    /// Create a `StateSnapshot` from a `State` and its converged `Values`
+    pub(super) fn new<T: Fluid>(state: &State<T>, values: &Values) -> Self {
+        Self::with_temp_zero(state, (values.T_c[0], values.T_e[0]))
+    }
+
+    /// Create a `StateSnapshot` from a `State` and the compression and
+    /// expansion space temperatures at the start of the cycle
+    pub(super) fn with_temp_zero<T: Fluid>(state: &State<T>, temp_zero: (f64, f64)) -> Self {
+        Self {
+            temp: state.temp,
+            pres: state.pres,
+            mass_flow: state.mass_flow,
+            heat_flow: state.heat_flow,
+            regen_imbalance: state.regen_imbalance,
+            temp_comp_zero: temp_zero.0,
+            temp_exp_zero: temp_zero.1,
         }
     }
 }
@@ -269,6 +466,30 @@ impl Pressure {
         }
     }
 
+    /// Return `self` scaled so that the pressure at time zero is `pres_zero`
+    ///
+    /// A `Pressure` without a positive, finite pressure at time zero cannot
+    /// be scaled, so a constant `pres_zero` is returned instead.
+    fn scaled_to(self, pres_zero: f64) -> Self {
+        if !(self.t_zero > 0.0 && self.t_zero.is_finite()) {
+            return Self::constant(pres_zero);
+        }
+        let scale = pres_zero / self.t_zero;
+        Self {
+            avg: self.avg * scale,
+            max: self.max * scale,
+            min: self.min * scale,
+            t_zero: pres_zero,
+        }
+    }
+
+    /// Check for convergence between `self` and `other`
+    fn is_converged(&self, other: Self, tol: ConvergenceTolerance) -> bool {
+        tol.is_converged(self.avg, other.avg)
+            && tol.is_converged(self.max, other.max)
+            && tol.is_converged(self.min, other.min)
+    }
+
     /// Calculate `Pressure` from `Values`
     pub fn from_values(values: &Values) -> Self {
         let t_final = values.final_time();
@@ -342,6 +563,13 @@ impl MassFlows {
         }
     }
 
+    /// Check for convergence between `self` and `other`
+    fn is_converged(&self, other: Self, tol: ConvergenceTolerance) -> bool {
+        tol.is_converged(self.chx, other.chx)
+            && tol.is_converged(self.regen, other.regen)
+            && tol.is_converged(self.hhx, other.hhx)
+    }
+
     /// Calculate `MassFlows` from `Values`
     pub fn from_values(values: &Values) -> Self {
         Self {
@@ -362,6 +590,13 @@ impl HeatFlows {
         }
     }
 
+    /// Check for convergence between `self` and `other`
+    fn is_converged(&self, other: Self, tol: ConvergenceTolerance) -> bool {
+        tol.is_converged(self.chx, other.chx)
+            && tol.is_converged(self.regen, other.regen)
+            && tol.is_converged(self.hhx, other.hhx)
+    }
+
     /// Calculate `HeatFlows` from `Values`
     ///
     /// To calculate the total heat flow in the cold and hot heat exchangers
@@ -369,6 +604,8 @@ impl HeatFlows {
     /// and expansion spaces to their respective heat exchangers.  Calculating
     /// this additional heat transfer requires us to provide the heat exchanger
     /// temperatures and the thermal resistance of the fluid in the two spaces.
+    /// Heat through the walls of the two spaces that is part of the state
+    /// equations is included from `values`.
     #[allow(non_snake_case, clippy::similar_names)]
     pub fn from_values(
         values: &Values,
@@ -390,7 +627,8 @@ impl HeatFlows {
             integrate(&values.time, &Q_dot_c) / t_final
         };
         let Q_dot_k = integrate(&values.time, &values.Q_dot_k) / t_final;
-        let chx = Q_dot_c + Q_dot_k;
+        let Q_dot_wall_c = integrate(&values.time, &values.Q_dot_c) / t_final;
+        let chx = Q_dot_c + Q_dot_wall_c + Q_dot_k;
 
         // Regenerator
         let regen = integrate(&values.time, &values.Q_dot_r) / t_final;
@@ -407,15 +645,16 @@ impl HeatFlows {
             integrate(&values.time, &Q_dot_e) / t_final
         };
         let Q_dot_l = integrate(&values.time, &values.Q_dot_l) / t_final;
-        let hhx = Q_dot_e + Q_dot_l;
+        let Q_dot_wall_e = integrate(&values.time, &values.Q_dot_e) / t_final;
+        let hhx = Q_dot_e + Q_dot_wall_e + Q_dot_l;
 
         Self { chx, regen, hhx }
     }
 }
 
 impl Values {
-    /// Return the last `self.time` value
-    fn final_time(&self) -> f64 {
+    /// Return the last `self.time` value, which is the period of the cycle
+    pub(crate) fn final_time(&self) -> f64 {
         *self.time.last().expect("values cannot be empty")
     }
 
@@ -457,6 +696,7 @@ impl From<Vec<state_equations::Values>> for Values {
         // Initialize all vectors with their known capacity
         let size = values.len();
         let mut time = Vec::with_capacity(size);
+        let mut crank_angle = Vec::with_capacity(size);
         let mut P = Vec::with_capacity(size);
         let mut T_c = Vec::with_capacity(size);
         let mut T_e = Vec::with_capacity(size);
@@ -467,10 +707,30 @@ impl From<Vec<state_equations::Values>> for Values {
         let mut Q_dot_k = Vec::with_capacity(size);
         let mut Q_dot_r = Vec::with_capacity(size);
         let mut Q_dot_l = Vec::with_capacity(size);
+        let mut P_drop = Vec::with_capacity(size);
+        let mut V_c = Vec::with_capacity(size);
+        let mut V_e = Vec::with_capacity(size);
+        let mut dVc_dt = Vec::with_capacity(size);
+        let mut dVe_dt = Vec::with_capacity(size);
+        let mut x_p = Vec::with_capacity(size);
+        let mut v_p = Vec::with_capacity(size);
+        let mut x_d = Vec::with_capacity(size);
+        let mut v_d = Vec::with_capacity(size);
+        let mut current = Vec::with_capacity(size);
+        let mut P_b = Vec::with_capacity(size);
+        let mut m_dot_leak = Vec::with_capacity(size);
+        let mut Q_dot_c = Vec::with_capacity(size);
+        let mut Q_dot_e = Vec::with_capacity(size);
+        let (num_faces, num_volumes) = values.first().map_or((0, 0), |value| {
+            (value.solution.m_dot.len(), value.solution.Q_dot.len())
+        });
+        let mut m_dot = vec![Vec::with_capacity(size); num_faces];
+        let mut Q_dot = vec![Vec::with_capacity(size); num_volumes];
 
         // Fill vectors using a single iteration over values
         for value in values {
             time.push(value.time);
+            crank_angle.push(value.crank_angle);
             P.push(value.conditions.P);
             T_c.push(value.conditions.T_c);
             T_e.push(value.conditions.T_e);
@@ -481,10 +741,31 @@ impl From<Vec<state_equations::Values>> for Values {
             Q_dot_k.push(value.solution.Q_dot_k);
             Q_dot_r.push(value.solution.Q_dot_r);
             Q_dot_l.push(value.solution.Q_dot_l);
+            P_drop.push(value.conditions.P_drop[3]);
+            V_c.push(value.volumes.V_c);
+            V_e.push(value.volumes.V_e);
+            dVc_dt.push(value.volumes.dVc_dt);
+            dVe_dt.push(value.volumes.dVe_dt);
+            x_p.push(value.conditions.motion.x_p);
+            v_p.push(value.conditions.motion.v_p);
+            x_d.push(value.conditions.motion.x_d);
+            v_d.push(value.conditions.motion.v_d);
+            current.push(value.conditions.motion.current);
+            P_b.push(value.buffer.P_b);
+            m_dot_leak.push(value.buffer.m_dot_leak);
+            Q_dot_c.push(value.wall.Q_dot_c);
+            Q_dot_e.push(value.wall.Q_dot_e);
+            for (face, &flow) in m_dot.iter_mut().zip(&value.solution.m_dot) {
+                face.push(flow);
+            }
+            for (volume, &heat) in Q_dot.iter_mut().zip(&value.solution.Q_dot) {
+                volume.push(heat);
+            }
         }
 
         Self {
             time,
+            crank_angle,
             P,
             T_c,
             T_e,
@@ -495,6 +776,22 @@ impl From<Vec<state_equations::Values>> for Values {
             Q_dot_k,
             Q_dot_r,
             Q_dot_l,
+            P_drop,
+            V_c,
+            V_e,
+            dVc_dt,
+            dVe_dt,
+            x_p,
+            v_p,
+            x_d,
+            v_d,
+            current,
+            P_b,
+            m_dot_leak,
+            Q_dot_c,
+            Q_dot_e,
+            m_dot,
+            Q_dot,
         }
     }
 }
@@ -521,6 +818,89 @@ impl RegenImbalance {
 
         RegenTemp { cold, avg, hot }
     }
+
+    /// Return the `RegenImbalance` that gives an average regenerator
+    /// temperature of `temp_avg`
+    ///
+    /// A positive imbalance lowers the average temperature and a negative one
+    /// raises it, so the imbalance is found by bisection.  It is limited so
+    /// that the hot side of the regenerator stays hotter than the cold side.
+    #[allow(clippy::similar_names)]
+    pub fn with_average(temp_chx: f64, temp_hhx: f64, approach: f64, temp_avg: f64) -> Self {
+        let limit = temp_hhx - temp_chx - 2. * approach;
+        if limit <= 0. {
+            return Self::default();
+        }
+        let (mut low, mut high) = (-limit, limit);
+        for _ in 0..BISECTION_ITERS {
+            let mid = 0.5 * (low + high);
+            if Self(mid).regen_temp(temp_chx, temp_hhx, approach).avg > temp_avg {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        Self(0.5 * (low + high))
+    }
+
+    /// Return the `RegenImbalance` that balances the regenerator enthalpy flows
+    ///
+    /// At cyclic steady state the regenerator matrix cannot gain or lose
+    /// energy, so the enthalpy carried in by the fluid must equal the enthalpy
+    /// carried out.  Fluid leaves the cold end at `T_r_cold` and the hot end at
+    /// `T_r_hot`, which depend on the imbalance.  Starting from the imbalance
+    /// that produced `values`, the net enthalpy flow into the regenerator is
+    /// removed by shifting the leaving temperatures, using the heat capacity
+    /// rate of the leaving flow at each end.
+    #[allow(non_snake_case)]
+    fn balance<T: Fluid>(self, fluid: &T, temp: &Temperatures, values: &Values) -> Self {
+        let t_final = values.final_time();
+        let cycle_avg = |y: &[f64]| integrate(&values.time, y) / t_final;
+
+        // Enthalpy flows into the cold end and out of the hot end, along with
+        // the heat capacity rates of flow leaving each end
+        let mut H_dot_cold = Vec::with_capacity(values.time.len());
+        let mut H_dot_hot = Vec::with_capacity(values.time.len());
+        let mut C_dot_cold = Vec::with_capacity(values.time.len());
+        let mut C_dot_hot = Vec::with_capacity(values.time.len());
+        for ((&pres, &m_dot_kr), &m_dot_rl) in
+            values.P.iter().zip(&values.m_dot_kr).zip(&values.m_dot_rl)
+        {
+            if m_dot_kr >= 0. {
+                H_dot_cold.push(m_dot_kr * fluid.enth(temp.chx, pres));
+                C_dot_cold.push(0.);
+            } else {
+                H_dot_cold.push(m_dot_kr * fluid.enth(temp.regen.cold, pres));
+                C_dot_cold.push(-m_dot_kr * fluid.cp(temp.regen.cold, pres));
+            }
+            if m_dot_rl >= 0. {
+                H_dot_hot.push(m_dot_rl * fluid.enth(temp.regen.hot, pres));
+                C_dot_hot.push(m_dot_rl * fluid.cp(temp.regen.hot, pres));
+            } else {
+                H_dot_hot.push(m_dot_rl * fluid.enth(temp.hhx, pres));
+                C_dot_hot.push(0.);
+            }
+        }
+        let net = cycle_avg(&H_dot_cold) - cycle_avg(&H_dot_hot);
+        let (C_dot_cold, C_dot_hot) = (cycle_avg(&C_dot_cold), cycle_avg(&C_dot_hot));
+
+        // A positive imbalance lowers `T_r_hot` and a negative one raises
+        // `T_r_cold`, and either one increases the net enthalpy flow in
+        let shift = |imbalance: f64| {
+            if imbalance >= 0. {
+                C_dot_hot * imbalance
+            } else {
+                C_dot_cold * imbalance
+            }
+        };
+        let target = shift(self.0) - net;
+        let rate = if target >= 0. { C_dot_hot } else { C_dot_cold };
+        if rate > 0. {
+            Self(target / rate)
+        } else {
+            self // no flow leaves the regenerator on that side
+        }
+    }
 }
 
 /// Integrate `y` over `x` using the trapezoidal rule
@@ -534,6 +914,8 @@ fn integrate(x: &[f64], y: &[f64]) -> f64 {
 
 #[cfg(test)]
 mod tests {
+    use approx::assert_relative_eq;
+
     use crate::fluid::IdealGas;
 
     use super::*;
@@ -553,6 +935,7 @@ mod tests {
             mass_flow: MassFlows::constant(1.0),
             heat_flow: HeatFlows::constant(1.0),
             regen_imbalance: RegenImbalance::default(),
+            imbalance_step: 0.0,
         };
     }
 
@@ -596,6 +979,30 @@ mod tests {
         assert_eq!(expected, Pressure::from_values(&values));
     }
 
+    #[test]
+    fn scales_pressure_to_time_zero() {
+        let pres = Pressure {
+            avg: 150.0,
+            max: 200.0,
+            min: 100.0,
+            t_zero: 100.0,
+        };
+        let expected = Pressure {
+            avg: 300.0,
+            max: 400.0,
+            min: 200.0,
+            t_zero: 200.0,
+        };
+        assert_eq!(expected, pres.scaled_to(200.0));
+
+        // Without a pressure at time zero the scaled pressure is constant
+        let pres = Pressure {
+            t_zero: 0.0,
+            ..pres
+        };
+        assert_eq!(Pressure::constant(200.0), pres.scaled_to(200.0));
+    }
+
     #[test]
     fn mass_flows_from_values() {
         // Constant mass flow rates
@@ -675,4 +1082,43 @@ mod tests {
         let actual = HeatFlows::from_values(&values, temp_chx, temp_hhx, thermal_res);
         assert_eq!(expected, actual);
     }
+
+    #[test]
+    fn regen_imbalance_balances_enthalpy_flows() {
+        use std::f64::consts::PI;
+
+        // More flow at the hot end than the cold end unbalances the regenerator
+        let time: Vec<_> = (0..=200).map(|i| f64::from(i) / 200.0).collect();
+        let m_dot_kr: Vec<_> = time.iter().map(|t| 0.05 * (2.0 * PI * t).sin()).collect();
+        let values = Values {
+            P: vec![10e6; time.len()],
+            m_dot_rl: m_dot_kr.iter().map(|m_dot| 1.2 * m_dot).collect(),
+            m_dot_kr,
+            time,
+            ..Values::default()
+        };
+        let fluid = IdealGas::hydrogen();
+        let approach = Approach {
+            chx: 20.0,
+            regen: 15.0,
+            hhx: 20.0,
+        };
+        let temp = |imbalance| Temperatures::from_approach(300.0, 900.0, approach, imbalance);
+
+        let imbalance =
+            RegenImbalance::default().balance(&fluid, &temp(RegenImbalance::default()), &values);
+        assert!(
+            imbalance.0.abs() > 1.0,
+            "the regenerator should be unbalanced"
+        );
+
+        // Repeated updates settle on the imbalance with no net enthalpy flow
+        let mut rebalanced = imbalance;
+        for _ in 0..5 {
+            rebalanced = rebalanced.balance(&fluid, &temp(rebalanced), &values);
+        }
+        let settled = rebalanced.balance(&fluid, &temp(rebalanced), &values);
+        assert_relative_eq!(settled.0, rebalanced.0, max_relative = 1e-9);
+        assert_relative_eq!(settled.0, imbalance.0, max_relative = 0.05);
+    }
 }
@@ -0,0 +1,367 @@
+use std::time::Instant;
+
+use crate::{
+    fluid::Fluid,
+    state_equations::{
+        Conditions, Cycle, DenseOutput, EliminationSolver, Interrupted, Limits, LuSolver,
+        MatrixDecomposition, QrSolver, RobustSolver, SvdDefaultSolver,
+    },
+    types::{
+        ConvergenceReport, Decomposition, MetalTemperatures, RunError, RunInputs, RunSettings,
+        SolverStats, ThermalMass, ThermalMasses,
+    },
+};
+
+use super::{
+    interrupted,
+    run::{check_buffer, Run},
+    state, Components, Engine, State,
+};
+
+/// The result of integrating a single cycle
+type CycleOutput = (state::Values, DenseOutput, SolverStats);
+
+/// Integrates a single cycle from the compression and expansion space
+/// temperatures at time zero, along with any free piston motion and buffer
+/// mass at the end of the previous cycle
+type Integrate<T> = fn(
+    &Components,
+    &State<T>,
+    &RunSettings,
+    (f64, f64),
+    Option<Conditions>,
+    &Limits,
+) -> anyhow::Result<CycleOutput>;
+
+/// A Stirling engine running through consecutive cycles while the metal of its
+/// heat exchangers warms up or cools down
+///
+/// Instead of finding cyclic steady state, each cycle is integrated once from
+/// the conditions at the end of the previous cycle.  The heat exchanger
+/// temperatures are set by the metal temperatures, which are then advanced
+/// over the cycle using the heat flows it found.  Once the metal temperatures
+/// stop changing, the cycles approach those of a steady run.  Any buffer
+/// space keeps the gas that leaked into it from one cycle to the next.
+pub struct Transient<T: Fluid> {
+    engine: Engine<T>,
+    thermal: ThermalMasses,
+    metal: MetalTemperatures,
+    inputs: RunInputs,
+    time: f64,
+    temp_zero: (f64, f64),
+    last_conditions: Option<Conditions>,
+    limits: Limits,
+    integrate: Integrate<T>,
+}
+
+impl<T: Fluid> Transient<T> {
+    /// Attempt to start a transient run by integrating its first cycle
+    ///
+    /// The first cycle starts from the `metal` temperatures, and the metal is
+    /// then advanced over it.  The settings' budget and cancel token apply to
+    /// the whole transient run, while its loop settings are not used.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err<RunError>` if the first cycle cannot be integrated,
+    /// or `RunError::InvalidConfig` if a heat capacity is not positive or a
+    /// conductance is negative.
+    #[allow(clippy::too_many_arguments, clippy::similar_names)]
+    pub fn start(
+        decomposition: Decomposition,
+        components: Components,
+        fluid: T,
+        inputs: RunInputs,
+        settings: RunSettings,
+        thermal: ThermalMasses,
+        metal: MetalTemperatures,
+    ) -> Result<Self, RunError> {
+        validate(thermal)?;
+        let integrate: Integrate<T> = match decomposition {
+            Decomposition::Lu => integrate::<T, LuSolver>,
+            Decomposition::Qr => integrate::<T, QrSolver>,
+            Decomposition::Svd => integrate::<T, SvdDefaultSolver>,
+            Decomposition::Robust => integrate::<T, RobustSolver>,
+            Decomposition::Elimination => integrate::<T, EliminationSolver>,
+        };
+        let mut state = State::new_hint(&components, fluid, inputs);
+        state.update_temperatures(&components, metal, inputs.pres_zero);
+        let temp_zero = (state.temp.chx, state.temp.hhx);
+        let limits = Limits {
+            budget: settings.budget,
+            cancel: settings.cancel.clone(),
+            start: Instant::now(),
+            spent: SolverStats::default(),
+        };
+        let (values, dense_output, stats) = cycle(
+            integrate,
+            &components,
+            &state,
+            &settings,
+            temp_zero,
+            None,
+            &limits,
+        )?;
+        state.update_flows(&components, &values);
+        let mut transient = Self {
+            temp_zero: (
+                values.T_c[values.T_c.len() - 1],
+                values.T_e[values.T_e.len() - 1],
+            ),
+            last_conditions: Some(dense_output.final_conditions()),
+            engine: Engine {
+                components,
+                state,
+                values,
+                stats,
+                convergence: ConvergenceReport::default(),
+                dense_output,
+                settings,
+            },
+            thermal,
+            metal,
+            inputs,
+            time: 0.0,
+            limits,
+            integrate,
+        };
+        transient.limits.spent = stats;
+        transient.advance_metal();
+        Ok(transient)
+    }
+
+    /// Attempt to integrate the next cycle at new `inputs`
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err<RunError>` if the cycle cannot be integrated, which
+    /// leaves the engine, metal temperatures and time at the end of the
+    /// previous cycle.
+    pub fn step(&mut self, inputs: RunInputs) -> Result<(), RunError> {
+        let engine = &mut self.engine;
+        let previous = (
+            engine.state.pres,
+            engine.state.temp,
+            engine.state.regen_imbalance,
+        );
+        engine
+            .state
+            .update_temperatures(&engine.components, self.metal, inputs.pres_zero);
+        let (values, dense_output, stats) = cycle(
+            self.integrate,
+            &engine.components,
+            &engine.state,
+            &engine.settings,
+            self.temp_zero,
+            self.last_conditions,
+            &self.limits,
+        )
+        .inspect_err(|_| {
+            // Keep the state of the previous cycle
+            (
+                engine.state.pres,
+                engine.state.temp,
+                engine.state.regen_imbalance,
+            ) = previous;
+        })?;
+        engine.state.update_flows(&engine.components, &values);
+        self.temp_zero = (
+            values.T_c[values.T_c.len() - 1],
+            values.T_e[values.T_e.len() - 1],
+        );
+        self.last_conditions = Some(dense_output.final_conditions());
+        engine.values = values;
+        engine.dense_output = dense_output;
+        engine.stats = stats;
+        self.limits.spent += stats;
+        self.inputs = inputs;
+        self.advance_metal();
+        Ok(())
+    }
+
+    /// Return the `Engine` holding the most recent cycle
+    ///
+    /// Its `stats` are for that cycle alone.
+    pub fn engine(&self) -> &Engine<T> {
+        &self.engine
+    }
+
+    /// Return the `Engine` holding the most recent cycle
+    ///
+    /// This function consumes the `Transient`.
+    pub fn into_engine(self) -> Engine<T> {
+        self.engine
+    }
+
+    /// Return the inputs used for the most recent cycle
+    pub fn inputs(&self) -> RunInputs {
+        self.inputs
+    }
+
+    /// Return the metal temperatures at the end of the most recent cycle
+    pub fn metal(&self) -> MetalTemperatures {
+        self.metal
+    }
+
+    /// Return the time (s) at the end of the most recent cycle
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    /// Return the statistics collected over every cycle
+    pub fn stats(&self) -> SolverStats {
+        self.limits.spent
+    }
+
+    /// Advance the metal temperatures over the most recent cycle
+    ///
+    /// The heat flows are averaged over the cycle, which is much shorter than
+    /// the time constants of the metal.  The heat exchanger metal is advanced
+    /// exactly for a constant heat flow, while the regenerator matrix only
+    /// gains or loses its net heat flow.
+    fn advance_metal(&mut self) {
+        let dt = self.engine.values.time[self.engine.values.time.len() - 1];
+        let heat_flow = self.engine.state.heat_flow;
+        self.metal = MetalTemperatures {
+            chx: advance(
+                self.thermal.chx,
+                self.metal.chx,
+                self.inputs.temp_sink,
+                heat_flow.chx,
+                dt,
+            ),
+            regen: self.metal.regen + heat_flow.regen * dt / self.thermal.regen,
+            hhx: advance(
+                self.thermal.hhx,
+                self.metal.hhx,
+                self.inputs.temp_source,
+                -heat_flow.hhx,
+                dt,
+            ),
+        };
+        self.time += dt;
+    }
+}
+
+/// Check that every thermal mass has a positive heat capacity and a
+/// non-negative conductance
+fn validate(thermal: ThermalMasses) -> Result<(), RunError> {
+    let is_positive = |value: f64| value > 0.0 && value.is_finite();
+    for (name, mass) in [("chx", thermal.chx), ("hhx", thermal.hhx)] {
+        if !is_positive(mass.capacitance) {
+            return Err(RunError::InvalidConfig(format!(
+                "{name} metal heat capacity must be positive, not {}",
+                mass.capacitance
+            )));
+        }
+        if mass.conductance < 0.0 || mass.conductance.is_nan() {
+            return Err(RunError::InvalidConfig(format!(
+                "{name} metal conductance must not be negative, not {}",
+                mass.conductance
+            )));
+        }
+    }
+    if !is_positive(thermal.regen) {
+        return Err(RunError::InvalidConfig(format!(
+            "regen matrix heat capacity must be positive, not {}",
+            thermal.regen
+        )));
+    }
+    Ok(())
+}
+
+/// Return the temperature of `mass` after `dt` seconds
+///
+/// `heat` is the heat flow (W) from the working fluid into the metal and
+/// `temp_external` is the temperature of the sink or source.
+fn advance(mass: ThermalMass, temp: f64, temp_external: f64, heat: f64, dt: f64) -> f64 {
+    let ThermalMass {
+        capacitance,
+        conductance,
+    } = mass;
+    if conductance == 0.0 {
+        return temp + heat * dt / capacitance;
+    }
+    let temp_eq = temp_external + heat / conductance;
+    temp_eq + (temp - temp_eq) * (-conductance * dt / capacitance).exp()
+}
+
+/// Integrate a single cycle, converting errors into a `RunError`
+fn cycle<T: Fluid>(
+    integrate: Integrate<T>,
+    components: &Components,
+    state: &State<T>,
+    settings: &RunSettings,
+    temp_zero: (f64, f64),
+    last_conditions: Option<Conditions>,
+    limits: &Limits,
+) -> Result<CycleOutput, RunError> {
+    if let Some(reason) = limits.check(SolverStats::default()) {
+        return Err(interrupted(
+            reason,
+            state,
+            temp_zero,
+            limits.spent,
+            ConvergenceReport::default(),
+        ));
+    }
+    check_buffer(components, state, None)?;
+    let output = integrate(
+        components,
+        state,
+        settings,
+        temp_zero,
+        last_conditions,
+        limits,
+    )
+    .map_err(|err| match err.downcast_ref::<Interrupted>() {
+        Some(&Interrupted { reason, stats }) => {
+            let mut spent = limits.spent;
+            spent += stats;
+            interrupted(
+                reason,
+                state,
+                temp_zero,
+                spent,
+                ConvergenceReport::default(),
+            )
+        }
+        None => RunError::InnerLoop,
+    })?;
+    check_buffer(components, state, Some(&output.0))?;
+    Ok(output)
+}
+
+/// Integrate a single cycle for a specific matrix solver
+#[allow(clippy::similar_names)]
+fn integrate<T: Fluid, U: MatrixDecomposition>(
+    components: &Components,
+    state: &State<T>,
+    settings: &RunSettings,
+    (temp_comp, temp_exp): (f64, f64),
+    last_conditions: Option<Conditions>,
+    limits: &Limits,
+) -> anyhow::Result<CycleOutput> {
+    let run: Run<T, U> = Run::new(components, state, settings);
+    let ic = Conditions {
+        P: run.pres_zero(),
+        T_c: temp_comp,
+        T_e: temp_exp,
+        motion: last_conditions
+            .map(|last| last.motion)
+            .or_else(|| run.initial_motion())
+            .unwrap_or_default(),
+        m_b: last_conditions
+            .map(|last| last.m_b)
+            .or_else(|| run.initial_buffer_mass())
+            .unwrap_or_default(),
+        P_drop: last_conditions.map_or([0.0; 4], |last| last.P_drop),
+    };
+    let integration = run.integrate(ic, settings.ode_tol, limits)?;
+    let mut stats = integration.stats();
+    let dense_output = integration.into_dense_output();
+    let (values, grid_stats) =
+        dense_output.values_on_grid(&run, settings.grid, settings.resolution)?;
+    stats += grid_stats;
+    Ok((values.into(), dense_output, stats))
+}
@@ -7,7 +7,13 @@ pub use ideal_gas::IdealGas;
 
 use serde::Deserialize;
 
-pub trait Fluid {
+use crate::types::Scalar;
+
+/// A model of working fluid properties
+///
+/// Properties are evaluated with `f64` by default, but a fluid can also be
+/// implemented for other `Scalar` types.
+pub trait Fluid<S: Scalar = f64> {
     /// Return density in kg/m3
     ///
     /// # Arguments
@@ -15,7 +21,7 @@ pub trait Fluid {
     /// * `temp` - temperature (K)
     /// * `pres` - pressure (Pa)
     ///
-    fn dens(&self, temp: f64, pres: f64) -> f64;
+    fn dens(&self, temp: S, pres: S) -> S;
 
     /// Return specific internal energy in J/kg
     ///
@@ -24,7 +30,7 @@ pub trait Fluid {
     /// * `temp` - temperature (K)
     /// * `pres` - pressure (Pa)
     ///
-    fn inte(&self, temp: f64, pres: f64) -> f64;
+    fn inte(&self, temp: S, pres: S) -> S;
 
     /// Return specific enthalpy in J/kg
     ///
@@ -33,7 +39,7 @@ pub trait Fluid {
     /// * `temp` - temperature (K)
     /// * `pres` - pressure (Pa)
     ///
-    fn enth(&self, temp: f64, pres: f64) -> f64;
+    fn enth(&self, temp: S, pres: S) -> S;
 
     /// Return specific heat at constant pressure in J/kg-K
     ///
@@ -42,7 +48,7 @@ pub trait Fluid {
     /// * `temp` - temperature (K)
     /// * `pres` - pressure (Pa)
     ///
-    fn cp(&self, temp: f64, pres: f64) -> f64;
+    fn cp(&self, temp: S, pres: S) -> S;
 
     /// Return derivative of density with respect to pressure at constant temperature in kg/m3-Pa
     ///
@@ -52,7 +58,7 @@ pub trait Fluid {
     /// * `pres` - pressure (Pa)
     ///
     #[allow(non_snake_case)]
-    fn dd_dP_T(&self, temp: f64, pres: f64) -> f64;
+    fn dd_dP_T(&self, temp: S, pres: S) -> S;
 
     /// Return derivative of density with respect to temperature at constant pressure in kg/m3-K
     ///
@@ -62,7 +68,7 @@ pub trait Fluid {
     /// * `pres` - pressure (Pa)
     ///
     #[allow(non_snake_case)]
-    fn dd_dT_P(&self, temp: f64, pres: f64) -> f64;
+    fn dd_dT_P(&self, temp: S, pres: S) -> S;
 
     /// Return derivative of internal energy with respect to pressure at constant temperature in J/kg-Pa
     ///
@@ -72,7 +78,7 @@ pub trait Fluid {
     /// * `pres` - pressure (Pa)
     ///
     #[allow(non_snake_case)]
-    fn du_dP_T(&self, temp: f64, pres: f64) -> f64;
+    fn du_dP_T(&self, temp: S, pres: S) -> S;
 
     /// Return derivative of internal energy with respect to temperature at constant pressure in J/kg-K
     ///
@@ -82,17 +88,17 @@ pub trait Fluid {
     /// * `pres` - pressure (Pa)
     ///
     #[allow(non_snake_case)]
-    fn du_dT_P(&self, temp: f64, pres: f64) -> f64;
+    fn du_dT_P(&self, temp: S, pres: S) -> S;
 }
 
-#[derive(Debug, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 pub enum Config {
     Hydrogen(ModelConfig),
     Helium(ModelConfig),
 }
 
-#[derive(Debug, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "snake_case", tag = "model")]
 pub enum ModelConfig {
     Custom,
@@ -1,3 +1,5 @@
+use crate::types::Scalar;
+
 use super::Fluid;
 
 pub struct IdealGas {
@@ -81,42 +83,42 @@ impl IdealGas {
 }
 
 #[allow(non_snake_case)]
-impl Fluid for IdealGas {
-    fn dens(&self, temp: f64, pres: f64) -> f64 {
-        pres / (self.gas_constant * temp)
+impl<S: Scalar> Fluid<S> for IdealGas {
+    fn dens(&self, temp: S, pres: S) -> S {
+        pres / (constant::<S>(self.gas_constant) * temp)
     }
 
-    fn inte(&self, temp: f64, pres: f64) -> f64 {
+    fn inte(&self, temp: S, pres: S) -> S {
         let enth = self.enth(temp, pres);
-        enth - self.gas_constant * (temp - self.ref_temp)
+        enth - constant::<S>(self.gas_constant) * (temp - constant(self.ref_temp))
     }
 
-    fn enth(&self, temp: f64, _pres: f64) -> f64 {
+    fn enth(&self, temp: S, _pres: S) -> S {
         // Need to adjust coefficients based on the difference from reference
-        let ref_diff = temp - self.ref_temp;
-        let coefs = self.enth_coefs.map(|x| x * ref_diff);
+        let ref_diff = temp - constant(self.ref_temp);
+        let coefs = self.enth_coefs.map(|x| constant::<S>(x) * ref_diff);
         poly(coefs, temp)
     }
 
-    fn cp(&self, temp: f64, _pres: f64) -> f64 {
-        poly(self.cp_coefs, temp)
+    fn cp(&self, temp: S, _pres: S) -> S {
+        poly(self.cp_coefs.map(constant), temp)
     }
 
-    fn dd_dP_T(&self, temp: f64, _pres: f64) -> f64 {
-        1. / (self.gas_constant * temp)
+    fn dd_dP_T(&self, temp: S, _pres: S) -> S {
+        S::one() / (constant::<S>(self.gas_constant) * temp)
     }
 
-    fn dd_dT_P(&self, temp: f64, pres: f64) -> f64 {
-        -pres / (self.gas_constant * temp.powi(2))
+    fn dd_dT_P(&self, temp: S, pres: S) -> S {
+        -pres / (constant::<S>(self.gas_constant) * temp.powi(2))
     }
 
-    fn du_dP_T(&self, _temp: f64, _pres: f64) -> f64 {
-        0.
+    fn du_dP_T(&self, _temp: S, _pres: S) -> S {
+        S::zero()
     }
 
-    fn du_dT_P(&self, temp: f64, pres: f64) -> f64 {
+    fn du_dT_P(&self, temp: S, pres: S) -> S {
         let cp = self.cp(temp, pres);
-        cp - self.gas_constant
+        cp - constant(self.gas_constant)
     }
 }
 
@@ -129,10 +131,15 @@ struct IdealGasParameters {
 /// Evaluate a 5th order polynomial using Horner's method
 ///
 /// Polynomial format is `a[0] + a[1]*x + a[2]*x^2 + a[3]*x^3 + a[4]*x^4 + a[5]*x^5`
-fn poly(a: [f64; 6], x: f64) -> f64 {
+fn poly<S: Scalar>(a: [S; 6], x: S) -> S {
     ((((a[5] * x + a[4]) * x + a[3]) * x + a[2]) * x + a[1]) * x + a[0]
 }
 
+/// Convert a model parameter to the scalar type used for properties
+fn constant<S: Scalar>(value: f64) -> S {
+    na::convert(value)
+}
+
 #[cfg(test)]
 mod tests {
     use serde::Serialize;
@@ -194,6 +201,30 @@ mod tests {
         assert_eq!(poly(coefs, 1.), 15.);
     }
 
+    /// Return every property of `fluid` evaluated with the scalar type `S`
+    fn props<S: Scalar>(fluid: &IdealGas, temp: S, pres: S) -> [S; 8] {
+        [
+            fluid.dens(temp, pres),
+            fluid.inte(temp, pres),
+            fluid.enth(temp, pres),
+            fluid.cp(temp, pres),
+            fluid.dd_dP_T(temp, pres),
+            fluid.dd_dT_P(temp, pres),
+            fluid.du_dP_T(temp, pres),
+            fluid.du_dT_P(temp, pres),
+        ]
+    }
+
+    #[test]
+    fn evaluates_other_scalar_types() {
+        let fluid = IdealGas::hydrogen();
+        let single = props::<f32>(&fluid, 500.0, 10e6);
+        let double = props::<f64>(&fluid, 500.0, 10e6);
+        for (single, double) in single.into_iter().zip(double) {
+            approx::assert_relative_eq!(f64::from(single), double, max_relative = 1e-6);
+        }
+    }
+
     #[test]
     fn helium() {
         check_at_reference(Name::Helium);
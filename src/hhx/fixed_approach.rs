@@ -20,7 +20,7 @@ pub struct FixedApproach {
 }
 
 #[allow(non_snake_case)]
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
 pub struct Config {
     pub vol: f64,
     pub DT: f64,
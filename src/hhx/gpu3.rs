@@ -7,19 +7,19 @@ use super::{HotHeatExchanger, State};
 pub struct GPU3 {}
 
 #[allow(non_snake_case)]
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
 pub struct Config {
-    L_total: f64,
-    L_htr: f64,
-    R_tc: f64,
-    D_outer: f64,
-    D_inner: f64,
-    roughness: f64,
-    N_total: u32,
-    vol_h: f64,
-    eta_comb: f64,
-    R_c_loss: f64,
-    W_parasitic: f64,
+    pub L_total: f64,
+    pub L_htr: f64,
+    pub R_tc: f64,
+    pub D_outer: f64,
+    pub D_inner: f64,
+    pub roughness: f64,
+    pub N_total: u32,
+    pub vol_h: f64,
+    pub eta_comb: f64,
+    pub R_c_loss: f64,
+    pub W_parasitic: f64,
 }
 
 impl HotHeatExchanger for GPU3 {
@@ -7,52 +7,52 @@ use super::{HotHeatExchanger, State};
 pub struct Mod2 {}
 
 #[allow(non_snake_case)]
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
 /// Configuration for a Mod II/I hot heat exchanger.
 pub struct Config {
-    geometry: Geometry,
-    correlation: Correlation,
+    pub geometry: Geometry,
+    pub correlation: Correlation,
 }
 
 #[allow(non_snake_case)]
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
 pub struct Geometry {
-    tubes: Tubes,
-    shell: Shell,
-    fins: Fins,
+    pub tubes: Tubes,
+    pub shell: Shell,
+    pub fins: Fins,
 }
 
 #[allow(non_snake_case)]
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
 pub struct Tubes {
-    L_front: f64,
-    L_rear: f64,
-    L_inactive: f64,
-    D_outer: f64,
-    D_inner: f64,
-    roughness: f64,
-    N_total: u32,
-    materialtube: Material,
-    materialfin: Material,
+    pub L_front: f64,
+    pub L_rear: f64,
+    pub L_inactive: f64,
+    pub D_outer: f64,
+    pub D_inner: f64,
+    pub roughness: f64,
+    pub N_total: u32,
+    pub materialtube: Material,
+    pub materialfin: Material,
 }
 
 #[allow(non_snake_case)]
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
 pub struct Shell {
-    R_outer: f64,
-    R_inner: f64,
-    V_header: f64,
+    pub R_outer: f64,
+    pub R_inner: f64,
+    pub V_header: f64,
 }
 
 #[allow(non_snake_case)]
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
 pub struct Fins {
-    thickness: f64,
-    pitch: f64,
-    L_fin: f64,
+    pub thickness: f64,
+    pub pitch: f64,
+    pub L_fin: f64,
 }
 
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
 pub enum Correlation {
     Oscillating,
     Steady,
@@ -7,19 +7,19 @@ use super::{HotHeatExchanger, State};
 pub struct NuclearIsomerGPU3 {}
 
 #[allow(non_snake_case)]
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
 pub struct Config {
-    R_f: f64,
-    L_f: f64,
-    R_regen: f64,
-    D_outer: f64,
-    D_inner: f64,
-    k_f: f64,
-    roughness: f64,
-    N_total: u32,
-    vol_h: f64,
-    R_ins: f64,
-    W_parasitic: f64,
+    pub R_f: f64,
+    pub L_f: f64,
+    pub R_regen: f64,
+    pub D_outer: f64,
+    pub D_inner: f64,
+    pub k_f: f64,
+    pub roughness: f64,
+    pub N_total: u32,
+    pub vol_h: f64,
+    pub R_ins: f64,
+    pub W_parasitic: f64,
 }
 
 impl HotHeatExchanger for NuclearIsomerGPU3 {
@@ -4,17 +4,19 @@ mod performance;
 mod state_equations;
 
 pub mod api;
+pub mod buffer;
 pub mod chx;
 pub mod fluid;
 pub mod hhx;
+pub mod observer;
 pub mod regen;
 pub mod types;
 pub mod ws;
 
 use crate::api::RunResults;
 pub use crate::config::{Config, Legacy};
-pub use engine::{Components, Engine};
-pub use state_equations::{LuSolver, QrSolver, SvdDefaultSolver};
+pub use engine::{Components, Engine, RunOptions, State, StateSnapshot, Transient};
+pub use state_equations::{EliminationSolver, LuSolver, QrSolver, RobustSolver, SvdDefaultSolver};
 
 pub use api::run_engine;
 
@@ -25,7 +27,7 @@ pub use api::run_engine;
 ///
 /// # Panics
 ///
-/// If an unsupported fluid model is provided.
+/// If an unsupported fluid model or an invalid solver config is provided.
 pub fn run_from_config(config: impl Into<Config>) {
     let config = config.into();
     let fluid = match config.engine.fluid {
@@ -43,11 +45,23 @@ pub fn run_from_config(config: impl Into<Config>) {
         },
     };
 
-    let engine = Engine::run::<LuSolver>(
-        config.engine.components.into(),
+    let options = RunOptions {
+        decomposition: config.solver.decomposition,
+        ..RunOptions::default()
+    };
+    let engine = Engine::run_with(
+        config
+            .engine
+            .components
+            .try_into()
+            .expect("components config should be valid"),
         fluid,
         config.conditions.into(),
-        config.solver.into(),
+        config
+            .solver
+            .try_into()
+            .expect("solver config should be valid"),
+        options,
     )
     .expect("engine should converge");
 
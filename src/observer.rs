@@ -0,0 +1,87 @@
+use std::ops::ControlFlow;
+
+use crate::{
+    engine::State,
+    fluid::Fluid,
+    types::{Quantity, SolverStats},
+};
+
+/// Receives events from the inner loop, which finds cyclic steady state
+///
+/// Every method has a default implementation that does nothing, so only the
+/// events of interest need to be implemented.  Returning
+/// `ControlFlow::Break` from any event cancels the run.
+pub trait CycleObserver {
+    /// Called after each integration of the cycle with its `Residuals`
+    ///
+    /// With harmonic balance this is called after each Newton iteration
+    /// instead.  `iteration` starts at zero for each inner loop.
+    fn inner_iteration(&mut self, _iteration: usize, _residuals: Residuals) -> ControlFlow<()> {
+        ControlFlow::Continue(())
+    }
+
+    /// Called after each integration of the cycle with its statistics
+    fn integration(&mut self, _stats: SolverStats) -> ControlFlow<()> {
+        ControlFlow::Continue(())
+    }
+
+    /// Called after each Newton iteration of harmonic balance with its
+    /// statistics
+    ///
+    /// Each iteration counts as one accepted step, and every evaluation of
+    /// the state equations it needed counts as a right-hand side evaluation.
+    fn newton_iteration(&mut self, _stats: SolverStats) -> ControlFlow<()> {
+        ControlFlow::Continue(())
+    }
+
+    /// Called when the flow directions had to be updated while integrating
+    ///
+    /// `time` is the time (s) within the cycle and `updates` is the number of
+    /// times the state equations were solved again with new flow directions.
+    fn flow_direction_retry(&mut self, _time: f64, _updates: usize) -> ControlFlow<()> {
+        ControlFlow::Continue(())
+    }
+}
+
+/// Receives events during an engine run
+///
+/// Every method has a default implementation that does nothing, so only the
+/// events of interest need to be implemented.  Returning
+/// `ControlFlow::Break` from any event cancels the run.
+pub trait Observer<T: Fluid>: CycleObserver {
+    /// Called before each outer loop iteration with the `State` being run
+    ///
+    /// `iteration` starts at zero.
+    fn outer_iteration_start(&mut self, _iteration: usize, _state: &State<T>) -> ControlFlow<()> {
+        ControlFlow::Continue(())
+    }
+
+    /// Called after each outer loop iteration with the updated `State`
+    ///
+    /// The quantities that had not converged are provided, which is empty
+    /// on the final iteration of a converged run.
+    fn outer_iteration_end(
+        &mut self,
+        _iteration: usize,
+        _state: &State<T>,
+        _unconverged: &[Quantity],
+    ) -> ControlFlow<()> {
+        ControlFlow::Continue(())
+    }
+}
+
+/// Change in the working space temperatures (K) over one integration of the
+/// cycle, from the start of the cycle to the end
+///
+/// With harmonic balance, this is the largest change in each temperature over
+/// one Newton iteration instead.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Residuals {
+    pub temp_comp: f64,
+    pub temp_exp: f64,
+}
+
+/// An observer that ignores every event
+impl CycleObserver for () {}
+
+impl<T: Fluid> Observer<T> for () {}
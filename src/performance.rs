@@ -5,7 +5,8 @@ use na::DVector;
 
 use crate::{
     fluid::Fluid,
-    ws::{self, CompVolume, ExpVolume},
+    types::PressureModel,
+    ws::{ElectricalPower, Motion, Parasitics, PistonForces, SpacePressures},
     Engine,
 };
 
@@ -13,13 +14,21 @@ pub(super) struct Performance {
     pub pressures_with_drops: PressuresWithDrops,
     pub power: Powers,
     pub heat: Heats,
+    pub ws_parasitics: Parasitics,
+    pub frequency: f64,
     pub shaft_torque: f64,
     pub efficiency: f64,
+    pub crank: Option<CrankLoads>,
+    pub seal: SealLeakage,
 }
 
 #[allow(non_snake_case)]
 /// Pressures in the expansion and compressions spaces, accounting for pressure
 /// drops in the HXs.
+///
+/// With the `Momentum` pressure model the working space pressures come from
+/// the state equations, otherwise the drops are calculated from the converged
+/// mass flow rates.
 pub(super) struct PressuresWithDrops {
     pub P_c: DVector<f64>,
     pub P_e: DVector<f64>,
@@ -31,6 +40,8 @@ pub(super) struct Powers {
     pub indicated_zero_dP: f64,
     pub shaft: f64,
     pub net: f64,
+    pub electrical: f64,
+    pub output: f64,
 }
 
 pub(super) struct Heats {
@@ -38,22 +49,65 @@ pub(super) struct Heats {
     pub rejected: f64,
 }
 
+/// Loads on the crank of a crank-driven engine over the cycle
+///
+/// The torque and forces come from the gas pressures alone, with the buffer
+/// pressure behind the pistons.  The energy fluctuation
+/// (J) is the largest swing in the work done on the crank by the torque less
+/// its mean, which a flywheel must absorb to hold the speed steady.
+pub(super) struct CrankLoads {
+    pub torque: DVector<f64>,
+    pub forces: Option<Vec<PistonForces>>,
+    pub energy_fluctuation: f64,
+}
+
+/// Leakage through the piston seal between the compression space and the
+/// buffer
+///
+/// `mass_flow` -- average magnitude of the leakage (kg/s)
+/// `power` -- power (W) lost to throttling the leakage through the seal,
+///            which is already reflected in the indicated power
+#[derive(Default)]
+pub(super) struct SealLeakage {
+    pub mass_flow: f64,
+    pub power: f64,
+}
+
 impl<T: Fluid> From<&Engine<T>> for Performance {
     fn from(engine: &Engine<T>) -> Self {
-        let frequency = engine.components.ws.frequency(&engine.state.ws());
+        let frequency = 1.0 / engine.values.final_time();
+        let electrical = electrical_power(engine, frequency);
+
+        // Alternator copper losses are electrical parasitics of the
+        // compression space, which holds the power piston
+        let mut ws_parasitics = engine.components.ws.parasitics(&engine.state.ws());
+        if let Some(electrical) = electrical {
+            ws_parasitics.comp.electrical += electrical.copper;
+        }
 
         let pressures_with_drops = PressuresWithDrops::new(engine);
-        let power = Powers::new(&pressures_with_drops, engine);
+        let power = Powers::new(
+            &pressures_with_drops,
+            &ws_parasitics,
+            electrical.map(|electrical| electrical.generated),
+            engine,
+        );
         let heat = Heats::new(&power, engine);
         let shaft_torque = power.shaft / (2. * PI * frequency);
         let efficiency = power.net / heat.input;
+        let crank = CrankLoads::new(&pressures_with_drops, frequency, engine);
+        let seal = SealLeakage::new(frequency, engine);
 
         Self {
             pressures_with_drops,
             power,
             heat,
+            ws_parasitics,
+            frequency,
             shaft_torque,
             efficiency,
+            crank,
+            seal,
         }
     }
 }
@@ -61,6 +115,14 @@ impl<T: Fluid> From<&Engine<T>> for Performance {
 impl PressuresWithDrops {
     pub(super) fn new<T: Fluid>(engine: &Engine<T>) -> Self {
         let pressure = DVector::from_row_slice(&engine.values.P);
+        if engine.settings().pressure_model == PressureModel::Momentum {
+            let total = DVector::from_row_slice(&engine.values.P_drop);
+            return Self {
+                P_e: &pressure - total,
+                P_c: pressure,
+            };
+        }
+
         let dens_cold_hx =
             pressure.map(|pres| engine.state.fluid.dens(engine.state.temp.chx, pres));
         let dens_hot_hx = pressure.map(|pres| engine.state.fluid.dens(engine.state.temp.hhx, pres));
@@ -115,14 +177,16 @@ impl PressuresWithDrops {
 
 impl Powers {
     #[allow(non_snake_case)]
-    fn new<T: Fluid>(pressures_with_drops: &PressuresWithDrops, engine: &Engine<T>) -> Self {
-        let frequency = engine.components.ws.frequency(&engine.state.ws());
+    fn new<T: Fluid>(
+        pressures_with_drops: &PressuresWithDrops,
+        ws_parasitics: &Parasitics,
+        generated: Option<f64>,
+        engine: &Engine<T>,
+    ) -> Self {
+        let frequency = 1.0 / engine.values.final_time();
         let time = DVector::from_row_slice(&engine.values.time);
-        let (dVc_dt, dVe_dt) = Self::dV_dts(
-            &engine.values.time,
-            &*engine.components.ws,
-            &engine.state.ws(),
-        );
+        let dVc_dt = DVector::from_row_slice(&engine.values.dVc_dt);
+        let dVe_dt = DVector::from_row_slice(&engine.values.dVe_dt);
 
         // Calculate indicated power.
         let indicated = frequency
@@ -138,7 +202,6 @@ impl Powers {
             frequency * integrate(&time, &pressure.component_mul(&(dVc_dt + dVe_dt)));
 
         // Calculate shaft power.
-        let ws_parasitics = &engine.components.ws.parasitics(&engine.state.ws());
         let shaft = indicated - ws_parasitics.comp.mechanical - ws_parasitics.exp.mechanical;
 
         // Calculate net power.
@@ -146,33 +209,39 @@ impl Powers {
         let hot_hx_parasitics = &engine.components.hhx.parasitics(&engine.state.hhx());
         let net = shaft - cold_hx_parasitics.mechanical - hot_hx_parasitics.mechanical;
 
+        // Calculate electrical power, which is only generated by an alternator.
+        let regen_parasitics = &engine.components.regen.parasitics(&engine.state.regen());
+        let electrical_parasitics = ws_parasitics.comp.electrical
+            + ws_parasitics.exp.electrical
+            + cold_hx_parasitics.electrical
+            + regen_parasitics.electrical
+            + hot_hx_parasitics.electrical;
+        let electrical = generated.map_or(0.0, |generated| generated - electrical_parasitics);
+
+        // Calculate output power, which is the electrical power of an engine
+        // with an alternator.  Without one, the electrical parasitics are
+        // driven from the net power.
+        let output = if generated.is_some() {
+            electrical
+        } else {
+            net - electrical_parasitics
+        };
+
         Self {
             indicated,
             indicated_zero_dP,
             shaft,
             net,
+            electrical,
+            output,
         }
     }
-
-    #[allow(non_snake_case)]
-    fn dV_dts(
-        time: &[f64],
-        ws_component: &dyn ws::WorkingSpaces,
-        ws_state: &ws::State,
-    ) -> (DVector<f64>, DVector<f64>) {
-        let volumes_func = ws_component.volumes(ws_state);
-        let (comp_volumes, exp_volumes): &(Vec<CompVolume>, Vec<ExpVolume>) =
-            &time.iter().map(|t: &f64| volumes_func(*t)).unzip();
-        let dVc_dt = DVector::from_vec(comp_volumes.iter().map(|cv| cv.deriv).collect());
-        let dVe_dt = DVector::from_vec(exp_volumes.iter().map(|ev| ev.deriv).collect());
-        (dVc_dt, dVe_dt)
-    }
 }
 
 impl Heats {
     #[allow(non_snake_case)]
     fn new<T: Fluid>(power: &Powers, engine: &Engine<T>) -> Self {
-        let frequency = engine.components.ws.frequency(&engine.state.ws());
+        let frequency = 1.0 / engine.values.final_time();
         let time = DVector::from_row_slice(&engine.values.time);
 
         let hhx_parasitics = engine.components.hhx.parasitics(&engine.state.hhx());
@@ -181,27 +250,31 @@ impl Heats {
 
         let ws_thermal_resistances = engine.components.ws.thermal_resistance(&engine.state.ws());
 
-        // Heat from HHX to the expansion space .
+        // Heat from the expansion space to the HHX, through a constant
+        // resistance or through walls that are part of the state equations.
         let T_e = &DVector::from_row_slice(&engine.values.T_e);
         let Q_dot_e = frequency
             * integrate(
                 &time,
                 &((T_e - DVector::from_element(T_e.nrows(), engine.state.temp.hhx))
                     / ws_thermal_resistances.exp),
-            );
+            )
+            - frequency * integrate(&time, &DVector::from_row_slice(&engine.values.Q_dot_e));
 
         // Heat input to the HHX.
         let Q_dot_l =
             frequency * integrate(&time, &DVector::from_row_slice(&engine.values.Q_dot_l));
 
-        // Heat from compression space to CHX.
+        // Heat from the compression space to the CHX, through a constant
+        // resistance or through walls that are part of the state equations.
         let T_c = &DVector::from_row_slice(&engine.values.T_c);
         let Q_dot_c = frequency
             * integrate(
                 &time,
                 &((T_c - DVector::from_element(T_c.nrows(), engine.state.temp.chx))
                     / ws_thermal_resistances.comp),
-            );
+            )
+            + frequency * integrate(&time, &DVector::from_row_slice(&engine.values.Q_dot_c));
 
         // Heat rejected from the CHX.
         let Q_dot_k =
@@ -224,6 +297,130 @@ impl Heats {
     }
 }
 
+impl CrankLoads {
+    /// Return the loads over the cycle if `engine` is driven by a crank
+    #[allow(non_snake_case)]
+    fn new<T: Fluid>(
+        pressures_with_drops: &PressuresWithDrops,
+        frequency: f64,
+        engine: &Engine<T>,
+    ) -> Option<Self> {
+        let ws_state = engine.state.ws();
+        if engine.components.ws.dynamics(&ws_state).is_some() {
+            return None;
+        }
+        let omega = 2. * PI * frequency;
+        let P_b = &engine.values.P_b;
+        let P_c = &pressures_with_drops.P_c;
+        let P_e = &pressures_with_drops.P_e;
+        let theta = DVector::from_row_slice(&engine.values.time) * omega;
+
+        // The buffer fills whatever volume the spaces give up, so only the
+        // difference from the buffer pressure turns the crank
+        let torque = DVector::from_fn(theta.nrows(), |i, _| {
+            ((P_c[i] - P_b[i]) * engine.values.dVc_dt[i]
+                + (P_e[i] - P_b[i]) * engine.values.dVe_dt[i])
+                / omega
+        });
+
+        let forces = engine
+            .components
+            .ws
+            .kinematics(&ws_state)
+            .map(|kinematics| {
+                theta
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &theta)| {
+                        kinematics.forces(
+                            theta,
+                            SpacePressures {
+                                comp: P_c[i],
+                                exp: P_e[i],
+                                buffer: P_b[i],
+                            },
+                        )
+                    })
+                    .collect()
+            });
+
+        Some(Self {
+            energy_fluctuation: energy_fluctuation(&theta, &torque),
+            torque,
+            forces,
+        })
+    }
+}
+
+impl SealLeakage {
+    /// Return the leakage over the cycle if `engine` has a buffer
+    #[allow(non_snake_case)]
+    fn new<T: Fluid>(frequency: f64, engine: &Engine<T>) -> Self {
+        let Some(buffer) = engine.components.buffer else {
+            return Self::default();
+        };
+        let values = &engine.values;
+        let time = DVector::from_row_slice(&values.time);
+        let m_dot = DVector::from_iterator(
+            values.m_dot_leak.len(),
+            values.m_dot_leak.iter().map(|m_dot| m_dot.abs()),
+        );
+        let power = DVector::from_fn(time.nrows(), |i, _| {
+            let (P, P_b) = (values.P[i], values.P_b[i]);
+            buffer.seal_flow(P, P_b) * (P - P_b)
+        });
+        Self {
+            mass_flow: frequency * integrate(&time, &m_dot),
+            power: frequency * integrate(&time, &power),
+        }
+    }
+}
+
+/// Return the largest swing in the work (J) done by `torque` less its mean
+/// over the crank angles `theta` (rad)
+fn energy_fluctuation(theta: &DVector<f64>, torque: &DVector<f64>) -> f64 {
+    let span = theta[theta.nrows() - 1] - theta[0];
+    let mean = integrate(theta, torque) / span;
+    let (mut energy, mut min, mut max) = (0.0, 0.0_f64, 0.0_f64);
+    for ((theta0, theta1), (torque0, torque1)) in theta
+        .iter()
+        .tuple_windows()
+        .zip(torque.iter().tuple_windows())
+    {
+        energy += (torque0 + torque1 - 2. * mean) * (theta1 - theta0) * 0.5;
+        min = min.min(energy);
+        max = max.max(energy);
+    }
+    max - min
+}
+
+/// Return the electrical power of the alternator averaged over the cycle, or
+/// `None` if the engine has no alternator
+fn electrical_power<T: Fluid>(engine: &Engine<T>, frequency: f64) -> Option<ElectricalPower> {
+    let dynamics = engine.components.ws.dynamics(&engine.state.ws())?;
+    let values = &engine.values;
+    let (generated, copper): (Vec<f64>, Vec<f64>) = (0..values.time.len())
+        .map(|i| {
+            dynamics
+                .electrical_power(Motion {
+                    x_p: values.x_p[i],
+                    v_p: values.v_p[i],
+                    x_d: values.x_d[i],
+                    v_d: values.v_d[i],
+                    current: values.current[i],
+                })
+                .map(|power| (power.generated, power.copper))
+        })
+        .collect::<Option<Vec<_>>>()?
+        .into_iter()
+        .unzip();
+    let time = DVector::from_row_slice(&values.time);
+    Some(ElectricalPower {
+        generated: frequency * integrate(&time, &DVector::from_vec(generated)),
+        copper: frequency * integrate(&time, &DVector::from_vec(copper)),
+    })
+}
+
 fn integrate(x: &DVector<f64>, y: &DVector<f64>) -> f64 {
     let xs = x.iter().tuple_windows();
     let ys = y.iter().tuple_windows();
@@ -236,7 +433,11 @@ fn integrate(x: &DVector<f64>, y: &DVector<f64>) -> f64 {
 mod test {
     use na::DVector;
 
-    use crate::performance::PressuresWithDrops;
+    use std::f64::consts::PI;
+
+    use approx::assert_relative_eq;
+
+    use crate::performance::{energy_fluctuation, PressuresWithDrops};
 
     #[test]
     fn calculating_hx_pressure_drop() {
@@ -253,4 +454,17 @@ mod test {
 
         assert_eq!(result, DVector::from_element(2, 0.1));
     }
+
+    #[test]
+    fn energy_fluctuation_of_sinusoidal_torque() {
+        // A torque of 5 + 2 sin(theta) does 4 J more work over half of the
+        // cycle than its mean
+        let theta = DVector::from_iterator(721, (0..=720).map(|i| f64::from(i) * PI / 360.));
+        let torque = theta.map(|theta| 5. + 2. * theta.sin());
+        assert_relative_eq!(
+            energy_fluctuation(&theta, &torque),
+            4.0,
+            max_relative = 1e-4
+        );
+    }
 }
@@ -14,12 +14,12 @@ pub struct FixedApproach {
 }
 
 #[allow(non_snake_case)]
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
 pub struct Config {
-    pub(crate) vol: f64,
-    pub(crate) DT: f64,
-    pub(crate) R_hyd: f64,
-    pub(crate) Q_parasitic: f64,
+    pub vol: f64,
+    pub DT: f64,
+    pub R_hyd: f64,
+    pub Q_parasitic: f64,
 }
 
 #[allow(non_snake_case)]
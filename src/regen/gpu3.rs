@@ -10,36 +10,36 @@ use super::{
 pub struct GPU3 {}
 
 #[allow(non_snake_case)]
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
 pub struct Config {
-    geometry: Geometry,
-    Q_parasitic: f64,
-    bypass: f64,
-    correlationf: FrictionFactorCorrelation,
-    correlationj: JFactorCorrelation,
+    pub geometry: Geometry,
+    pub Q_parasitic: f64,
+    pub bypass: f64,
+    pub correlationf: FrictionFactorCorrelation,
+    pub correlationj: JFactorCorrelation,
 }
 
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
 pub struct Geometry {
-    vol_h: f64,
-    mesh: Mesh,
-    shell: Shell,
+    pub vol_h: f64,
+    pub mesh: Mesh,
+    pub shell: Shell,
 }
 
 #[allow(non_snake_case)]
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
 pub struct Mesh {
-    material: Material,
-    D_wire: f64,
-    pitch: f64,
+    pub material: Material,
+    pub D_wire: f64,
+    pub pitch: f64,
 }
 
 #[allow(non_snake_case)]
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
 pub struct Shell {
-    diameter: f64,
-    length: f64,
-    number: u32,
+    pub diameter: f64,
+    pub length: f64,
+    pub number: u32,
 }
 
 impl super::Regenerator for GPU3 {
@@ -10,39 +10,39 @@ use super::{
 pub struct Mod2 {}
 
 #[allow(non_snake_case)]
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
 pub struct Config {
-    geometry: Geometry,
-    correlationtype: Correlation,
-    correlationf: FrictionFactorCorrelation,
-    correlationj: JFactorCorrelation,
+    pub geometry: Geometry,
+    pub correlationtype: Correlation,
+    pub correlationf: FrictionFactorCorrelation,
+    pub correlationj: JFactorCorrelation,
 }
 
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
 pub struct Geometry {
-    mesh: Mesh,
-    shell: Shell,
+    pub mesh: Mesh,
+    pub shell: Shell,
 }
 
 #[allow(non_snake_case)]
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
 pub struct Mesh {
-    material: Material,
-    D_wire: f64,
-    pitch: f64,
+    pub material: Material,
+    pub D_wire: f64,
+    pub pitch: f64,
 }
 
 #[allow(non_snake_case)]
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
 pub struct Shell {
-    material: Material,
-    R_sh: f64,
-    th_sh_cold: f64,
-    th_sh_hot: f64,
-    length: f64,
+    pub material: Material,
+    pub R_sh: f64,
+    pub th_sh_cold: f64,
+    pub th_sh_hot: f64,
+    pub length: f64,
 }
 
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
 pub enum Correlation {
     Steady,
     Oscillating,
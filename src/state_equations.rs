@@ -1,57 +1,132 @@
 mod cycle;
+mod dense_output;
 mod flow_direction;
+mod harmonic_balance;
 mod inputs;
 mod integrator;
 mod solver;
 
 use serde::Serialize;
 
+use crate::{types::Scalar, ws::Motion};
+
 // Export traits
 pub use self::{cycle::Cycle, solver::MatrixDecomposition};
 
+// Export output types
+pub use self::{cycle::Interrupted, dense_output::DenseOutput};
+
 // Export input types
 pub use self::{
-    cycle::SteadyStateInputs, inputs::HeatExchanger as HeatExchangerInputs, inputs::Inputs,
-    inputs::Regenerator as RegeneratorInputs, inputs::WorkingSpace as WorkingSpaceInputs,
+    cycle::Limits, cycle::SteadyStateInputs, inputs::HeatExchanger as HeatExchangerInputs,
+    inputs::Inputs, inputs::Regenerator as RegeneratorInputs,
+    inputs::WorkingSpace as WorkingSpaceInputs,
 };
 
 // Export matrix decomposition solvers
-pub use self::solver::{SvdDefault as SvdDefaultSolver, LU as LuSolver, QR as QrSolver};
+pub use self::solver::{
+    Elimination as EliminationSolver, Robust as RobustSolver, SvdDefault as SvdDefaultSolver,
+    LU as LuSolver, QR as QrSolver,
+};
 
 /// Conditions within the cycle
 ///
-/// `P`   -- pressure (Pa) in all control volumes
+/// `P`   -- pressure (Pa) in the compression space, which is the pressure in
+///          all control volumes unless they have pressure drops
 /// `T_c` -- temperature (K) in the compression space
 /// `T_e` -- temperature (K) in the expansion space
+/// `motion` -- positions and velocities of free pistons, which are zero when
+///             the volumes are prescribed
+/// `m_b` -- mass (kg) of gas in the buffer space, which is zero without one
+/// `P_drop` -- pressure drops (Pa) from the compression space to the cold
+///             heat exchanger, the regenerator, the hot heat exchanger, and
+///             the expansion space, which are zero with a uniform pressure
 #[allow(non_snake_case)]
 #[derive(Debug, Clone, Copy, Serialize)]
-pub struct Conditions {
-    pub P: f64,
-    pub T_c: f64,
-    pub T_e: f64,
+pub struct Conditions<S: Scalar = f64> {
+    pub P: S,
+    pub T_c: S,
+    pub T_e: S,
+    pub motion: Motion<S>,
+    pub m_b: S,
+    pub P_drop: [S; 4],
 }
 
 /// Represents a solution to the state equations
+///
+/// The mass flow rates between components and the total heat flows for each
+/// component are always provided.  When the heat exchangers and regenerator
+/// are discretized, `m_dot` holds the mass flow rate across every control
+/// volume interface (from the compression space to the expansion space) and
+/// `Q_dot` holds the heat flow for every control volume (from the cold heat
+/// exchanger to the hot heat exchanger), using the same sign conventions as
+/// the totals.
+///
+/// `dP_dt` is the rate of change of the compression space pressure and
+/// `dPdrop_dt` holds the rates of change of the pressure drops in the
+/// `Conditions`, which are zero unless the control volumes have hydraulic
+/// resistance.
+///
+/// Like the `Inputs` they are found from, solutions are `f64` by default but
+/// can use any `Scalar`.
 #[allow(non_snake_case)]
 #[derive(Debug, Clone, Serialize)]
-pub struct Solution {
-    pub m_dot_ck: f64,
-    pub m_dot_kr: f64,
-    pub m_dot_rl: f64,
-    pub m_dot_le: f64,
-    pub Q_dot_k: f64,
-    pub Q_dot_r: f64,
-    pub Q_dot_l: f64,
-    pub dTc_dt: f64,
-    pub dTe_dt: f64,
-    pub dP_dt: f64,
+pub struct Solution<S: Scalar = f64> {
+    pub m_dot_ck: S,
+    pub m_dot_kr: S,
+    pub m_dot_rl: S,
+    pub m_dot_le: S,
+    pub Q_dot_k: S,
+    pub Q_dot_r: S,
+    pub Q_dot_l: S,
+    pub dTc_dt: S,
+    pub dTe_dt: S,
+    pub dP_dt: S,
+    pub dPdrop_dt: [S; 4],
+    pub m_dot: Vec<S>,
+    pub Q_dot: Vec<S>,
+}
+
+/// Working space volumes (m^3) and their time derivatives (m^3/s)
+#[allow(non_snake_case)]
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Volumes {
+    pub V_c: f64,
+    pub V_e: f64,
+    pub dVc_dt: f64,
+    pub dVe_dt: f64,
+}
+
+/// Buffer pressure (Pa) and the seal leakage (kg/s) from the compression
+/// space into the buffer
+#[allow(non_snake_case)]
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Buffer {
+    pub P_b: f64,
+    pub m_dot_leak: f64,
+}
+
+/// Heat (W) through the walls of the working spaces
+///
+/// `Q_dot_c` flows from the compression space gas to its wall and `Q_dot_e`
+/// flows from the expansion space wall to its gas, matching the signs of the
+/// adjacent heat exchangers.
+#[allow(non_snake_case)]
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct WallHeat {
+    pub Q_dot_c: f64,
+    pub Q_dot_e: f64,
 }
 
 /// The solution to the state equations for some conditions and time
 #[derive(Debug, Clone, Serialize)]
 pub struct Values {
     pub time: f64,
+    pub crank_angle: f64,
     pub conditions: Conditions,
+    pub volumes: Volumes,
+    pub buffer: Buffer,
+    pub wall: WallHeat,
     pub solution: Solution,
 }
 
@@ -61,7 +136,7 @@ mod tests {
 
     use approx::assert_relative_eq;
 
-    use crate::types::{ConvergenceTolerance, OdeTolerance};
+    use crate::types::{ConvergenceTolerance, OdeTolerance, OutputGrid, SteadyStateMethod};
 
     use super::*;
 
@@ -94,8 +169,8 @@ mod tests {
     impl Cycle for TestEngine {
         type Solver = solver::LU;
 
-        fn calculate_inputs(&self, _time: f64, _conditions: Conditions) -> Inputs {
-            self.inputs.clone()
+        fn calculate_inputs(&self, _time: f64, _conditions: Conditions, inputs: &mut Inputs) {
+            inputs.clone_from(&self.inputs);
         }
 
         fn period(&self) -> f64 {
@@ -114,16 +189,18 @@ mod tests {
             P: 10e6,
             T_c: 400.0,
             T_e: 600.0,
+            motion: Motion::default(),
+            m_b: 0.0,
+            P_drop: [0.0; 4],
         };
-        let num_points = 21;
         let ode_tol = OdeTolerance::new(1e-4, 1e-4);
         let integration = engine
-            .integrate(initial_conditions, num_points, ode_tol)
+            .integrate(initial_conditions, ode_tol, &Limits::default())
             .expect("integration should work");
 
         let conv_tol = ConvergenceTolerance::new(1e-4, 1e-4);
         assert!(
-            !integration.is_converged(conv_tol),
+            !integration.is_converged(conv_tol, conv_tol),
             "integration should not be converged"
         );
 
@@ -137,17 +214,30 @@ mod tests {
             P: 10e6,
             T_c: 300.0,
             T_e: 500.0,
+            motion: Motion::default(),
+            m_b: 0.0,
+            P_drop: [0.0; 4],
         };
-        let num_points = 101;
         let ode_tol = OdeTolerance::new(1e-6, 1e-6);
         let integration = engine
-            .integrate(initial_conditions, num_points, ode_tol)
+            .integrate(initial_conditions, ode_tol, &Limits::default())
             .expect("integration should work");
 
         assert_relative_eq!(integration.final_time(), engine.period(), epsilon = 1e-12);
 
-        let values = integration.into_state_values();
+        let dense_output = integration.into_dense_output();
+        let (values, _) = dense_output
+            .values_on_grid(&engine, OutputGrid::Uniform, 101)
+            .expect("values should be calculated");
         assert_eq!(values.len(), 101);
+        assert_relative_eq!(values[50].crank_angle, 180.0);
+
+        let (values, _) = dense_output
+            .values_on_grid(&engine, OutputGrid::DegreesPerStep(7.0), 101)
+            .expect("values should be calculated");
+        assert_eq!(values.len(), 53, "51 full steps plus the end of the cycle");
+        assert_relative_eq!(values[51].crank_angle, 357.0);
+        assert_relative_eq!(values[52].time, engine.period());
     }
 
     #[test]
@@ -157,13 +247,18 @@ mod tests {
             pres_zero: 10e6,
             temp_comp_hint: 300.,
             temp_exp_hint: 500.,
+            motion_hint: None,
             num_points: 100,
+            grid: OutputGrid::Uniform,
             ode_tol: OdeTolerance::new(1e-4, 1e-4),
             conv_tol: ConvergenceTolerance::new(1e-4, 1e-4),
+            motion_tol: ConvergenceTolerance::new(1e-4, 1e-4),
             max_iters: 20,
+            method: SteadyStateMethod::TimeMarching,
+            limits: Limits::default(),
         };
         engine
-            .find_steady_state(inputs)
+            .find_steady_state(inputs, &mut ())
             .expect_err("should not find steady state");
     }
 }
@@ -1,14 +1,34 @@
+use std::{fmt, time::Instant};
+
 use anyhow::{bail, Result};
 
-use crate::types::{ConvergenceTolerance, OdeTolerance};
+use crate::{
+    observer::{CycleObserver, Residuals},
+    types::{
+        Budget, CancelToken, ConvergenceTolerance, Interruption, OdeTolerance, OutputGrid,
+        SolverStats, SteadyStateMethod,
+    },
+    ws::Motion,
+};
+
+use super::{
+    harmonic_balance, integrator::Integration, Conditions, DenseOutput, Inputs,
+    MatrixDecomposition, Solution, Values,
+};
 
-use super::{integrator::Integration, Conditions, Inputs, MatrixDecomposition, Values};
+// When the inner loop stalls, the ODE tolerances are multiplied by this
+// factor, down to the tightest tolerance
+const ODE_TOL_FACTOR: f64 = 0.1;
+const MIN_ODE_TOL: f64 = 1e-12;
 
 pub trait Cycle: Sized {
     type Solver: MatrixDecomposition;
 
-    /// Calculate the inputs to the state equations
-    fn calculate_inputs(&self, time: f64, conditions: Conditions) -> Inputs;
+    /// Calculate the inputs to the state equations, overwriting `inputs`
+    ///
+    /// The same `inputs` are reused for every evaluation over a cycle, so
+    /// their control volumes should be refilled rather than reallocated.
+    fn calculate_inputs(&self, time: f64, conditions: Conditions, inputs: &mut Inputs);
 
     /// Return the period in seconds for the cycle
     fn period(&self) -> f64;
@@ -16,47 +36,169 @@ pub trait Cycle: Sized {
     /// Return the pressure in Pa at time zero in the cycle
     fn pres_zero(&self) -> f64;
 
+    /// Return the piston motion at the start of the cycle, or `None` when the
+    /// volumes are prescribed
+    ///
+    /// With free pistons, `period` is only an estimate and each integration
+    /// ends when the power piston returns to its position at the start of
+    /// the cycle.
+    fn initial_motion(&self) -> Option<Motion> {
+        None
+    }
+
+    /// Calculate the time derivatives of the piston motion from the
+    /// `solution` at `conditions`
+    ///
+    /// Without free pistons there is no motion, which is the default.
+    fn motion_derivatives(&self, _conditions: Conditions, _solution: &Solution) -> Motion {
+        Motion::default()
+    }
+
+    /// Return the mass in kg of gas in the buffer space at the start of the
+    /// cycle, or `None` when there is no buffer space
+    ///
+    /// The buffer gains the gas that leaks through the piston seal, which is
+    /// `m_dot_leak` of the compression space inputs.
+    fn initial_buffer_mass(&self) -> Option<f64> {
+        None
+    }
+
+    /// Return the buffer mass to start the next cycle from, after a cycle
+    /// that went from the conditions at `start` to those at `end`
+    ///
+    /// The buffer can take many more cycles to settle than the working gas,
+    /// so a cycle may look ahead to where the buffer mass is heading rather
+    /// than continue from the mass at `end`, which is the default.
+    fn next_buffer_mass(&self, _start: Conditions, end: Conditions) -> f64 {
+        end.m_b
+    }
+
+    /// Return `true` if the control volumes have pressures of their own,
+    /// linked by momentum balances, rather than a single pressure
+    ///
+    /// The pressure drops from the compression space are then integrated
+    /// along with the other conditions.  A uniform pressure is the default.
+    fn has_pressure_drops(&self) -> bool {
+        false
+    }
+
     /// Attempt to integrate the state equations
+    ///
+    /// The integration stops with an `Interrupted` error if any of the
+    /// `limits` are exceeded.
     fn integrate(
         &self,
         initial_conditions: Conditions,
-        num_points: u32,
         tol: OdeTolerance,
-    ) -> Result<Integration<Self>> {
-        Integration::try_from(self, initial_conditions, num_points, tol)
+        limits: &Limits,
+    ) -> Result<Integration> {
+        Integration::try_from(self, initial_conditions, tol, limits)
     }
 
     /// Determine the values that correspond to cyclic steady state
     ///
     /// Cyclic steady state occurs when the temperature conditions (`T_c` and
-    /// `T_e`) at the end of the cycle are equal to those at the start.
-    fn find_steady_state(&self, inputs: SteadyStateInputs) -> Result<Vec<Values>> {
+    /// `T_e`), and any buffer mass (`m_b`), at the end of the cycle are
+    /// equal to those at the start.  Each cycle starts from the buffer mass
+    /// given by `next_buffer_mass`, which is settled once it is within the
+    /// relative tolerance of `conv_tol` of the mass the cycle started with.  The
+    /// values are calculated on `grid` from the dense output of the converged
+    /// integration, so the cycle is not integrated again.  Harmonic balance
+    /// finds cyclic steady state directly instead of integrating the cycle.
+    ///
+    /// The end of each integrated cycle is only as accurate as `ode_tol`
+    /// allows, so the temperature residuals stop shrinking once they reach
+    /// the integration error.  When a residual is no smaller than the one
+    /// before it, `ode_tol` is tightened for the rest of the search, so that
+    /// a convergence tolerance finer than the integration error can still be
+    /// met.
+    ///
+    /// Each integration is reported to `observer`, which can interrupt the
+    /// search along with any of the `limits`.
+    fn find_steady_state<O: CycleObserver + ?Sized>(
+        &self,
+        inputs: SteadyStateInputs,
+        observer: &mut O,
+    ) -> Result<SteadyState> {
+        if let SteadyStateMethod::HarmonicBalance { harmonics } = inputs.method {
+            return harmonic_balance::find_steady_state(self, inputs, harmonics, observer);
+        }
         let SteadyStateInputs {
             pres_zero,
             temp_comp_hint,
             temp_exp_hint,
+            motion_hint,
             num_points,
-            ode_tol,
+            grid,
+            mut ode_tol,
             conv_tol,
+            motion_tol,
             max_iters,
+            limits,
+            ..
         } = inputs;
 
         // Use successive substition to find the right initial conditions
-        // TODO: Create an abstraction of this so we can try different convergence methods
         let mut ic = Conditions {
             P: pres_zero,
             T_c: temp_comp_hint,
             T_e: temp_exp_hint,
+            motion: motion_hint
+                .or_else(|| self.initial_motion())
+                .unwrap_or_default(),
+            m_b: self.initial_buffer_mass().unwrap_or_default(),
+            P_drop: [0.0; 4],
         };
-        for _ in 0..max_iters {
-            let integration = Integration::try_from(self, ic, 2, ode_tol)?; // using 2 points here is faster and doesn't affect integration
-            if integration.is_converged(conv_tol) {
-                let integration = Integration::try_from(self, ic, num_points, ode_tol)?;
-                return Ok(integration.into_state_values());
+        let mut stats = SolverStats::default();
+        let mut last_residual = f64::INFINITY;
+        for iteration in 0..max_iters {
+            let integration = self
+                .integrate(ic, ode_tol, &limits)
+                .map_err(|err| match err.downcast::<Interrupted>() {
+                    Ok(interrupted) => interrupted.with_earlier(stats).into(),
+                    Err(err) => err,
+                })?;
+            stats += integration.stats();
+            let last = integration.final_conditions();
+            let residuals = Residuals {
+                temp_comp: last.T_c - ic.T_c,
+                temp_exp: last.T_e - ic.T_e,
+            };
+            let aborted =
+                integration.flow_retries().iter().any(|&(time, updates)| {
+                    observer.flow_direction_retry(time, updates).is_break()
+                }) || observer.integration(integration.stats()).is_break()
+                    || observer.inner_iteration(iteration, residuals).is_break();
+            if aborted {
+                let reason = Interruption::Cancelled;
+                return Err(Interrupted { reason, stats }.into());
+            }
+            if let Some(reason) = limits.check(stats) {
+                return Err(Interrupted { reason, stats }.into());
+            }
+            let buffer_mass = self.next_buffer_mass(ic, last);
+            let buffer_settled = (buffer_mass - ic.m_b).abs() <= conv_tol.rel * ic.m_b;
+            if buffer_settled && integration.is_converged(conv_tol, motion_tol) {
+                let dense_output = integration.into_dense_output();
+                let (values, grid_stats) = dense_output.values_on_grid(self, grid, num_points)?;
+                stats += grid_stats;
+                return Ok(SteadyState {
+                    values,
+                    dense_output,
+                    stats,
+                });
+            }
+            let residual = residuals.temp_comp.abs().max(residuals.temp_exp.abs());
+            if residual >= last_residual {
+                ode_tol = tighten(ode_tol);
+                last_residual = f64::INFINITY;
+            } else {
+                last_residual = residual;
             }
             ic = Conditions {
                 P: pres_zero,
-                ..integration.final_conditions() // succesive substitution
+                m_b: buffer_mass,
+                ..last // succesive substitution
             };
         }
 
@@ -64,12 +206,95 @@ pub trait Cycle: Sized {
     }
 }
 
+/// Return `tol` tightened by `ODE_TOL_FACTOR`, but no tighter than `MIN_ODE_TOL`
+fn tighten(tol: OdeTolerance) -> OdeTolerance {
+    OdeTolerance {
+        abs: (tol.abs * ODE_TOL_FACTOR).max(MIN_ODE_TOL).min(tol.abs),
+        rel: (tol.rel * ODE_TOL_FACTOR).max(MIN_ODE_TOL).min(tol.rel),
+    }
+}
+
+/// The values at cyclic steady state and the statistics collected finding them
+#[derive(Debug)]
+pub struct SteadyState {
+    pub values: Vec<Values>,
+    pub dense_output: DenseOutput,
+    pub stats: SolverStats,
+}
+
 pub struct SteadyStateInputs {
     pub pres_zero: f64,
     pub temp_comp_hint: f64,
     pub temp_exp_hint: f64,
+    pub motion_hint: Option<Motion>,
     pub num_points: u32,
+    pub grid: OutputGrid,
     pub ode_tol: OdeTolerance,
     pub conv_tol: ConvergenceTolerance,
+    pub motion_tol: ConvergenceTolerance,
     pub max_iters: usize,
+    pub method: SteadyStateMethod,
+    pub limits: Limits,
 }
+
+/// Limits on the effort spent finding cyclic steady state
+///
+/// The `budget` applies to the whole run, so `spent` holds the statistics
+/// from earlier inner loops and `start` is when the run started.
+#[derive(Debug, Clone)]
+pub struct Limits {
+    pub budget: Budget,
+    pub cancel: Option<CancelToken>,
+    pub start: Instant,
+    pub spent: SolverStats,
+}
+
+/// The error returned when finding cyclic steady state is interrupted
+///
+/// The statistics include the work done before the interruption, which does
+/// not include the `spent` statistics from the `Limits`.
+#[derive(Debug)]
+pub struct Interrupted {
+    pub reason: Interruption,
+    pub stats: SolverStats,
+}
+
+impl Limits {
+    /// Return the reason to stop after spending `stats` in addition to `spent`
+    pub fn check(&self, stats: SolverStats) -> Option<Interruption> {
+        if self.cancel.as_ref().is_some_and(CancelToken::is_cancelled) {
+            return Some(Interruption::Cancelled);
+        }
+        let mut total = self.spent;
+        total += stats;
+        self.budget.exceeded(total, self.start.elapsed())
+    }
+}
+
+impl Default for Limits {
+    /// No limits, starting now
+    fn default() -> Self {
+        Self {
+            budget: Budget::default(),
+            cancel: None,
+            start: Instant::now(),
+            spent: SolverStats::default(),
+        }
+    }
+}
+
+impl Interrupted {
+    /// Add the statistics from work done before `self` was created
+    fn with_earlier(mut self, stats: SolverStats) -> Self {
+        self.stats += stats;
+        self
+    }
+}
+
+impl fmt::Display for Interrupted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "interrupted: {:?}", self.reason)
+    }
+}
+
+impl std::error::Error for Interrupted {}
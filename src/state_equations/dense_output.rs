@@ -0,0 +1,502 @@
+use anyhow::{ensure, Result};
+
+use crate::{
+    types::{OutputGrid, SolverStats},
+    ws::Motion,
+};
+
+use super::{
+    flow_direction::FlowDirection, solver::LinearSystem, Buffer, Conditions, Cycle, Inputs, Values,
+    Volumes, WallHeat,
+};
+
+/// A continuous representation of the conditions over a cycle
+///
+/// The integrator stores the conditions and their time derivatives at the end
+/// of every step it takes, which allows values to be calculated at any time in
+/// the cycle without re-integrating the state equations.
+///
+/// Within a step of fraction `s`, the conditions are a cubic Hermite
+/// interpolant through the ends of the step plus a quartic term,
+/// `s^2 (1 - s)^2` times a vector for each step.  With the vectors from the
+/// Dormand-Prince stages of each step, this is the continuous extension of
+/// the method, which is fourth order and is what `ode_solvers` itself uses
+/// for dense output.  Without them, as for harmonic balance, the interpolant
+/// is cubic with an error of `O(h^4)` in the step size `h`.
+#[derive(Debug, Clone)]
+pub struct DenseOutput {
+    period: f64,
+    times: Vec<f64>,
+    conditions: Vec<Conditions>,
+    derivatives: Vec<Conditions>,
+    quartic: Vec<Conditions>,
+}
+
+impl DenseOutput {
+    /// Create a `DenseOutput` from the steps taken by an integrator
+    ///
+    /// The three vectors must have the same nonzero length, and `times` must
+    /// be strictly increasing.
+    pub(super) fn new(
+        period: f64,
+        times: Vec<f64>,
+        conditions: Vec<Conditions>,
+        derivatives: Vec<Conditions>,
+    ) -> Self {
+        assert!(!times.is_empty(), "dense output requires at least one step");
+        assert_eq!(times.len(), conditions.len());
+        assert_eq!(times.len(), derivatives.len());
+        Self {
+            period,
+            times,
+            conditions,
+            derivatives,
+            quartic: Vec::new(),
+        }
+    }
+
+    /// Return `self` with the quartic term of the interpolant in each step
+    ///
+    /// There must be one `quartic` vector for every step.
+    pub(super) fn with_quartic(mut self, quartic: Vec<Conditions>) -> Self {
+        assert_eq!(quartic.len() + 1, self.times.len());
+        self.quartic = quartic;
+        self
+    }
+
+    /// Return `self` ending at `time` with the given conditions and
+    /// derivatives, which replace its final step
+    ///
+    /// This ends a cycle at an event within its final step, such as a free
+    /// piston returning to its starting position, and `time` becomes the
+    /// period of the cycle.  The quartic term of the final step is scaled to
+    /// the shorter step, so that the highest order term of the interpolant is
+    /// unchanged.  An error is returned if there is no final step or `time`
+    /// is not within it.
+    pub(super) fn end_at(
+        mut self,
+        time: f64,
+        conditions: Conditions,
+        derivative: Conditions,
+    ) -> Result<Self> {
+        ensure!(self.times.len() > 1, "there is no final step to end within");
+        let (start, end) = self.final_step();
+        ensure!(
+            time > start && time <= end,
+            "time {time} s is not within the final step from {start} s to {end} s"
+        );
+        if let Some(quartic) = self.quartic.last_mut() {
+            *quartic = scale(*quartic, ((time - start) / (end - start)).powi(4));
+        }
+        self.times.pop();
+        self.conditions.pop();
+        self.derivatives.pop();
+        self.times.push(time);
+        self.conditions.push(conditions);
+        self.derivatives.push(derivative);
+        self.period = time;
+        Ok(self)
+    }
+
+    /// Return the start and end times of the final step
+    pub(super) fn final_step(&self) -> (f64, f64) {
+        let last = self.times.len() - 1;
+        (self.times[last.saturating_sub(1)], self.times[last])
+    }
+
+    /// Return the final time of the integration
+    pub fn final_time(&self) -> f64 {
+        *self.times.last().unwrap() // `self.times` is never empty
+    }
+
+    /// Return the conditions at the start of the integration
+    pub fn initial_conditions(&self) -> Conditions {
+        self.conditions[0]
+    }
+
+    /// Return the conditions at the end of the integration
+    pub fn final_conditions(&self) -> Conditions {
+        *self.conditions.last().unwrap() // `self.conditions` is never empty
+    }
+
+    /// Return the conditions at `time`
+    ///
+    /// Times outside of the integration are clamped to its bounds.
+    pub fn conditions_at(&self, time: f64) -> Conditions {
+        let last = self.times.len() - 1;
+        if last == 0 || time <= self.times[0] {
+            return self.conditions[0];
+        }
+        if time >= self.times[last] {
+            return self.conditions[last];
+        }
+
+        // Index of the step that ends after `time`
+        let end = self.times.partition_point(|&t| t <= time);
+        let start = end - 1;
+        let h = self.times[end] - self.times[start];
+        let s = (time - self.times[start]) / h;
+        let hermite = |y0: f64, dy0: f64, y1: f64, dy1: f64| {
+            let h00 = (1.0 + 2.0 * s) * (1.0 - s).powi(2);
+            let h10 = s * (1.0 - s).powi(2);
+            let h01 = s.powi(2) * (3.0 - 2.0 * s);
+            let h11 = s.powi(2) * (s - 1.0);
+            h00 * y0 + h10 * h * dy0 + h01 * y1 + h11 * h * dy1
+        };
+        let (y0, dy0) = (self.conditions[start], self.derivatives[start]);
+        let (y1, dy1) = (self.conditions[end], self.derivatives[end]);
+        let (m0, dm0, m1, dm1) = (y0.motion, dy0.motion, y1.motion, dy1.motion);
+        let cubic = Conditions {
+            P: hermite(y0.P, dy0.P, y1.P, dy1.P),
+            T_c: hermite(y0.T_c, dy0.T_c, y1.T_c, dy1.T_c),
+            T_e: hermite(y0.T_e, dy0.T_e, y1.T_e, dy1.T_e),
+            motion: Motion {
+                x_p: hermite(m0.x_p, dm0.x_p, m1.x_p, dm1.x_p),
+                v_p: hermite(m0.v_p, dm0.v_p, m1.v_p, dm1.v_p),
+                x_d: hermite(m0.x_d, dm0.x_d, m1.x_d, dm1.x_d),
+                v_d: hermite(m0.v_d, dm0.v_d, m1.v_d, dm1.v_d),
+                current: hermite(m0.current, dm0.current, m1.current, dm1.current),
+            },
+            m_b: hermite(y0.m_b, dy0.m_b, y1.m_b, dy1.m_b),
+            P_drop: [0, 1, 2, 3]
+                .map(|i| hermite(y0.P_drop[i], dy0.P_drop[i], y1.P_drop[i], dy1.P_drop[i])),
+        };
+        match self.quartic.get(start) {
+            Some(&quartic) => add(cubic, scale(quartic, (s * (1.0 - s)).powi(2))),
+            None => cubic,
+        }
+    }
+
+    /// Calculate state equation values at each time in `times`
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the state equations cannot be solved at any time.
+    pub fn values<T: Cycle>(&self, cycle: &T, times: &[f64]) -> Result<(Vec<Values>, SolverStats)> {
+        let mut system = LinearSystem::default();
+        let mut inputs = Inputs::default();
+        let mut flow_dir = FlowDirection::default();
+        let mut stats = SolverStats::default();
+        let mut values = Vec::with_capacity(times.len());
+        for &time in times {
+            let conditions = self.conditions_at(time);
+            cycle.calculate_inputs(time, conditions, &mut inputs);
+            let volumes = Volumes {
+                V_c: inputs.comp.vol,
+                V_e: inputs.exp.vol,
+                dVc_dt: inputs.comp.dV_dt,
+                dVe_dt: inputs.exp.dV_dt,
+            };
+            let buffer = Buffer {
+                P_b: inputs.pres_buffer,
+                m_dot_leak: inputs.comp.m_dot_leak,
+            };
+            let wall = WallHeat {
+                Q_dot_c: inputs.comp.Q_dot_wall,
+                Q_dot_e: -inputs.exp.Q_dot_wall,
+            };
+            let (solution, solve_stats) = system.solve::<T::Solver>(&inputs, flow_dir)?;
+            stats += solve_stats;
+            flow_dir = FlowDirection::from_solution(&solution);
+            values.push(Values {
+                time,
+                crank_angle: 360.0 * time / self.period,
+                conditions,
+                volumes,
+                buffer,
+                wall,
+                solution,
+            });
+        }
+        Ok((values, stats))
+    }
+
+    /// Calculate state equation values at each crank angle (degrees)
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the state equations cannot be solved at any angle.
+    pub fn values_at_angles<T: Cycle>(
+        &self,
+        cycle: &T,
+        crank_angles: &[f64],
+    ) -> Result<(Vec<Values>, SolverStats)> {
+        let times: Vec<_> = crank_angles
+            .iter()
+            .map(|angle| angle / 360.0 * self.period)
+            .collect();
+        self.values(cycle, &times)
+    }
+
+    /// Calculate state equation values on an `OutputGrid`
+    ///
+    /// `resolution` is the number of points in a uniform grid, which is also
+    /// the starting point for grids that are refined at flow reversals.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the grid is invalid or if the state equations
+    /// cannot be solved at any point on it.
+    pub fn values_on_grid<T: Cycle>(
+        &self,
+        cycle: &T,
+        grid: OutputGrid,
+        resolution: u32,
+    ) -> Result<(Vec<Values>, SolverStats)> {
+        match grid {
+            OutputGrid::Uniform => self.values(cycle, &uniform_times(self.period, resolution)?),
+            OutputGrid::DegreesPerStep(step) => {
+                ensure!(
+                    step > 0.0 && step <= 360.0,
+                    "degrees per step must be in (0, 360], got {step}"
+                );
+                #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+                let num_steps = (360.0 / step).ceil() as u32;
+                let mut angles: Vec<_> = (0..num_steps).map(|i| f64::from(i) * step).collect();
+                angles.push(360.0);
+                self.values_at_angles(cycle, &angles)
+            }
+            OutputGrid::RefinedAtFlowReversal { extra_points } => {
+                let (base, mut stats) =
+                    self.values(cycle, &uniform_times(self.period, resolution)?)?;
+                let mut times = Vec::with_capacity(base.len());
+                for pair in base.windows(2) {
+                    let (start, end) = (pair[0].time, pair[1].time);
+                    times.push(start);
+                    if flow_reverses(&pair[0], &pair[1]) {
+                        let step = (end - start) / f64::from(extra_points + 1);
+                        times.extend((1..=extra_points).map(|i| start + f64::from(i) * step));
+                    }
+                }
+                times.push(self.final_time());
+                let (values, refined_stats) = self.values(cycle, &times)?;
+                stats += refined_stats;
+                Ok((values, stats))
+            }
+        }
+    }
+}
+
+/// Return the sum of two sets of conditions
+fn add(a: Conditions, b: Conditions) -> Conditions {
+    let (m, n) = (a.motion, b.motion);
+    Conditions {
+        P: a.P + b.P,
+        T_c: a.T_c + b.T_c,
+        T_e: a.T_e + b.T_e,
+        motion: Motion {
+            x_p: m.x_p + n.x_p,
+            v_p: m.v_p + n.v_p,
+            x_d: m.x_d + n.x_d,
+            v_d: m.v_d + n.v_d,
+            current: m.current + n.current,
+        },
+        m_b: a.m_b + b.m_b,
+        P_drop: [0, 1, 2, 3].map(|i| a.P_drop[i] + b.P_drop[i]),
+    }
+}
+
+/// Return `conditions` multiplied by `factor`
+fn scale(conditions: Conditions, factor: f64) -> Conditions {
+    let m = conditions.motion;
+    Conditions {
+        P: factor * conditions.P,
+        T_c: factor * conditions.T_c,
+        T_e: factor * conditions.T_e,
+        motion: Motion {
+            x_p: factor * m.x_p,
+            v_p: factor * m.v_p,
+            x_d: factor * m.x_d,
+            v_d: factor * m.v_d,
+            current: factor * m.current,
+        },
+        m_b: factor * conditions.m_b,
+        P_drop: conditions.P_drop.map(|drop| factor * drop),
+    }
+}
+
+/// Return `num_points` evenly spaced times over a cycle
+fn uniform_times(period: f64, num_points: u32) -> Result<Vec<f64>> {
+    ensure!(num_points >= 2, "at least two points are required");
+    let dt = period / f64::from(num_points - 1);
+    let mut times: Vec<_> = (0..num_points - 1).map(|i| f64::from(i) * dt).collect();
+    times.push(period);
+    Ok(times)
+}
+
+/// Return `true` if any mass flow changes sign between two values
+fn flow_reverses(a: &Values, b: &Values) -> bool {
+    let (a, b) = (&a.solution, &b.solution);
+    [
+        (a.m_dot_ck, b.m_dot_ck),
+        (a.m_dot_kr, b.m_dot_kr),
+        (a.m_dot_rl, b.m_dot_rl),
+        (a.m_dot_le, b.m_dot_le),
+    ]
+    .iter()
+    .any(|(a, b)| a * b < 0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+
+    /// Dense output of `P = sin(t)`, `T_c = t^3`, and `T_e = 1`
+    fn dense_output(num_steps: u32) -> DenseOutput {
+        let period = 2.0;
+        let times: Vec<_> = uniform_times(period, num_steps + 1).unwrap();
+        let conditions = times
+            .iter()
+            .map(|&t| Conditions {
+                P: t.sin(),
+                T_c: t.powi(3),
+                T_e: 1.0,
+                motion: Motion::default(),
+                m_b: 0.0,
+                P_drop: [0.0; 4],
+            })
+            .collect();
+        let derivatives = times
+            .iter()
+            .map(|&t| Conditions {
+                P: t.cos(),
+                T_c: 3.0 * t.powi(2),
+                T_e: 0.0,
+                motion: Motion::default(),
+                m_b: 0.0,
+                P_drop: [0.0; 4],
+            })
+            .collect();
+        DenseOutput::new(period, times, conditions, derivatives)
+    }
+
+    #[test]
+    fn interpolates_between_steps() {
+        let dense = dense_output(20);
+        for time in [0.0, 0.033, 0.5, 1.2345, 1.99, 2.0] {
+            let conditions = dense.conditions_at(time);
+            assert_relative_eq!(conditions.P, time.sin(), epsilon = 1e-6);
+            assert_relative_eq!(conditions.T_c, time.powi(3), epsilon = 1e-12); // exact for cubics
+            assert_relative_eq!(conditions.T_e, 1.0);
+        }
+    }
+
+    #[test]
+    fn interpolation_error_is_fourth_order() {
+        let max_error = |num_steps| {
+            let dense = dense_output(num_steps);
+            (0..=200)
+                .map(|i| f64::from(i) * 0.01)
+                .map(|time| (dense.conditions_at(time).P - time.sin()).abs())
+                .fold(0.0, f64::max)
+        };
+        let ratio = max_error(10) / max_error(20);
+        assert!((14.0..18.0).contains(&ratio), "got {ratio}");
+    }
+
+    #[test]
+    fn interpolates_quartics_with_quartic_terms() {
+        // `T_c = t^4` is `(t_0 + h s)^4` within a step, whose quartic term is `h^4`
+        let dense = dense_output(4);
+        let quartic = dense
+            .times
+            .windows(2)
+            .map(|step| Conditions {
+                P: 0.0,
+                T_c: (step[1] - step[0]).powi(4),
+                T_e: 0.0,
+                motion: Motion::default(),
+                m_b: 0.0,
+                P_drop: [0.0; 4],
+            })
+            .collect();
+        let conditions = dense.conditions.iter().zip(&dense.times);
+        let quartic_dense = DenseOutput::new(
+            dense.period,
+            dense.times.clone(),
+            conditions
+                .clone()
+                .map(|(&c, &t)| Conditions {
+                    T_c: t.powi(4),
+                    ..c
+                })
+                .collect(),
+            conditions
+                .map(|(&c, &t)| Conditions {
+                    T_c: 4.0 * t.powi(3),
+                    ..c
+                })
+                .collect(),
+        )
+        .with_quartic(quartic);
+        for time in [0.1, 0.77, 1.3, 1.95] {
+            let temp_comp = quartic_dense.conditions_at(time).T_c;
+            assert_relative_eq!(temp_comp, time.powi(4), epsilon = 1e-12);
+        }
+
+        // The quartic term still matches within a shortened final step
+        let time: f64 = 1.9;
+        let conditions = Conditions {
+            T_c: time.powi(4),
+            ..quartic_dense.conditions_at(time)
+        };
+        let derivative = Conditions {
+            T_c: 4.0 * time.powi(3),
+            ..conditions
+        };
+        let ended = quartic_dense
+            .end_at(time, conditions, derivative)
+            .expect("time is within the final step");
+        let temp_comp = ended.conditions_at(1.7).T_c;
+        assert_relative_eq!(temp_comp, 1.7f64.powi(4), epsilon = 1e-12);
+    }
+
+    #[test]
+    fn clamps_to_integration_bounds() {
+        let dense = dense_output(4);
+        assert_relative_eq!(dense.conditions_at(-1.0).T_c, 0.0);
+        assert_relative_eq!(dense.conditions_at(3.0).T_c, 8.0);
+    }
+
+    #[test]
+    fn ends_within_final_step() {
+        let end = |dense: DenseOutput, time: f64| {
+            let conditions = dense.conditions_at(time);
+            dense.end_at(time, conditions, conditions)
+        };
+        let dense = end(dense_output(4), 1.9).expect("time is within the final step");
+        assert_eq!(dense.final_time(), 1.9);
+        assert_relative_eq!(
+            dense.final_conditions().T_c,
+            1.9f64.powi(3),
+            epsilon = 1e-12
+        );
+        assert!(
+            end(dense_output(4), 1.2).is_err(),
+            "time is before the final step"
+        );
+        assert!(
+            end(dense_output(4), 2.1).is_err(),
+            "time is after the final step"
+        );
+
+        let start = dense_output(4).initial_conditions();
+        let single = DenseOutput::new(2.0, vec![0.0], vec![start], vec![start]);
+        assert!(end(single, 0.0).is_err(), "there is no final step");
+    }
+
+    #[test]
+    fn creates_uniform_times() {
+        let times = uniform_times(0.5, 11).unwrap();
+        assert_eq!(times.len(), 11);
+        assert_relative_eq!(times[1], 0.05);
+        assert_eq!(
+            *times.last().unwrap(),
+            0.5,
+            "grid should end exactly on the period"
+        );
+        assert!(uniform_times(0.5, 1).is_err());
+    }
+}
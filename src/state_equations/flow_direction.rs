@@ -1,3 +1,5 @@
+use crate::types::Scalar;
+
 use super::Solution;
 
 /// A direction of mass flow
@@ -12,8 +14,8 @@ impl Direction {
     /// Return a `Direction` based on the sign of a number
     ///
     /// If `value` is exactly `0.0`, a positive direction is assumed.
-    pub(super) fn from_value(value: f64) -> Self {
-        if value >= 0.0 {
+    pub(super) fn from_value<S: Scalar>(value: S) -> Self {
+        if value >= S::zero() {
             Self::Positive
         } else {
             Self::Negative
@@ -23,54 +25,76 @@ impl Direction {
     /// Return a value based on the direction of `self`
     ///
     /// An average of the two values is returned if the direction is `Unknown`.
-    pub(super) fn select(self, positive: f64, negative: f64) -> f64 {
+    pub(super) fn select<S: Scalar>(self, positive: S, negative: S) -> S {
         match self {
             Self::Positive => positive,
             Self::Negative => negative,
-            Self::Unknown => 0.5 * (positive + negative),
+            Self::Unknown => na::convert::<f64, S>(0.5) * (positive + negative),
         }
     }
 }
 
-/// The direction of mass flow between each control volume
+/// The direction of mass flow across each control volume interface
 ///
-/// The abbreviations are:
-///   - `ck` compression space to cold heat exchanger
-///   - `kr` cold heat exchanger to regenerator
-///   - `rl` regenerator to hot heat exchanger
-///   - `le` hot heat exchanger to expansion space
+/// Interfaces are ordered from the compression space to the expansion space,
+/// so the first is between the compression space and the cold heat exchanger
+/// and the last is between the hot heat exchanger and the expansion space.
+/// When the heat exchangers and regenerator are discretized into several
+/// control volumes, the interfaces between those volumes are included too.
 ///
-/// Positive flow is from the first volume in the abbreviation to the second.
-/// For example, a `Direction::Positive` for `kr` means mass is flowing from
-/// the cold heat exhanger into the regenerator.  A `Direction::Negative` for
-/// `le` means flow is from the expansion space to the hot heat exhanger.
-#[derive(Clone, Copy, PartialEq)]
-pub(super) struct FlowDirection {
-    pub(super) ck: Direction,
-    pub(super) kr: Direction,
-    pub(super) rl: Direction,
-    pub(super) le: Direction,
-}
+/// Positive flow is toward the expansion space.  For example, a
+/// `Direction::Positive` at the first interface means mass is flowing from
+/// the compression space into the cold heat exchanger.
+///
+/// Any interface without a known direction, including every interface of the
+/// default `FlowDirection`, is treated as `Direction::Unknown`.
+#[derive(Clone, Default)]
+pub(super) struct FlowDirection(pub(super) Vec<Direction>);
 
 impl FlowDirection {
     /// Determine the flow directions from a `Solution`
-    pub(super) fn from_solution(solution: &Solution) -> Self {
-        Self {
-            ck: Direction::from_value(solution.m_dot_ck),
-            kr: Direction::from_value(solution.m_dot_kr),
-            rl: Direction::from_value(solution.m_dot_rl),
-            le: Direction::from_value(solution.m_dot_le),
-        }
+    pub(super) fn from_solution<S: Scalar>(solution: &Solution<S>) -> Self {
+        Self(
+            solution
+                .m_dot
+                .iter()
+                .map(|&m_dot| Direction::from_value(m_dot))
+                .collect(),
+        )
     }
-}
 
-impl Default for FlowDirection {
-    fn default() -> Self {
-        Self {
-            ck: Direction::Unknown,
-            kr: Direction::Unknown,
-            rl: Direction::Unknown,
-            le: Direction::Unknown,
-        }
+    /// Return `true` if every direction agrees with a `Solution`
+    pub(super) fn matches<S: Scalar>(&self, solution: &Solution<S>) -> bool {
+        self.0.len() == solution.m_dot.len()
+            && self
+                .0
+                .iter()
+                .zip(&solution.m_dot)
+                .all(|(&dir, &m_dot)| dir == Direction::from_value(m_dot))
+    }
+
+    /// Give an unknown direction to every interface that disagrees with a
+    /// `Solution`
+    pub(super) fn settle<S: Scalar>(&self, solution: &Solution<S>) -> Self {
+        Self(
+            solution
+                .m_dot
+                .iter()
+                .enumerate()
+                .map(|(interface, &m_dot)| {
+                    let dir = self.get(interface);
+                    if dir == Direction::from_value(m_dot) {
+                        dir
+                    } else {
+                        Direction::Unknown
+                    }
+                })
+                .collect(),
+        )
+    }
+
+    /// Return the direction of flow across an interface
+    pub(super) fn get(&self, interface: usize) -> Direction {
+        self.0.get(interface).copied().unwrap_or(Direction::Unknown)
     }
 }
@@ -0,0 +1,390 @@
+use std::f64::consts::PI;
+
+use anyhow::{bail, ensure, Result};
+use na::{DMatrix, DVector, Vector3};
+
+use crate::{
+    observer::{CycleObserver, Residuals},
+    types::{Interruption, SolverStats},
+    ws::Motion,
+};
+
+use super::{
+    cycle::{Interrupted, SteadyState, SteadyStateInputs},
+    flow_direction::FlowDirection,
+    solver::LinearSystem,
+    Conditions, Cycle, DenseOutput, Inputs,
+};
+
+// Relative perturbation used to approximate the Jacobian
+const PERTURBATION: f64 = 1e-7;
+
+// Smallest fraction of a Newton step tried before giving up on reducing the residual
+const MIN_DAMPING: f64 = 1e-3;
+
+/// Find cyclic steady state using harmonic balance
+///
+/// The conditions (`P`, `T_c`, and `T_e`) are represented by truncated Fourier
+/// series with `harmonics` harmonics, which are defined by their values at
+/// `2 * harmonics + 1` evenly spaced collocation points.  Their time
+/// derivatives at the collocation points come from spectral differentiation,
+/// which must match the state equations at every point.  These residuals are
+/// driven to zero with Newton's method, along with one extra equation that
+/// sets the pressure at time zero.  The extra equation is needed because
+/// conservation of mass in the state equations allows periodic solutions at
+/// any pressure level, so the Newton steps are found in a least squares sense.
+/// The period must be known in advance, so free pistons are not supported.
+/// Neither is a buffer space or pressure drops between control volumes,
+/// since the buffer mass and the pressure drops are not among the series.
+///
+/// The search is converged when a Newton step does not change the
+/// temperatures at any collocation point by more than the convergence
+/// tolerance.  Statistics count every evaluation of the state equations as a
+/// right-hand side evaluation, and every Newton iteration as an accepted step.
+pub(super) fn find_steady_state<T: Cycle, O: CycleObserver + ?Sized>(
+    cycle: &T,
+    inputs: SteadyStateInputs,
+    harmonics: u32,
+    observer: &mut O,
+) -> Result<SteadyState> {
+    let SteadyStateInputs {
+        pres_zero,
+        temp_comp_hint,
+        temp_exp_hint,
+        num_points,
+        grid,
+        conv_tol,
+        max_iters,
+        limits,
+        ..
+    } = inputs;
+    ensure!(harmonics > 0, "at least one harmonic is required");
+    ensure!(
+        cycle.initial_motion().is_none(),
+        "harmonic balance requires prescribed volumes"
+    );
+    ensure!(
+        cycle.initial_buffer_mass().is_none() && !cycle.has_pressure_drops(),
+        "harmonic balance does not support a buffer space or pressure drops"
+    );
+
+    // Every condition is scaled by its initial value
+    let period = cycle.period();
+    let n = 2 * harmonics as usize + 1;
+    let times: Vec<_> = (0..n).map(|j| point_time(j, n, period)).collect();
+    let scale = Vector3::new(pres_zero, temp_comp_hint, temp_exp_hint);
+    let diff = differentiation_matrix(n, period);
+    let weight = 2.0 * PI / period * f64::from(2 * harmonics + 1);
+    let mut system = Collocation {
+        cycle,
+        times: &times,
+        scale,
+        flow_dirs: vec![FlowDirection::default(); n],
+        system: LinearSystem::default(),
+        inputs: Inputs::default(),
+        stats: SolverStats::default(),
+    };
+    let mut x = vec![Vector3::repeat(1.0); n];
+    let mut total = SolverStats::default();
+
+    let mut derivs = system.derivatives(&x)?;
+    for iteration in 0..max_iters {
+        let step = system.newton_step(&diff, weight, &x, &derivs)?;
+
+        // The search is converged when the Newton step leaves every
+        // temperature unchanged within the tolerance
+        let converged = x.iter().zip(&step).all(|(x_j, step_j)| {
+            (1..3)
+                .all(|v| conv_tol.is_converged(x_j[v] * scale[v], (x_j[v] + step_j[v]) * scale[v]))
+        });
+        if converged {
+            x = x
+                .iter()
+                .zip(&step)
+                .map(|(x_j, step_j)| x_j + step_j)
+                .collect();
+        } else {
+            system.line_search(&diff, weight, &mut x, &mut derivs, &step)?;
+        }
+
+        // Report the largest temperature change in the Newton step
+        let largest = |v: usize| {
+            step.iter()
+                .map(|step_j| step_j[v] * scale[v])
+                .fold(
+                    0.0,
+                    |max: f64, dx| if dx.abs() > max.abs() { dx } else { max },
+                )
+        };
+        let residuals = Residuals {
+            temp_comp: largest(1),
+            temp_exp: largest(2),
+        };
+        let stats = SolverStats {
+            accepted_steps: 1,
+            ..std::mem::take(&mut system.stats)
+        };
+        total += stats;
+        let aborted = observer.newton_iteration(stats).is_break()
+            || observer.inner_iteration(iteration, residuals).is_break();
+        if aborted {
+            let reason = Interruption::Cancelled;
+            return Err(Interrupted {
+                reason,
+                stats: total,
+            }
+            .into());
+        }
+        if let Some(reason) = limits.check(total) {
+            return Err(Interrupted {
+                reason,
+                stats: total,
+            }
+            .into());
+        }
+
+        if converged {
+            let dense_output = system.dense_output(&diff, &x, period);
+            let (values, grid_stats) = dense_output.values_on_grid(cycle, grid, num_points)?;
+            total += grid_stats;
+            return Ok(SteadyState {
+                values,
+                dense_output,
+                stats: total,
+            });
+        }
+    }
+
+    bail!("did not converge")
+}
+
+/// The state equations evaluated at the collocation points
+struct Collocation<'a, T: Cycle> {
+    cycle: &'a T,
+    times: &'a [f64],
+    scale: Vector3<f64>,
+    flow_dirs: Vec<FlowDirection>,
+    system: LinearSystem,
+    inputs: Inputs,
+    stats: SolverStats,
+}
+
+impl<T: Cycle> Collocation<'_, T> {
+    /// Return the scaled time derivatives at collocation point `j`
+    fn derivative(&mut self, j: usize, x: &Vector3<f64>) -> Result<Vector3<f64>> {
+        let y = x.component_mul(&self.scale);
+        let conditions = Conditions {
+            P: y[0],
+            T_c: y[1],
+            T_e: y[2],
+            motion: Motion::default(),
+            m_b: 0.0,
+            P_drop: [0.0; 4],
+        };
+        self.cycle
+            .calculate_inputs(self.times[j], conditions, &mut self.inputs);
+        let (solution, stats) = self
+            .system
+            .solve::<T::Solver>(&self.inputs, self.flow_dirs[j].clone())?;
+        self.stats += stats;
+        self.stats.rhs_evals += 1;
+        self.flow_dirs[j] = FlowDirection::from_solution(&solution);
+        Ok(
+            Vector3::new(solution.dP_dt, solution.dTc_dt, solution.dTe_dt)
+                .component_div(&self.scale),
+        )
+    }
+
+    /// Return the scaled time derivatives at every collocation point
+    fn derivatives(&mut self, x: &[Vector3<f64>]) -> Result<Vec<Vector3<f64>>> {
+        x.iter()
+            .enumerate()
+            .map(|(j, x_j)| self.derivative(j, x_j))
+            .collect()
+    }
+
+    /// Return the Newton step at `x` in the least squares sense
+    ///
+    /// The Jacobian of the state equations is approximated with finite
+    /// differences, which only couple the conditions at the same point.
+    fn newton_step(
+        &mut self,
+        diff: &DMatrix<f64>,
+        weight: f64,
+        x: &[Vector3<f64>],
+        derivs: &[Vector3<f64>],
+    ) -> Result<Vec<Vector3<f64>>> {
+        let n = x.len();
+        let mut jac = DMatrix::zeros(3 * n + 1, 3 * n);
+        for v in 0..3 {
+            jac.slice_mut((v * n, v * n), (n, n)).copy_from(diff);
+        }
+        for (j, x_j) in x.iter().enumerate() {
+            for v in 0..3 {
+                let mut x_pert = *x_j;
+                let h = PERTURBATION * x_j[v].abs().max(1.0);
+                x_pert[v] += h;
+                let deriv_pert = self.derivative(j, &x_pert)?;
+                for u in 0..3 {
+                    jac[(u * n + j, v * n + j)] -= (deriv_pert[u] - derivs[j][u]) / h;
+                }
+            }
+        }
+        jac[(3 * n, 0)] = weight;
+        let res = residual(diff, weight, x, derivs);
+        let step = jac
+            .svd(true, true)
+            .solve(&(-res), f64::EPSILON)
+            .map_err(anyhow::Error::msg)?;
+        Ok((0..n)
+            .map(|j| Vector3::new(step[j], step[n + j], step[2 * n + j]))
+            .collect())
+    }
+
+    /// Update the conditions and their derivatives with a damped Newton step
+    ///
+    /// The step is halved until the residual decreases and every condition
+    /// stays positive.
+    fn line_search(
+        &mut self,
+        diff: &DMatrix<f64>,
+        weight: f64,
+        x: &mut Vec<Vector3<f64>>,
+        derivs: &mut Vec<Vector3<f64>>,
+        step: &[Vector3<f64>],
+    ) -> Result<()> {
+        let norm = residual(diff, weight, x, derivs).norm();
+        let mut damping = 1.0;
+        loop {
+            let trial: Vec<_> = x
+                .iter()
+                .zip(step)
+                .map(|(x_j, step_j)| x_j + damping * step_j)
+                .collect();
+            let accepted = if trial.iter().all(|x_j| x_j.min() > 0.0) {
+                self.derivatives(&trial).ok().filter(|trial_derivs| {
+                    damping < MIN_DAMPING
+                        || residual(diff, weight, &trial, trial_derivs).norm() < norm
+                })
+            } else {
+                None
+            };
+            if let Some(trial_derivs) = accepted {
+                *x = trial;
+                *derivs = trial_derivs;
+                return Ok(());
+            }
+            ensure!(damping >= MIN_DAMPING, "unable to reduce the residual");
+            damping *= 0.5;
+        }
+    }
+
+    /// Return a `DenseOutput` over the whole cycle
+    ///
+    /// The Fourier series is not evaluated between collocation points.
+    /// Instead, the conditions are interpolated with cubic Hermite polynomials
+    /// through their values and spectral derivatives at the collocation
+    /// points, and the cycle is closed by repeating the first collocation
+    /// point at the end of the period.
+    fn dense_output(&self, diff: &DMatrix<f64>, x: &[Vector3<f64>], period: f64) -> DenseOutput {
+        let n = x.len();
+        let to_conditions = |y: Vector3<f64>| Conditions {
+            P: y[0],
+            T_c: y[1],
+            T_e: y[2],
+            motion: Motion::default(),
+            m_b: 0.0,
+            P_drop: [0.0; 4],
+        };
+        let values = |v: usize| DVector::from_iterator(n, x.iter().map(|x_j| x_j[v]));
+        let rates: Vec<_> = (0..3).map(|v| diff * values(v)).collect();
+        let mut times = self.times.to_vec();
+        let mut conditions: Vec<_> = x
+            .iter()
+            .map(|x_j| to_conditions(x_j.component_mul(&self.scale)))
+            .collect();
+        let mut derivatives: Vec<_> = (0..n)
+            .map(|j| {
+                let rate = Vector3::new(rates[0][j], rates[1][j], rates[2][j]);
+                to_conditions(rate.component_mul(&self.scale))
+            })
+            .collect();
+        times.push(period);
+        conditions.push(conditions[0]);
+        derivatives.push(derivatives[0]);
+        DenseOutput::new(period, times, conditions, derivatives)
+    }
+}
+
+/// Return the residuals of the collocation equations
+///
+/// The residuals are ordered by condition, with the residual of the equation
+/// that sets the pressure at time zero last.
+fn residual(
+    diff: &DMatrix<f64>,
+    weight: f64,
+    x: &[Vector3<f64>],
+    derivs: &[Vector3<f64>],
+) -> DVector<f64> {
+    let n = x.len();
+    let mut residual = DVector::zeros(3 * n + 1);
+    for v in 0..3 {
+        let values = DVector::from_iterator(n, x.iter().map(|x_j| x_j[v]));
+        let rates = diff * values;
+        for j in 0..n {
+            residual[v * n + j] = rates[j] - derivs[j][v];
+        }
+    }
+    residual[3 * n] = weight * (x[0][0] - 1.0);
+    residual
+}
+
+/// Return the time of collocation point `j` out of `n` in the cycle
+#[allow(clippy::cast_precision_loss)]
+fn point_time(j: usize, n: usize, period: f64) -> f64 {
+    period * j as f64 / n as f64
+}
+
+/// Return the spectral differentiation matrix for `n` evenly spaced points
+///
+/// The matrix gives the time derivatives at the points of the trigonometric
+/// interpolant through values at those points, where `n` is odd.
+fn differentiation_matrix(n: usize, period: f64) -> DMatrix<f64> {
+    let omega = 2.0 * PI / period;
+    DMatrix::from_fn(n, n, |j, k| {
+        if j == k {
+            0.0
+        } else {
+            let offset = point_time(j, n, period) - point_time(k, n, period);
+            let sign = if (j + k) % 2 == 0 { 1.0 } else { -1.0 };
+            omega * 0.5 * sign / (0.5 * omega * offset).sin()
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+
+    #[test]
+    fn differentiates_trigonometric_polynomials() {
+        let period = 0.5;
+        let n = 11;
+        let omega = 2.0 * PI / period;
+        let diff = differentiation_matrix(n, period);
+        let times = (0..n).map(|j| point_time(j, n, period));
+        let values = DVector::from_iterator(
+            n,
+            times
+                .clone()
+                .map(|t| 3.0 + (omega * t).sin() - 2.0 * (5.0 * omega * t).cos()),
+        );
+        let rates = diff * values;
+        for (rate, t) in rates.iter().zip(times) {
+            let expected = omega * (omega * t).cos() + 10.0 * omega * (5.0 * omega * t).sin();
+            assert_relative_eq!(*rate, expected, epsilon = 1e-9 * omega);
+        }
+    }
+}
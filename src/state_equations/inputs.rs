@@ -1,54 +1,105 @@
 use serde::Deserialize;
 
+use crate::types::Scalar;
+
 /// Inputs required to generate the `Ax=b` system of state equations
-#[derive(Debug, Clone, Deserialize)]
-pub struct Inputs {
-    pub pres: f64,
-    pub enth_norm: f64,
-    pub comp: WorkingSpace,
-    pub chx: HeatExchanger,
-    pub regen: Regenerator,
-    pub hhx: HeatExchanger,
-    pub exp: WorkingSpace,
+///
+/// The heat exchangers and the regenerator are each discretized into one or
+/// more control volumes, ordered from the cold end to the hot end.
+///
+/// `pres` is the compression space pressure, and `pres_drop` holds the
+/// pressure drops from it to the cold heat exchanger, the regenerator, the
+/// hot heat exchanger, and the expansion space.  Each control volume has a
+/// hydraulic resistance (Pa-s/m^3), and the flow between two of these
+/// pressures is the drop between them over the resistance from the middle of
+/// one to the middle of the other.  Components with no resistance between
+/// them share a pressure instead.  Resistances and drops default to zero,
+/// which gives a uniform pressure in all control volumes.
+///
+/// `pres_buffer` is the pressure behind the pistons, which is only reported
+/// with the solution.
+///
+/// Inputs are `f64` by default, but the state equations can be solved with
+/// any `Scalar`.  The default inputs have no control volumes, and are only a
+/// starting point for a `Cycle` to fill.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Inputs<S: Scalar = f64> {
+    pub pres: S,
+    #[serde(default = "no_drops")]
+    pub pres_drop: [S; 4],
+    #[serde(default = "na::zero")]
+    pub pres_buffer: S,
+    pub enth_norm: S,
+    pub comp: WorkingSpace<S>,
+    pub chx: Vec<HeatExchanger<S>>,
+    pub regen: Vec<Regenerator<S>>,
+    pub hhx: Vec<HeatExchanger<S>>,
+    pub exp: WorkingSpace<S>,
 }
 
 /// State equation inputs related to the working spaces
+///
+/// `m_dot_leak` is the mass flow rate (kg/s) leaving the space through a
+/// piston seal, which carries the specific enthalpy `enth_leak` (J/kg).  Both
+/// default to zero for a perfect seal.
+///
+/// `Q_dot_wall` is the heat (W) leaving the gas through the walls of the
+/// space, which is reported with the solution apart from `Q_dot`.  It
+/// defaults to zero for adiabatic walls.
 #[allow(non_snake_case)]
-#[derive(Debug, Clone, Deserialize)]
-pub struct WorkingSpace {
-    pub vol: f64,
-    pub dens: f64,
-    pub inte: f64,
-    pub enth: f64,
-    pub dd_dP_T: f64,
-    pub dd_dT_P: f64,
-    pub du_dP_T: f64,
-    pub du_dT_P: f64,
-    pub dV_dt: f64,
-    pub Q_dot: f64,
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct WorkingSpace<S: Scalar = f64> {
+    pub vol: S,
+    pub dens: S,
+    pub inte: S,
+    pub enth: S,
+    pub dd_dP_T: S,
+    pub dd_dT_P: S,
+    pub du_dP_T: S,
+    pub du_dT_P: S,
+    pub dV_dt: S,
+    pub Q_dot: S,
+    #[serde(default = "na::zero")]
+    pub Q_dot_wall: S,
+    #[serde(default = "na::zero")]
+    pub m_dot_leak: S,
+    #[serde(default = "na::zero")]
+    pub enth_leak: S,
 }
 
-/// State equation inputs related to the heat exchangers
+/// State equation inputs related to a heat exchanger control volume
 #[allow(non_snake_case)]
 #[derive(Debug, Clone, Deserialize)]
-pub struct HeatExchanger {
-    pub vol: f64,
-    pub dens: f64,
-    pub inte: f64,
-    pub enth: f64,
-    pub dd_dP_T: f64,
-    pub du_dP_T: f64,
+pub struct HeatExchanger<S: Scalar = f64> {
+    pub vol: S,
+    pub dens: S,
+    pub inte: S,
+    pub enth: S,
+    pub dd_dP_T: S,
+    pub du_dP_T: S,
+    #[serde(default = "na::zero")]
+    pub hyd_res: S,
 }
 
-/// State equation inputs related to the regenerator
+/// State equation inputs related to a regenerator control volume
+///
+/// The enthalpies at the cold and hot faces of the volume are used for the
+/// enthalpy carried by flow leaving through that face.
 #[allow(non_snake_case)]
 #[derive(Debug, Clone, Deserialize)]
-pub struct Regenerator {
-    pub vol: f64,
-    pub dens: f64,
-    pub inte: f64,
-    pub enth_cold: f64,
-    pub enth_hot: f64,
-    pub dd_dP_T: f64,
-    pub du_dP_T: f64,
+pub struct Regenerator<S: Scalar = f64> {
+    pub vol: S,
+    pub dens: S,
+    pub inte: S,
+    pub enth_cold: S,
+    pub enth_hot: S,
+    pub dd_dP_T: S,
+    pub du_dP_T: S,
+    #[serde(default = "na::zero")]
+    pub hyd_res: S,
+}
+
+/// Return the pressure drops of a uniform pressure
+fn no_drops<S: Scalar>() -> [S; 4] {
+    [S::zero(); 4]
 }
@@ -1,59 +1,222 @@
-use std::cell::RefCell;
+use std::{
+    cell::{Cell, RefCell},
+    collections::VecDeque,
+    f64::consts::PI,
+};
 
-use anyhow::Result;
-use ode_solvers::{Dopri5, System, Vector3};
+use anyhow::{ensure, Result};
+use ode_solvers::{dop_shared::OutputType, DVector, Dopri5, System};
 
-use crate::types::{ConvergenceTolerance, OdeTolerance};
+use crate::{
+    types::{ConvergenceTolerance, Interruption, OdeTolerance, SolverStats},
+    ws::Motion,
+};
 
-use super::{flow_direction::FlowDirection, solver::solve, Conditions, Cycle, Values};
+use super::{
+    cycle::{Interrupted, Limits},
+    dense_output::DenseOutput,
+    flow_direction::FlowDirection,
+    solver::LinearSystem,
+    Conditions, Cycle, Inputs, Solution,
+};
+
+// Step size control parameters, which match the `Dopri5::new` defaults
+const SAFETY_FACTOR: f64 = 0.9;
+const BETA: f64 = 0.04;
+const FAC_MIN: f64 = 0.2;
+const FAC_MAX: f64 = 10.0;
+const MAX_STEPS: u32 = 100_000;
+const STIFFNESS_CHECK: u32 = 1000;
+
+// Coefficients of the stage derivatives `k1` to `k7` in the quartic term of
+// the Dormand-Prince continuous extension, which match `ode_solvers`
+const DENSE_COEFFS: [f64; 7] = [
+    -12_715_105_075.0 / 11_282_082_432.0,
+    0.0,
+    87_487_479_700.0 / 32_700_410_799.0,
+    -10_690_763_975.0 / 1_880_347_072.0,
+    701_980_252_875.0 / 199_316_789_632.0,
+    -1_453_857_185.0 / 822_651_844.0,
+    69_997_945.0 / 29_380_423.0,
+];
+
+// Stage derivatives evaluated by each Dormand-Prince step, after the first
+const NEW_STAGES: usize = 6;
+
+// With free pistons, the longest cycle as a multiple of the estimated period
+const MAX_PERIOD_RATIO: f64 = 4.0;
+
+// Bisection iterations used to find when a free piston returns to its start
+const RETURN_ITERS: usize = 50;
+
+// Positions among all of the conditions of the state variables that are
+// always integrated, and of those integrated with free pistons, a buffer
+// space, and pressure drops
+const THERMAL: [usize; 3] = [0, 1, 2];
+const MOTION: [usize; 5] = [3, 4, 5, 6, 7];
+const BUFFER: [usize; 1] = [8];
+const PRESSURE_DROPS: [usize; 4] = [9, 10, 11, 12];
+const ALL_STATES: usize = 13;
 
 /// Represents an integration of the state equations over a cycle
-pub struct Integration<'a, T: Cycle> {
-    cycle: &'a T,
-    points: Vec<Point>,
+///
+/// The conditions are stored at every step taken by the integrator, along
+/// with their time derivatives and the quartic term of the Dormand-Prince
+/// continuous extension, which together provide a dense output over the
+/// entire cycle.
+///
+/// `ode_solvers` does not expose that term with sparse output, so it is
+/// rebuilt from the stage derivatives.  Every step evaluates the state
+/// equations at six new stages, the last at the end of the step, and the
+/// last six evaluations before an accepted step is reported are its stages.
+pub struct Integration {
+    dense_output: DenseOutput,
+    stats: SolverStats,
+    flow_retries: Vec<(f64, usize)>,
+    free: bool,
 }
 
-impl<'a, T: Cycle> Integration<'a, T> {
+impl Integration {
     /// Attempt to integrate the state equations
-    pub fn try_from(
-        cycle: &'a T,
+    ///
+    /// The `limits` are checked after every accepted step, and the
+    /// integration stops with an `Interrupted` error if any are exceeded.
+    ///
+    /// With free pistons, the integration ends when the power piston returns
+    /// to mid-stroke while moving toward the expansion space, and the time
+    /// that takes is the period of the cycle.  Only the pressure and
+    /// temperatures are integrated for prescribed volumes, the buffer mass
+    /// only when there is a buffer space, and the pressure drops only when
+    /// the cycle has them, so that the error estimate of each step is not
+    /// diluted by conditions that never change.
+    pub fn try_from<T: Cycle>(
+        cycle: &T,
         initial_conditions: Conditions,
-        num_points: u32,
         tol: OdeTolerance,
+        limits: &Limits,
     ) -> Result<Self> {
+        let mut layout = THERMAL.to_vec();
+        if cycle.initial_motion().is_some() {
+            layout.extend(MOTION);
+        }
+        if cycle.initial_buffer_mass().is_some() {
+            layout.extend(BUFFER);
+        }
+        if cycle.has_pressure_drops() {
+            layout.extend(PRESSURE_DROPS);
+        }
+        Self::integrate(cycle, initial_conditions, &layout, tol, limits)
+    }
+
+    /// Integrate the state equations with the state variables in `layout`
+    fn integrate<T: Cycle>(
+        cycle: &T,
+        initial_conditions: Conditions,
+        layout: &[usize],
+        tol: OdeTolerance,
+        limits: &Limits,
+    ) -> Result<Self> {
+        let eval_stats = Cell::new(SolverStats::default());
+        let flow_retries = RefCell::new(Vec::new());
+        let derivs = RefCell::new(Vec::new());
+        let quartic = RefCell::new(Vec::new());
+        let interruption = Cell::new(None);
+        let failure = RefCell::new(None);
+        let returned = Cell::new(false);
+        let free = cycle.initial_motion().is_some();
         let state = IntegrationState {
             cycle,
+            layout: layout.to_vec(),
+            system: RefCell::new(LinearSystem::default()),
+            inputs: RefCell::new(Inputs::default()),
             last_flow_dir: RefCell::new(FlowDirection::default()),
+            eval_stats: &eval_stats,
+            flow_retries: &flow_retries,
+            derivs: &derivs,
+            stages: RefCell::new(VecDeque::with_capacity(NEW_STAGES + 1)),
+            quartic: &quartic,
+            last_time: 0.0,
+            limits,
+            interruption: &interruption,
+            failure: &failure,
+            free,
+            last_x_p: initial_conditions.motion.x_p,
+            returned: &returned,
         };
         let period = cycle.period();
-        let dx = period / f64::from(num_points - 1);
-        let y0 = StateVariables::new(
-            initial_conditions.P,
-            initial_conditions.T_c,
-            initial_conditions.T_e,
-        );
-        let mut stepper = Dopri5::new(
+        let end = if free {
+            MAX_PERIOD_RATIO * period
+        } else {
+            period
+        };
+        let y0 = to_state_variables(&initial_conditions, layout);
+
+        // The derivative at the start of the cycle is not reported by the stepper
+        let mut dy0 = StateVariables::zeros(layout.len());
+        state.system(0.0, &y0, &mut dy0);
+        if let Some(err) = failure.take() {
+            return Err(err);
+        }
+        derivs.borrow_mut().push(dy0);
+
+        let (initial_step, stiffness_check) = step_control(cycle, period);
+        let mut stepper = Dopri5::from_param(
             state,
             0.0,
-            period + 1e-14, // TODO: https://github.com/isentropic-dev/sett-rs/issues/32
-            dx,
+            end,
+            0.0, // unused with sparse output
             y0,
             tol.rel,
             tol.abs,
+            SAFETY_FACTOR,
+            BETA,
+            FAC_MIN,
+            FAC_MAX,
+            period,
+            initial_step,
+            MAX_STEPS,
+            stiffness_check,
+            OutputType::Sparse,
         );
-        stepper.integrate()?;
-
-        let mut points = Vec::with_capacity(num_points as usize);
-        for (&time, variables) in stepper.x_out().iter().zip(stepper.y_out().iter()) {
-            let conditions = Conditions {
-                P: variables[0],
-                T_c: variables[1],
-                T_e: variables[2],
-            };
-            points.push(Point { time, conditions });
+        let stepper_stats = stepper.integrate();
+        if let Some(err) = failure.take() {
+            return Err(err);
+        }
+        let stepper_stats = stepper_stats?;
+        let mut solver_stats = SolverStats {
+            accepted_steps: stepper_stats.accepted_steps as usize,
+            rejected_steps: stepper_stats.rejected_steps as usize,
+            ..eval_stats.get()
+        };
+        if let Some(reason) = interruption.get() {
+            return Err(Interrupted {
+                reason,
+                stats: solver_stats,
+            }
+            .into());
+        }
+        let to_vec = |y: &[StateVariables]| y.iter().map(|y| to_conditions(y, layout)).collect();
+        let mut dense_output = DenseOutput::new(
+            end,
+            stepper.x_out().clone(),
+            to_vec(stepper.y_out()),
+            to_vec(&derivs.into_inner()),
+        )
+        .with_quartic(to_vec(&quartic.into_inner()));
+        if free {
+            ensure!(
+                returned.get(),
+                "the power piston did not return to mid-stroke within {end} s"
+            );
+            dense_output = end_at_return(cycle, dense_output, &mut solver_stats)?;
         }
 
-        Ok(Self { cycle, points })
+        Ok(Self {
+            dense_output,
+            stats: solver_stats,
+            flow_retries: flow_retries.into_inner(),
+            free,
+        })
     }
 
     /// Check if the integration over the cycle is converged
@@ -63,82 +226,428 @@ impl<'a, T: Cycle> Integration<'a, T> {
     /// the cycle are equal to their respective initial conditions.  Only
     /// the temperatures are checked because pressure must converge due to
     /// conservation of mass and energy in the state equations.
-    pub fn is_converged(&self, tol: ConvergenceTolerance) -> bool {
-        let first_point = self.points.first().unwrap(); // `self.points` is never empty
-        let last_point = self.points.last().unwrap();
-        tol.is_converged(first_point.conditions.T_c, last_point.conditions.T_c)
-            && tol.is_converged(first_point.conditions.T_e, last_point.conditions.T_e)
+    ///
+    /// Free pistons must also return to their initial motion within
+    /// `motion_tol`.  The displacer position is compared as the velocity it
+    /// implies at the frequency of the cycle, so every change is a velocity
+    /// (m/s), which is converged when it is within either the absolute
+    /// tolerance or the relative tolerance of the piston velocity.  The
+    /// piston velocity is at its peak when the cycle starts at mid-stroke.
+    /// Any alternator current is compared as the piston velocity it implies,
+    /// scaling its change by the ratio of the piston velocity to the current
+    /// at the start of the cycle.
+    pub fn is_converged(
+        &self,
+        tol: ConvergenceTolerance,
+        motion_tol: ConvergenceTolerance,
+    ) -> bool {
+        let initial = self.dense_output.initial_conditions();
+        let last = self.dense_output.final_conditions();
+        if !(tol.is_converged(initial.T_c, last.T_c) && tol.is_converged(initial.T_e, last.T_e)) {
+            return false;
+        }
+        if !self.free {
+            return true;
+        }
+        let omega = 2.0 * PI / self.dense_output.final_time();
+        let (m0, m1) = (initial.motion, last.motion);
+        let speed = m0.v_p.abs();
+        let per_amp = if m0.current == 0.0 {
+            0.0
+        } else {
+            speed / m0.current.abs()
+        };
+        [
+            (m0.v_p, m1.v_p),
+            (m0.v_d, m1.v_d),
+            (omega * m0.x_d, omega * m1.x_d),
+            (per_amp * m0.current, per_amp * m1.current),
+        ]
+        .into_iter()
+        .all(|(old, new)| {
+            let change = (new - old).abs();
+            change < motion_tol.abs || change < motion_tol.rel * speed
+        })
     }
 
     /// Return the final time of the integration
     #[cfg(test)]
     pub fn final_time(&self) -> f64 {
-        let last_point = self.points.last().unwrap(); // `self.points` is never empty
-        last_point.time
+        self.dense_output.final_time()
     }
 
     /// Return the conditions at the end of the integration
     pub fn final_conditions(&self) -> Conditions {
-        let last_point = self.points.last().unwrap(); // `self.points` is never empty
-        last_point.conditions
+        self.dense_output.final_conditions()
+    }
+
+    /// Return the solver statistics collected during the integration
+    pub fn stats(&self) -> SolverStats {
+        self.stats
+    }
+
+    /// Return the times (s) at which the flow directions had to be updated,
+    /// along with the number of updates needed at each time
+    pub fn flow_retries(&self) -> &[(f64, usize)] {
+        &self.flow_retries
     }
 
-    /// Calculate state equation values at each point
+    /// Return the dense output of the integration
     ///
     /// This function consumes the `Integration`.
-    pub fn into_state_values(self) -> Vec<Values> {
-        let mut flow_dir = FlowDirection::default();
-        self.points
-            .into_iter()
-            .map(|point| {
-                let Point { time, conditions } = point;
-                let inputs = self.cycle.calculate_inputs(time, conditions);
-                let solution = solve::<T::Solver>(inputs, flow_dir)
-                    .expect("TODO: what do we do if this fails?");
-                flow_dir = FlowDirection::from_solution(&solution);
-                Values {
-                    time,
-                    conditions,
-                    solution,
-                }
-            })
-            .collect()
+    pub fn into_dense_output(self) -> DenseOutput {
+        self.dense_output
     }
 }
 
-/// The conditions within the cycle at a point in time
-struct Point {
+/// The variables being integrated
+///
+/// All of the conditions are ordered as [`P`, `T_c`, `T_e`, `x_p`, `v_p`,
+/// `x_d`, `v_d`, `current`, `m_b`, `P_drop`], and a layout holds the
+/// positions of those that are integrated.  The rest are zero.
+type StateVariables = DVector<f64>;
+
+/// Return the initial step size and the stiffness check interval
+///
+/// Pressure drops between control volumes relax much faster than the cycle,
+/// so the steps stay small, which the stiffness check would report as
+/// stiffness, and an automatic initial step can be too large to start from.
+fn step_control<T: Cycle>(cycle: &T, period: f64) -> (f64, u32) {
+    if cycle.has_pressure_drops() {
+        (period * 1e-6, u32::MAX)
+    } else {
+        (0.0, STIFFNESS_CHECK) // determine the initial step size automatically
+    }
+}
+
+/// Convert `StateVariables` in `layout`, or their derivatives, into `Conditions`
+fn to_conditions(y: &StateVariables, layout: &[usize]) -> Conditions {
+    let mut all = [0.0; ALL_STATES];
+    for (&position, &value) in layout.iter().zip(y.iter()) {
+        all[position] = value;
+    }
+    Conditions {
+        P: all[0],
+        T_c: all[1],
+        T_e: all[2],
+        motion: Motion {
+            x_p: all[3],
+            v_p: all[4],
+            x_d: all[5],
+            v_d: all[6],
+            current: all[7],
+        },
+        m_b: all[8],
+        P_drop: [all[9], all[10], all[11], all[12]],
+    }
+}
+
+/// Convert `Conditions`, or their derivatives, into `StateVariables` in `layout`
+fn to_state_variables(conditions: &Conditions, layout: &[usize]) -> StateVariables {
+    let Motion {
+        x_p,
+        v_p,
+        x_d,
+        v_d,
+        current,
+    } = conditions.motion;
+    let [drop_k, drop_r, drop_l, drop_e] = conditions.P_drop;
+    let all = [
+        conditions.P,
+        conditions.T_c,
+        conditions.T_e,
+        x_p,
+        v_p,
+        x_d,
+        v_d,
+        current,
+        conditions.m_b,
+        drop_k,
+        drop_r,
+        drop_l,
+        drop_e,
+    ];
+    StateVariables::from_iterator(layout.len(), layout.iter().map(|&position| all[position]))
+}
+
+/// Solve the state equations at `conditions` and return their solution along
+/// with the time derivatives of the conditions
+fn derivatives<T: Cycle>(
+    cycle: &T,
+    system: &mut LinearSystem,
+    inputs: &mut Inputs,
     time: f64,
     conditions: Conditions,
+    flow_dir: FlowDirection,
+) -> Result<(Solution, Conditions, SolverStats)> {
+    cycle.calculate_inputs(time, conditions, inputs);
+    let (solution, stats) = system.solve::<T::Solver>(inputs, flow_dir)?;
+    let derivative = Conditions {
+        P: solution.dP_dt,
+        T_c: solution.dTc_dt,
+        T_e: solution.dTe_dt,
+        motion: cycle.motion_derivatives(conditions, &solution),
+        m_b: inputs.comp.m_dot_leak,
+        P_drop: solution.dPdrop_dt,
+    };
+    Ok((solution, derivative, stats))
 }
 
-/// The variables being integrated
-///
-/// The order is [`P`, `T_c`, `T_e`].
-type StateVariables = Vector3<f64>;
+/// Return `dense_output` ended when the power piston returns to mid-stroke,
+/// adding the evaluation of the state equations there to `stats`
+fn end_at_return<T: Cycle>(
+    cycle: &T,
+    dense_output: DenseOutput,
+    stats: &mut SolverStats,
+) -> Result<DenseOutput> {
+    let (time, conditions) = return_to_start(&dense_output);
+    let (_, derivative, end_stats) = derivatives(
+        cycle,
+        &mut LinearSystem::default(),
+        &mut Inputs::default(),
+        time,
+        conditions,
+        FlowDirection::default(),
+    )?;
+    *stats += end_stats;
+    stats.rhs_evals += 1;
+    dense_output.end_at(time, conditions, derivative)
+}
+
+/// Keep `dy` as the latest of the last `NEW_STAGES` stage derivatives
+fn record_stage(stages: &mut VecDeque<StateVariables>, dy: StateVariables) {
+    if stages.len() == NEW_STAGES {
+        stages.pop_front();
+    }
+    stages.push_back(dy);
+}
+
+/// Return the quartic term of the Dormand-Prince continuous extension over a
+/// step of size `step`, from the derivative at its `start` and the six
+/// `stages` evaluated by the step
+fn quartic_term(
+    step: f64,
+    start: &StateVariables,
+    stages: &VecDeque<StateVariables>,
+) -> StateVariables {
+    debug_assert_eq!(stages.len(), NEW_STAGES);
+    let weighted = std::iter::once(start)
+        .chain(stages)
+        .zip(DENSE_COEFFS)
+        .fold(StateVariables::zeros(start.len()), |sum, (k, d)| {
+            sum + k * d
+        });
+    weighted * step
+}
+
+/// Return the time and conditions when the power piston returns to
+/// mid-stroke, which is within the final step of `dense_output`
+fn return_to_start(dense_output: &DenseOutput) -> (f64, Conditions) {
+    let (mut low, mut high) = dense_output.final_step();
+    for _ in 0..RETURN_ITERS {
+        let mid = 0.5 * (low + high);
+        if dense_output.conditions_at(mid).motion.x_p < 0.0 {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    let mut conditions = dense_output.conditions_at(high);
+    conditions.motion.x_p = 0.0;
+    (high, conditions)
+}
 
 struct IntegrationState<'a, T: Cycle> {
     cycle: &'a T,
+    layout: Vec<usize>,
+    system: RefCell<LinearSystem>,
+    inputs: RefCell<Inputs>,
     last_flow_dir: RefCell<FlowDirection>,
+    eval_stats: &'a Cell<SolverStats>,
+    flow_retries: &'a RefCell<Vec<(f64, usize)>>,
+    derivs: &'a RefCell<Vec<StateVariables>>,
+    stages: RefCell<VecDeque<StateVariables>>,
+    quartic: &'a RefCell<Vec<StateVariables>>,
+    last_time: f64,
+    limits: &'a Limits,
+    interruption: &'a Cell<Option<Interruption>>,
+    failure: &'a RefCell<Option<anyhow::Error>>,
+    free: bool,
+    last_x_p: f64,
+    returned: &'a Cell<bool>,
 }
 
 impl<T: Cycle> System<StateVariables> for IntegrationState<'_, T> {
+    /// Evaluate the time derivatives of the state variables
+    ///
+    /// If the state equations cannot be solved, the first error is kept and
+    /// the derivatives are set to NaN, which makes the integrator reject every
+    /// step until the step size underflows and the integration stops.
     fn system(&self, time: f64, y: &StateVariables, dy: &mut StateVariables) {
-        let conditions = Conditions {
-            P: y[0],
-            T_c: y[1],
-            T_e: y[2],
+        let conditions = to_conditions(y, &self.layout);
+        let flow_dir_hint = self.last_flow_dir.take();
+        let (solution, derivative, stats) = match derivatives(
+            self.cycle,
+            &mut self.system.borrow_mut(),
+            &mut self.inputs.borrow_mut(),
+            time,
+            conditions,
+            flow_dir_hint,
+        ) {
+            Ok(result) => result,
+            Err(err) => {
+                self.failure.borrow_mut().get_or_insert(err);
+                dy.fill(f64::NAN);
+                record_stage(&mut self.stages.borrow_mut(), dy.clone());
+                return;
+            }
         };
-        let inputs = self.cycle.calculate_inputs(time, conditions);
-        let flow_dir_hint = *self.last_flow_dir.borrow();
-        let solution = solve::<T::Solver>(inputs, flow_dir_hint)
-            .expect("TODO: what should we do if this fails?");
+        let mut total = self.eval_stats.get();
+        total += stats;
+        total.rhs_evals += 1;
+        self.eval_stats.set(total);
+        if stats.flow_updates > 0 {
+            self.flow_retries
+                .borrow_mut()
+                .push((time, stats.flow_updates));
+        }
 
         let flow_dir = FlowDirection::from_solution(&solution);
         self.last_flow_dir.replace(flow_dir);
 
-        dy[0] = solution.dP_dt;
-        dy[1] = solution.dTc_dt;
-        dy[2] = solution.dTe_dt;
+        *dy = to_state_variables(&derivative, &self.layout);
+        record_stage(&mut self.stages.borrow_mut(), dy.clone());
+    }
+
+    /// Record the derivative at the end of each accepted step
+    ///
+    /// The integration is stopped early if any of the limits are exceeded,
+    /// or once a free power piston has returned to mid-stroke.
+    fn solout(&mut self, time: f64, y: &StateVariables, dy: &StateVariables) -> bool {
+        let mut derivs = self.derivs.borrow_mut();
+        let stages = self.stages.borrow();
+        debug_assert_eq!(stages.back(), Some(dy), "the last stage ends the step");
+        let start = derivs
+            .last()
+            .expect("the start is recorded before the first step");
+        let step = time - self.last_time;
+        self.quartic
+            .borrow_mut()
+            .push(quartic_term(step, start, &stages));
+        self.last_time = time;
+        derivs.push(dy.clone());
+        let stats = SolverStats {
+            accepted_steps: derivs.len() - 1,
+            ..self.eval_stats.get()
+        };
+        self.interruption.set(self.limits.check(stats));
+        if self.free {
+            let x_p = to_conditions(y, &self.layout).motion.x_p;
+            if self.last_x_p < 0.0 && x_p >= 0.0 {
+                self.returned.set(true);
+            }
+            self.last_x_p = x_p;
+        }
+        self.interruption.get().is_some() || self.returned.get()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+
+    /// Records the stages of a rotation, `y0' = -y1` and `y1' = y0`, along
+    /// with a growth of `y2' = y2 sin(3 t)`, in the same way as
+    /// `IntegrationState`
+    #[derive(Default)]
+    struct Recorder<'a> {
+        stages: RefCell<VecDeque<StateVariables>>,
+        derivs: Option<&'a RefCell<Vec<StateVariables>>>,
+        quartic: Option<&'a RefCell<Vec<StateVariables>>>,
+        last_time: f64,
+    }
+
+    impl System<StateVariables> for Recorder<'_> {
+        fn system(&self, time: f64, y: &StateVariables, dy: &mut StateVariables) {
+            *dy = StateVariables::from_vec(vec![-y[1], y[0], y[2] * (3.0 * time).sin()]);
+            record_stage(&mut self.stages.borrow_mut(), dy.clone());
+        }
+
+        fn solout(&mut self, time: f64, _y: &StateVariables, dy: &StateVariables) -> bool {
+            let (Some(derivs), Some(quartic)) = (self.derivs, self.quartic) else {
+                return false;
+            };
+            let mut derivs = derivs.borrow_mut();
+            if let Some(start) = derivs.last() {
+                let stages = self.stages.borrow();
+                quartic
+                    .borrow_mut()
+                    .push(quartic_term(time - self.last_time, start, &stages));
+            }
+            self.last_time = time;
+            derivs.push(dy.clone());
+            false
+        }
+    }
+
+    #[test]
+    fn matches_dormand_prince_dense_output() {
+        let (end, tol) = (2.0, 1e-6);
+        let y0 = StateVariables::from_vec(vec![1.0, 0.0, 1.0]);
+        let stepper = |recorder, dx, output| {
+            Dopri5::from_param(
+                recorder,
+                0.0,
+                end,
+                dx,
+                y0.clone(),
+                tol,
+                tol,
+                SAFETY_FACTOR,
+                BETA,
+                FAC_MIN,
+                FAC_MAX,
+                end,
+                0.0,
+                MAX_STEPS,
+                STIFFNESS_CHECK,
+                output,
+            )
+        };
+
+        let (derivs, quartic) = (RefCell::new(Vec::new()), RefCell::new(Vec::new()));
+        let mut recorder = Recorder {
+            derivs: Some(&derivs),
+            quartic: Some(&quartic),
+            ..Recorder::default()
+        };
+        let mut dy0 = StateVariables::zeros(3);
+        recorder.system(0.0, &y0, &mut dy0);
+        recorder.solout(0.0, &y0, &dy0);
+        let mut sparse = stepper(recorder, 0.0, OutputType::Sparse);
+        sparse.integrate().expect("integration should work");
+        let to_vec =
+            |y: &Vec<StateVariables>| y.iter().map(|y| to_conditions(y, &THERMAL)).collect();
+        let cubic = DenseOutput::new(
+            end,
+            sparse.x_out().clone(),
+            to_vec(sparse.y_out()),
+            to_vec(&derivs.borrow()),
+        );
+        let dense = cubic.clone().with_quartic(to_vec(&quartic.borrow()));
+
+        let mut expected = stepper(Recorder::default(), 0.01, OutputType::Dense);
+        expected.integrate().expect("integration should work");
+        let mut cubic_error: f64 = 0.0;
+        for (&time, y) in expected.x_out().iter().zip(expected.y_out()) {
+            let actual = dense.conditions_at(time);
+            assert_relative_eq!(actual.P, y[0], epsilon = 1e-12);
+            assert_relative_eq!(actual.T_c, y[1], epsilon = 1e-12);
+            assert_relative_eq!(actual.T_e, y[2], epsilon = 1e-12);
+            cubic_error = cubic_error.max((cubic.conditions_at(time).P - y[0]).abs());
+        }
+        assert!(cubic_error > 1e-6, "the quartic term should matter");
     }
 }
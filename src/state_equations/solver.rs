@@ -1,209 +1,439 @@
-use anyhow::{anyhow, bail, Context, Result};
-use na::{SMatrix, SVector};
+use std::iter;
 
-use super::{flow_direction::FlowDirection, Inputs, Solution};
+use anyhow::{anyhow, ensure, Context, Result};
+use na::{DMatrix, DVector, Matrix4, Vector4};
 
-type Matrix = SMatrix<f64, 10, 10>;
-type Vector = SVector<f64, 10>;
+use crate::types::{Scalar, SolverStats};
 
-// The maximum number of times flow directions can be updated before failing
+use super::{
+    flow_direction::FlowDirection,
+    inputs::{HeatExchanger, Regenerator},
+    Inputs, Solution,
+};
+
+type Matrix<S = f64> = DMatrix<S>;
+type Vector<S = f64> = DVector<S>;
+
+// The number of times flow directions are updated before any interface that
+// still disagrees with the solution is given an unknown direction
 const ALLOWED_FLOW_UPDATES: usize = 3;
 
-/// Solve the state equations
+/// Represents the `Ax=b` system of state equations
+///
+/// The `A` matrix depends on the direction of fluid flow between control
+/// volumes, which are adjusted iteratively when solving the state equations.
+/// Only the enthalpy flow terms change with flow direction, so they are
+/// written into `a` for each direction tried, starting from the `base` values
+/// those entries have without any enthalpy flow.
+///
+/// With `M` heat exchanger control volumes between the working spaces, the
+/// unknowns are the `M + 1` interface mass flow rates, the `M` volume heat
+/// flows, `dTc_dt`, `dTe_dt`, and the time derivative of each distinct
+/// pressure.  Undiscretized heat exchangers with a uniform pressure give the
+/// original system of ten equations.
 ///
-/// This function is generic over the decomposition function used to solve `Ax=b`.
+/// The working spaces, heat exchangers, and regenerator each have a pressure,
+/// and neighbours share one unless there is hydraulic resistance between
+/// them.  The resistance between two neighbours runs from the middle of one
+/// to the middle of the other, and is half of the resistance of each heat
+/// exchanger volume over its density, summed over the volumes on that path.
+/// A momentum balance then sets the mass flow rate across their boundary to
+/// the drop between their pressures over that resistance, which adds one
+/// equation for every pressure beyond the first.
 ///
-/// Better documentation will be added per [issue](https://github.com/isentropic-dev/sett-rs/issues/9)
-pub(super) fn solve<T: MatrixDecomposition>(
-    inputs: Inputs,
-    flow_dir_hint: FlowDirection,
-) -> Result<Solution> {
-    let system = System::new(inputs);
-    let mut flow_dir = flow_dir_hint;
-    for _ in 0..=ALLOWED_FLOW_UPDATES {
-        let solution = system.solve::<T>(flow_dir)?;
-        let actual_flow_dir = FlowDirection::from_solution(&solution);
-        if flow_dir == actual_flow_dir {
-            return Ok(solution);
+/// The state equations are solved many times over a cycle with the same
+/// number of control volumes, so a `LinearSystem` keeps its matrix, vectors,
+/// and control volumes between solves and only refills them.  Decompositions
+/// that factor `A` in place still work on their own copy of it.
+pub(super) struct LinearSystem<S: Scalar = f64> {
+    a: Matrix<S>,
+    b: Vector<S>,
+    base: Vec<(S, S)>,             // `a` entries where enthalpy flows are added
+    h_interface_norm: Vec<(S, S)>, // (positive flow, negative flow)
+    groups: [usize; 5],            // pressure of each working space and heat exchanger
+    momentum_faces: Vec<usize>,    // interfaces whose flow is set by a momentum balance
+    volumes: Vec<Volume<S>>,
+    num_chx: usize,
+    num_regen: usize,
+}
+
+impl<S: Scalar> Default for LinearSystem<S> {
+    fn default() -> Self {
+        Self {
+            a: Matrix::zeros(0, 0),
+            b: Vector::zeros(0),
+            base: Vec::new(),
+            h_interface_norm: Vec::new(),
+            groups: [0; 5],
+            momentum_faces: Vec::new(),
+            volumes: Vec::new(),
+            num_chx: 0,
+            num_regen: 0,
         }
-        flow_dir = actual_flow_dir;
     }
-    bail!("unable to determine flow directions")
 }
 
-/// Represents the `Ax=b` system of state equations
-///
-/// The `A` matrix depends on the direction of fluid flow between control
-/// volumes, which are adjusted iteratively when solving the state equations.
-/// The stored `MatrixStencil` is responsible for generating this matrix as a
-/// function of flow directions.
-struct System {
-    stencil: MatrixStencil,
-    b: Vector,
+/// A heat exchanger or regenerator control volume within the `LinearSystem`
+#[allow(non_snake_case)]
+struct Volume<S: Scalar> {
+    vol: S,
+    dens: S,
+    inte: S,
+    enth_cold: S,
+    enth_hot: S,
+    dd_dP_T: S,
+    du_dP_T: S,
+    hyd_res: S,
+    heat_sign: S,
 }
 
-impl System {
-    /// Create a new `System`
-    fn new(inputs: Inputs) -> Self {
+impl<S: Scalar> LinearSystem<S> {
+    /// Solve the state equations
+    ///
+    /// This function is generic over the decomposition function used to solve
+    /// `Ax=b`, and a `LinearSystem` can use any `Scalar` type for its
+    /// `Inputs`.  Along with the `Solution`, the number of fallback
+    /// decompositions and flow direction updates that were needed to reach it
+    /// are returned.
+    ///
+    /// Better documentation will be added per [issue](https://github.com/isentropic-dev/sett-rs/issues/9)
+    pub(super) fn solve<T: MatrixDecomposition>(
+        &mut self,
+        inputs: &Inputs<S>,
+        flow_dir_hint: FlowDirection,
+    ) -> Result<(Solution<S>, SolverStats)> {
+        self.fill(inputs)?;
+        ensure!(
+            !T::UNIFORM_PRESSURE_ONLY || self.groups[4] == 0,
+            "the matrix decomposition only supports a uniform pressure"
+        );
+        let mut flow_dir = flow_dir_hint;
+        let mut stats = SolverStats::default();
+        for _ in 0..ALLOWED_FLOW_UPDATES {
+            let (solution, fallbacks) = self.solve_with::<T>(&flow_dir)?;
+            stats.matrix_fallbacks += fallbacks;
+            if flow_dir.matches(&solution) {
+                return Ok((solution, stats));
+            }
+            flow_dir = FlowDirection::from_solution(&solution);
+            stats.flow_updates += 1;
+        }
+        let (solution, fallbacks) = self.solve_with::<T>(&flow_dir)?;
+        stats.matrix_fallbacks += fallbacks;
+        if flow_dir.matches(&solution) {
+            return Ok((solution, stats));
+        }
+
+        // A flow that is zero apart from rounding can change sign with every
+        // update, so the interfaces that still disagree average their
+        // enthalpies instead
+        flow_dir = flow_dir.settle(&solution);
+        stats.flow_updates += 1;
+        let (solution, fallbacks) = self.solve_with::<T>(&flow_dir)?;
+        stats.matrix_fallbacks += fallbacks;
+        Ok((solution, stats))
+    }
+
+    /// Group the volumes by pressure, returning the resistance to flow across
+    /// the boundaries between the working spaces and heat exchangers
+    ///
+    /// Neighbours without any resistance between them share a pressure.
+    fn group_pressures(&mut self) -> [S; 4] {
+        let half: S = na::convert(0.5);
+        let (kr, rl) = (self.num_chx, self.num_chx + self.num_regen);
+        let half_res = |volumes: &[Volume<S>]| {
+            volumes.iter().fold(S::zero(), |sum, volume| {
+                sum + half * volume.hyd_res / volume.dens
+            })
+        };
+        let (chx_res, regen_res, hhx_res) = (
+            half_res(&self.volumes[..kr]),
+            half_res(&self.volumes[kr..rl]),
+            half_res(&self.volumes[rl..]),
+        );
+        let boundary_res = [chx_res, chx_res + regen_res, regen_res + hhx_res, hhx_res];
+        for (i, res) in boundary_res.iter().enumerate() {
+            self.groups[i + 1] = self.groups[i] + usize::from(!res.is_zero());
+        }
+        boundary_res
+    }
+
+    /// Add a momentum balance across each boundary between two pressures
+    ///
+    /// The pressure drop of each part is measured from the compression space.
+    fn fill_momentum(&mut self, boundary_res: [S; 4], pres_drop: [S; 4]) {
+        let m = self.volumes.len();
+        let faces = [0, self.num_chx, self.num_chx + self.num_regen, m];
+        self.momentum_faces.clear();
+        let mut upstream_drop = S::zero();
+        for ((face, res), drop) in faces.into_iter().zip(boundary_res).zip(pres_drop) {
+            if !res.is_zero() {
+                let row = 2 * m + 4 + self.momentum_faces.len();
+                self.a[(row, face)] = S::one(); // m_dot[face]
+                self.b[row] = (drop - upstream_drop) / res;
+                self.momentum_faces.push(face);
+            }
+            upstream_drop = drop;
+        }
+    }
+
+    /// Fill the system from `inputs`
+    #[allow(non_snake_case)]
+    fn fill(&mut self, inputs: &Inputs<S>) -> Result<()> {
+        let (pres, pres_drop, enth_norm) = (inputs.pres, inputs.pres_drop, inputs.enth_norm);
         let Inputs {
-            pres,
-            enth_norm,
             comp,
             chx,
             regen,
             hhx,
             exp,
+            ..
         } = inputs;
+        ensure!(
+            !chx.is_empty() && !regen.is_empty() && !hhx.is_empty(),
+            "each heat exchanger needs at least one control volume"
+        );
+
+        self.volumes.clear();
+        self.volumes.extend(Volume::chain(chx, regen, hhx));
+        self.num_chx = chx.len();
+        self.num_regen = regen.len();
 
-        let mut a = Matrix::zeros();
-        let mut b = Vector::zeros();
+        let (m, kr, rl) = (
+            self.volumes.len(),
+            self.num_chx,
+            self.num_chx + self.num_regen,
+        );
+        let boundary_res = self.group_pressures();
+        let groups = self.groups;
+        let group = |k: usize| match k {
+            k if k < kr => groups[1],
+            k if k < rl => groups[2],
+            _ => groups[3],
+        };
+
+        let size = 2 * m + 4 + groups[4];
+        let i_dTc = 2 * m + 1;
+        let i_dTe = i_dTc + 1;
+        let i_dP = i_dTe + 1; // followed by each pressure after the compression space
+        if self.a.shape() == (size, size) {
+            self.a.fill(S::zero());
+            self.b.fill(S::zero());
+        } else {
+            self.a = Matrix::zeros(size, size);
+            self.b = Vector::zeros(size);
+        }
+        let (a, b) = (&mut self.a, &mut self.b);
 
         // Build the `A` matrix
         //
         // Some terms in the matrix relate to the enthalpy flow between control
-        // volumes.  These values are set later by the `MatrixStencil` based
-        // on flow direction, but their locations in the matrix are included
-        // below as commented code.
+        // volumes.  These values are set later based on flow direction, but
+        // their locations in the matrix are included below as commented code.
         //
         // All terms derived from energy balances are normalized by the
         // provided average enthalpy to reduce the matrix condition number.
 
         // Mass balance on compression space
-        a[(0, 0)] = 1.0; // m_dot_ck
-        a[(0, 7)] = comp.vol * comp.dd_dT_P; // dTc_dt
-        a[(0, 9)] = comp.vol * comp.dd_dP_T; // dP_dt
-        b[(0)] = -comp.dens * comp.dV_dt;
+        a[(0, 0)] = S::one(); // m_dot_ck
+        a[(0, i_dTc)] = comp.vol * comp.dd_dT_P; // dTc_dt
+        a[(0, i_dP)] = comp.vol * comp.dd_dP_T; // dP_dt
+        b[0] = -comp.dens * comp.dV_dt - comp.m_dot_leak;
 
         // Energy balance on compression space
         // a[(1, 0)] = h_ck_norm; // m_dot_ck
-        a[(1, 7)] = comp.vol * (comp.dens * comp.du_dT_P + comp.inte * comp.dd_dT_P) / enth_norm; // dTc_dt
-        a[(1, 9)] = comp.vol * (comp.dens * comp.du_dP_T + comp.inte * comp.dd_dP_T) / enth_norm; // dP_dt
-        b[(1)] = (-(pres + comp.dens * comp.inte) * comp.dV_dt - comp.Q_dot) / enth_norm;
-
-        // Mass balance on cold heat exchanger
-        a[(2, 0)] = -1.0; // m_dot_ck
-        a[(2, 1)] = 1.0; // m_dot_kr
-        a[(2, 9)] = chx.vol * chx.dd_dP_T; // dP_dt
-
-        // Energy balance on cold heat exchanger
-        // a[(3, 0)] = -h_ck_norm; // m_dot_ck
-        // a[(3, 1)] = h_kr_norm; // m_dot_kr
-        a[(3, 4)] = 1.0 / enth_norm; // Q_dot_k
-        a[(3, 9)] = chx.vol * (chx.dens * chx.du_dP_T + chx.inte * chx.dd_dP_T) / enth_norm; // dP_dt
-
-        // Mass balance on regenerator
-        a[(4, 1)] = -1.0; // m_dot_kr
-        a[(4, 2)] = 1.0; // m_dot_rl
-        a[(4, 9)] = regen.vol * regen.dd_dP_T; // dP_dt
-
-        // Energy balance on regenerator
-        // a[(5, 1)] = -h_kr_norm; // m_dot_kr
-        // a[(5, 2)] = h_rl_norm; // m_dot_rl
-        a[(5, 5)] = 1.0 / enth_norm; // Q_dot_r
-        a[(5, 9)] =
-            regen.vol * (regen.dens * regen.du_dP_T + regen.inte * regen.dd_dP_T) / enth_norm; // dP_dt
-
-        // Mass balance on hot heat exchanger
-        a[(6, 2)] = -1.0; // m_dot_rl
-        a[(6, 3)] = 1.0; // m_dot_le
-        a[(6, 9)] = hhx.vol * hhx.dd_dP_T; // dP_dt
-
-        // Energy balance on hot heat exchanger
-        // a[(7, 2)] = -h_rl_norm; // m_dot_rl
-        // a[(7, 3)] = h_le_norm; // m_dot_le
-        a[(7, 6)] = -1.0 / enth_norm; // Q_dot_l
-        a[(7, 9)] = hhx.vol * (hhx.dens * hhx.du_dP_T + hhx.inte * hhx.dd_dP_T) / enth_norm; // dP_dt
+        a[(1, i_dTc)] =
+            comp.vol * (comp.dens * comp.du_dT_P + comp.inte * comp.dd_dT_P) / enth_norm; // dTc_dt
+        a[(1, i_dP)] = comp.vol * (comp.dens * comp.du_dP_T + comp.inte * comp.dd_dP_T) / enth_norm; // dP_dt
+        b[1] = (-(pres + comp.dens * comp.inte) * comp.dV_dt
+            - comp.Q_dot
+            - comp.Q_dot_wall
+            - comp.m_dot_leak * comp.enth_leak)
+            / enth_norm;
+
+        // Balances on each heat exchanger volume, where volume `k` has flow
+        // `m_dot[k]` across its cold side and `m_dot[k + 1]` across its hot side
+        for (k, volume) in self.volumes.iter().enumerate() {
+            let (mass, energy) = (2 + 2 * k, 3 + 2 * k);
+            let i_dP = i_dP + group(k);
+
+            // Mass balance
+            a[(mass, k)] = -S::one(); // m_dot[k]
+            a[(mass, k + 1)] = S::one(); // m_dot[k + 1]
+            a[(mass, i_dP)] = volume.vol * volume.dd_dP_T; // dP_dt
+
+            // Energy balance
+            // a[(energy, k)] = -h_cold_side_norm; // m_dot[k]
+            // a[(energy, k + 1)] = h_hot_side_norm; // m_dot[k + 1]
+            a[(energy, m + 1 + k)] = volume.heat_sign / enth_norm; // Q_dot[k]
+            a[(energy, i_dP)] = volume.vol
+                * (volume.dens * volume.du_dP_T + volume.inte * volume.dd_dP_T)
+                / enth_norm; // dP_dt
+        }
 
         // Mass balance on expansion space
-        a[(8, 3)] = -1.0; // m_dot_le
-        a[(8, 8)] = exp.vol * exp.dd_dT_P; // dTe_dt
-        a[(8, 9)] = exp.vol * exp.dd_dP_T; // dP_dt
-        b[(8)] = -exp.dens * exp.dV_dt;
+        let (mass, energy) = (2 * m + 2, 2 * m + 3);
+        let (pres, i_dP) = (pres - pres_drop[3], i_dP + groups[4]);
+        a[(mass, m)] = -S::one(); // m_dot_le
+        a[(mass, i_dTe)] = exp.vol * exp.dd_dT_P; // dTe_dt
+        a[(mass, i_dP)] = exp.vol * exp.dd_dP_T; // dP_dt
+        b[mass] = -exp.dens * exp.dV_dt - exp.m_dot_leak;
 
         // Energy balance on expansion space
-        // a[(9, 3)] = -h_le_norm; // m_dot_le
-        a[(9, 8)] = exp.vol * (exp.dens * exp.du_dT_P + exp.inte * exp.dd_dT_P) / enth_norm; // dTe_dt
-        a[(9, 9)] = exp.vol * (exp.dens * exp.du_dP_T + exp.inte * exp.dd_dP_T) / enth_norm; // dP_dt
-        b[(9)] = (-(pres + exp.dens * exp.inte) * exp.dV_dt - exp.Q_dot) / enth_norm;
+        // a[(energy, m)] = -h_le_norm; // m_dot_le
+        a[(energy, i_dTe)] =
+            exp.vol * (exp.dens * exp.du_dT_P + exp.inte * exp.dd_dT_P) / enth_norm; // dTe_dt
+        a[(energy, i_dP)] = exp.vol * (exp.dens * exp.du_dP_T + exp.inte * exp.dd_dP_T) / enth_norm; // dP_dt
+        b[energy] = (-(pres + exp.dens * exp.inte) * exp.dV_dt
+            - exp.Q_dot
+            - exp.Q_dot_wall
+            - exp.m_dot_leak * exp.enth_leak)
+            / enth_norm;
 
-        Self {
-            stencil: MatrixStencil {
-                a,
-                h_comp_norm: comp.enth / enth_norm,
-                h_chx_norm: chx.enth / enth_norm,
-                h_regen_cold_norm: regen.enth_cold / enth_norm,
-                h_regen_hot_norm: regen.enth_hot / enth_norm,
-                h_hhx_norm: hhx.enth / enth_norm,
-                h_exp_norm: exp.enth / enth_norm,
-            },
-            b,
+        // Flow across an interface carries the enthalpy of the upstream side
+        self.h_interface_norm.clear();
+        self.h_interface_norm.extend(
+            iter::once(comp.enth)
+                .chain(self.volumes.iter().map(|volume| volume.enth_hot))
+                .zip(
+                    self.volumes
+                        .iter()
+                        .map(|volume| volume.enth_cold)
+                        .chain(iter::once(exp.enth)),
+                )
+                .map(|(h_positive, h_negative)| (h_positive / enth_norm, h_negative / enth_norm)),
+        );
+        self.base.clear();
+        self.base
+            .extend((0..=m).map(|i| (a[(2 * i + 1, i)], a[(2 * i + 3, i)])));
+
+        self.fill_momentum(boundary_res, pres_drop);
+        Ok(())
+    }
+
+    /// Set the enthalpy flow entries of `A` for the provided flow directions
+    fn apply_flow_direction(&mut self, flow_dir: &FlowDirection) {
+        let entries = self.h_interface_norm.iter().zip(&self.base);
+        for (i, (&(positive, negative), &(cold_side, hot_side))) in entries.enumerate() {
+            // Add the enthalpy entries to the energy balances on either side
+            let h_norm = flow_dir.get(i).select(positive, negative);
+            self.a[(2 * i + 1, i)] = cold_side + h_norm;
+            self.a[(2 * i + 3, i)] = hot_side - h_norm;
         }
     }
 
-    /// Solve the system of equations
-    fn solve<T: MatrixDecomposition>(&self, flow_dir: FlowDirection) -> Result<Solution> {
-        let a = self.stencil.create_matrix(flow_dir);
-        let x = T::solve(&a, &self.b)?;
-        Ok(Solution {
-            m_dot_ck: x[0],
-            m_dot_kr: x[1],
-            m_dot_rl: x[2],
-            m_dot_le: x[3],
-            Q_dot_k: x[4],
-            Q_dot_r: x[5],
-            Q_dot_l: x[6],
-            dTc_dt: x[7],
-            dTe_dt: x[8],
-            dP_dt: x[9],
-        })
+    /// Solve the system of equations for the provided flow directions
+    ///
+    /// The number of fallback decompositions used is returned with the `Solution`.
+    #[allow(non_snake_case)]
+    fn solve_with<T: MatrixDecomposition>(
+        &mut self,
+        flow_dir: &FlowDirection,
+    ) -> Result<(Solution<S>, usize)> {
+        self.apply_flow_direction(flow_dir);
+        let (mut x, fallbacks) = T::solve_with_fallbacks(&self.a, &self.b)?;
+        let m = self.volumes.len();
+        let i_dP = 2 * m + 3;
+
+        // Flows set by a momentum balance are known exactly, so round-off
+        // cannot flip the direction of one that is close to zero
+        for (i, &face) in self.momentum_faces.iter().enumerate() {
+            x[face] = self.b[2 * m + 4 + i];
+        }
+        let m_dot: Vec<_> = x.rows(0, m + 1).iter().copied().collect();
+        let Q_dot: Vec<_> = x.rows(m + 1, m).iter().copied().collect();
+        let (kr, rl) = (self.num_chx, self.num_chx + self.num_regen);
+        let solution = Solution {
+            m_dot_ck: m_dot[0],
+            m_dot_kr: m_dot[kr],
+            m_dot_rl: m_dot[rl],
+            m_dot_le: m_dot[m],
+            Q_dot_k: sum(&Q_dot[..kr]),
+            Q_dot_r: sum(&Q_dot[kr..rl]),
+            Q_dot_l: sum(&Q_dot[rl..]),
+            dTc_dt: x[2 * m + 1],
+            dTe_dt: x[2 * m + 2],
+            dP_dt: x[i_dP],
+            dPdrop_dt: [1, 2, 3, 4].map(|part| x[i_dP] - x[i_dP + self.groups[part]]),
+            m_dot,
+            Q_dot,
+        };
+        Ok((solution, fallbacks))
     }
 }
 
-struct MatrixStencil {
-    a: Matrix,
-    h_comp_norm: f64,
-    h_chx_norm: f64,
-    h_regen_cold_norm: f64,
-    h_regen_hot_norm: f64,
-    h_hhx_norm: f64,
-    h_exp_norm: f64,
+/// Return the sum of `values`
+fn sum<S: Scalar>(values: &[S]) -> S {
+    values.iter().fold(S::zero(), |sum, &value| sum + value)
 }
 
-impl MatrixStencil {
-    /// Create an `A` matrix based on the provided flow directions
-    fn create_matrix(&self, flow_dir: FlowDirection) -> Matrix {
-        // Determine enthalpies at the volume interfaces
-        let h_ck_norm = flow_dir.ck.select(self.h_comp_norm, self.h_chx_norm);
-        let h_kr_norm = flow_dir.kr.select(self.h_chx_norm, self.h_regen_cold_norm);
-        let h_rl_norm = flow_dir.rl.select(self.h_regen_hot_norm, self.h_hhx_norm);
-        let h_le_norm = flow_dir.le.select(self.h_hhx_norm, self.h_exp_norm);
-
-        // Set the enthalpy entries in the matrix
-        let mut a = self.a; // `SMatrix` is copy
-        a[(1, 0)] = h_ck_norm;
-        a[(3, 0)] = -h_ck_norm;
-
-        a[(3, 1)] = h_kr_norm;
-        a[(5, 1)] = -h_kr_norm;
-
-        a[(5, 2)] = h_rl_norm;
-        a[(7, 2)] = -h_rl_norm;
-
-        a[(7, 3)] = h_le_norm;
-        a[(9, 3)] = -h_le_norm;
-
-        a
+impl<S: Scalar> Volume<S> {
+    /// Return the chain of control volumes from the cold end to the hot end
+    ///
+    /// Heat flows are positive out of the fluid, except in the hot heat exchanger.
+    fn chain<'a>(
+        chx: &'a [HeatExchanger<S>],
+        regen: &'a [Regenerator<S>],
+        hhx: &'a [HeatExchanger<S>],
+    ) -> impl Iterator<Item = Self> + 'a {
+        chx.iter()
+            .map(|chx| Volume {
+                vol: chx.vol,
+                dens: chx.dens,
+                inte: chx.inte,
+                enth_cold: chx.enth,
+                enth_hot: chx.enth,
+                dd_dP_T: chx.dd_dP_T,
+                du_dP_T: chx.du_dP_T,
+                hyd_res: chx.hyd_res,
+                heat_sign: S::one(),
+            })
+            .chain(regen.iter().map(|regen| Volume {
+                vol: regen.vol,
+                dens: regen.dens,
+                inte: regen.inte,
+                enth_cold: regen.enth_cold,
+                enth_hot: regen.enth_hot,
+                dd_dP_T: regen.dd_dP_T,
+                du_dP_T: regen.du_dP_T,
+                hyd_res: regen.hyd_res,
+                heat_sign: S::one(),
+            }))
+            .chain(hhx.iter().map(|hhx| Volume {
+                vol: hhx.vol,
+                dens: hhx.dens,
+                inte: hhx.inte,
+                enth_cold: hhx.enth,
+                enth_hot: hhx.enth,
+                dd_dP_T: hhx.dd_dP_T,
+                du_dP_T: hhx.du_dP_T,
+                hyd_res: hhx.hyd_res,
+                heat_sign: -S::one(),
+            }))
     }
 }
 
 pub trait MatrixDecomposition {
-    fn solve(a: &Matrix, b: &Vector) -> Result<Vector>;
+    /// Whether the decomposition relies on the state equations having a
+    /// single pressure, so it cannot solve them with pressure drops
+    const UNIFORM_PRESSURE_ONLY: bool = false;
+
+    fn solve<S: Scalar>(a: &Matrix<S>, b: &Vector<S>) -> Result<Vector<S>>;
+
+    /// Solve `Ax=b` and return the number of fallback decompositions used
+    ///
+    /// Decompositions that never fall back to another method can rely on
+    /// this default implementation, which always reports zero fallbacks.
+    fn solve_with_fallbacks<S: Scalar>(a: &Matrix<S>, b: &Vector<S>) -> Result<(Vector<S>, usize)> {
+        Self::solve(a, b).map(|x| (x, 0))
+    }
 }
 
 pub struct QR;
 impl MatrixDecomposition for QR {
-    fn solve(a: &Matrix, b: &Vector) -> Result<Vector> {
-        a.qr()
+    fn solve<S: Scalar>(a: &Matrix<S>, b: &Vector<S>) -> Result<Vector<S>> {
+        a.clone()
+            .qr()
             .solve(b)
             .context("unable to solve matrix with QR decompositon")
     }
@@ -211,46 +441,163 @@ impl MatrixDecomposition for QR {
 
 pub struct LU;
 impl MatrixDecomposition for LU {
-    fn solve(a: &Matrix, b: &Vector) -> Result<Vector> {
-        a.lu()
+    fn solve<S: Scalar>(a: &Matrix<S>, b: &Vector<S>) -> Result<Vector<S>> {
+        a.clone()
+            .lu()
             .solve(b)
             .context("unable to solve matrix with LU decomposition")
     }
 }
 
-pub struct Cholesky;
-impl MatrixDecomposition for Cholesky {
-    fn solve(a: &Matrix, b: &Vector) -> Result<Vector> {
-        let decomp = a
-            .cholesky()
-            .context("unable to solve matrix with Cholesky decomposition")?;
-        let x = decomp.solve(b);
-        Ok(x)
-    }
-}
-
 pub struct SvdDefault;
 impl MatrixDecomposition for SvdDefault {
-    fn solve(a: &Matrix, b: &Vector) -> Result<Vector> {
-        let eps = 1e-12;
-        a.svd_unordered(true, true)
+    fn solve<S: Scalar>(a: &Matrix<S>, b: &Vector<S>) -> Result<Vector<S>> {
+        let eps = na::convert(1e-12);
+        a.clone()
+            .svd_unordered(true, true)
             .solve(b, eps)
             .map_err(|_| anyhow!("unable to solve matrix with SVD decomposition"))
     }
 }
 
+/// A closed-form elimination that exploits the structure of the state equations
+///
+/// The control volumes form a chain from the compression space to the
+/// expansion space, so the mass balances let every mass flow rate be written
+/// as an affine function of `m_dot_ck` and `dP_dt`.  Substituting these into
+/// the four working space balances leaves a small system in `m_dot_ck`,
+/// `dTc_dt`, `dTe_dt`, and `dP_dt`, and the remaining unknowns follow by back
+/// substitution.  Every entry that a `LinearSystem` fills, including the
+/// enthalpy flow entries for each flow direction, is read from `a`, so the
+/// elimination is valid for all `FlowDirection` patterns and any number of
+/// heat exchanger volumes, but only with a uniform pressure.
+pub struct Elimination;
+impl MatrixDecomposition for Elimination {
+    const UNIFORM_PRESSURE_ONLY: bool = true;
+
+    #[allow(non_snake_case, clippy::many_single_char_names)]
+    fn solve<S: Scalar>(a: &Matrix<S>, b: &Vector<S>) -> Result<Vector<S>> {
+        let n = a.nrows();
+        ensure!(
+            n >= 6 && n.is_multiple_of(2) && a.ncols() == n && b.len() == n,
+            "unable to solve matrix with elimination"
+        );
+        let volumes = (n - 4) / 2;
+        let i_dTc = 2 * volumes + 1;
+        let i_dTe = i_dTc + 1;
+        let i_dP = i_dTe + 1;
+
+        // Each mass flow rate is represented as `p + r * m_dot_ck + q * dP_dt`
+        let mut p = vec![S::zero(); volumes + 1];
+        let mut r = vec![S::zero(); volumes + 1];
+        let mut q = vec![S::zero(); volumes + 1];
+        r[0] = S::one();
+
+        // Each heat exchanger mass balance gives the flow leaving that volume
+        for k in 0..volumes {
+            let row = 2 + 2 * k;
+            let out = pivot(a[(row, k + 1)])?;
+            p[k + 1] = (b[row] - a[(row, k)] * p[k]) / out;
+            r[k + 1] = -a[(row, k)] * r[k] / out;
+            q[k + 1] = -(a[(row, k)] * q[k] + a[(row, i_dP)]) / out;
+        }
+
+        // Reduce the working space balances to four equations in
+        // `m_dot_ck`, `dTc_dt`, `dTe_dt`, and `dP_dt`
+        let mut reduced = Matrix4::zeros();
+        let mut rhs = Vector4::zeros();
+        for (i, row) in [0, 1, n - 2, n - 1].into_iter().enumerate() {
+            rhs[i] = b[row];
+            for k in 0..=volumes {
+                rhs[i] -= a[(row, k)] * p[k];
+                reduced[(i, 0)] += a[(row, k)] * r[k];
+                reduced[(i, 3)] += a[(row, k)] * q[k];
+            }
+            reduced[(i, 1)] = a[(row, i_dTc)];
+            reduced[(i, 2)] = a[(row, i_dTe)];
+            reduced[(i, 3)] += a[(row, i_dP)];
+        }
+        let y = reduced
+            .lu()
+            .solve(&rhs)
+            .context("unable to solve matrix with elimination")?;
+        let (m_dot_ck, dP_dt) = (y[0], y[3]);
+
+        // Back substitute for the remaining unknowns
+        let mut x = Vector::zeros(n);
+        for k in 0..=volumes {
+            x[k] = p[k] + r[k] * m_dot_ck + q[k] * dP_dt;
+        }
+        for k in 0..volumes {
+            let row = 3 + 2 * k;
+            let i_Q = volumes + 1 + k;
+            x[i_Q] =
+                (b[row] - a[(row, k)] * x[k] - a[(row, k + 1)] * x[k + 1] - a[(row, i_dP)] * dP_dt)
+                    / pivot(a[(row, i_Q)])?;
+        }
+        x[i_dTc] = y[1];
+        x[i_dTe] = y[2];
+        x[i_dP] = dP_dt;
+
+        ensure!(
+            x.iter().all(|&value| value.is_finite()),
+            "unable to solve matrix with elimination"
+        );
+        Ok(x)
+    }
+}
+
+/// Return `value` if it can be used as a pivot in `Elimination`
+fn pivot<S: Scalar>(value: S) -> Result<S> {
+    ensure!(
+        !value.is_zero() && value.is_finite(),
+        "unable to solve matrix with elimination"
+    );
+    Ok(value)
+}
+
+/// A decomposition that falls back to more robust methods when needed
+///
+/// An LU decomposition is tried first.  If it reports a singular system, a QR
+/// decomposition is tried next, followed by an SVD as a last resort.  Each
+/// step past LU counts as one fallback.
+pub struct Robust;
+impl MatrixDecomposition for Robust {
+    fn solve<S: Scalar>(a: &Matrix<S>, b: &Vector<S>) -> Result<Vector<S>> {
+        Self::solve_with_fallbacks(a, b).map(|(x, _)| x)
+    }
+
+    fn solve_with_fallbacks<S: Scalar>(a: &Matrix<S>, b: &Vector<S>) -> Result<(Vector<S>, usize)> {
+        if let Ok(x) = LU::solve(a, b) {
+            return Ok((x, 0));
+        }
+        if let Ok(x) = QR::solve(a, b) {
+            return Ok((x, 1));
+        }
+        let x = SvdDefault::solve(a, b)?;
+        Ok((x, 2))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::super::tests::read_test_inputs;
     use super::*;
 
+    /// Solve the state equations with a new `LinearSystem`
+    fn solve<T: MatrixDecomposition, S: Scalar>(
+        inputs: Inputs<S>,
+        flow_dir_hint: FlowDirection,
+    ) -> Result<(Solution<S>, SolverStats)> {
+        LinearSystem::default().solve::<T>(&inputs, flow_dir_hint)
+    }
+
     #[test]
     fn test_typical_ideal_gas_hydrogen_values() {
         let inputs = read_test_inputs("ideal_gas_hydrogen.json");
         let inputs: Inputs = serde_json::from_str(&inputs).expect("test inputs file is invalid");
-        let flow_dir = FlowDirection::default();
-
-        let lu_solution = solve::<LU>(inputs.clone(), flow_dir).expect("should solve");
+        let (lu_solution, _) =
+            solve::<LU, _>(inputs.clone(), FlowDirection::default()).expect("should solve");
         insta::assert_yaml_snapshot!(lu_solution, @r###"
         ---
         m_dot_ck: -0.0369671135868011
@@ -263,9 +610,24 @@ mod tests {
         dTc_dt: 2845.6263552639434
         dTe_dt: 31166.869984699082
         dP_dt: 390423950.31296676
+        dPdrop_dt:
+          - 0
+          - 0
+          - 0
+          - 0
+        m_dot:
+          - -0.0369671135868011
+          - -0.04739861686084307
+          - -0.06366524032668251
+          - -0.07450887512267486
+        Q_dot:
+          - 11687.524503992354
+          - 436791.3029835215
+          - 83208.74490054866
         "###);
 
-        let qr_solution = solve::<QR>(inputs.clone(), flow_dir).expect("should solve");
+        let (qr_solution, _) =
+            solve::<QR, _>(inputs.clone(), FlowDirection::default()).expect("should solve");
         insta::assert_yaml_snapshot!(qr_solution, @r###"
         ---
         m_dot_ck: -0.03696711358680108
@@ -278,9 +640,24 @@ mod tests {
         dTc_dt: 2845.6263552639507
         dTe_dt: 31166.869984699046
         dP_dt: 390423950.3129669
+        dPdrop_dt:
+          - 0
+          - 0
+          - 0
+          - 0
+        m_dot:
+          - -0.03696711358680108
+          - -0.04739861686084307
+          - -0.06366524032668251
+          - -0.07450887512267484
+        Q_dot:
+          - 11687.524503992556
+          - 436791.3029835213
+          - 83208.74490054866
         "###);
 
-        let svd_solution = solve::<SvdDefault>(inputs, flow_dir).expect("should solve");
+        let (svd_solution, _) =
+            solve::<SvdDefault, _>(inputs, FlowDirection::default()).expect("should solve");
         insta::assert_yaml_snapshot!(svd_solution, @r###"
         ---
         m_dot_ck: -0.03696711358657141
@@ -293,6 +670,20 @@ mod tests {
         dTc_dt: 2845.626355263992
         dTe_dt: 31166.86998469886
         dP_dt: 390423950.3129672
+        dPdrop_dt:
+          - 0
+          - 0
+          - 0
+          - 0
+        m_dot:
+          - -0.03696711358657141
+          - -0.047398616860491946
+          - -0.06366524032648578
+          - -0.07450887512248028
+        Q_dot:
+          - 11687.524503991895
+          - 436791.3029835225
+          - 83208.74490054886
         "###);
     }
 
@@ -300,9 +691,8 @@ mod tests {
     fn test_typical_refprop_hydrogen_values() {
         let inputs = read_test_inputs("refprop_hydrogen.json");
         let inputs: Inputs = serde_json::from_str(&inputs).expect("test inputs file is invalid");
-        let flow_dir = FlowDirection::default();
-
-        let lu_solution = solve::<LU>(inputs.clone(), flow_dir).expect("should solve");
+        let (lu_solution, _) =
+            solve::<LU, _>(inputs.clone(), FlowDirection::default()).expect("should solve");
         insta::assert_yaml_snapshot!(lu_solution, @r###"
         ---
         m_dot_ck: -0.028538301905757048
@@ -315,9 +705,24 @@ mod tests {
         dTc_dt: 4548.878957390213
         dTe_dt: 8995.893667930639
         dP_dt: 417493124.93544215
+        dPdrop_dt:
+          - 0
+          - 0
+          - 0
+          - 0
+        m_dot:
+          - -0.028538301905757048
+          - -0.038613913985699036
+          - -0.05488487354380802
+          - -0.06597724838021778
+        Q_dot:
+          - 21675.379440630473
+          - 392576.6689847019
+          - 60769.16953666401
         "###);
 
-        let qr_solution = solve::<QR>(inputs.clone(), flow_dir).expect("should solve");
+        let (qr_solution, _) =
+            solve::<QR, _>(inputs.clone(), FlowDirection::default()).expect("should solve");
         insta::assert_yaml_snapshot!(qr_solution, @r###"
         ---
         m_dot_ck: -0.028538301905757044
@@ -330,9 +735,24 @@ mod tests {
         dTc_dt: 4548.878957390214
         dTe_dt: 8995.893667930726
         dP_dt: 417493124.9354423
+        dPdrop_dt:
+          - 0
+          - 0
+          - 0
+          - 0
+        m_dot:
+          - -0.028538301905757044
+          - -0.038613913985699
+          - -0.054884873543808
+          - -0.06597724838021779
+        Q_dot:
+          - 21675.37944063022
+          - 392576.66898470203
+          - 60769.169536663976
         "###);
 
-        let svd_solution = solve::<SvdDefault>(inputs, flow_dir).expect("should solve");
+        let (svd_solution, _) =
+            solve::<SvdDefault, _>(inputs, FlowDirection::default()).expect("should solve");
         insta::assert_yaml_snapshot!(svd_solution, @r###"
         ---
         m_dot_ck: -0.02853830190560719
@@ -345,6 +765,400 @@ mod tests {
         dTc_dt: 4548.878957390116
         dTe_dt: 8995.893667931086
         dP_dt: 417493124.9354424
+        dPdrop_dt:
+          - 0
+          - 0
+          - 0
+          - 0
+        m_dot:
+          - -0.02853830190560719
+          - -0.038613913985456536
+          - -0.05488487354363928
+          - -0.06597724838007397
+        Q_dot:
+          - 21675.379440630168
+          - 392576.6689847028
+          - 60769.16953666335
         "###);
     }
+
+    #[test]
+    fn robust_matches_lu_without_fallbacks() {
+        let inputs = read_test_inputs("ideal_gas_hydrogen.json");
+        let inputs: Inputs = serde_json::from_str(&inputs).expect("test inputs file is invalid");
+        let (lu_solution, _) =
+            solve::<LU, _>(inputs.clone(), FlowDirection::default()).expect("should solve");
+        let (robust_solution, stats) =
+            solve::<Robust, _>(inputs, FlowDirection::default()).expect("should solve");
+        assert_eq!(stats.matrix_fallbacks, 0, "LU should not need to fall back");
+        assert_eq!(lu_solution.dP_dt, robust_solution.dP_dt);
+        assert_eq!(lu_solution.Q_dot_r, robust_solution.Q_dot_r);
+    }
+
+    #[test]
+    fn solves_with_other_scalar_types() {
+        use approx::assert_relative_eq;
+
+        let inputs = read_test_inputs("ideal_gas_hydrogen.json");
+        let (expected, _) = solve::<LU, f64>(
+            serde_json::from_str(&inputs).expect("test inputs file is invalid"),
+            FlowDirection::default(),
+        )
+        .expect("should solve");
+        for decomposition in [solve::<LU, f32>, solve::<Elimination, f32>] {
+            let (actual, _) = decomposition(
+                serde_json::from_str(&inputs).expect("test inputs file is invalid"),
+                FlowDirection::default(),
+            )
+            .expect("should solve");
+            for (expected, actual) in [
+                (expected.m_dot_ck, actual.m_dot_ck),
+                (expected.m_dot_le, actual.m_dot_le),
+                (expected.Q_dot_r, actual.Q_dot_r),
+                (expected.dTc_dt, actual.dTc_dt),
+                (expected.dTe_dt, actual.dTe_dt),
+                (expected.dP_dt, actual.dP_dt),
+            ] {
+                assert_relative_eq!(expected, f64::from(actual), max_relative = 1e-3);
+            }
+        }
+    }
+
+    /// Return the inputs for a cycle state with properties of hydrogen
+    /// evaluated in `S`
+    #[allow(non_snake_case)]
+    fn hydrogen_inputs<S: Scalar>() -> Inputs<S> {
+        use super::super::inputs::{HeatExchanger, Regenerator, WorkingSpace};
+        use crate::fluid::{Fluid, IdealGas};
+
+        let fluid = IdealGas::hydrogen();
+        let c = |value: f64| -> S { na::convert(value) };
+        let pres = c(10e6);
+        let (temp_cold, temp_regen, temp_hot) = (c(300.), c(600.), c(900.));
+        let space = |vol: f64, dV_dt: f64, temp: S| WorkingSpace {
+            vol: c(vol),
+            dens: fluid.dens(temp, pres),
+            inte: fluid.inte(temp, pres),
+            enth: fluid.enth(temp, pres),
+            dd_dP_T: fluid.dd_dP_T(temp, pres),
+            dd_dT_P: fluid.dd_dT_P(temp, pres),
+            du_dP_T: fluid.du_dP_T(temp, pres),
+            du_dT_P: fluid.du_dT_P(temp, pres),
+            dV_dt: c(dV_dt),
+            Q_dot: S::zero(),
+            Q_dot_wall: S::zero(),
+            m_dot_leak: S::zero(),
+            enth_leak: S::zero(),
+        };
+        let hxr = |vol: f64, temp: S| HeatExchanger {
+            vol: c(vol),
+            dens: fluid.dens(temp, pres),
+            inte: fluid.inte(temp, pres),
+            enth: fluid.enth(temp, pres),
+            dd_dP_T: fluid.dd_dP_T(temp, pres),
+            du_dP_T: fluid.du_dP_T(temp, pres),
+            hyd_res: S::zero(),
+        };
+        Inputs {
+            pres,
+            pres_drop: [S::zero(); 4],
+            pres_buffer: pres,
+            enth_norm: c(0.5) * (fluid.enth(temp_cold, pres) + fluid.enth(temp_hot, pres)),
+            comp: space(1e-4, -2e-2, temp_cold),
+            chx: vec![hxr(4e-5, temp_cold)],
+            regen: vec![Regenerator {
+                vol: c(1e-4),
+                dens: fluid.dens(temp_regen, pres),
+                inte: fluid.inte(temp_regen, pres),
+                enth_cold: fluid.enth(temp_cold, pres),
+                enth_hot: fluid.enth(temp_hot, pres),
+                dd_dP_T: fluid.dd_dP_T(temp_regen, pres),
+                du_dP_T: fluid.du_dP_T(temp_regen, pres),
+                hyd_res: S::zero(),
+            }],
+            hhx: vec![hxr(1e-4, temp_hot)],
+            exp: space(1e-4, 3e-2, temp_hot),
+        }
+    }
+
+    #[test]
+    fn solves_from_fluid_properties_in_other_scalar_types() {
+        use approx::assert_relative_eq;
+
+        // Fluid properties and the state equations are generic, while the
+        // components and the integration over the cycle are `f64` only
+        let (expected, _) =
+            solve::<LU, f64>(hydrogen_inputs(), FlowDirection::default()).expect("should solve");
+        let (actual, _) =
+            solve::<LU, f32>(hydrogen_inputs(), FlowDirection::default()).expect("should solve");
+        for (expected, actual) in [
+            (expected.m_dot_ck, actual.m_dot_ck),
+            (expected.m_dot_le, actual.m_dot_le),
+            (expected.Q_dot_r, actual.Q_dot_r),
+            (expected.dTc_dt, actual.dTc_dt),
+            (expected.dTe_dt, actual.dTe_dt),
+            (expected.dP_dt, actual.dP_dt),
+        ] {
+            assert_relative_eq!(expected, f64::from(actual), max_relative = 1e-3);
+        }
+    }
+
+    #[test]
+    fn robust_falls_back_on_singular_matrix() {
+        // A zero row makes the system singular for both LU and QR
+        let mut a = Matrix::identity(10, 10);
+        a[(4, 4)] = 0.0;
+        let b = Vector::from_element(10, 1.0);
+        LU::solve(&a, &b).expect_err("LU should fail on a singular matrix");
+
+        let (x, fallbacks) = Robust::solve_with_fallbacks(&a, &b).expect("SVD should solve");
+        assert_eq!(fallbacks, 2, "should fall back to QR and then SVD");
+        assert_eq!(x[0], 1.0);
+        assert_eq!(x[4], 0.0, "SVD returns the minimum norm solution");
+    }
+
+    #[test]
+    fn elimination_matches_lu_for_all_flow_directions() {
+        use super::super::flow_direction::Direction;
+        use approx::assert_relative_eq;
+
+        let directions = [Direction::Positive, Direction::Negative, Direction::Unknown];
+        for filename in ["ideal_gas_hydrogen.json", "refprop_hydrogen.json"] {
+            let inputs = read_test_inputs(filename);
+            let inputs: Inputs =
+                serde_json::from_str(&inputs).expect("test inputs file is invalid");
+            let mut system = LinearSystem::default();
+            system.fill(&inputs).expect("inputs should be valid");
+            for ck in directions {
+                for kr in directions {
+                    for rl in directions {
+                        for le in directions {
+                            system.apply_flow_direction(&FlowDirection(vec![ck, kr, rl, le]));
+                            let (a, b) = (&system.a, &system.b);
+                            let lu = LU::solve(a, b).expect("LU should solve");
+                            let elimination =
+                                Elimination::solve(a, b).expect("elimination should solve");
+                            for (expected, actual) in lu.iter().zip(elimination.iter()) {
+                                assert_relative_eq!(expected, actual, max_relative = 1e-9);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn elimination_fails_on_singular_matrix() {
+        let mut a = Matrix::identity(10, 10);
+        a[(2, 1)] = 0.0; // no flow can leave the cold heat exchanger
+        let b = Vector::from_element(10, 1.0);
+        Elimination::solve(&a, &b).expect_err("elimination should fail");
+    }
+
+    #[test]
+    fn discretized_volumes_match_lumped_totals() {
+        use super::super::inputs::{HeatExchanger, Regenerator};
+        use approx::assert_relative_eq;
+
+        // Identical control volumes should reproduce the lumped model
+        fn split_hx(volumes: &[HeatExchanger], n: usize) -> Vec<HeatExchanger> {
+            let hx = HeatExchanger {
+                vol: volumes[0].vol / n as f64,
+                ..volumes[0].clone()
+            };
+            vec![hx; n]
+        }
+        fn split_regen(volumes: &[Regenerator], n: usize) -> Vec<Regenerator> {
+            let Regenerator {
+                enth_cold,
+                enth_hot,
+                ..
+            } = volumes[0];
+            let face = |i: usize| enth_cold + (enth_hot - enth_cold) * i as f64 / n as f64;
+            (0..n)
+                .map(|i| Regenerator {
+                    vol: volumes[0].vol / n as f64,
+                    enth_cold: face(i),
+                    enth_hot: face(i + 1),
+                    ..volumes[0].clone()
+                })
+                .collect()
+        }
+
+        for filename in ["ideal_gas_hydrogen.json", "refprop_hydrogen.json"] {
+            let inputs = read_test_inputs(filename);
+            let lumped: Inputs =
+                serde_json::from_str(&inputs).expect("test inputs file is invalid");
+            let discretized = Inputs {
+                chx: split_hx(&lumped.chx, 2),
+                regen: split_regen(&lumped.regen, 5),
+                hhx: split_hx(&lumped.hhx, 3),
+                ..lumped.clone()
+            };
+
+            let (expected, _) =
+                solve::<LU, _>(lumped, FlowDirection::default()).expect("should solve");
+            for (actual, _) in [
+                solve::<LU, _>(discretized.clone(), FlowDirection::default()),
+                solve::<Elimination, _>(discretized, FlowDirection::default()),
+            ]
+            .into_iter()
+            .map(|result| result.expect("should solve"))
+            {
+                assert_eq!(actual.m_dot.len(), 11);
+                assert_eq!(actual.Q_dot.len(), 10);
+                assert_relative_eq!(expected.m_dot_ck, actual.m_dot_ck, max_relative = 1e-9);
+                assert_relative_eq!(expected.m_dot_kr, actual.m_dot_kr, max_relative = 1e-9);
+                assert_relative_eq!(expected.m_dot_rl, actual.m_dot_rl, max_relative = 1e-9);
+                assert_relative_eq!(expected.m_dot_le, actual.m_dot_le, max_relative = 1e-9);
+                assert_relative_eq!(expected.Q_dot_k, actual.Q_dot_k, max_relative = 1e-9);
+                assert_relative_eq!(expected.Q_dot_r, actual.Q_dot_r, max_relative = 1e-9);
+                assert_relative_eq!(expected.Q_dot_l, actual.Q_dot_l, max_relative = 1e-9);
+                assert_relative_eq!(expected.dTc_dt, actual.dTc_dt, max_relative = 1e-9);
+                assert_relative_eq!(expected.dTe_dt, actual.dTe_dt, max_relative = 1e-9);
+                assert_relative_eq!(expected.dP_dt, actual.dP_dt, max_relative = 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn reused_system_matches_new_system() {
+        use super::super::flow_direction::Direction;
+
+        let lumped: Inputs = serde_json::from_str(&read_test_inputs("ideal_gas_hydrogen.json"))
+            .expect("test inputs file is invalid");
+        let discretized = Inputs {
+            regen: vec![lumped.regen[0].clone(); 3],
+            ..lumped.clone()
+        };
+        let reversed = FlowDirection(vec![Direction::Negative; 4]);
+        let mut system = LinearSystem::default();
+        for (inputs, flow_dir) in [
+            (lumped.clone(), reversed.clone()),
+            (discretized, FlowDirection::default()),
+            (lumped.clone(), FlowDirection::default()),
+            (lumped, reversed),
+        ] {
+            let (expected, _) =
+                solve::<LU, _>(inputs.clone(), flow_dir.clone()).expect("should solve");
+            let (actual, _) = system.solve::<LU>(&inputs, flow_dir).expect("should solve");
+            assert_eq!(expected.m_dot, actual.m_dot);
+            assert_eq!(expected.Q_dot, actual.Q_dot);
+            assert_eq!(expected.dP_dt, actual.dP_dt);
+        }
+    }
+
+    #[test]
+    fn seal_leakage_removes_mass() {
+        use approx::assert_relative_eq;
+
+        use super::super::inputs::WorkingSpace;
+
+        let inputs = read_test_inputs("ideal_gas_hydrogen.json");
+        let inputs: Inputs = serde_json::from_str(&inputs).expect("test inputs file is invalid");
+        let m_dot_leak = 0.01;
+        let inputs = Inputs {
+            comp: WorkingSpace {
+                m_dot_leak,
+                enth_leak: inputs.comp.enth,
+                ..inputs.comp.clone()
+            },
+            ..inputs
+        };
+        let (solution, _) =
+            solve::<LU, _>(inputs.clone(), FlowDirection::default()).expect("should solve");
+
+        // The mass in every control volume changes by the leakage alone
+        let ws_mass_change = |ws: &WorkingSpace, temp_deriv: f64| {
+            ws.vol * (ws.dd_dT_P * temp_deriv + ws.dd_dP_T * solution.dP_dt) + ws.dens * ws.dV_dt
+        };
+        let hx_mass_change: f64 = inputs
+            .chx
+            .iter()
+            .chain(&inputs.hhx)
+            .map(|hx| hx.vol * hx.dd_dP_T * solution.dP_dt)
+            .chain(
+                inputs
+                    .regen
+                    .iter()
+                    .map(|regen| regen.vol * regen.dd_dP_T * solution.dP_dt),
+            )
+            .sum();
+        let total = ws_mass_change(&inputs.comp, solution.dTc_dt)
+            + hx_mass_change
+            + ws_mass_change(&inputs.exp, solution.dTe_dt);
+        assert_relative_eq!(total, -m_dot_leak, max_relative = 1e-9);
+    }
+
+    #[test]
+    fn momentum_sets_flows_between_pressures() {
+        use approx::assert_relative_eq;
+
+        use super::super::inputs::{HeatExchanger, Regenerator, WorkingSpace};
+
+        let inputs = read_test_inputs("ideal_gas_hydrogen.json");
+        let inputs: Inputs = serde_json::from_str(&inputs).expect("test inputs file is invalid");
+        let inputs = Inputs {
+            pres_drop: [0.0, 2e3, 5e3, 5e3],
+            chx: vec![HeatExchanger {
+                hyd_res: 1e6,
+                ..inputs.chx[0].clone()
+            }],
+            regen: vec![
+                Regenerator {
+                    hyd_res: 4e6,
+                    ..inputs.regen[0].clone()
+                };
+                2
+            ],
+            ..inputs
+        };
+        solve::<Elimination, _>(inputs.clone(), FlowDirection::default())
+            .expect_err("elimination only supports a uniform pressure");
+        let (solution, _) =
+            solve::<LU, _>(inputs.clone(), FlowDirection::default()).expect("should solve");
+
+        // Each flow between two pressures follows the drop between them, and
+        // the hot heat exchanger without resistance shares the pressure of
+        // the expansion space
+        let half_res = |hyd_res: f64, dens: f64| 0.5 * hyd_res / dens;
+        let chx_res = half_res(1e6, inputs.chx[0].dens);
+        let regen_res = 2.0 * half_res(4e6, inputs.regen[0].dens);
+        assert_eq!(solution.m_dot_ck, 0.0);
+        assert_relative_eq!(solution.m_dot_kr, 2e3 / (chx_res + regen_res));
+        assert_relative_eq!(solution.m_dot_rl, 3e3 / regen_res);
+        assert_eq!(solution.dPdrop_dt[2], solution.dPdrop_dt[3]);
+
+        // The mass in every control volume changes with its own pressure
+        let pres_deriv = |drop: f64| solution.dP_dt - drop;
+        let mass_change = |ws: &WorkingSpace, temp_deriv: f64, pres_deriv: f64| {
+            ws.vol * (ws.dd_dT_P * temp_deriv + ws.dd_dP_T * pres_deriv) + ws.dens * ws.dV_dt
+        };
+        let comp = mass_change(&inputs.comp, solution.dTc_dt, solution.dP_dt);
+        let chx = inputs.chx[0].vol * inputs.chx[0].dd_dP_T * pres_deriv(solution.dPdrop_dt[0]);
+        let regen: f64 = (inputs.regen.iter())
+            .map(|regen| regen.vol * regen.dd_dP_T * pres_deriv(solution.dPdrop_dt[1]))
+            .sum();
+        let hhx = inputs.hhx[0].vol * inputs.hhx[0].dd_dP_T * pres_deriv(solution.dPdrop_dt[2]);
+        let exp = mass_change(
+            &inputs.exp,
+            solution.dTe_dt,
+            pres_deriv(solution.dPdrop_dt[3]),
+        );
+        assert_relative_eq!(comp, -solution.m_dot_ck, epsilon = 1e-12);
+        assert_relative_eq!(chx + regen + hhx, solution.m_dot_ck - solution.m_dot_le);
+        assert_relative_eq!(exp, solution.m_dot_le);
+    }
+
+    #[test]
+    fn requires_a_volume_for_each_heat_exchanger() {
+        let inputs = read_test_inputs("ideal_gas_hydrogen.json");
+        let inputs: Inputs = serde_json::from_str(&inputs).expect("test inputs file is invalid");
+        let inputs = Inputs {
+            regen: Vec::new(),
+            ..inputs
+        };
+        solve::<LU, _>(inputs, FlowDirection::default()).expect_err("should not solve");
+    }
 }
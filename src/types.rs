@@ -1,14 +1,58 @@
+use std::{
+    ops::AddAssign,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use anyhow::{ensure, Context};
 use serde::Deserialize;
 
+use crate::engine::StateSnapshot;
+
 pub const DEFAULT_MAX_ITERS: u32 = 20;
 
+/// Tolerance on the free piston motion when none is configured, in m/s and
+/// relative to the peak piston velocity
+pub const DEFAULT_MOTION_TOL: ConvergenceTolerance = ConvergenceTolerance {
+    abs: 1e-4,
+    rel: 1e-4,
+};
+
 /// An error that can occur during an engine run
 /// TODO: <https://github.com/isentropic-dev/sett-rs/issues/64>
 /// TODO: <https://github.com/isentropic-dev/sett-rs/issues/65>
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub enum RunError {
     InnerLoop,
     OuterLoop,
+    Interrupted(Box<PartialRun>),
+    /// The config describes an engine or settings that cannot be run
+    InvalidConfig(String),
+}
+
+/// The reason a run was interrupted before it converged
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interruption {
+    /// The run was cancelled by a `CancelToken` or an observer
+    Cancelled,
+    WallTime,
+    RhsEvals,
+    Steps,
+}
+
+/// The progress made by a run before it was interrupted
+///
+/// The snapshot holds the latest engine state, which has not converged but
+/// can still be used to warm start another run.
+#[derive(Debug, Clone)]
+pub struct PartialRun {
+    pub reason: Interruption,
+    pub state: StateSnapshot,
+    pub stats: SolverStats,
+    pub convergence: ConvergenceReport,
 }
 
 /// Inputs to an engine run
@@ -20,19 +64,42 @@ pub struct RunInputs {
 }
 
 /// Settings for an engine run
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub struct RunSettings {
     pub resolution: u32,
+    pub grid: OutputGrid,
+    pub discretization: Discretization,
+    pub pressure_model: PressureModel,
+    pub properties: PropertyEvaluation,
     pub loop_tol: LoopTolerance,
     pub ode_tol: OdeTolerance,
     pub max_iters: MaxIters,
+    pub steady_state: SteadyStateMethod,
+    pub budget: Budget,
+    pub cancel: Option<CancelToken>,
 }
 
 /// Tolerances related to the two iteration loops
+///
+/// `motion` is the tolerance on free piston motion at the end of each cycle
+/// in the inner loop, which is only checked with free pistons.
 #[derive(Debug, Clone, Copy)]
 pub struct LoopTolerance {
     pub inner: ConvergenceTolerance,
-    pub outer: ConvergenceTolerance,
+    pub motion: ConvergenceTolerance,
+    pub outer: OuterTolerance,
+}
+
+/// Tolerances for the quantities checked by the outer loop
+///
+/// Temperatures are always checked.  Pressure, mass flow rates, and heat flow
+/// rates are only checked when they have a tolerance.
+#[derive(Debug, Clone, Copy)]
+pub struct OuterTolerance {
+    pub temp: ConvergenceTolerance,
+    pub pres: Option<ConvergenceTolerance>,
+    pub mass_flow: Option<ConvergenceTolerance>,
+    pub heat_flow: Option<ConvergenceTolerance>,
 }
 
 /// Tolerances used by the ODE integrator
@@ -56,6 +123,220 @@ pub struct MaxIters {
     pub outer: usize,
 }
 
+/// Limits on the effort spent in an engine run
+///
+/// Each limit applies to the whole run, across every inner and outer loop
+/// iteration.  Steps include both accepted and rejected integration steps.
+/// A run without any limits stops only when it runs out of iterations.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Budget {
+    pub wall_time: Option<Duration>,
+    pub rhs_evals: Option<usize>,
+    pub steps: Option<usize>,
+}
+
+/// Lumped thermal mass of a heat exchanger's metal
+///
+/// The metal exchanges heat with the working fluid and, through
+/// `conductance`, with the sink or source.  A conductance of zero isolates
+/// the metal from the sink or source.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ThermalMass {
+    /// Heat capacity of the metal (J/K)
+    pub capacitance: f64,
+    /// Thermal conductance between the metal and the sink or source (W/K)
+    pub conductance: f64,
+}
+
+/// Lumped thermal masses used by a transient run
+///
+/// The regenerator matrix only exchanges heat with the working fluid, so it
+/// only has a heat capacity (J/K).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ThermalMasses {
+    pub chx: ThermalMass,
+    pub regen: f64,
+    pub hhx: ThermalMass,
+}
+
+/// Metal temperatures in a transient run (K)
+///
+/// The regenerator temperature is the average temperature of its matrix.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MetalTemperatures {
+    pub chx: f64,
+    pub regen: f64,
+    pub hhx: f64,
+}
+
+/// A shared flag used to cancel a running engine, such as from another thread
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+/// How the inner loop finds cyclic steady state
+///
+/// `TimeMarching` integrates the state equations over whole cycles, starting
+/// each cycle from the conditions at the end of the last, until the
+/// conditions repeat.  `HarmonicBalance` represents the conditions as Fourier
+/// series truncated after `harmonics` harmonics and solves the state equations
+/// at `2 * harmonics + 1` collocation points with Newton's method.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SteadyStateMethod {
+    #[default]
+    TimeMarching,
+    HarmonicBalance {
+        harmonics: u32,
+    },
+}
+
+/// The matrix decomposition used to solve the state equations
+///
+/// The `Robust` option starts with an LU decomposition and falls back to QR,
+/// and then to SVD, whenever the system is reported as singular.  The
+/// `Elimination` option solves the state equations in closed form using their
+/// known sparsity, which is the fastest choice for large sweeps.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Decomposition {
+    #[default]
+    Lu,
+    Qr,
+    Svd,
+    Robust,
+    Elimination,
+}
+
+/// The points in the cycle where values are reported
+///
+/// `Uniform` uses evenly spaced points in time, with the number of points set
+/// by the run resolution.  `DegreesPerStep` uses a fixed crank angle step,
+/// always ending at 360 degrees.  `RefinedAtFlowReversal` starts from the
+/// uniform grid and adds `extra_points` evenly spaced points to every interval
+/// where any mass flow rate changes direction.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputGrid {
+    #[default]
+    Uniform,
+    DegreesPerStep(f64),
+    RefinedAtFlowReversal {
+        extra_points: u32,
+    },
+}
+
+/// The number of control volumes used for each heat exchanger
+///
+/// The heat exchangers and the regenerator are each split into this many
+/// equally sized control volumes in the state equations.  A single volume for
+/// each is the lumped model.
+///
+/// More volumes resolve how the mass and heat flows vary along each heat
+/// exchanger, but not its temperatures.  The heat exchangers are isothermal,
+/// so their volumes are identical, and the regenerator temperature is imposed
+/// as a linear profile between its cold and hot ends rather than solved for.
+/// The control volumes of each heat exchanger share a pressure, which is set
+/// by the `PressureModel`, so pressure is not resolved along the heat
+/// exchangers.
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct Discretization {
+    pub chx: u32,
+    pub regen: u32,
+    pub hhx: u32,
+}
+
+/// How heat exchanger pressure drops are accounted for
+///
+/// `Uniform` uses a single pressure in every control volume while integrating
+/// the state equations, and heat exchanger pressure drops are only applied
+/// afterwards when calculating performance.
+///
+/// `Momentum` gives the compression space, each heat exchanger, the
+/// regenerator, and the expansion space a pressure of their own, which are
+/// all integrated with the state equations.  Neighbouring pressures are
+/// linked by a momentum balance on the flow between them, in which the
+/// pressure difference drives the flow against the hydraulic resistance
+/// from the middle of one to the middle of the other.  A component without
+/// hydraulic resistance shares the pressure of its neighbours.  Inertance
+/// is neglected, so the flow between two pressures follows their difference
+/// at every instant.  The control volumes of a discretized heat exchanger
+/// share its pressure.  The elimination solver only supports `Uniform`.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PressureModel {
+    #[default]
+    Uniform,
+    Momentum,
+}
+
+/// How heat exchanger fluid properties are evaluated during a run
+///
+/// `Tabulated` evaluates properties at pressure nodes spaced at 1/32 of the
+/// average cycle pressure and interpolates linearly between them, which is
+/// exact for an ideal gas.  Only pressures up to twice the average are
+/// tabulated, and the rest are evaluated directly.  `Direct` evaluates the
+/// fluid at every pressure, which is slower but avoids the interpolation
+/// error for a real gas.
+///
+/// The table matters most for a real gas whose properties are costly to
+/// evaluate, but the REFPROP fluid is still a placeholder, so the speedup has
+/// only been measured with `IdealGas`, including one slowed down to stand in
+/// for costly property calls.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PropertyEvaluation {
+    #[default]
+    Tabulated,
+    Direct,
+}
+
+/// A quantity checked for convergence by the outer loop
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quantity {
+    Temperature,
+    Pressure,
+    MassFlow,
+    HeatFlow,
+}
+
+/// The quantities that had not converged after each outer loop iteration
+///
+/// The final iteration of a converged run has no unconverged quantities.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConvergenceReport {
+    pub unconverged: Vec<Vec<Quantity>>,
+}
+
+/// A real number type that fluid properties and the state equations can be
+/// evaluated with
+///
+/// This is implemented for `f64`, which is used for every engine run, and
+/// for any other `RealField`, such as `f32`.
+///
+/// Only `Fluid` properties and a single solution of the state equations,
+/// from `Inputs` to `Solution`, are generic.  The components, the integration
+/// over the cycle and the `Engine` are `f64` only.
+pub trait Scalar: na::RealField + Copy + Default {}
+
+impl<S: na::RealField + Copy + Default> Scalar for S {}
+
+/// Statistics collected while solving the state equations
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SolverStats {
+    /// Number of times a fallback matrix decomposition was needed
+    pub matrix_fallbacks: usize,
+    /// Number of times the flow directions had to be updated and the state
+    /// equations solved again
+    pub flow_updates: usize,
+    /// Number of times the state equations were evaluated by the integrator
+    pub rhs_evals: usize,
+    /// Number of integration steps that were accepted
+    pub accepted_steps: usize,
+    /// Number of integration steps that were rejected
+    pub rejected_steps: usize,
+}
+
 /// Parasitic power loss in a component
 ///
 /// Each type of power has units of watts (W).
@@ -78,11 +359,31 @@ pub struct HeatExchanger {
     pub Q_dot: f64,
 }
 
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
 pub struct SolverConfig {
     pub inner_loop: InnerLoopConfig,
     pub outer_loop: OuterLoopConfig,
     pub ode: OdeConfig,
+    #[serde(default)]
+    pub decomposition: Decomposition,
+    #[serde(default)]
+    pub discretization: Discretization,
+    #[serde(default)]
+    pub pressure_model: PressureModel,
+    #[serde(default)]
+    pub properties: PropertyEvaluation,
+    #[serde(default)]
+    pub steady_state: SteadyStateMethod,
+    #[serde(default)]
+    pub budget: BudgetConfig,
+}
+
+/// Limits on the effort spent in a run, with the wall time in seconds
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+pub struct BudgetConfig {
+    pub wall_time: Option<f64>,
+    pub rhs_evals: Option<usize>,
+    pub steps: Option<usize>,
 }
 
 #[derive(Debug, Deserialize, PartialEq)]
@@ -101,31 +402,38 @@ pub enum LegacyOdeSolver {
     Ode45,
 }
 
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
 pub struct InnerLoopConfig {
     pub tolerance: ToleranceConfig,
     pub max_iterations: u32,
+    #[serde(default)]
+    pub motion: Option<ToleranceConfig>,
 }
 
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
 pub struct OuterLoopConfig {
     pub tolerance: ToleranceConfig,
     pub max_iterations: u32,
+    pub pressure: Option<ToleranceConfig>,
+    pub mass_flow: Option<ToleranceConfig>,
+    pub heat_flow: Option<ToleranceConfig>,
 }
 
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
 pub struct OdeConfig {
     pub tolerance: ToleranceConfig,
     pub num_timesteps: u32,
+    #[serde(default)]
+    pub output_grid: OutputGrid,
 }
 
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
 pub struct ToleranceConfig {
     pub abs: f64,
     pub rel: f64,
 }
 
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
 pub struct ConditionsConfig {
     pub temp_sink: f64,
     pub temp_source: f64,
@@ -140,7 +448,7 @@ pub struct LegacyConditionsConfig {
     pub P_0: f64,
 }
 
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
 pub enum Material {
     SS304,
     Multimet,
@@ -162,13 +470,104 @@ impl ConvergenceTolerance {
 
     /// Return `true` if the change from `old` to `new` is sufficiently small
     #[must_use]
+    #[allow(clippy::float_cmp)]
     pub fn is_converged(&self, old: f64, new: f64) -> bool {
+        if old == new {
+            return true; // an unchanged zero would give a relative change of NaN
+        }
         let abs_change = new - old;
         let rel_change = abs_change / old;
         abs_change.abs() < self.abs && rel_change.abs() < self.rel
     }
 }
 
+impl Budget {
+    /// Return the first limit exceeded by `stats` after `elapsed` time
+    #[must_use]
+    pub fn exceeded(&self, stats: SolverStats, elapsed: Duration) -> Option<Interruption> {
+        let over = |limit: Option<usize>, used: usize| limit.is_some_and(|limit| used > limit);
+        if self.wall_time.is_some_and(|limit| elapsed > limit) {
+            Some(Interruption::WallTime)
+        } else if over(self.rhs_evals, stats.rhs_evals) {
+            Some(Interruption::RhsEvals)
+        } else if over(self.steps, stats.accepted_steps + stats.rejected_steps) {
+            Some(Interruption::Steps)
+        } else {
+            None
+        }
+    }
+}
+
+impl CancelToken {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Request that every run using this token stops
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Return `true` if the token has been cancelled
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+impl From<ConvergenceTolerance> for OuterTolerance {
+    /// Check only temperatures for convergence
+    fn from(temp: ConvergenceTolerance) -> Self {
+        Self {
+            temp,
+            pres: None,
+            mass_flow: None,
+            heat_flow: None,
+        }
+    }
+}
+
+impl ConvergenceReport {
+    /// Return the number of outer loop iterations
+    #[must_use]
+    pub fn iterations(&self) -> usize {
+        self.unconverged.len()
+    }
+
+    /// Return the quantities that were the last to converge
+    ///
+    /// These are the quantities that limited the outer loop iteration.
+    #[must_use]
+    pub fn limiting(&self) -> &[Quantity] {
+        self.unconverged
+            .iter()
+            .rev()
+            .find(|quantities| !quantities.is_empty())
+            .map_or(&[], Vec::as_slice)
+    }
+}
+
+impl Default for Discretization {
+    fn default() -> Self {
+        Self {
+            chx: 1,
+            regen: 1,
+            hhx: 1,
+        }
+    }
+}
+
+impl AddAssign for SolverStats {
+    fn add_assign(&mut self, other: Self) {
+        self.matrix_fallbacks += other.matrix_fallbacks;
+        self.flow_updates += other.flow_updates;
+        self.rhs_evals += other.rhs_evals;
+        self.accepted_steps += other.accepted_steps;
+        self.rejected_steps += other.rejected_steps;
+    }
+}
+
 impl From<ToleranceConfig> for ConvergenceTolerance {
     fn from(config: ToleranceConfig) -> Self {
         Self {
@@ -178,13 +577,33 @@ impl From<ToleranceConfig> for ConvergenceTolerance {
     }
 }
 
-impl From<SolverConfig> for RunSettings {
-    fn from(config: SolverConfig) -> Self {
-        Self {
+impl TryFrom<SolverConfig> for RunSettings {
+    type Error = anyhow::Error;
+
+    fn try_from(config: SolverConfig) -> Result<Self, Self::Error> {
+        let Discretization { chx, regen, hhx } = config.discretization;
+        ensure!(
+            chx > 0 && regen > 0 && hhx > 0,
+            "every heat exchanger needs at least one control volume, got {chx}, {regen}, and {hhx}"
+        );
+        Ok(Self {
             resolution: config.ode.num_timesteps,
+            grid: config.ode.output_grid,
+            discretization: config.discretization,
+            pressure_model: config.pressure_model,
+            properties: config.properties,
             loop_tol: LoopTolerance {
                 inner: config.inner_loop.tolerance.into(),
-                outer: config.outer_loop.tolerance.into(),
+                motion: config
+                    .inner_loop
+                    .motion
+                    .map_or(DEFAULT_MOTION_TOL, Into::into),
+                outer: OuterTolerance {
+                    temp: config.outer_loop.tolerance.into(),
+                    pres: config.outer_loop.pressure.map(Into::into),
+                    mass_flow: config.outer_loop.mass_flow.map(Into::into),
+                    heat_flow: config.outer_loop.heat_flow.map(Into::into),
+                },
             },
             ode_tol: OdeTolerance {
                 abs: config.ode.tolerance.abs,
@@ -194,7 +613,29 @@ impl From<SolverConfig> for RunSettings {
                 inner: config.inner_loop.max_iterations as usize,
                 outer: config.outer_loop.max_iterations as usize,
             },
-        }
+            steady_state: config.steady_state,
+            budget: config.budget.try_into()?,
+            cancel: None,
+        })
+    }
+}
+
+impl TryFrom<BudgetConfig> for Budget {
+    type Error = anyhow::Error;
+
+    fn try_from(config: BudgetConfig) -> Result<Self, Self::Error> {
+        let wall_time = config
+            .wall_time
+            .map(|secs| {
+                Duration::try_from_secs_f64(secs)
+                    .with_context(|| format!("invalid wall time budget of {secs} s"))
+            })
+            .transpose()?;
+        Ok(Self {
+            wall_time,
+            rhs_evals: config.rhs_evals,
+            steps: config.steps,
+        })
     }
 }
 
@@ -204,15 +645,26 @@ impl From<LegacySolverConfig> for SolverConfig {
             inner_loop: InnerLoopConfig {
                 tolerance: config.inner_loop_tolerance,
                 max_iterations: DEFAULT_MAX_ITERS,
+                motion: None,
             },
             outer_loop: OuterLoopConfig {
                 tolerance: config.outer_loop_tolerance,
                 max_iterations: DEFAULT_MAX_ITERS,
+                pressure: None,
+                mass_flow: None,
+                heat_flow: None,
             },
             ode: OdeConfig {
                 tolerance: config.ode_tolerance,
                 num_timesteps: config.time_resolution,
+                output_grid: OutputGrid::default(),
             },
+            decomposition: Decomposition::default(),
+            discretization: Discretization::default(),
+            pressure_model: PressureModel::default(),
+            properties: PropertyEvaluation::default(),
+            steady_state: SteadyStateMethod::default(),
+            budget: BudgetConfig::default(),
         }
     }
 }
@@ -1,16 +1,29 @@
-mod gpu3;
-mod mod2;
-mod rhombic_drive;
+pub mod appendix_gap;
+pub mod crank_drive;
+pub mod free_piston;
+pub mod gpu3;
+pub mod mod2;
+pub mod rhombic_drive;
 pub mod sinusoidal_drive;
+pub mod volume_profile;
+pub mod wall_heat;
 
 // Export all available working spaces components
+pub use appendix_gap::AppendixGap;
+pub use crank_drive::CrankDrive;
+pub use free_piston::FreePiston;
 pub use gpu3::GPU3;
 pub use mod2::Mod2;
 pub use rhombic_drive::RhombicDrive;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 pub use sinusoidal_drive::SinusoidalDrive;
+pub use volume_profile::VolumeProfile;
+pub use wall_heat::WallHeat;
 
-use crate::{engine::Pressure, types::ParasiticPower};
+use crate::{
+    engine::Pressure,
+    types::{ParasiticPower, Scalar},
+};
 
 pub trait WorkingSpaces {
     /// Returns the frequency (Hz) of the engine
@@ -24,6 +37,159 @@ pub trait WorkingSpaces {
 
     /// Returns the parasitic power associated with the working spaces
     fn parasitics(&self, state: &State) -> Parasitics;
+
+    /// Returns the piston `Dynamics` when the pistons are driven by the gas
+    ///
+    /// When there are dynamics, the volumes follow from the piston `Motion`
+    /// and `frequency` is only an estimate that the operating frequency is
+    /// found from.  Working spaces with prescribed volumes have no dynamics.
+    fn dynamics(&self, _state: &State) -> Option<Box<dyn Dynamics>> {
+        None
+    }
+
+    /// Returns the `Kinematics` of the drive when its linkages are known
+    ///
+    /// The shaft torque is found from the volumes alone, so only the forces
+    /// on the pistons and rods need the kinematics of the drive.
+    fn kinematics(&self, _state: &State) -> Option<Box<dyn Kinematics>> {
+        None
+    }
+
+    /// Returns the `WallConductance` when wall heat transfer varies over the
+    /// cycle
+    ///
+    /// Heat transferred through a `WallConductance` is part of the gas energy
+    /// balance, so working spaces that have one should not also return a
+    /// finite `thermal_resistance`.  The drives with a wall heat correlation
+    /// treat their spaces as adiabatic when it is configured, which replaces
+    /// their constant resistances.
+    fn wall_conductance(&self, _state: &State) -> Option<Box<dyn WallConductance>> {
+        None
+    }
+}
+
+/// Heat transfer between the gas and the walls of the working spaces
+pub trait WallConductance {
+    /// Returns the conductances (W/K) between the gas and the walls of the
+    /// compression and expansion spaces at their instantaneous conditions
+    fn conductances(&self, comp: SpaceConditions, exp: SpaceConditions) -> (f64, f64);
+}
+
+/// Instantaneous conditions in a working space
+///
+/// `vol` -- volume (m^3) of the space
+/// `dV_dt` -- rate of change (m^3/s) of the volume
+/// `dens` -- density (kg/m^3) of the gas
+/// `cp` -- specific heat (J/kg-K) of the gas
+#[allow(non_snake_case)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpaceConditions {
+    pub vol: f64,
+    pub dV_dt: f64,
+    pub dens: f64,
+    pub cp: f64,
+}
+
+/// The linkages between the pistons and the crank of a crank-driven engine
+pub trait Kinematics {
+    /// Returns the gas forces at crank angle `theta` (rad) when the spaces
+    /// are at `pres`
+    fn forces(&self, theta: f64, pres: SpacePressures) -> PistonForces;
+}
+
+/// Pressures (Pa) acting on the pistons
+///
+/// `buffer` is the pressure behind the pistons, which fills the rest of the
+/// engine housing.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpacePressures {
+    pub comp: f64,
+    pub exp: f64,
+    pub buffer: f64,
+}
+
+/// Gas forces (N) on the pistons and the rods that drive them
+///
+/// `piston` -- net gas force on the power piston
+/// `displacer` -- net gas force on the displacer
+/// `piston_rod` -- load in each connecting rod of the power piston
+/// `displacer_rod` -- load in each connecting rod of the displacer
+///
+/// Piston forces are positive toward the expansion space and rod loads are
+/// positive in compression.  The inertia of the pistons and rods is not
+/// included.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PistonForces {
+    pub piston: f64,
+    pub displacer: f64,
+    pub piston_rod: f64,
+    pub displacer_rod: f64,
+}
+
+/// The motion of free pistons, which are driven by the gas instead of a crank
+pub trait Dynamics {
+    /// Returns the volumes for the piston `motion`
+    fn volumes(&self, motion: Motion) -> (CompVolume, ExpVolume);
+
+    /// Returns the time derivatives of `motion` when the compression and
+    /// expansion spaces are at `pres_comp` and `pres_exp` (Pa)
+    fn derivatives(&self, motion: Motion, pres_comp: f64, pres_exp: f64) -> Motion;
+
+    /// Returns the motion at the start of a cycle
+    ///
+    /// A cycle starts with the piston at mid-stroke and moving toward the
+    /// expansion space, and the period is the time until it returns there.
+    fn initial_motion(&self) -> Motion;
+
+    /// Returns the power (W) of the generator driven by the pistons, or
+    /// `None` if there is no generator
+    fn electrical_power(&self, _motion: Motion) -> Option<ElectricalPower> {
+        None
+    }
+}
+
+/// Electrical power (W) of a generator driven by free pistons
+///
+/// `generated` -- power taken from the pistons by the generator
+/// `copper` -- resistive losses in the generator's coil
+///
+/// The power delivered to the load is what remains of `generated` after the
+/// copper losses, once averaged over a cycle.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ElectricalPower {
+    pub generated: f64,
+    pub copper: f64,
+}
+
+/// Positions and velocities of free pistons
+///
+/// `x_p` -- position (m) of the power piston
+/// `v_p` -- velocity (m/s) of the power piston
+/// `x_d` -- position (m) of the displacer
+/// `v_d` -- velocity (m/s) of the displacer
+/// `current` -- current (A) in the coil of a linear alternator
+///
+/// Positions are measured from mid-stroke and are positive toward the
+/// expansion space.  Working spaces with prescribed volumes have no motion.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Motion<S: Scalar = f64> {
+    pub x_p: S,
+    pub v_p: S,
+    pub x_d: S,
+    pub v_d: S,
+    pub current: S,
+}
+
+impl<S: Scalar> Default for Motion<S> {
+    fn default() -> Self {
+        Self {
+            x_p: S::zero(),
+            v_p: S::zero(),
+            x_d: S::zero(),
+            v_d: S::zero(),
+            current: S::zero(),
+        }
+    }
 }
 
 /// Compression space volume (m^3) and its derivative (m^3/s)
@@ -68,19 +234,49 @@ pub struct Parasitics {
 }
 
 /// Information available to a ws component for calculating its parameters
+///
+/// `temp_chx` and `temp_hhx` are the heat exchanger temperatures (K) at
+/// either end of the working spaces, while `dens` (kg/m^3) and `cp`
+/// (J/kg-K) are gas properties at the mean of those temperatures and the
+/// mean pressure.
 pub struct State {
     pub pres: Pressure,
+    pub temp_chx: f64,
+    pub temp_hhx: f64,
+    pub dens: f64,
+    pub cp: f64,
 }
 
 #[allow(non_snake_case)]
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum Config {
     Sinusoidal(sinusoidal_drive::Config),
     Rhombic(rhombic_drive::Config),
     GPU3(gpu3::Config),
     Mod2(mod2::Config),
+    FreePiston(free_piston::Config),
+    Profile(volume_profile::Config),
+    Crank(crank_drive::Config),
+}
+
+impl Config {
+    /// Returns the frequency (Hz) input of the working spaces
+    ///
+    /// `None` is returned for free pistons, which find their own frequency.
+    pub fn frequency_mut(&mut self) -> Option<&mut f64> {
+        match self {
+            Config::Sinusoidal(config) => Some(&mut config.frequency),
+            Config::Rhombic(config) => Some(&mut config.frequency),
+            Config::GPU3(config) => Some(&mut config.frequency),
+            Config::Mod2(config) => Some(&mut config.frequency),
+            Config::Profile(config) => Some(&mut config.frequency),
+            Config::Crank(config) => Some(&mut config.frequency),
+            Config::FreePiston(_) => None,
+        }
+    }
 }
+
 #[allow(non_snake_case)]
 #[derive(Debug, Deserialize, PartialEq)]
 #[serde(tag = "model", content = "params")]
@@ -102,3 +298,39 @@ impl LegacyConfig {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{sinusoidal_drive::Geometry, *};
+
+    /// Returns a state at a constant `pres` (Pa) between heat exchangers at
+    /// 300 and 900 K, for the ws tests that need no gas properties
+    pub(super) fn state(pres: f64) -> State {
+        State {
+            pres: Pressure::constant(pres),
+            temp_chx: 300.0,
+            temp_hhx: 900.0,
+            dens: 0.0,
+            cp: 0.0,
+        }
+    }
+
+    /// Returns a lossless sinusoidal drive whose expansion space leads by 90
+    /// degrees
+    pub(super) fn sinusoidal_drive(
+        frequency: f64,
+        comp: Geometry,
+        exp: Geometry,
+    ) -> SinusoidalDrive {
+        SinusoidalDrive {
+            frequency,
+            phase_angle: 90.0,
+            comp_geometry: comp,
+            exp_geometry: exp,
+            thermal_resistance: ThermalResistance::default(),
+            parasitics: Parasitics::default(),
+            appendix_gap: None,
+            wall_heat: None,
+        }
+    }
+}
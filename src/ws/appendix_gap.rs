@@ -0,0 +1,135 @@
+use std::f64::consts::PI;
+
+use serde::Deserialize;
+
+use super::State;
+
+/// The annular gap between a displacer and its cylinder
+///
+/// The gap connects the expansion space to the cold end of the displacer,
+/// and it carries heat from the hot end to the cold end in two ways:
+///
+/// * Shuttle heat transfer -- the displacer wall stores heat as it moves
+///   toward the hot end and gives it up to the cylinder as it moves back.
+/// * Enthalpy pumping -- gas that fills the gap from the expansion space as
+///   the pressure rises leaves again after cooling to the mean temperature of
+///   the gap.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AppendixGap {
+    pub diameter: f64,
+    pub width: f64,
+    pub length: f64,
+    pub stroke: f64,
+    pub conductivity: f64,
+}
+
+/// Configuration for an appendix gap
+///
+/// The displacer diameter and stroke are found from the working spaces, so
+/// only the gap width, the gap length (both in m), and the thermal
+/// conductivity of the gas (W/m-K) are configured.  A sinusoidal drive has
+/// no geometry to find them from, so it also needs the displacer `stroke`
+/// (m), from which its diameter follows.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct Config {
+    pub width: f64,
+    pub length: f64,
+    #[serde(default)]
+    pub stroke: Option<f64>,
+    pub k_gas: f64,
+}
+
+impl AppendixGap {
+    /// Create an appendix gap around a displacer of `diameter` (m) that moves
+    /// through `stroke` (m)
+    #[must_use]
+    pub fn new(diameter: f64, stroke: f64, config: &Config) -> Self {
+        Self {
+            diameter,
+            width: config.width,
+            length: config.length,
+            stroke,
+            conductivity: config.k_gas,
+        }
+    }
+
+    /// Returns the shuttle heat transfer (W) from the hot end to the cold end
+    #[must_use]
+    pub fn shuttle_loss(&self, state: &State) -> f64 {
+        PI * self.stroke.powi(2) * self.conductivity * self.diameter
+            / (8. * self.width * self.length)
+            * (state.temp_hhx - state.temp_chx)
+    }
+
+    /// Returns the heat (W) carried from the hot end by gas pumped in and out
+    /// of the gap at `frequency` (Hz)
+    ///
+    /// The gas in the gap stays near the mean temperature of the heat
+    /// exchangers, so the mass pumped each cycle follows the pressure swing.
+    #[must_use]
+    pub fn enthalpy_pumping_loss(&self, state: &State, frequency: f64) -> f64 {
+        let volume = PI * self.diameter * self.width * self.length;
+        let mass = state.dens * volume * (state.pres.max - state.pres.min) / state.pres.avg;
+        frequency * mass * state.cp * 0.5 * (state.temp_hhx - state.temp_chx)
+    }
+
+    /// Returns the total heat (W) carried through the gap at `frequency` (Hz)
+    #[must_use]
+    pub fn loss(&self, state: &State, frequency: f64) -> f64 {
+        self.shuttle_loss(state) + self.enthalpy_pumping_loss(state, frequency)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use crate::engine::Pressure;
+
+    use super::*;
+
+    #[test]
+    fn losses_follow_the_temperature_difference() {
+        let gap = AppendixGap::new(
+            0.07,
+            0.02,
+            &Config {
+                width: 5e-4,
+                length: 0.05,
+                stroke: None,
+                k_gas: 0.2,
+            },
+        );
+        let state = |temp_hhx| State {
+            pres: Pressure {
+                avg: 5e6,
+                max: 6e6,
+                min: 4e6,
+                t_zero: 5e6,
+            },
+            temp_chx: 300.,
+            temp_hhx,
+            dens: 2.0,
+            cp: 14_300.,
+        };
+
+        // Shuttle loss = pi * 0.02^2 * 0.2 * 0.07 / (8 * 5e-4 * 0.05) * 600
+        assert_relative_eq!(
+            gap.shuttle_loss(&state(900.)),
+            PI * 0.028 * 600.,
+            max_relative = 1e-12
+        );
+
+        // 2 kg/m^3 of gas in 5.5e-6 m^3 of gap is pumped with a 40% pressure
+        // swing, carrying half of the 600 K temperature difference
+        let volume = PI * 0.07 * 5e-4 * 0.05;
+        assert_relative_eq!(
+            gap.enthalpy_pumping_loss(&state(900.), 50.),
+            50. * 2.0 * volume * 0.4 * 14_300. * 300.,
+            max_relative = 1e-12
+        );
+
+        // There is no loss without a temperature difference
+        assert_eq!(gap.loss(&state(300.), 50.), 0.0);
+    }
+}
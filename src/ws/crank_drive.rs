@@ -0,0 +1,1012 @@
+use std::f64::consts::PI;
+
+use anyhow::{bail, ensure};
+use serde::Deserialize;
+
+use crate::types::ParasiticPower;
+
+use super::{
+    appendix_gap, wall_heat, wall_heat::Cylinder, AppendixGap, CompVolume, ExpVolume, Kinematics,
+    Parasitics, PistonForces, SpacePressures, State, ThermalResistance, WallConductance, WallHeat,
+    WorkingSpaces,
+};
+
+// Number of crank angles sampled to find the ends of each stroke
+const STROKE_POINTS: u32 = 7200;
+
+/// Working spaces of a crank-driven engine, found from its geometry
+///
+/// The `layout` sets how the cylinders and pistons form the two spaces and
+/// the `drive` sets how the pistons move with the crank.  Each space holds
+/// its clearance volume (m^3) beyond what the pistons sweep, so it is at its
+/// clearance volume when its pistons are at their closest approach, except
+/// for the compression space of a gamma engine, whose power piston and
+/// displacer never close it together.
+///
+/// Crank angles start with the crank pin at the bottom, like the
+/// compression space volume of the sinusoidal drive.
+///
+/// The piston on the expansion side is called the expansion piston, which is
+/// the displacer of a beta or gamma engine, and the other is the compression
+/// piston, which is the power piston of a beta or gamma engine.  Only a
+/// displacer has an appendix gap, which has its diameter and stroke.  Wall
+/// heat transfer in each space follows the bore and stroke of its piston.
+pub struct CrankDrive {
+    pub frequency: f64,
+    pub layout: Layout,
+    pub drive: Drive,
+    pub clearance_comp: f64,
+    pub clearance_exp: f64,
+    pub thermal_resistance: ThermalResistance,
+    pub parasitics: Parasitics,
+    pub appendix_gap: Option<AppendixGap>,
+    pub wall_heat: Option<WallHeat>,
+}
+
+/// The arrangement of cylinders and pistons
+///
+/// Diameters are in m, and a displacer rod of diameter `D_rod` passes
+/// through the compression space.
+#[allow(non_snake_case)]
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Layout {
+    /// Two cylinders, each holding one space and its piston
+    Alpha { D_c: f64, D_e: f64 },
+
+    /// A single cylinder with the expansion space above the displacer and
+    /// the compression space between the displacer and the power piston
+    ///
+    /// The power piston is placed so that it just meets the displacer at
+    /// their closest approach, which lets their strokes overlap.
+    Beta {
+        D: f64,
+        #[serde(default)]
+        D_rod: f64,
+    },
+
+    /// A displacer cylinder, whose cold end joins a separate power cylinder
+    /// to form the compression space
+    Gamma {
+        D_c: f64,
+        D_e: f64,
+        #[serde(default)]
+        D_rod: f64,
+    },
+}
+
+/// The mechanism that moves the pistons with the crank
+///
+/// Lengths are in m, and the expansion piston leads the compression piston
+/// by `phase_angle` (degrees) where it can be chosen.
+#[allow(non_snake_case)]
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Drive {
+    /// Each piston has its own crank throw and connecting rod
+    SliderCrank {
+        stroke_c: f64,
+        stroke_e: f64,
+        L_conn_c: f64,
+        L_conn_e: f64,
+        phase_angle: f64,
+    },
+
+    /// Each piston has its own crank throw and a slotted yoke, so the
+    /// pistons move sinusoidally
+    ScotchYoke {
+        stroke_c: f64,
+        stroke_e: f64,
+        phase_angle: f64,
+    },
+
+    /// A triangular yoke on a single crank pin, with the pistons on rods of
+    /// length `L_conn` from either end of the yoke
+    ///
+    /// The ends of the yoke are `b_yoke` to either side of the crank pin, and
+    /// its third corner is `a_yoke` below the pin and held on the vertical
+    /// through the crank axis by a rocker.  The cylinders are above the ends
+    /// of the yoke, with the expansion cylinder on the leading side.  Equal
+    /// `a_yoke` and `b_yoke` give a phase angle of 90 degrees.
+    RossYoke {
+        r_crank: f64,
+        a_yoke: f64,
+        b_yoke: f64,
+        L_conn: f64,
+    },
+}
+
+#[allow(non_snake_case)]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct Config {
+    pub frequency: f64,
+    pub layout: Layout,
+    pub drive: Drive,
+    pub V_clearance_c: f64,
+    pub R_c: f64,
+    pub W_parasitic_c: f64,
+    pub V_clearance_e: f64,
+    pub R_e: f64,
+    pub W_parasitic_e: f64,
+    pub Q_parasitic_e: f64,
+    #[serde(default)]
+    pub appendix_gap: Option<appendix_gap::Config>,
+    #[serde(default)]
+    pub wall_heat: Option<wall_heat::Config>,
+}
+
+/// Height (m) of a piston and its derivative (m/rad) with the crank angle
+///
+/// `rod_factor` is the load in the piston's rod over the axial force on the
+/// piston, which grows as the rod leans away from the cylinder axis.
+#[derive(Debug, Clone, Copy)]
+struct Height {
+    value: f64,
+    deriv: f64,
+    rod_factor: f64,
+}
+
+/// The heights (m) at either end of a piston's stroke
+#[derive(Debug, Clone, Copy)]
+struct Travel {
+    top: f64,
+    bottom: f64,
+}
+
+impl Travel {
+    fn stroke(self) -> f64 {
+        self.top - self.bottom
+    }
+}
+
+impl Drive {
+    /// Returns the heights of the compression and expansion pistons at crank
+    /// angle `theta` (rad), which increase toward the expansion end
+    #[allow(non_snake_case)]
+    fn heights(&self, theta: f64) -> (Height, Height) {
+        // The mechanisms are written with the crank pin at the top
+        let theta = theta + PI;
+        match *self {
+            Drive::SliderCrank {
+                stroke_c,
+                stroke_e,
+                L_conn_c,
+                L_conn_e,
+                phase_angle,
+            } => (
+                slider_crank(0.5 * stroke_c, L_conn_c, theta),
+                slider_crank(0.5 * stroke_e, L_conn_e, theta + phase_angle.to_radians()),
+            ),
+            Drive::ScotchYoke {
+                stroke_c,
+                stroke_e,
+                phase_angle,
+            } => (
+                scotch_yoke(0.5 * stroke_c, theta),
+                scotch_yoke(0.5 * stroke_e, theta + phase_angle.to_radians()),
+            ),
+            Drive::RossYoke {
+                r_crank,
+                a_yoke,
+                b_yoke,
+                L_conn,
+            } => {
+                // The rocker keeps the third corner on the vertical, which
+                // sets how far the yoke tilts
+                let (sin, cos) = theta.sin_cos();
+                let sin_tilt = -r_crank * sin / a_yoke;
+                let cos_tilt = (1. - sin_tilt * sin_tilt).sqrt();
+                let dtilt = -r_crank * cos / (a_yoke * cos_tilt);
+
+                // Each end of the yoke drives a rod to the piston above it
+                let end = |side: f64| {
+                    let x = r_crank * sin + side * b_yoke * (cos_tilt - 1.);
+                    let dx = r_crank * cos - side * b_yoke * sin_tilt * dtilt;
+                    let y = r_crank * cos + side * b_yoke * sin_tilt;
+                    let dy = -r_crank * sin + side * b_yoke * cos_tilt * dtilt;
+                    let rise = (L_conn * L_conn - x * x).sqrt();
+                    Height {
+                        value: y + rise,
+                        deriv: dy - x * dx / rise,
+                        rod_factor: L_conn / rise,
+                    }
+                };
+                (end(-1.), end(1.))
+            }
+        }
+    }
+
+    /// Returns the travel of the compression and expansion pistons
+    fn travel(&self) -> (Travel, Travel) {
+        let mut comp = Travel {
+            top: f64::NEG_INFINITY,
+            bottom: f64::INFINITY,
+        };
+        let mut exp = comp;
+        for i in 0..STROKE_POINTS {
+            let theta = 2. * PI * f64::from(i) / f64::from(STROKE_POINTS);
+            let (comp_height, exp_height) = self.heights(theta);
+            for (travel, height) in [(&mut comp, comp_height), (&mut exp, exp_height)] {
+                travel.top = travel.top.max(height.value);
+                travel.bottom = travel.bottom.min(height.value);
+            }
+        }
+        (comp, exp)
+    }
+
+    /// Checks that the mechanism can turn through a full revolution
+    #[allow(non_snake_case)]
+    fn check(&self) -> anyhow::Result<()> {
+        match *self {
+            Drive::SliderCrank {
+                stroke_c,
+                stroke_e,
+                L_conn_c,
+                L_conn_e,
+                ..
+            } => {
+                for (stroke, L_conn, space) in [
+                    (stroke_c, L_conn_c, "compression"),
+                    (stroke_e, L_conn_e, "expansion"),
+                ] {
+                    ensure!(stroke > 0., "{space} stroke must be positive, not {stroke}");
+                    ensure!(
+                        L_conn > 0.5 * stroke,
+                        "{space} connecting rod of {L_conn} m must be longer than its crank radius of {} m",
+                        0.5 * stroke
+                    );
+                }
+            }
+            Drive::ScotchYoke {
+                stroke_c, stroke_e, ..
+            } => {
+                ensure!(
+                    stroke_c > 0. && stroke_e > 0.,
+                    "strokes must be positive, not {stroke_c} and {stroke_e}"
+                );
+            }
+            Drive::RossYoke {
+                r_crank,
+                a_yoke,
+                b_yoke,
+                L_conn,
+            } => {
+                ensure!(
+                    r_crank > 0. && b_yoke > 0.,
+                    "crank radius and yoke half-width must be positive, not {r_crank} and {b_yoke}"
+                );
+                ensure!(
+                    a_yoke > r_crank,
+                    "yoke depth of {a_yoke} m must exceed the crank radius of {r_crank} m"
+                );
+
+                // The ends of the yoke swing furthest from the cylinder axes
+                // when the crank is level and the yoke tilts the most
+                let swing = r_crank + b_yoke * (1. - (1. - (r_crank / a_yoke).powi(2)).sqrt());
+                ensure!(
+                    L_conn > swing,
+                    "connecting rods of {L_conn} m must be longer than the {swing} m swing of the yoke ends"
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns the height of a piston on a crank of radius `r_crank` and a rod
+/// of length `l_conn` at crank angle `theta` (rad), measured from the crank
+/// axis
+fn slider_crank(r_crank: f64, l_conn: f64, theta: f64) -> Height {
+    let (sin, cos) = theta.sin_cos();
+    let rise = (l_conn * l_conn - r_crank * r_crank * sin * sin).sqrt();
+    Height {
+        value: r_crank * cos + rise,
+        deriv: -r_crank * sin - r_crank * r_crank * sin * cos / rise,
+        rod_factor: l_conn / rise,
+    }
+}
+
+/// Returns the height of a piston on a Scotch yoke with a crank of radius
+/// `r_crank` at crank angle `theta` (rad), measured from the crank axis
+///
+/// The piston rod stays on the cylinder axis, so it carries the piston force.
+fn scotch_yoke(r_crank: f64, theta: f64) -> Height {
+    let (sin, cos) = theta.sin_cos();
+    Height {
+        value: r_crank * cos,
+        deriv: -r_crank * sin,
+        rod_factor: 1.,
+    }
+}
+
+fn area(diameter: f64) -> f64 {
+    PI * diameter * diameter / 4.
+}
+
+/// Areas (m^2) of the pistons that sweep each space
+///
+/// The compression space of a beta or gamma engine includes the cold end of
+/// the displacer cylinder, which grows over its `cold_end` area as the
+/// displacer rises.
+#[derive(Debug, Clone, Copy)]
+struct Areas {
+    comp: f64,
+    cold_end: f64,
+    exp: f64,
+}
+
+#[allow(non_snake_case)]
+impl Layout {
+    fn areas(self) -> Areas {
+        let (comp, cold_end, exp) = match self {
+            Layout::Alpha { D_c, D_e } => (area(D_c), 0., area(D_e)),
+            Layout::Beta { D, D_rod } => {
+                let annulus = area(D) - area(D_rod);
+                (annulus, annulus, area(D))
+            }
+            Layout::Gamma { D_c, D_e, D_rod } => (area(D_c), area(D_e) - area(D_rod), area(D_e)),
+        };
+        Areas {
+            comp,
+            cold_end,
+            exp,
+        }
+    }
+
+    /// Checks that the cylinders have positive diameters and that the
+    /// displacer rod fits within its cylinder
+    fn check(self) -> anyhow::Result<()> {
+        let (D_c, D_e, D_rod) = match self {
+            Layout::Alpha { D_c, D_e } => (D_c, D_e, 0.),
+            Layout::Beta { D, D_rod } => (D, D, D_rod),
+            Layout::Gamma { D_c, D_e, D_rod } => (D_c, D_e, D_rod),
+        };
+        ensure!(
+            D_c > 0. && D_e > 0.,
+            "cylinder diameters must be positive, not {D_c} and {D_e}"
+        );
+        ensure!(
+            (0. ..D_e).contains(&D_rod),
+            "displacer rod diameter of {D_rod} m must fit within the {D_e} m cylinder"
+        );
+        Ok(())
+    }
+}
+
+/// The linkages of a crank drive, which carry the gas forces on its pistons
+/// to the crank
+#[derive(Debug, Clone, Copy)]
+struct Linkages {
+    drive: Drive,
+    areas: Areas,
+}
+
+impl Kinematics for Linkages {
+    /// Return the gas forces at crank angle `theta` (rad)
+    ///
+    /// The compression piston is taken as the piston and the expansion
+    /// piston as the displacer.  Each space pushes on the pistons that sweep
+    /// it, while the buffer pushes back on the same areas, and each rod
+    /// carries the force on its piston along its lean.
+    fn forces(&self, theta: f64, pres: SpacePressures) -> PistonForces {
+        let (comp, exp) = self.drive.heights(theta);
+        let (comp_pres, exp_pres) = (pres.comp - pres.buffer, pres.exp - pres.buffer);
+        let piston = -comp_pres * self.areas.comp;
+        let displacer = comp_pres * self.areas.cold_end - exp_pres * self.areas.exp;
+
+        // The pistons sit above the crank, so their rods push up on them
+        PistonForces {
+            piston,
+            displacer,
+            piston_rod: -piston * comp.rod_factor,
+            displacer_rod: -displacer * exp.rod_factor,
+        }
+    }
+}
+
+impl CrankDrive {
+    /// Returns the volume (m^3) swept by the strokes of a beta engine's
+    /// displacer and power piston that overlap
+    ///
+    /// Other layouts have no overlap.
+    #[must_use]
+    pub fn overlap_volume(&self) -> f64 {
+        match self.layout {
+            #[allow(non_snake_case)]
+            Layout::Beta { D, D_rod } => {
+                let (comp, exp) = self.drive.travel();
+                (area(D) - area(D_rod)) * min_beta_gap(&self.drive, comp, exp)
+            }
+            Layout::Alpha { .. } | Layout::Gamma { .. } => 0.,
+        }
+    }
+}
+
+/// Returns the closest approach (m) of a beta engine's pistons if the power
+/// piston at the top of its stroke met the displacer at the bottom of its
+/// stroke
+fn min_beta_gap(drive: &Drive, comp: Travel, exp: Travel) -> f64 {
+    (0..STROKE_POINTS)
+        .map(|i| {
+            let theta = 2. * PI * f64::from(i) / f64::from(STROKE_POINTS);
+            let (comp_height, exp_height) = drive.heights(theta);
+            (exp_height.value - exp.bottom) + (comp.top - comp_height.value)
+        })
+        .fold(f64::INFINITY, f64::min)
+}
+
+impl WorkingSpaces for CrankDrive {
+    fn frequency(&self, _state: &State) -> f64 {
+        self.frequency
+    }
+
+    #[allow(non_snake_case)]
+    fn volumes(&self, _state: &State) -> Box<dyn Fn(f64) -> (CompVolume, ExpVolume)> {
+        let omega = 2. * PI * self.frequency;
+        let drive = self.drive;
+        let (comp_travel, exp_travel) = drive.travel();
+        let (clearance_comp, clearance_exp) = (self.clearance_comp, self.clearance_exp);
+        let areas = self.layout.areas();
+        let min_gap = match self.layout {
+            Layout::Beta { .. } => min_beta_gap(&drive, comp_travel, exp_travel),
+            Layout::Alpha { .. } | Layout::Gamma { .. } => 0.,
+        };
+
+        Box::new(move |time: f64| {
+            let theta = omega * time;
+            let (comp, exp) = drive.heights(theta);
+            let comp_swept = comp_travel.top - comp.value;
+            let exp_swept = exp_travel.top - exp.value;
+            let cold_end = exp_travel.stroke() - exp_swept - min_gap;
+            (
+                CompVolume {
+                    value: clearance_comp + areas.comp * comp_swept + areas.cold_end * cold_end,
+                    deriv: (-areas.comp * comp.deriv + areas.cold_end * exp.deriv) * omega,
+                },
+                ExpVolume {
+                    value: clearance_exp + areas.exp * exp_swept,
+                    deriv: -areas.exp * exp.deriv * omega,
+                },
+            )
+        })
+    }
+
+    fn thermal_resistance(&self, _state: &State) -> ThermalResistance {
+        if self.wall_heat.is_some() {
+            return ThermalResistance::default();
+        }
+        self.thermal_resistance
+    }
+
+    fn parasitics(&self, state: &State) -> Parasitics {
+        let mut parasitics = self.parasitics;
+        if let Some(gap) = &self.appendix_gap {
+            parasitics.exp.thermal += gap.loss(state, self.frequency);
+        }
+        parasitics
+    }
+
+    fn kinematics(&self, _state: &State) -> Option<Box<dyn Kinematics>> {
+        Some(Box::new(Linkages {
+            drive: self.drive,
+            areas: self.layout.areas(),
+        }))
+    }
+
+    fn wall_conductance(&self, _state: &State) -> Option<Box<dyn WallConductance>> {
+        self.wall_heat
+            .map(|wall_heat| Box::new(wall_heat) as Box<dyn WallConductance>)
+    }
+}
+
+impl TryFrom<Config> for CrankDrive {
+    type Error = anyhow::Error;
+
+    #[allow(non_snake_case)]
+    fn try_from(config: Config) -> Result<Self, Self::Error> {
+        config.layout.check()?;
+        config.drive.check()?;
+        let parasitics = Parasitics {
+            comp: ParasiticPower {
+                mechanical: config.W_parasitic_c,
+                ..ParasiticPower::default()
+            },
+            exp: ParasiticPower {
+                thermal: config.Q_parasitic_e,
+                mechanical: config.W_parasitic_e,
+                ..ParasiticPower::default()
+            },
+        };
+        let (comp_travel, exp_travel) = config.drive.travel();
+
+        // The gap surrounds the displacer, which is the expansion piston
+        let appendix_gap = config
+            .appendix_gap
+            .map(|gap| {
+                let diameter = match config.layout {
+                    Layout::Alpha { .. } => {
+                        bail!("an appendix gap needs the displacer of a beta or gamma engine")
+                    }
+                    Layout::Beta { D, .. } => D,
+                    Layout::Gamma { D_e, .. } => D_e,
+                };
+                Ok(AppendixGap::new(diameter, exp_travel.stroke(), &gap))
+            })
+            .transpose()?;
+        let wall_heat = config.wall_heat.map(|wall_heat| {
+            let (bore_comp, bore_exp) = match config.layout {
+                Layout::Alpha { D_c, D_e } | Layout::Gamma { D_c, D_e, .. } => (D_c, D_e),
+                Layout::Beta { D, .. } => (D, D),
+            };
+            let comp = Cylinder {
+                bore: bore_comp,
+                stroke: comp_travel.stroke(),
+            };
+            let exp = Cylinder {
+                bore: bore_exp,
+                stroke: exp_travel.stroke(),
+            };
+            WallHeat::new(&wall_heat, config.frequency, comp, exp)
+        });
+        Ok(Self {
+            frequency: config.frequency,
+            layout: config.layout,
+            drive: config.drive,
+            clearance_comp: config.V_clearance_c,
+            clearance_exp: config.V_clearance_e,
+            thermal_resistance: ThermalResistance {
+                comp: config.R_c,
+                exp: config.R_e,
+            },
+            parasitics,
+            appendix_gap,
+            wall_heat,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use crate::ws::{
+        sinusoidal_drive::Geometry,
+        tests::{sinusoidal_drive, state},
+    };
+
+    use super::*;
+
+    fn crank_drive(layout: Layout, drive: Drive) -> CrankDrive {
+        CrankDrive {
+            frequency: 50.0,
+            layout,
+            drive,
+            clearance_comp: 4e-5,
+            clearance_exp: 2e-5,
+            thermal_resistance: ThermalResistance::default(),
+            parasitics: Parasitics::default(),
+            appendix_gap: None,
+            wall_heat: None,
+        }
+    }
+
+    fn config(layout: Layout, drive: Drive) -> Config {
+        Config {
+            frequency: 50.0,
+            layout,
+            drive,
+            V_clearance_c: 4e-5,
+            R_c: f64::INFINITY,
+            W_parasitic_c: 0.0,
+            V_clearance_e: 2e-5,
+            R_e: f64::INFINITY,
+            W_parasitic_e: 0.0,
+            Q_parasitic_e: 0.0,
+            appendix_gap: None,
+            wall_heat: None,
+        }
+    }
+
+    fn scotch_yoke() -> Drive {
+        Drive::ScotchYoke {
+            stroke_c: 0.03,
+            stroke_e: 0.02,
+            phase_angle: 90.,
+        }
+    }
+
+    /// Returns the smallest volumes over a revolution
+    fn min_volumes(drive: &CrankDrive) -> (f64, f64) {
+        let volumes = drive.volumes(&state(10e6));
+        (0..3600)
+            .map(|i| volumes(f64::from(i) / 3600. / 50.))
+            .fold((f64::INFINITY, f64::INFINITY), |(c, e), (comp, exp)| {
+                (c.min(comp.value), e.min(exp.value))
+            })
+    }
+
+    #[test]
+    fn alpha_scotch_yoke_matches_sinusoidal_drive() {
+        let drive = crank_drive(
+            Layout::Alpha {
+                D_c: 0.06,
+                D_e: 0.08,
+            },
+            scotch_yoke(),
+        );
+        let sinusoidal = sinusoidal_drive(
+            50.0,
+            Geometry {
+                clearance_volume: 4e-5,
+                swept_volume: area(0.06) * 0.03,
+            },
+            Geometry {
+                clearance_volume: 2e-5,
+                swept_volume: area(0.08) * 0.02,
+            },
+        );
+        let (actual, expected) = (
+            drive.volumes(&state(10e6)),
+            sinusoidal.volumes(&state(10e6)),
+        );
+        for i in 0..40 {
+            let time = 0.0007 + f64::from(i) * 0.0011;
+            let ((comp, exp), (comp_expected, exp_expected)) = (actual(time), expected(time));
+            assert_relative_eq!(comp.value, comp_expected.value, max_relative = 1e-9);
+            assert_relative_eq!(exp.value, exp_expected.value, max_relative = 1e-9);
+            assert_relative_eq!(comp.deriv, comp_expected.deriv, epsilon = 1e-12);
+            assert_relative_eq!(exp.deriv, exp_expected.deriv, epsilon = 1e-12);
+        }
+        assert_eq!(drive.overlap_volume(), 0.0);
+    }
+
+    fn layouts() -> [Layout; 3] {
+        [
+            Layout::Alpha {
+                D_c: 0.06,
+                D_e: 0.08,
+            },
+            Layout::Beta {
+                D: 0.07,
+                D_rod: 0.01,
+            },
+            Layout::Gamma {
+                D_c: 0.05,
+                D_e: 0.07,
+                D_rod: 0.01,
+            },
+        ]
+    }
+
+    fn drives() -> [Drive; 3] {
+        [
+            Drive::SliderCrank {
+                stroke_c: 0.03,
+                stroke_e: 0.02,
+                L_conn_c: 0.06,
+                L_conn_e: 0.05,
+                phase_angle: 100.,
+            },
+            scotch_yoke(),
+            Drive::RossYoke {
+                r_crank: 0.01,
+                a_yoke: 0.04,
+                b_yoke: 0.04,
+                L_conn: 0.08,
+            },
+        ]
+    }
+
+    #[test]
+    fn derivatives_match_volume_changes() {
+        let step = 1e-7;
+        for layout in layouts() {
+            for drive in drives() {
+                let drive = crank_drive(layout, drive);
+                let volumes = drive.volumes(&state(10e6));
+                for i in 0..24 {
+                    let time = f64::from(i) / 24. / 50. + 1e-4;
+                    let (comp, exp) = volumes(time);
+                    let ((comp_after, exp_after), (comp_before, exp_before)) =
+                        (volumes(time + step), volumes(time - step));
+                    let comp_change = (comp_after.value - comp_before.value) / (2. * step);
+                    let exp_change = (exp_after.value - exp_before.value) / (2. * step);
+                    assert_relative_eq!(comp.deriv, comp_change, epsilon = 1e-6);
+                    assert_relative_eq!(exp.deriv, exp_change, epsilon = 1e-6);
+                }
+
+                // Each space reaches its clearance volume, except for the
+                // compression space of a gamma engine
+                let (comp_min, exp_min) = min_volumes(&drive);
+                assert_relative_eq!(exp_min, 2e-5, max_relative = 1e-4);
+                if let Layout::Gamma { .. } = layout {
+                    assert!(comp_min > 4e-5);
+                } else {
+                    assert_relative_eq!(comp_min, 4e-5, max_relative = 1e-4);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn forces_do_the_work_of_the_gas() {
+        let pres = SpacePressures {
+            comp: 11e6,
+            exp: 9.5e6,
+            buffer: 10e6,
+        };
+        let omega = 2. * PI * 50.0;
+        for layout in layouts() {
+            for drive in drives() {
+                let drive = crank_drive(layout, drive);
+                let volumes = drive.volumes(&state(10e6));
+                let kinematics = drive
+                    .kinematics(&state(10e6))
+                    .expect("drive has kinematics");
+                for i in 0..12 {
+                    let theta = 0.1 + f64::from(i) * PI / 6.;
+
+                    // The work done on the pistons over a small rotation
+                    // matches the work done by the gas as the volumes change
+                    let (comp, exp) = volumes(theta / omega);
+                    let gas_torque = ((pres.comp - pres.buffer) * comp.deriv
+                        + (pres.exp - pres.buffer) * exp.deriv)
+                        / omega;
+                    let (comp_height, exp_height) = drive.drive.heights(theta);
+                    let forces = kinematics.forces(theta, pres);
+                    let piston_torque =
+                        forces.piston * comp_height.deriv + forces.displacer * exp_height.deriv;
+                    assert_relative_eq!(piston_torque, gas_torque, max_relative = 1e-9);
+                }
+            }
+        }
+
+        // A Scotch yoke's rods carry the piston forces, while a slider
+        // crank's rods lean the most when the crank is level
+        let forces = |drive| {
+            crank_drive(layouts()[0], drive)
+                .kinematics(&state(10e6))
+                .expect("drive has kinematics")
+                .forces(0.5 * PI, pres)
+        };
+        let yoke = forces(scotch_yoke());
+        assert_eq!(yoke.piston_rod, -yoke.piston);
+        assert_eq!(yoke.displacer_rod, -yoke.displacer);
+        let crank = forces(drives()[0]);
+        assert!(crank.piston_rod > 0.0);
+        assert_relative_eq!(
+            crank.piston_rod,
+            -crank.piston * 0.06 / (0.06_f64.powi(2) - 0.015_f64.powi(2)).sqrt(),
+            max_relative = 1e-12
+        );
+    }
+
+    #[test]
+    fn rejects_impossible_geometry() {
+        for layout in layouts() {
+            for drive in drives() {
+                assert!(CrankDrive::try_from(config(layout, drive)).is_ok());
+            }
+        }
+        let alpha = layouts()[0];
+        let invalid = [
+            (
+                alpha,
+                Drive::SliderCrank {
+                    stroke_c: 0.03,
+                    stroke_e: 0.02,
+                    L_conn_c: 0.01,
+                    L_conn_e: 0.05,
+                    phase_angle: 90.,
+                },
+            ),
+            (
+                alpha,
+                Drive::ScotchYoke {
+                    stroke_c: 0.0,
+                    stroke_e: 0.02,
+                    phase_angle: 90.,
+                },
+            ),
+            (
+                alpha,
+                Drive::RossYoke {
+                    r_crank: 0.05,
+                    a_yoke: 0.04,
+                    b_yoke: 0.04,
+                    L_conn: 0.08,
+                },
+            ),
+            // With the crank level, the yoke tilts by 30 degrees and its ends
+            // swing 0.0154 m off the cylinder axes
+            (
+                alpha,
+                Drive::RossYoke {
+                    r_crank: 0.01,
+                    a_yoke: 0.02,
+                    b_yoke: 0.04,
+                    L_conn: 0.015,
+                },
+            ),
+            (
+                Layout::Beta {
+                    D: 0.07,
+                    D_rod: 0.07,
+                },
+                scotch_yoke(),
+            ),
+            (
+                Layout::Gamma {
+                    D_c: 0.0,
+                    D_e: 0.07,
+                    D_rod: 0.01,
+                },
+                scotch_yoke(),
+            ),
+        ];
+        for (layout, drive) in invalid {
+            assert!(CrankDrive::try_from(config(layout, drive)).is_err());
+        }
+    }
+
+    #[test]
+    fn beta_strokes_overlap() {
+        // With sinusoidal strokes a quarter turn apart, the pistons come no
+        // closer than 1 - 1/sqrt(2) of the stroke to touching
+        let drive = crank_drive(
+            Layout::Beta {
+                D: 0.07,
+                D_rod: 0.0,
+            },
+            Drive::ScotchYoke {
+                stroke_c: 0.02,
+                stroke_e: 0.02,
+                phase_angle: 90.,
+            },
+        );
+        assert_relative_eq!(
+            drive.overlap_volume(),
+            area(0.07) * 0.02 * (1. - 0.5_f64.sqrt()),
+            max_relative = 1e-6
+        );
+
+        // The total volume swings by less than the two swept volumes
+        let volumes = drive.volumes(&state(10e6));
+        let totals: Vec<_> = (0..360)
+            .map(|i| {
+                let (comp, exp) = volumes(f64::from(i) / 360. / 50.);
+                comp.value + exp.value
+            })
+            .collect();
+        let swing = totals.iter().copied().fold(f64::NEG_INFINITY, f64::max)
+            - totals.iter().copied().fold(f64::INFINITY, f64::min);
+        assert_relative_eq!(swing, area(0.07) * 0.02, max_relative = 1e-3);
+    }
+
+    #[test]
+    fn appendix_gap_surrounds_the_displacer() {
+        let with_gap = |layout| Config {
+            appendix_gap: Some(appendix_gap::Config {
+                width: 5e-4,
+                length: 0.05,
+                stroke: None,
+                k_gas: 0.2,
+            }),
+            ..config(layout, scotch_yoke())
+        };
+
+        // The displacer of either engine is 0.07 m across with a 0.02 m
+        // stroke, so the shuttle loss is
+        // pi * 0.02^2 * 0.2 * 0.07 / (8 * 5e-4 * 0.05) * 600 W
+        let layouts = [
+            Layout::Beta {
+                D: 0.07,
+                D_rod: 0.01,
+            },
+            Layout::Gamma {
+                D_c: 0.05,
+                D_e: 0.07,
+                D_rod: 0.01,
+            },
+        ];
+        for layout in layouts {
+            let drive = CrankDrive::try_from(with_gap(layout)).expect("config is valid");
+            let parasitics = drive.parasitics(&state(10e6));
+            assert_relative_eq!(
+                parasitics.exp.thermal,
+                PI * 0.028 * 600.,
+                max_relative = 1e-9
+            );
+            assert_eq!(parasitics.comp.thermal, 0.0);
+        }
+
+        // An alpha engine has no displacer
+        let alpha = Layout::Alpha {
+            D_c: 0.06,
+            D_e: 0.08,
+        };
+        assert!(CrankDrive::try_from(with_gap(alpha)).is_err());
+    }
+
+    #[test]
+    fn wall_heat_follows_the_pistons() {
+        let gamma = Layout::Gamma {
+            D_c: 0.05,
+            D_e: 0.07,
+            D_rod: 0.01,
+        };
+        let config = Config {
+            R_c: 1e3,
+            R_e: 1e3,
+            wall_heat: Some(wall_heat::Config {
+                correlation: wall_heat::Correlation::Annand { a: 0.5, b: 0.7 },
+                k_gas: 0.2,
+                mu_gas: 1e-5,
+                bore_c: None,
+                bore_e: None,
+            }),
+            ..config(gamma, scotch_yoke())
+        };
+        let drive = CrankDrive::try_from(config).expect("config is valid");
+        let wall_heat = drive.wall_heat.expect("drive has wall heat");
+        for (cylinder, bore, stroke) in [(wall_heat.comp, 0.05, 0.03), (wall_heat.exp, 0.07, 0.02)]
+        {
+            assert_eq!(cylinder.bore, bore);
+            assert_relative_eq!(cylinder.stroke, stroke, max_relative = 1e-6);
+        }
+
+        // The correlation replaces the constant resistances
+        let resistance = drive.thermal_resistance(&state(10e6));
+        assert!(resistance.comp.is_infinite() && resistance.exp.is_infinite());
+        assert!(drive.wall_conductance(&state(10e6)).is_some());
+    }
+
+    #[test]
+    fn ross_yoke_leads_by_a_quarter_turn() {
+        let config: Config = serde_json::from_str(
+            r#"{
+                "frequency": 50.0,
+                "layout": {"alpha": {"D_c": 0.06, "D_e": 0.06}},
+                "drive": {"ross_yoke": {"r_crank": 0.01, "a_yoke": 0.05, "b_yoke": 0.05, "L_conn": 0.5}},
+                "V_clearance_c": 4e-5,
+                "R_c": 1e3,
+                "W_parasitic_c": 0.0,
+                "V_clearance_e": 2e-5,
+                "R_e": 1e3,
+                "W_parasitic_e": 0.0,
+                "Q_parasitic_e": 0.0
+            }"#,
+        )
+        .expect("config is valid");
+        let drive = CrankDrive::try_from(config).expect("config is valid");
+
+        // Each piston sweeps about sqrt(2) times the crank diameter, and the
+        // expansion space is smallest about a quarter turn before the
+        // compression space
+        let (comp_travel, exp_travel) = drive.drive.travel();
+        for travel in [comp_travel, exp_travel] {
+            assert_relative_eq!(travel.stroke(), 0.02 * 2_f64.sqrt(), max_relative = 1e-2);
+        }
+        let volumes = drive.volumes(&state(10e6));
+        let smallest = |space: fn((CompVolume, ExpVolume)) -> f64| {
+            (0..3600)
+                .map(|i| {
+                    (
+                        f64::from(i) / 10.,
+                        space(volumes(f64::from(i) / 3600. / 50.)),
+                    )
+                })
+                .fold((0., f64::INFINITY), |min, (angle, vol)| {
+                    if vol < min.1 {
+                        (angle, vol)
+                    } else {
+                        min
+                    }
+                })
+                .0
+        };
+        let comp_angle = smallest(|(comp, _)| comp.value);
+        let exp_angle = smallest(|(_, exp)| exp.value);
+        assert_relative_eq!((comp_angle - exp_angle).rem_euclid(360.), 90., epsilon = 2.);
+    }
+}
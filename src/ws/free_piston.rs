@@ -0,0 +1,585 @@
+use std::f64::consts::PI;
+
+use anyhow::ensure;
+use serde::Deserialize;
+
+use crate::types::ParasiticPower;
+
+use super::{
+    CompVolume, Dynamics, ElectricalPower, ExpVolume, Motion, Parasitics, State, ThermalResistance,
+    WorkingSpaces,
+};
+
+/// Working spaces of a beta-type free-piston engine
+///
+/// The power piston and displacer share a cylinder.  The displacer separates
+/// the expansion space above it from the compression space below it, and its
+/// rod passes through the power piston into the bounce space, which is held
+/// at the average engine pressure.  Both move on springs, with the power
+/// piston driving a load that is modeled as linear and quadratic damping,
+/// along with an optional linear alternator.  With linear damping alone the
+/// stroke has no stable amplitude, since the power absorbed grows with the
+/// stroke at the same rate as the power produced.
+pub struct FreePiston {
+    pub frequency_hint: f64,
+    pub stroke_hint: f64,
+    pub comp_volume: f64,
+    pub exp_volume: f64,
+    pub piston: Piston,
+    pub displacer: Displacer,
+    pub alternator: Option<Alternator>,
+    pub thermal_resistance: ThermalResistance,
+    pub parasitics: Parasitics,
+}
+
+/// The power piston and the load it drives
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Piston {
+    /// Moving mass (kg)
+    pub mass: f64,
+    /// Face area (m^2)
+    pub area: f64,
+    /// Spring stiffness (N/m)
+    pub stiffness: f64,
+    /// Linear damping (N-s/m)
+    pub damping: f64,
+    /// Quadratic damping (N-s^2/m^2)
+    pub quadratic_damping: f64,
+}
+
+/// The displacer and its rod
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Displacer {
+    /// Moving mass (kg)
+    pub mass: f64,
+    /// Face area (m^2)
+    pub area: f64,
+    /// Rod area (m^2)
+    pub rod_area: f64,
+    /// Spring stiffness (N/m)
+    pub stiffness: f64,
+    /// Linear damping (N-s/m)
+    pub damping: f64,
+}
+
+/// A linear alternator driven by the power piston and connected to a
+/// resistive load
+///
+/// The coil current `i` follows `L di/dt = K v_p - (R_coil + R_load) i`, and
+/// the alternator pushes back on the piston with a force of `K i`.  Without
+/// inductance, the current follows the piston velocity directly.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Alternator {
+    /// Motor constant (N/A), which is also the back-emf constant (V-s/m)
+    pub motor_constant: f64,
+    /// Coil resistance (ohm)
+    pub coil_resistance: f64,
+    /// Coil inductance (H)
+    pub coil_inductance: f64,
+    /// Load resistance (ohm)
+    pub load_resistance: f64,
+}
+
+#[allow(non_snake_case)]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct AlternatorConfig {
+    pub K_m: f64,
+    pub R_coil: f64,
+    pub L_coil: f64,
+    pub R_load: f64,
+}
+
+#[allow(non_snake_case)]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct Config {
+    pub frequency_hint: f64,
+    pub stroke_hint: f64,
+    pub V_mid_c: f64,
+    pub R_c: f64,
+    pub W_parasitic_c: f64,
+    pub V_mid_e: f64,
+    pub R_e: f64,
+    pub W_parasitic_e: f64,
+    pub Q_parasitic_e: f64,
+    pub D_p: f64,
+    pub m_p: f64,
+    pub k_p: f64,
+    pub c_p: f64,
+    pub c2_p: f64,
+    pub D_d: f64,
+    pub D_rod: f64,
+    pub m_d: f64,
+    pub k_d: f64,
+    pub c_d: f64,
+    pub alternator: Option<AlternatorConfig>,
+}
+
+/// The `Dynamics` of a `FreePiston` for a single run
+struct FreePistonDynamics {
+    omega: f64,
+    amplitude: f64,
+    comp_volume: f64,
+    exp_volume: f64,
+    piston: Piston,
+    displacer: Displacer,
+    alternator: Option<Alternator>,
+    pres_bounce: f64,
+}
+
+impl WorkingSpaces for FreePiston {
+    fn frequency(&self, _state: &State) -> f64 {
+        self.frequency_hint
+    }
+
+    /// Return the volumes with both pistons at mid-stroke
+    ///
+    /// The volumes of a free-piston engine follow from its `Dynamics` rather
+    /// than time.
+    fn volumes(&self, _state: &State) -> Box<dyn Fn(f64) -> (CompVolume, ExpVolume)> {
+        let (comp_volume, exp_volume) = (self.comp_volume, self.exp_volume);
+        Box::new(move |_time: f64| {
+            let comp = CompVolume {
+                value: comp_volume,
+                deriv: 0.0,
+            };
+            let exp = ExpVolume {
+                value: exp_volume,
+                deriv: 0.0,
+            };
+            (comp, exp)
+        })
+    }
+
+    fn thermal_resistance(&self, _state: &State) -> ThermalResistance {
+        self.thermal_resistance
+    }
+
+    fn parasitics(&self, _state: &State) -> Parasitics {
+        self.parasitics
+    }
+
+    fn dynamics(&self, state: &State) -> Option<Box<dyn Dynamics>> {
+        Some(Box::new(FreePistonDynamics {
+            omega: 2.0 * PI * self.frequency_hint,
+            amplitude: 0.5 * self.stroke_hint,
+            comp_volume: self.comp_volume,
+            exp_volume: self.exp_volume,
+            piston: self.piston,
+            displacer: self.displacer,
+            alternator: self.alternator,
+            pres_bounce: state.pres.avg,
+        }))
+    }
+}
+
+impl Dynamics for FreePistonDynamics {
+    fn volumes(&self, motion: Motion) -> (CompVolume, ExpVolume) {
+        let Motion {
+            x_p, v_p, x_d, v_d, ..
+        } = motion;
+        let annulus = self.displacer.area - self.displacer.rod_area;
+        let comp = CompVolume {
+            value: self.comp_volume + annulus * x_d - self.piston.area * x_p,
+            deriv: annulus * v_d - self.piston.area * v_p,
+        };
+        let exp = ExpVolume {
+            value: self.exp_volume - self.displacer.area * x_d,
+            deriv: -self.displacer.area * v_d,
+        };
+        (comp, exp)
+    }
+
+    #[allow(clippy::similar_names)]
+    fn derivatives(&self, motion: Motion, pres_comp: f64, pres_exp: f64) -> Motion {
+        let Motion {
+            x_p, v_p, x_d, v_d, ..
+        } = motion;
+        let Piston {
+            mass: m_p,
+            area: a_p,
+            stiffness: k_p,
+            damping: c_p,
+            quadratic_damping: c2_p,
+        } = self.piston;
+        let Displacer {
+            mass: m_d,
+            area: a_d,
+            rod_area: a_rod,
+            stiffness: k_d,
+            damping: c_d,
+        } = self.displacer;
+
+        // The compression space pushes the piston away from the expansion
+        // space, against the bounce space below it
+        let mut force_p =
+            (self.pres_bounce - pres_comp) * a_p - k_p * x_p - c_p * v_p - c2_p * v_p * v_p.abs();
+
+        // The alternator resists the piston in proportion to its current
+        let mut di_dt = 0.0;
+        if let Some(alternator) = self.alternator {
+            force_p -= alternator.motor_constant * alternator.current(motion);
+            di_dt = alternator.current_derivative(motion);
+        }
+
+        // The displacer is pushed up by the compression space on its annulus
+        // and by the bounce space on its rod, and down by the expansion space,
+        // which is written in terms of pressure differences to balance exactly
+        let force_d = (pres_comp - pres_exp) * a_d + (self.pres_bounce - pres_comp) * a_rod
+            - k_d * x_d
+            - c_d * v_d;
+
+        Motion {
+            x_p: v_p,
+            v_p: force_p / m_p,
+            x_d: v_d,
+            v_d: force_d / m_d,
+            current: di_dt,
+        }
+    }
+
+    /// Return the piston at mid-stroke with the displacer leading it by 90°
+    ///
+    /// Any alternator current starts at the value it would have without
+    /// inductance.
+    fn initial_motion(&self) -> Motion {
+        let mut motion = Motion {
+            x_p: 0.0,
+            v_p: self.omega * self.amplitude,
+            x_d: self.amplitude,
+            v_d: 0.0,
+            current: 0.0,
+        };
+        if let Some(alternator) = self.alternator {
+            motion.current = alternator.motor_constant * motion.v_p / alternator.resistance();
+        }
+        motion
+    }
+
+    fn electrical_power(&self, motion: Motion) -> Option<ElectricalPower> {
+        let alternator = self.alternator?;
+        let current = alternator.current(motion);
+        Some(ElectricalPower {
+            generated: alternator.motor_constant * motion.v_p * current,
+            copper: alternator.coil_resistance * current * current,
+        })
+    }
+}
+
+impl Alternator {
+    /// Return the total resistance (ohm) of the coil and load
+    fn resistance(&self) -> f64 {
+        self.coil_resistance + self.load_resistance
+    }
+
+    /// Return the coil current (A) during `motion`
+    fn current(&self, motion: Motion) -> f64 {
+        if self.coil_inductance > 0.0 {
+            motion.current
+        } else {
+            self.motor_constant * motion.v_p / self.resistance()
+        }
+    }
+
+    /// Return the time derivative of the coil current (A/s) during `motion`
+    fn current_derivative(&self, motion: Motion) -> f64 {
+        if self.coil_inductance > 0.0 {
+            (self.motor_constant * motion.v_p - self.resistance() * motion.current)
+                / self.coil_inductance
+        } else {
+            0.0
+        }
+    }
+}
+
+impl TryFrom<Config> for FreePiston {
+    type Error = anyhow::Error;
+
+    fn try_from(config: Config) -> Result<Self, Self::Error> {
+        ensure!(
+            config.frequency_hint > 0. && config.stroke_hint > 0.,
+            "frequency and stroke hints must be positive, not {} and {}",
+            config.frequency_hint,
+            config.stroke_hint
+        );
+        ensure!(
+            config.V_mid_c > 0. && config.V_mid_e > 0.,
+            "mid-stroke volumes must be positive, not {} and {}",
+            config.V_mid_c,
+            config.V_mid_e
+        );
+        ensure!(
+            config.m_p > 0. && config.m_d > 0.,
+            "piston and displacer masses must be positive, not {} and {}",
+            config.m_p,
+            config.m_d
+        );
+        ensure!(
+            config.D_p > 0. && config.D_d > 0.,
+            "piston and displacer diameters must be positive, not {} and {}",
+            config.D_p,
+            config.D_d
+        );
+        ensure!(
+            (0. ..config.D_d).contains(&config.D_rod),
+            "displacer rod diameter of {} m must fit within the {} m displacer",
+            config.D_rod,
+            config.D_d
+        );
+        if let Some(alternator) = &config.alternator {
+            ensure!(
+                alternator.R_coil >= 0. && alternator.R_load >= 0. && alternator.L_coil >= 0.,
+                "alternator resistances and inductance must not be negative"
+            );
+            ensure!(
+                alternator.R_coil + alternator.R_load > 0.,
+                "alternator coil and load must have some resistance"
+            );
+        }
+        let area = |diameter: f64| 0.25 * PI * diameter * diameter;
+        let parasitics = Parasitics {
+            comp: ParasiticPower {
+                mechanical: config.W_parasitic_c,
+                ..ParasiticPower::default()
+            },
+            exp: ParasiticPower {
+                thermal: config.Q_parasitic_e,
+                mechanical: config.W_parasitic_e,
+                ..ParasiticPower::default()
+            },
+        };
+        Ok(Self {
+            frequency_hint: config.frequency_hint,
+            stroke_hint: config.stroke_hint,
+            comp_volume: config.V_mid_c,
+            exp_volume: config.V_mid_e,
+            piston: Piston {
+                mass: config.m_p,
+                area: area(config.D_p),
+                stiffness: config.k_p,
+                damping: config.c_p,
+                quadratic_damping: config.c2_p,
+            },
+            displacer: Displacer {
+                mass: config.m_d,
+                area: area(config.D_d),
+                rod_area: area(config.D_rod),
+                stiffness: config.k_d,
+                damping: config.c_d,
+            },
+            alternator: config.alternator.map(|alternator| Alternator {
+                motor_constant: alternator.K_m,
+                coil_resistance: alternator.R_coil,
+                coil_inductance: alternator.L_coil,
+                load_resistance: alternator.R_load,
+            }),
+            thermal_resistance: ThermalResistance {
+                comp: config.R_c,
+                exp: config.R_e,
+            },
+            parasitics,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use crate::ws::tests::state;
+
+    use super::*;
+
+    fn free_piston() -> FreePiston {
+        FreePiston {
+            frequency_hint: 50.0,
+            stroke_hint: 0.02,
+            comp_volume: 2e-4,
+            exp_volume: 1e-4,
+            piston: Piston {
+                mass: 5.0,
+                area: 5e-3,
+                stiffness: 1e5,
+                damping: 100.0,
+                quadratic_damping: 10.0,
+            },
+            displacer: Displacer {
+                mass: 1.0,
+                area: 5e-3,
+                rod_area: 5e-4,
+                stiffness: 5e4,
+                damping: 10.0,
+            },
+            alternator: None,
+            thermal_resistance: ThermalResistance::default(),
+            parasitics: Parasitics::default(),
+        }
+    }
+
+    fn alternator() -> Alternator {
+        Alternator {
+            motor_constant: 20.0,
+            coil_resistance: 0.5,
+            coil_inductance: 0.01,
+            load_resistance: 9.5,
+        }
+    }
+
+    fn dynamics() -> Box<dyn Dynamics> {
+        dynamics_of(free_piston())
+    }
+
+    fn dynamics_of(free_piston: FreePiston) -> Box<dyn Dynamics> {
+        free_piston
+            .dynamics(&state(10e6))
+            .expect("free pistons have dynamics")
+    }
+
+    #[test]
+    fn rejects_impossible_config() {
+        let config = Config {
+            frequency_hint: 50.0,
+            stroke_hint: 0.02,
+            V_mid_c: 2e-4,
+            R_c: 0.0,
+            W_parasitic_c: 0.0,
+            V_mid_e: 1e-4,
+            R_e: 0.0,
+            W_parasitic_e: 0.0,
+            Q_parasitic_e: 0.0,
+            D_p: 0.08,
+            m_p: 5.0,
+            k_p: 1e5,
+            c_p: 100.0,
+            c2_p: 10.0,
+            D_d: 0.08,
+            D_rod: 0.025,
+            m_d: 1.0,
+            k_d: 5e4,
+            c_d: 10.0,
+            alternator: Some(AlternatorConfig {
+                K_m: 20.0,
+                R_coil: 0.5,
+                L_coil: 0.0,
+                R_load: 9.5,
+            }),
+        };
+        assert!(FreePiston::try_from(config.clone()).is_ok());
+        for invalid in [
+            Config {
+                m_p: 0.0,
+                ..config.clone()
+            },
+            Config {
+                m_d: 0.0,
+                ..config.clone()
+            },
+            Config {
+                D_rod: 0.08,
+                ..config.clone()
+            },
+            Config {
+                stroke_hint: f64::NAN,
+                ..config.clone()
+            },
+            Config {
+                alternator: Some(AlternatorConfig {
+                    K_m: 20.0,
+                    R_coil: 0.0,
+                    L_coil: 0.0,
+                    R_load: 0.0,
+                }),
+                ..config.clone()
+            },
+        ] {
+            assert!(FreePiston::try_from(invalid).is_err());
+        }
+    }
+
+    #[test]
+    fn volumes_follow_piston_motion() {
+        let dynamics = dynamics();
+        let (comp, exp) = dynamics.volumes(Motion::default());
+        assert_eq!((comp.value, exp.value), (2e-4, 1e-4));
+
+        // Moving the piston toward the expansion space compresses the gas
+        // below the displacer, and moving the displacer shifts gas from the
+        // expansion space to the compression space
+        let (comp, exp) = dynamics.volumes(Motion {
+            x_p: 0.01,
+            v_p: 1.0,
+            x_d: 0.01,
+            v_d: 2.0,
+            current: 0.0,
+        });
+        assert_relative_eq!(comp.value, 2e-4 + 4.5e-3 * 0.01 - 5e-3 * 0.01);
+        assert_relative_eq!(comp.deriv, 4.5e-3 * 2.0 - 5e-3);
+        assert_relative_eq!(exp.value, 1e-4 - 5e-3 * 0.01);
+        assert_relative_eq!(exp.deriv, -5e-3 * 2.0);
+    }
+
+    #[test]
+    fn gas_pressure_drives_pistons() {
+        let dynamics = dynamics();
+        let at_rest = dynamics.derivatives(Motion::default(), 10e6, 10e6);
+        assert_eq!(at_rest, Motion::default());
+
+        // Pressure above the bounce space pushes both pistons away from the
+        // expansion space, through the piston face and the displacer rod
+        let pushed = dynamics.derivatives(Motion::default(), 10.1e6, 10.1e6);
+        assert_relative_eq!(pushed.v_p, -0.1e6 * 5e-3 / 5.0);
+        assert_relative_eq!(pushed.v_d, -0.1e6 * 5e-4 / 1.0, max_relative = 1e-9);
+
+        // The initial motion starts the piston at mid-stroke moving up
+        let initial = dynamics.initial_motion();
+        assert_eq!(initial.x_p, 0.0);
+        assert_relative_eq!(initial.v_p, 2.0 * PI * 50.0 * 0.01);
+        assert_eq!(initial.x_d, 0.01);
+        assert_eq!(initial.current, 0.0);
+    }
+
+    #[test]
+    fn alternator_loads_piston() {
+        let dynamics = dynamics_of(FreePiston {
+            alternator: Some(alternator()),
+            ..free_piston()
+        });
+        let motion = Motion {
+            v_p: 2.0,
+            current: 3.0,
+            ..Motion::default()
+        };
+
+        // The coil current lags the back-emf, and the current resists the
+        // piston on top of its damping
+        let deriv = dynamics.derivatives(motion, 10e6, 10e6);
+        assert_relative_eq!(deriv.current, (20.0 * 2.0 - 10.0 * 3.0) / 0.01);
+        assert_relative_eq!(deriv.v_p, (-100.0 * 2.0 - 10.0 * 4.0 - 20.0 * 3.0) / 5.0);
+
+        // Power is taken from the piston, some of which heats the coil
+        let power = dynamics
+            .electrical_power(motion)
+            .expect("an alternator generates power");
+        assert_relative_eq!(power.generated, 20.0 * 2.0 * 3.0);
+        assert_relative_eq!(power.copper, 0.5 * 3.0 * 3.0);
+
+        // The first cycle starts with the current that matches the velocity
+        let initial = dynamics.initial_motion();
+        assert_relative_eq!(initial.current, 20.0 * initial.v_p / 10.0);
+
+        // Without inductance, the current follows the piston velocity
+        let dynamics = dynamics_of(FreePiston {
+            alternator: Some(Alternator {
+                coil_inductance: 0.0,
+                ..alternator()
+            }),
+            ..free_piston()
+        });
+        let deriv = dynamics.derivatives(motion, 10e6, 10e6);
+        assert_eq!(deriv.current, 0.0);
+        assert_relative_eq!(deriv.v_p, (-100.0 * 2.0 - 10.0 * 4.0 - 20.0 * 4.0) / 5.0);
+        let power = dynamics
+            .electrical_power(motion)
+            .expect("an alternator generates power");
+        assert_relative_eq!(power.copper, 0.5 * 4.0 * 4.0);
+    }
+}
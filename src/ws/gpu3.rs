@@ -5,20 +5,20 @@ use super::{CompVolume, ExpVolume, Parasitics, State, ThermalResistance, Working
 pub struct GPU3 {}
 
 #[allow(non_snake_case)]
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
 pub struct Config {
-    frequency: f64,
-    V_clearance_c: f64,
-    R_c: f64,
-    V_clearance_e: f64,
-    R_e: f64,
-    r_crank: f64,
-    L_conn: f64,
-    eccentricity: f64,
-    D: f64,
-    D_dr: f64,
-    L: f64,
-    h: f64,
+    pub frequency: f64,
+    pub V_clearance_c: f64,
+    pub R_c: f64,
+    pub V_clearance_e: f64,
+    pub R_e: f64,
+    pub r_crank: f64,
+    pub L_conn: f64,
+    pub eccentricity: f64,
+    pub D: f64,
+    pub D_dr: f64,
+    pub L: f64,
+    pub h: f64,
 }
 
 impl WorkingSpaces for GPU3 {
@@ -26,7 +26,7 @@ impl WorkingSpaces for GPU3 {
         todo!()
     }
 
-    fn volumes(&self, _state: &State) -> Box<(dyn Fn(f64) -> (CompVolume, ExpVolume))> {
+    fn volumes(&self, _state: &State) -> Box<dyn Fn(f64) -> (CompVolume, ExpVolume)> {
         todo!()
     }
 
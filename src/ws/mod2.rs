@@ -7,24 +7,24 @@ use super::{CompVolume, ExpVolume, Parasitics, State, ThermalResistance, Working
 pub struct Mod2 {}
 
 #[allow(non_snake_case)]
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
 pub struct Config {
-    frequency: f64,
-    phaseAngle: f64,
-    D: f64,
-    h: f64,
-    L: f64,
-    stroke: f64,
-    V_clearance_c: f64,
-    R_c: f64,
-    V_clearance_e: f64,
-    R_e: f64,
-    material_p: Material,
-    material_c: Material,
-    th_pw: f64,
-    th_cw: f64,
-    L_cond: f64,
-    e: f64,
+    pub frequency: f64,
+    pub phaseAngle: f64,
+    pub D: f64,
+    pub h: f64,
+    pub L: f64,
+    pub stroke: f64,
+    pub V_clearance_c: f64,
+    pub R_c: f64,
+    pub V_clearance_e: f64,
+    pub R_e: f64,
+    pub material_p: Material,
+    pub material_c: Material,
+    pub th_pw: f64,
+    pub th_cw: f64,
+    pub L_cond: f64,
+    pub e: f64,
 }
 
 impl WorkingSpaces for Mod2 {
@@ -32,7 +32,7 @@ impl WorkingSpaces for Mod2 {
         todo!()
     }
 
-    fn volumes(&self, _state: &State) -> Box<(dyn Fn(f64) -> (CompVolume, ExpVolume))> {
+    fn volumes(&self, _state: &State) -> Box<dyn Fn(f64) -> (CompVolume, ExpVolume)> {
         todo!()
     }
 
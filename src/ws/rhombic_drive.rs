@@ -4,7 +4,11 @@ use serde::Deserialize;
 
 use crate::types::ParasiticPower;
 
-use super::{CompVolume, ExpVolume, Parasitics, State, ThermalResistance, WorkingSpaces};
+use super::{
+    appendix_gap, wall_heat, wall_heat::Cylinder, AppendixGap, CompVolume, ExpVolume, Kinematics,
+    Parasitics, PistonForces, SpacePressures, State, ThermalResistance, WallConductance, WallHeat,
+    WorkingSpaces,
+};
 
 const DEFAULT_FREQ: f64 = 50.;
 const DEFAULT_V_CLEARANCE_C: f64 = 5.785e-6;
@@ -20,6 +24,9 @@ const DEFAULT_ECCENTRICITY: f64 = 0.02065;
 const DEFAULT_D_P: f64 = 0.0698;
 const DEFAULT_D_D: f64 = 0.0696;
 
+// Number of crank angles sampled to find the ends of the strokes
+const STROKE_POINTS: u32 = 7200;
+
 #[allow(non_snake_case)]
 pub struct RhombicDrive {
     frequency: f64,
@@ -29,9 +36,12 @@ pub struct RhombicDrive {
     R_exp: f64,
     V_clearance_c: f64,
     V_clearance_e: f64,
+    appendix_gap: Option<AppendixGap>,
+    wall_heat: Option<WallHeat>,
 }
 
 #[allow(non_snake_case)]
+#[derive(Debug, Clone, Copy)]
 pub struct Geometry {
     eccentricity: f64,
     r_crank: f64,
@@ -41,21 +51,25 @@ pub struct Geometry {
 }
 
 #[allow(non_snake_case)]
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
 pub struct Config {
-    frequency: f64,
-    V_clearance_c: f64,
-    R_c: f64,
-    W_parasitic_c: f64,
-    V_clearance_e: f64,
-    R_e: f64,
-    W_parasitic_e: f64,
-    Q_parasitic_e: f64,
-    r_crank: f64,
-    L_conn: f64,
-    eccentricity: f64,
-    D_p: f64,
-    D_d: f64,
+    pub frequency: f64,
+    pub V_clearance_c: f64,
+    pub R_c: f64,
+    pub W_parasitic_c: f64,
+    pub V_clearance_e: f64,
+    pub R_e: f64,
+    pub W_parasitic_e: f64,
+    pub Q_parasitic_e: f64,
+    pub r_crank: f64,
+    pub L_conn: f64,
+    pub eccentricity: f64,
+    pub D_p: f64,
+    pub D_d: f64,
+    #[serde(default)]
+    pub appendix_gap: Option<appendix_gap::Config>,
+    #[serde(default)]
+    pub wall_heat: Option<wall_heat::Config>,
 }
 
 #[allow(non_snake_case)]
@@ -78,6 +92,8 @@ impl RhombicDrive {
             R_exp,
             V_clearance_c,
             V_clearance_e,
+            appendix_gap: None,
+            wall_heat: None,
         }
     }
 }
@@ -88,7 +104,7 @@ impl WorkingSpaces for RhombicDrive {
     }
 
     #[allow(non_snake_case)]
-    fn volumes(&self, _state: &State) -> Box<(dyn Fn(f64) -> (CompVolume, ExpVolume))> {
+    fn volumes(&self, _state: &State) -> Box<dyn Fn(f64) -> (CompVolume, ExpVolume)> {
         let omega = 2. * PI * self.frequency;
 
         let eccentricity = self.geometry.eccentricity;
@@ -126,14 +142,86 @@ impl WorkingSpaces for RhombicDrive {
     }
 
     fn thermal_resistance(&self, _state: &State) -> ThermalResistance {
+        if self.wall_heat.is_some() {
+            return ThermalResistance::default();
+        }
         ThermalResistance {
             comp: self.R_comp,
             exp: self.R_exp,
         }
     }
 
-    fn parasitics(&self, _state: &State) -> Parasitics {
-        self.parasitics
+    fn parasitics(&self, state: &State) -> Parasitics {
+        let mut parasitics = self.parasitics;
+        if let Some(gap) = &self.appendix_gap {
+            parasitics.exp.thermal += gap.loss(state, self.frequency);
+        }
+        parasitics
+    }
+
+    fn kinematics(&self, _state: &State) -> Option<Box<dyn Kinematics>> {
+        Some(Box::new(self.geometry))
+    }
+
+    fn wall_conductance(&self, _state: &State) -> Option<Box<dyn WallConductance>> {
+        self.wall_heat
+            .map(|wall_heat| Box::new(wall_heat) as Box<dyn WallConductance>)
+    }
+}
+
+impl Geometry {
+    /// Returns the strokes (m) of the piston and the displacer
+    ///
+    /// The piston yoke sits a height `b` above the crank pins and the
+    /// displacer yoke the same height below them, so they follow
+    /// `r_crank * sin(theta) + b` and `b - r_crank * sin(theta)`, which are
+    /// sampled over a revolution for the ends of their strokes.
+    fn strokes(&self) -> (f64, f64) {
+        let (mut piston, mut displacer) = (
+            (f64::INFINITY, f64::NEG_INFINITY),
+            (f64::INFINITY, f64::NEG_INFINITY),
+        );
+        for i in 0..STROKE_POINTS {
+            let theta = 2. * PI * f64::from(i) / f64::from(STROKE_POINTS);
+            let b_theta = (self.L_conn.powi(2)
+                - (self.eccentricity + self.r_crank * theta.cos()).powi(2))
+            .sqrt();
+            let y = self.r_crank * theta.sin();
+            for (ends, height) in [(&mut piston, b_theta + y), (&mut displacer, b_theta - y)] {
+                *ends = (ends.0.min(height), ends.1.max(height));
+            }
+        }
+        (piston.1 - piston.0, displacer.1 - displacer.0)
+    }
+}
+
+impl Kinematics for Geometry {
+    /// Return the gas forces at crank angle `theta` (rad)
+    ///
+    /// The piston yoke sits a height `b` above the crank pins and the
+    /// displacer yoke the same height below them, and each yoke is driven by
+    /// a pair of rods.  The compression space acts on the piston and on the
+    /// piston's share of the displacer face, while the buffer acts on the
+    /// rest of both.
+    #[allow(non_snake_case)]
+    fn forces(&self, theta: f64, pres: SpacePressures) -> PistonForces {
+        let A_p = PI * self.D_p.powi(2) / 4.;
+        let A_d = PI * self.D_d.powi(2) / 4.;
+        let b_theta =
+            (self.L_conn.powi(2) - (self.eccentricity + self.r_crank * theta.cos()).powi(2)).sqrt();
+
+        let piston = (pres.buffer - pres.comp) * A_p;
+        let displacer = (pres.comp - pres.buffer) * A_p - (pres.exp - pres.buffer) * A_d;
+
+        // The axial force on a yoke is shared by its two rods, which lean
+        // away from the axis
+        let rod_factor = self.L_conn / (2. * b_theta);
+        PistonForces {
+            piston,
+            displacer,
+            piston_rod: -piston * rod_factor,
+            displacer_rod: displacer * rod_factor,
+        }
     }
 }
 
@@ -157,6 +245,8 @@ impl Default for RhombicDrive {
             R_exp: DEFAULT_R_E,
             V_clearance_c: DEFAULT_V_CLEARANCE_C,
             V_clearance_e: DEFAULT_V_CLEARANCE_E,
+            appendix_gap: None,
+            wall_heat: None,
         }
     }
 }
@@ -189,6 +279,8 @@ impl Default for Config {
             eccentricity: DEFAULT_ECCENTRICITY,
             D_p: DEFAULT_D_P,
             D_d: DEFAULT_D_D,
+            appendix_gap: None,
+            wall_heat: None,
         }
     }
 }
@@ -199,9 +291,10 @@ impl From<Config> for RhombicDrive {
             eccentricity: config.eccentricity,
             r_crank: config.r_crank,
             D_p: config.D_p,
-            D_d: config.D_p,
+            D_d: config.D_d,
             L_conn: config.L_conn,
         };
+        let (piston_stroke, displacer_stroke) = geometry.strokes();
         let parasitics = Parasitics {
             comp: ParasiticPower {
                 mechanical: config.W_parasitic_c,
@@ -221,6 +314,167 @@ impl From<Config> for RhombicDrive {
             R_exp: config.R_e,
             V_clearance_c: config.V_clearance_c,
             V_clearance_e: config.V_clearance_e,
+            appendix_gap: config
+                .appendix_gap
+                .map(|gap| AppendixGap::new(config.D_d, displacer_stroke, &gap)),
+            wall_heat: config.wall_heat.map(|wall_heat| {
+                let comp = Cylinder {
+                    bore: config.D_p,
+                    stroke: piston_stroke,
+                };
+                let exp = Cylinder {
+                    bore: config.D_d,
+                    stroke: displacer_stroke,
+                };
+                WallHeat::new(&wall_heat, config.frequency, comp, exp)
+            }),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use crate::{engine::Pressure, ws::tests::state};
+
+    use super::*;
+
+    /// Return the heights (m) of the piston and displacer yokes above the
+    /// crank pins
+    fn positions(geometry: &Geometry, theta: f64) -> (f64, f64) {
+        let b_theta = (geometry.L_conn.powi(2)
+            - (geometry.eccentricity + geometry.r_crank * theta.cos()).powi(2))
+        .sqrt();
+        let y = geometry.r_crank * theta.sin();
+        (y + b_theta, y - b_theta)
+    }
+
+    #[test]
+    fn forces_do_the_work_of_the_gas() {
+        let drive = RhombicDrive::default();
+        let state = state(10e6);
+        let volumes = drive.volumes(&state);
+        let kinematics = drive.kinematics(&state).expect("drive has kinematics");
+        let omega = 2. * PI * drive.frequency;
+        let pres = SpacePressures {
+            comp: 11e6,
+            exp: 9.5e6,
+            buffer: 10e6,
+        };
+        let step = 1e-6;
+        for i in 0..12 {
+            let theta = 0.1 + f64::from(i) * PI / 6.;
+
+            // The work done on the yokes over a small rotation matches the
+            // work done by the gas as the volumes change
+            let (comp, exp) = volumes(theta / omega);
+            let gas_torque = ((pres.comp - pres.buffer) * comp.deriv
+                + (pres.exp - pres.buffer) * exp.deriv)
+                / omega;
+            let (after, before) = (
+                positions(&drive.geometry, theta + step),
+                positions(&drive.geometry, theta - step),
+            );
+            let forces = kinematics.forces(theta, pres);
+            let yoke_torque = (forces.piston * (after.0 - before.0)
+                + forces.displacer * (after.1 - before.1))
+                / (2. * step);
+            assert_relative_eq!(yoke_torque, gas_torque, max_relative = 1e-6, epsilon = 1e-6);
+        }
+
+        // Rods carry no load when the pressures are balanced
+        let balanced = SpacePressures {
+            comp: 10e6,
+            exp: 10e6,
+            buffer: 10e6,
+        };
+        assert_eq!(kinematics.forces(1.0, balanced), PistonForces::default());
+    }
+
+    #[test]
+    fn appendix_gap_follows_the_displacer() {
+        let gap = appendix_gap::Config {
+            width: 5e-4,
+            length: 0.05,
+            stroke: None,
+            k_gas: 0.2,
+        };
+        let config = Config {
+            Q_parasitic_e: 10.0,
+            appendix_gap: Some(gap.clone()),
+            ..Config::default()
+        };
+        let drive = RhombicDrive::from(config.clone());
+        let state = State {
+            pres: Pressure {
+                avg: 10e6,
+                max: 12e6,
+                min: 8e6,
+                t_zero: 10e6,
+            },
+            temp_chx: 300.0,
+            temp_hhx: 900.0,
+            dens: 4.0,
+            cp: 14_300.0,
+        };
+
+        // The displacer alone sweeps the expansion space, so its stroke is
+        // the swing in that volume over the displacer area
+        let volumes = drive.volumes(&state);
+        let period = 1. / drive.frequency;
+        let (min, max) = (0..3600)
+            .map(|i| volumes(period * f64::from(i) / 3600.).1.value)
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), vol| {
+                (min.min(vol), max.max(vol))
+            });
+        let stroke = (max - min) / (PI * drive.geometry.D_d.powi(2) / 4.);
+        assert_relative_eq!(drive.geometry.strokes().1, stroke, max_relative = 1e-6);
+
+        let loss = AppendixGap::new(config.D_d, stroke, &gap).loss(&state, drive.frequency);
+        let parasitics = drive.parasitics(&state);
+        assert!(loss > 0.0);
+        assert_relative_eq!(parasitics.exp.thermal, 10.0 + loss, max_relative = 1e-6);
+    }
+
+    #[test]
+    fn displacer_uses_its_own_diameter() {
+        let config = Config {
+            D_d: 0.05,
+            ..Config::default()
+        };
+        let drive = RhombicDrive::from(config);
+        let state = state(10e6);
+
+        // With the compression space at the buffer pressure, only the
+        // expansion space pushes on the displacer, over the displacer area
+        let pres = SpacePressures {
+            comp: 10e6,
+            exp: 11e6,
+            buffer: 10e6,
+        };
+        let forces = drive
+            .kinematics(&state)
+            .expect("drive has kinematics")
+            .forces(1.0, pres);
+        assert_relative_eq!(
+            forces.displacer,
+            -1e6 * PI * 0.05_f64.powi(2) / 4.,
+            max_relative = 1e-12
+        );
+        assert_eq!(forces.piston, 0.0);
+
+        // The displacer alone sweeps the expansion space
+        let volumes = drive.volumes(&state);
+        let (min, max) = (0..3600)
+            .map(|i| volumes(f64::from(i) / 3600. / drive.frequency).1.value)
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), vol| {
+                (min.min(vol), max.max(vol))
+            });
+        assert_relative_eq!(
+            max - min,
+            PI * 0.05_f64.powi(2) / 4. * drive.geometry.strokes().1,
+            max_relative = 1e-6
+        );
+    }
+}
@@ -1,10 +1,14 @@
 use std::f64::consts::PI;
 
+use anyhow::{ensure, Context};
 use serde::Deserialize;
 
 use crate::types::ParasiticPower;
 
-use super::{CompVolume, ExpVolume, Parasitics, State, ThermalResistance, WorkingSpaces};
+use super::{
+    appendix_gap, wall_heat, AppendixGap, CompVolume, ExpVolume, Parasitics, State,
+    ThermalResistance, WallConductance, WallHeat, WorkingSpaces,
+};
 
 pub struct SinusoidalDrive {
     pub frequency: f64,
@@ -13,6 +17,8 @@ pub struct SinusoidalDrive {
     pub exp_geometry: Geometry,
     pub thermal_resistance: ThermalResistance,
     pub parasitics: Parasitics,
+    pub appendix_gap: Option<AppendixGap>,
+    pub wall_heat: Option<WallHeat>,
 }
 
 pub struct Geometry {
@@ -21,19 +27,23 @@ pub struct Geometry {
 }
 
 #[allow(non_snake_case)]
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
 pub struct Config {
-    pub(crate) frequency: f64,
-    pub(crate) phase_angle: f64,
-    pub(crate) V_swept_c: f64,
-    pub(crate) V_clearance_c: f64,
-    pub(crate) R_c: f64,
-    pub(crate) W_parasitic_c: f64,
-    pub(crate) V_swept_e: f64,
-    pub(crate) V_clearance_e: f64,
-    pub(crate) R_e: f64,
-    pub(crate) W_parasitic_e: f64,
-    pub(crate) Q_parasitic_e: f64,
+    pub frequency: f64,
+    pub phase_angle: f64,
+    pub V_swept_c: f64,
+    pub V_clearance_c: f64,
+    pub R_c: f64,
+    pub W_parasitic_c: f64,
+    pub V_swept_e: f64,
+    pub V_clearance_e: f64,
+    pub R_e: f64,
+    pub W_parasitic_e: f64,
+    pub Q_parasitic_e: f64,
+    #[serde(default)]
+    pub appendix_gap: Option<appendix_gap::Config>,
+    #[serde(default)]
+    pub wall_heat: Option<wall_heat::Config>,
 }
 
 impl WorkingSpaces for SinusoidalDrive {
@@ -41,7 +51,7 @@ impl WorkingSpaces for SinusoidalDrive {
         self.frequency
     }
 
-    fn volumes(&self, _state: &State) -> Box<(dyn Fn(f64) -> (CompVolume, ExpVolume))> {
+    fn volumes(&self, _state: &State) -> Box<dyn Fn(f64) -> (CompVolume, ExpVolume)> {
         let vol_clear_c = self.comp_geometry.clearance_volume;
         let vol_swept_c = self.comp_geometry.swept_volume;
 
@@ -66,11 +76,23 @@ impl WorkingSpaces for SinusoidalDrive {
     }
 
     fn thermal_resistance(&self, _state: &State) -> ThermalResistance {
+        if self.wall_heat.is_some() {
+            return ThermalResistance::default();
+        }
         self.thermal_resistance
     }
 
-    fn parasitics(&self, _state: &State) -> Parasitics {
-        self.parasitics
+    fn parasitics(&self, state: &State) -> Parasitics {
+        let mut parasitics = self.parasitics;
+        if let Some(gap) = &self.appendix_gap {
+            parasitics.exp.thermal += gap.loss(state, self.frequency);
+        }
+        parasitics
+    }
+
+    fn wall_conductance(&self, _state: &State) -> Option<Box<dyn WallConductance>> {
+        self.wall_heat
+            .map(|wall_heat| Box::new(wall_heat) as Box<dyn WallConductance>)
     }
 }
 
@@ -88,12 +110,16 @@ impl Default for Config {
             R_e: f64::INFINITY,
             W_parasitic_e: 0.,
             Q_parasitic_e: 0.,
+            appendix_gap: None,
+            wall_heat: None,
         }
     }
 }
 
-impl From<Config> for SinusoidalDrive {
-    fn from(config: Config) -> Self {
+impl TryFrom<Config> for SinusoidalDrive {
+    type Error = anyhow::Error;
+
+    fn try_from(config: Config) -> Result<Self, Self::Error> {
         let parasitics = Parasitics {
             comp: ParasiticPower {
                 mechanical: config.W_parasitic_c,
@@ -105,7 +131,32 @@ impl From<Config> for SinusoidalDrive {
                 ..ParasiticPower::default()
             },
         };
-        Self {
+        // The displacer sweeps the expansion space, so its area follows from
+        // the swept volume and its stroke
+        let appendix_gap = config
+            .appendix_gap
+            .map(|gap| {
+                let stroke = gap
+                    .stroke
+                    .context("an appendix gap in a sinusoidal drive needs the displacer stroke")?;
+                ensure!(
+                    stroke > 0.,
+                    "displacer stroke must be positive, not {stroke}"
+                );
+                let diameter = (4. * config.V_swept_e / (PI * stroke)).sqrt();
+                Ok(AppendixGap::new(diameter, stroke, &gap))
+            })
+            .transpose()?;
+        // The spaces have no geometry beyond their volumes, so the bores are
+        // configured and the strokes follow from the swept volumes
+        let wall_heat = config
+            .wall_heat
+            .map(|wall_heat| {
+                let (comp, exp) = wall_heat.cylinders(config.V_swept_c, config.V_swept_e)?;
+                Ok::<_, anyhow::Error>(WallHeat::new(&wall_heat, config.frequency, comp, exp))
+            })
+            .transpose()?;
+        Ok(Self {
             frequency: config.frequency,
             phase_angle: config.phase_angle,
             comp_geometry: Geometry {
@@ -121,7 +172,9 @@ impl From<Config> for SinusoidalDrive {
                 exp: config.R_e,
             },
             parasitics,
-        }
+            appendix_gap,
+            wall_heat,
+        })
     }
 }
 
@@ -129,7 +182,10 @@ impl From<Config> for SinusoidalDrive {
 mod tests {
     use approx::{assert_relative_eq, relative_eq};
 
-    use crate::engine::Pressure;
+    use crate::{
+        engine::Pressure,
+        ws::tests::{sinusoidal_drive, state},
+    };
 
     use super::*;
 
@@ -139,23 +195,18 @@ mod tests {
         let swept_vol_c = 2e-4;
         let clear_vol_e = 3e-5;
         let swept_vol_e = 4e-4;
-        let drive = SinusoidalDrive {
-            frequency: 10.0,
-            phase_angle: 90.0,
-            comp_geometry: Geometry {
+        let drive = sinusoidal_drive(
+            10.0,
+            Geometry {
                 clearance_volume: clear_vol_c,
                 swept_volume: swept_vol_c,
             },
-            exp_geometry: Geometry {
+            Geometry {
                 clearance_volume: clear_vol_e,
                 swept_volume: swept_vol_e,
             },
-            thermal_resistance: ThermalResistance::default(),
-            parasitics: Parasitics::default(),
-        };
-        let volumes = drive.volumes(&State {
-            pres: Pressure::constant(0.0),
-        }); // volumes as a function of time
+        );
+        let volumes = drive.volumes(&state(0.0)); // volumes as a function of time
 
         let (vol_c_0, vol_e_0) = volumes(0.0); // volumes at time zero
         let (_, vol_e_25) = volumes(0.025); // volumes at 25 ms (1/4 through cycle)
@@ -208,4 +259,56 @@ mod tests {
             "expansion piston is not moving 3/4 through cycle"
         );
     }
+
+    #[test]
+    fn appendix_gap_adds_expansion_heat_loss() {
+        let gap = appendix_gap::Config {
+            width: 5e-4,
+            length: 0.05,
+            stroke: Some(0.03),
+            k_gas: 0.2,
+        };
+        // A displacer 0.07 m across sweeps the expansion space over its stroke
+        let config = Config {
+            V_swept_e: PI * 0.07_f64.powi(2) / 4. * 0.03,
+            Q_parasitic_e: 10.0,
+            appendix_gap: Some(gap.clone()),
+            ..Config::default()
+        };
+        let drive = SinusoidalDrive::try_from(config.clone()).expect("config is valid");
+        let state = State {
+            pres: Pressure {
+                avg: 10e6,
+                max: 12e6,
+                min: 8e6,
+                t_zero: 10e6,
+            },
+            temp_chx: 300.0,
+            temp_hhx: 900.0,
+            dens: 4.0,
+            cp: 14_300.0,
+        };
+
+        // Shuttle loss = pi * 0.03^2 * 0.2 * 0.07 / (8 * 5e-4 * 0.05) * 600 W,
+        // and 4 kg/m^3 of gas in the pi * 0.07 * 5e-4 * 0.05 m^3 gap is
+        // pumped with a 40% pressure swing, carrying 300 K at 66.6667 Hz
+        let parasitics = drive.parasitics(&state);
+        assert_relative_eq!(
+            parasitics.exp.thermal,
+            10.0 + 118.752_202 + 2_515.788_655,
+            max_relative = 1e-8
+        );
+        assert_eq!(parasitics.comp.thermal, 0.0);
+
+        // Without a stroke, the displacer diameter cannot be found
+        let gap = appendix_gap::Config {
+            stroke: None,
+            ..gap
+        };
+        assert!(SinusoidalDrive::try_from(Config {
+            appendix_gap: Some(gap),
+            ..config
+        })
+        .is_err());
+    }
 }
@@ -0,0 +1,522 @@
+use std::f64::consts::PI;
+
+use anyhow::{ensure, Context};
+use serde::Deserialize;
+
+use crate::types::ParasiticPower;
+
+use super::{
+    wall_heat, CompVolume, ExpVolume, Parasitics, State, ThermalResistance, WallConductance,
+    WallHeat, WorkingSpaces,
+};
+
+// Number of crank angles sampled to find the swept volume of a profile
+const SWEPT_POINTS: u32 = 3600;
+
+/// Working spaces with volumes given as profiles over a crank revolution
+///
+/// Mechanisms without a closed form for their volumes, like a Ross yoke, a
+/// swashplate, or a cam-driven displacer, are described by a volume profile
+/// for each space.  A profile is either a table of volumes, which may be
+/// measured or exported from CAD, or the coefficients of a Fourier series.
+/// Wall heat transfer treats each space as a cylinder of its configured bore
+/// that sweeps the swing in its profile.
+pub struct VolumeProfile {
+    pub frequency: f64,
+    pub comp: Profile,
+    pub exp: Profile,
+    pub thermal_resistance: ThermalResistance,
+    pub parasitics: Parasitics,
+    pub wall_heat: Option<WallHeat>,
+}
+
+/// The volume (m^3) of a space as a function of the crank angle
+#[derive(Debug, Clone, PartialEq)]
+pub enum Profile {
+    Spline(Spline),
+    Fourier(Fourier),
+}
+
+/// A periodic cubic spline through tabulated volumes
+///
+/// The spline and its first two derivatives are continuous everywhere,
+/// including where the crank angle wraps around.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spline {
+    angles: Vec<f64>,
+    volumes: Vec<f64>,
+    curvatures: Vec<f64>,
+}
+
+/// A Fourier series in the crank angle `theta`
+///
+/// The volume is `mean + cos[n-1] cos(n theta) + sin[n-1] sin(n theta)`
+/// summed over the harmonics `n`, starting from one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fourier {
+    pub mean: f64,
+    pub cos: Vec<f64>,
+    pub sin: Vec<f64>,
+}
+
+/// Configuration for the volume profile of one space
+///
+/// A `table` gives volumes (m^3) at crank angles (degrees), which must
+/// increase and span less than one revolution.  A final angle that closes
+/// the revolution is allowed and ignored.  A `fourier` series gives its
+/// coefficients (m^3) directly.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ProfileConfig {
+    Table {
+        crank_angle: Vec<f64>,
+        volume: Vec<f64>,
+    },
+    Fourier {
+        mean: f64,
+        cos: Vec<f64>,
+        sin: Vec<f64>,
+    },
+}
+
+#[allow(non_snake_case)]
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct Config {
+    pub frequency: f64,
+    pub comp: ProfileConfig,
+    pub R_c: f64,
+    pub W_parasitic_c: f64,
+    pub exp: ProfileConfig,
+    pub R_e: f64,
+    pub W_parasitic_e: f64,
+    pub Q_parasitic_e: f64,
+    #[serde(default)]
+    pub wall_heat: Option<wall_heat::Config>,
+}
+
+impl Profile {
+    /// Returns the volume (m^3) and its derivative (m^3/rad) at crank angle
+    /// `theta` (rad)
+    #[must_use]
+    pub fn volume(&self, theta: f64) -> (f64, f64) {
+        match self {
+            Profile::Spline(spline) => spline.volume(theta),
+            Profile::Fourier(fourier) => fourier.volume(theta),
+        }
+    }
+
+    /// Returns the swing (m^3) between the largest and smallest volumes over
+    /// a revolution
+    #[must_use]
+    pub fn swept_volume(&self) -> f64 {
+        let (min, max) = (0..SWEPT_POINTS)
+            .map(|i| {
+                self.volume(2. * PI * f64::from(i) / f64::from(SWEPT_POINTS))
+                    .0
+            })
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), vol| {
+                (min.min(vol), max.max(vol))
+            });
+        max - min
+    }
+}
+
+impl Spline {
+    /// Create a spline through `volumes` (m^3) at crank `angles` (degrees)
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if there are fewer than three angles, if the
+    /// number of angles and volumes differ, or if the angles do not increase
+    /// within a single revolution.
+    pub fn new(angles: &[f64], volumes: &[f64]) -> anyhow::Result<Self> {
+        ensure!(
+            angles.len() == volumes.len(),
+            "each crank angle needs a volume, but there are {} angles and {} volumes",
+            angles.len(),
+            volumes.len()
+        );
+        let mut angles: Vec<_> = angles.iter().map(|angle| angle.to_radians()).collect();
+        let mut volumes = volumes.to_vec();
+        if angles.len() > 1 && (angles[angles.len() - 1] - angles[0] - 2. * PI).abs() < 1e-9 {
+            angles.pop();
+            volumes.pop();
+        }
+        let n = angles.len();
+        ensure!(n >= 3, "at least three crank angles are required");
+        ensure!(
+            angles.windows(2).all(|pair| pair[0] < pair[1]) && angles[n - 1] - angles[0] < 2. * PI,
+            "crank angles must increase within a single revolution"
+        );
+
+        // Continuity of the slope at each knot, with the knots wrapping
+        // around the revolution, sets the curvatures
+        let width = |i: usize| {
+            if i + 1 < n {
+                angles[i + 1] - angles[i]
+            } else {
+                angles[0] + 2. * PI - angles[n - 1]
+            }
+        };
+        let slope = |i: usize| (volumes[(i + 1) % n] - volumes[i]) / width(i);
+        let mut a = na::DMatrix::zeros(n, n);
+        let mut b = na::DVector::zeros(n);
+        for i in 0..n {
+            let prev = (i + n - 1) % n;
+            a[(i, prev)] += width(prev);
+            a[(i, i)] += 2. * (width(prev) + width(i));
+            a[(i, (i + 1) % n)] += width(i);
+            b[i] = 6. * (slope(i) - slope(prev));
+        }
+        let curvatures = a
+            .lu()
+            .solve(&b)
+            .context("spline curvatures could not be found")?
+            .data
+            .into();
+
+        Ok(Self {
+            angles,
+            volumes,
+            curvatures,
+        })
+    }
+
+    /// Returns the volume (m^3) and its derivative (m^3/rad) at crank angle
+    /// `theta` (rad)
+    #[must_use]
+    pub fn volume(&self, theta: f64) -> (f64, f64) {
+        let start = self.angles[0];
+        let theta = start + (theta - start).rem_euclid(2. * PI);
+        let lo = self.angles.partition_point(|&angle| angle <= theta) - 1;
+        let hi = (lo + 1) % self.angles.len();
+        let next_angle = if hi == 0 {
+            start + 2. * PI
+        } else {
+            self.angles[hi]
+        };
+
+        // Distances from the knots on either side of `theta`
+        let width = next_angle - self.angles[lo];
+        let (to_hi, from_lo) = (next_angle - theta, theta - self.angles[lo]);
+        let (curv_lo, curv_hi) = (self.curvatures[lo], self.curvatures[hi]);
+        let (line_lo, line_hi) = (
+            self.volumes[lo] - curv_lo * width * width / 6.,
+            self.volumes[hi] - curv_hi * width * width / 6.,
+        );
+        let value = (curv_lo * to_hi.powi(3) + curv_hi * from_lo.powi(3)) / (6. * width)
+            + (line_lo * to_hi + line_hi * from_lo) / width;
+        let deriv = (curv_hi * from_lo * from_lo - curv_lo * to_hi * to_hi) / (2. * width)
+            + (line_hi - line_lo) / width;
+        (value, deriv)
+    }
+}
+
+impl Fourier {
+    /// Returns the volume (m^3) and its derivative (m^3/rad) at crank angle
+    /// `theta` (rad)
+    #[must_use]
+    pub fn volume(&self, theta: f64) -> (f64, f64) {
+        let harmonic = |k: usize| {
+            #[allow(clippy::cast_precision_loss)]
+            let n = (k + 1) as f64;
+            let (sin, cos) = (n * theta).sin_cos();
+            (n, sin, cos)
+        };
+        let cos_terms = self.cos.iter().enumerate().map(|(k, coef)| {
+            let (n, sin, cos) = harmonic(k);
+            (coef * cos, -n * coef * sin)
+        });
+        let sin_terms = self.sin.iter().enumerate().map(|(k, coef)| {
+            let (n, sin, cos) = harmonic(k);
+            (coef * sin, n * coef * cos)
+        });
+        cos_terms
+            .chain(sin_terms)
+            .fold((self.mean, 0.), |(value, deriv), (v, d)| {
+                (value + v, deriv + d)
+            })
+    }
+}
+
+impl WorkingSpaces for VolumeProfile {
+    fn frequency(&self, _state: &State) -> f64 {
+        self.frequency
+    }
+
+    fn volumes(&self, _state: &State) -> Box<dyn Fn(f64) -> (CompVolume, ExpVolume)> {
+        let omega = 2. * PI * self.frequency;
+        let (comp, exp) = (self.comp.clone(), self.exp.clone());
+        Box::new(move |time: f64| {
+            let theta = omega * time;
+            let (comp_value, comp_deriv) = comp.volume(theta);
+            let (exp_value, exp_deriv) = exp.volume(theta);
+            (
+                CompVolume {
+                    value: comp_value,
+                    deriv: comp_deriv * omega,
+                },
+                ExpVolume {
+                    value: exp_value,
+                    deriv: exp_deriv * omega,
+                },
+            )
+        })
+    }
+
+    fn thermal_resistance(&self, _state: &State) -> ThermalResistance {
+        if self.wall_heat.is_some() {
+            return ThermalResistance::default();
+        }
+        self.thermal_resistance
+    }
+
+    fn parasitics(&self, _state: &State) -> Parasitics {
+        self.parasitics
+    }
+
+    fn wall_conductance(&self, _state: &State) -> Option<Box<dyn WallConductance>> {
+        self.wall_heat
+            .map(|wall_heat| Box::new(wall_heat) as Box<dyn WallConductance>)
+    }
+}
+
+impl TryFrom<ProfileConfig> for Profile {
+    type Error = anyhow::Error;
+
+    fn try_from(config: ProfileConfig) -> Result<Self, Self::Error> {
+        Ok(match config {
+            ProfileConfig::Table {
+                crank_angle,
+                volume,
+            } => Profile::Spline(Spline::new(&crank_angle, &volume)?),
+            ProfileConfig::Fourier { mean, cos, sin } => {
+                Profile::Fourier(Fourier { mean, cos, sin })
+            }
+        })
+    }
+}
+
+impl TryFrom<Config> for VolumeProfile {
+    type Error = anyhow::Error;
+
+    fn try_from(config: Config) -> Result<Self, Self::Error> {
+        let parasitics = Parasitics {
+            comp: ParasiticPower {
+                mechanical: config.W_parasitic_c,
+                ..ParasiticPower::default()
+            },
+            exp: ParasiticPower {
+                thermal: config.Q_parasitic_e,
+                mechanical: config.W_parasitic_e,
+                ..ParasiticPower::default()
+            },
+        };
+        let comp = Profile::try_from(config.comp).context("invalid compression space profile")?;
+        let exp = Profile::try_from(config.exp).context("invalid expansion space profile")?;
+        let wall_heat = config
+            .wall_heat
+            .map(|wall_heat| {
+                let (comp_cylinder, exp_cylinder) =
+                    wall_heat.cylinders(comp.swept_volume(), exp.swept_volume())?;
+                Ok::<_, anyhow::Error>(WallHeat::new(
+                    &wall_heat,
+                    config.frequency,
+                    comp_cylinder,
+                    exp_cylinder,
+                ))
+            })
+            .transpose()?;
+        Ok(Self {
+            frequency: config.frequency,
+            comp,
+            exp,
+            thermal_resistance: ThermalResistance {
+                comp: config.R_c,
+                exp: config.R_e,
+            },
+            parasitics,
+            wall_heat,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use crate::ws::{
+        sinusoidal_drive::Geometry,
+        tests::{sinusoidal_drive, state},
+        SinusoidalDrive,
+    };
+
+    use super::*;
+
+    fn sinusoidal() -> SinusoidalDrive {
+        sinusoidal_drive(
+            50.0,
+            Geometry {
+                clearance_volume: 4e-5,
+                swept_volume: 1e-4,
+            },
+            Geometry {
+                clearance_volume: 2e-5,
+                swept_volume: 8e-5,
+            },
+        )
+    }
+
+    fn profiles(comp: Profile, exp: Profile) -> VolumeProfile {
+        VolumeProfile {
+            frequency: 50.0,
+            comp,
+            exp,
+            thermal_resistance: ThermalResistance::default(),
+            parasitics: Parasitics::default(),
+            wall_heat: None,
+        }
+    }
+
+    /// Assert that `volumes` matches the sinusoidal drive within `tol`
+    fn assert_sinusoidal(profile: &VolumeProfile, tol: f64) {
+        let expected = sinusoidal().volumes(&state(10e6));
+        let actual = profile.volumes(&state(10e6));
+        for i in 0..50 {
+            let time = 0.0013 + f64::from(i) * 0.0017;
+            let (comp, exp) = actual(time);
+            let (comp_expected, exp_expected) = expected(time);
+            assert_relative_eq!(comp.value, comp_expected.value, max_relative = tol);
+            assert_relative_eq!(exp.value, exp_expected.value, max_relative = tol);
+            assert_relative_eq!(comp.deriv, comp_expected.deriv, epsilon = tol * 3e-2);
+            assert_relative_eq!(exp.deriv, exp_expected.deriv, epsilon = tol * 3e-2);
+        }
+    }
+
+    #[test]
+    fn fourier_series_matches_sinusoidal_drive() {
+        // The expansion space leads by 90 degrees, so its cosine becomes a
+        // negative sine
+        let comp = Fourier {
+            mean: 9e-5,
+            cos: vec![5e-5],
+            sin: vec![],
+        };
+        let exp = Fourier {
+            mean: 6e-5,
+            cos: vec![0.0],
+            sin: vec![-4e-5],
+        };
+        let profile = profiles(Profile::Fourier(comp), Profile::Fourier(exp));
+        assert_sinusoidal(&profile, 1e-12);
+    }
+
+    #[test]
+    fn spline_interpolates_tabulated_volumes() {
+        // Tabulate the sinusoidal drive every 10 degrees, closing the table
+        // at 360 degrees
+        let volumes = sinusoidal().volumes(&state(10e6));
+        let angles: Vec<_> = (0..=36).map(|i| f64::from(i) * 10.).collect();
+        let (comp, exp): (Vec<_>, Vec<_>) = angles
+            .iter()
+            .map(|angle| {
+                let (comp, exp) = volumes(angle / 360. / 50.);
+                (comp.value, exp.value)
+            })
+            .unzip();
+        let profile = profiles(
+            Profile::Spline(Spline::new(&angles, &comp).expect("table is valid")),
+            Profile::Spline(Spline::new(&angles, &exp).expect("table is valid")),
+        );
+        assert_sinusoidal(&profile, 1e-3);
+
+        // The spline passes through each tabulated volume, including across
+        // the start of the revolution
+        let Profile::Spline(spline) = &profile.comp else {
+            unreachable!()
+        };
+        for (angle, volume) in angles.iter().zip(&comp) {
+            assert_relative_eq!(
+                spline.volume(angle.to_radians()).0,
+                volume,
+                max_relative = 1e-12
+            );
+        }
+        assert_relative_eq!(
+            spline.volume(-0.01).0,
+            spline.volume(2. * PI - 0.01).0,
+            max_relative = 1e-12
+        );
+    }
+
+    #[test]
+    fn config_builds_profiles() {
+        let config: Config = serde_json::from_str(
+            r#"{
+                "frequency": 50.0,
+                "comp": {"table": {"crank_angle": [0, 120, 240], "volume": [1e-4, 2e-4, 3e-4]}},
+                "R_c": 1e3,
+                "W_parasitic_c": 0.0,
+                "exp": {"fourier": {"mean": 6e-5, "cos": [], "sin": [-4e-5]}},
+                "R_e": 1e3,
+                "W_parasitic_e": 0.0,
+                "Q_parasitic_e": 10.0
+            }"#,
+        )
+        .expect("config is valid");
+        let profile = VolumeProfile::try_from(config.clone()).expect("config is valid");
+        let (comp, exp) = profile.volumes(&state(10e6))(0.0);
+        assert_relative_eq!(comp.value, 1e-4, max_relative = 1e-12);
+        assert_relative_eq!(exp.value, 6e-5);
+        assert_relative_eq!(exp.deriv, -4e-5 * 100. * PI, max_relative = 1e-12);
+        assert_eq!(profile.parasitics.exp.thermal, 10.0);
+        assert!(profile.wall_heat.is_none());
+
+        // Wall heat needs the bores, and each space sweeps its profile's swing
+        let wall_heat = wall_heat::Config {
+            correlation: wall_heat::Correlation::KornhauserSmith,
+            k_gas: 0.2,
+            mu_gas: 1e-5,
+            bore_c: Some(0.05),
+            bore_e: None,
+        };
+        let config = Config {
+            wall_heat: Some(wall_heat.clone()),
+            ..config
+        };
+        assert!(VolumeProfile::try_from(config.clone()).is_err());
+        let config = Config {
+            wall_heat: Some(wall_heat::Config {
+                bore_e: Some(0.04),
+                ..wall_heat
+            }),
+            ..config
+        };
+        let profile = VolumeProfile::try_from(config).expect("config is valid");
+        let wall_heat = profile.wall_heat.expect("profile has wall heat");
+        assert_relative_eq!(
+            wall_heat.exp.stroke,
+            8e-5 / (PI * 0.04_f64.powi(2) / 4.),
+            max_relative = 1e-6
+        );
+        assert!(wall_heat.comp.stroke > 2e-4 / (PI * 0.05_f64.powi(2) / 4.));
+    }
+
+    #[test]
+    fn rejects_invalid_tables() {
+        let table = |crank_angle: &[f64], volume: &[f64]| ProfileConfig::Table {
+            crank_angle: crank_angle.to_vec(),
+            volume: volume.to_vec(),
+        };
+        for invalid in [
+            table(&[0., 180., 90.], &[1e-4, 2e-4, 3e-4]),
+            table(&[0., 120., 240.], &[1e-4, 2e-4]),
+            table(&[0., 180.], &[1e-4, 2e-4]),
+            table(&[0., 180., 360.], &[1e-4, 2e-4, 1e-4]),
+            table(&[0., 120., 400.], &[1e-4, 2e-4, 3e-4]),
+        ] {
+            assert!(Profile::try_from(invalid).is_err());
+        }
+        assert!(Profile::try_from(table(&[0., 120., 240.], &[1e-4, 2e-4, 3e-4])).is_ok());
+    }
+}
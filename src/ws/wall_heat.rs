@@ -0,0 +1,246 @@
+use std::f64::consts::PI;
+
+use anyhow::{ensure, Context};
+use serde::Deserialize;
+
+use super::{SpaceConditions, WallConductance};
+
+/// A correlation for heat transfer between the gas and the cylinder walls
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Correlation {
+    /// Annand-style forced convection, `Nu = a Re^b`, where the Nusselt and
+    /// Reynolds numbers are based on the bore and the Reynolds number on the
+    /// mean piston speed
+    Annand { a: f64, b: f64 },
+
+    /// Woschni-style forced convection, `Nu = 0.035 Re^0.8`, where the
+    /// Nusselt and Reynolds numbers are based on the bore and the Reynolds
+    /// number on a gas velocity of `c1` times the mean piston speed
+    ///
+    /// There is no combustion term, so Woschni's `c1` of 2.28 for
+    /// compression and expansion applies throughout the cycle.
+    Woschni { c1: f64 },
+
+    /// Kornhauser-Smith-style oscillating flow, `Nu = 0.56 Pe^0.69`, where
+    /// the Nusselt and Peclet numbers are based on the instantaneous
+    /// hydraulic diameter and the Peclet number on the angular frequency
+    KornhauserSmith,
+}
+
+/// Configuration for wall heat transfer in the working spaces
+///
+/// `k_gas` is the thermal conductivity (W/m-K) and `mu_gas` the viscosity
+/// (Pa-s) of the gas, which are taken as constant.  Drives with a geometry
+/// find the bores of their spaces from it, while the others need `bore_c`
+/// and `bore_e` (m).
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct Config {
+    pub correlation: Correlation,
+    pub k_gas: f64,
+    pub mu_gas: f64,
+    #[serde(default)]
+    pub bore_c: Option<f64>,
+    #[serde(default)]
+    pub bore_e: Option<f64>,
+}
+
+impl Config {
+    /// Returns the cylinders of the configured bores that sweep `swept_comp`
+    /// and `swept_exp` (m^3)
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if either bore is missing or not positive.
+    pub fn cylinders(
+        &self,
+        swept_comp: f64,
+        swept_exp: f64,
+    ) -> anyhow::Result<(Cylinder, Cylinder)> {
+        let bore = |bore: Option<f64>, space| {
+            let bore = bore.with_context(|| format!("wall heat needs the {space} space bore"))?;
+            ensure!(bore > 0., "{space} space bore must be positive, not {bore}");
+            Ok(bore)
+        };
+        Ok((
+            Cylinder::sweeping(bore(self.bore_c, "compression")?, swept_comp),
+            Cylinder::sweeping(bore(self.bore_e, "expansion")?, swept_exp),
+        ))
+    }
+}
+
+/// A cylindrical working space of `bore` (m) swept by a piston moving
+/// through `stroke` (m)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cylinder {
+    pub bore: f64,
+    pub stroke: f64,
+}
+
+impl Cylinder {
+    /// Returns a cylinder of `bore` (m) whose piston sweeps `swept_volume`
+    /// (m^3)
+    #[must_use]
+    pub fn sweeping(bore: f64, swept_volume: f64) -> Self {
+        Self {
+            bore,
+            stroke: swept_volume / (PI * bore.powi(2) / 4.),
+        }
+    }
+}
+
+/// Heat transfer between the gas and the walls of two cylindrical working
+/// spaces
+///
+/// Each space is a cylinder, which is closed by a head and a piston, so its
+/// wall area grows with its volume.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WallHeat {
+    pub correlation: Correlation,
+    pub conductivity: f64,
+    pub viscosity: f64,
+    pub omega: f64,
+    pub comp: Cylinder,
+    pub exp: Cylinder,
+}
+
+impl WallHeat {
+    /// Create wall heat transfer for the `comp` and `exp` cylinders in an
+    /// engine running at `frequency` (Hz)
+    #[must_use]
+    pub fn new(config: &Config, frequency: f64, comp: Cylinder, exp: Cylinder) -> Self {
+        Self {
+            correlation: config.correlation,
+            conductivity: config.k_gas,
+            viscosity: config.mu_gas,
+            omega: 2. * PI * frequency,
+            comp,
+            exp,
+        }
+    }
+
+    /// Returns the conductance (W/K) between the gas and the walls of
+    /// `cylinder`
+    #[must_use]
+    pub fn conductance(&self, cylinder: Cylinder, space: SpaceConditions) -> f64 {
+        let bore = cylinder.bore;
+        let area = PI * bore.powi(2) / 4.;
+        let wall_area = 2. * area + PI * bore * space.vol / area;
+        let mean_piston_speed = cylinder.stroke * self.omega / PI;
+        let reynolds = |speed: f64| space.dens * speed * bore / self.viscosity;
+        let (nusselt, length) = match self.correlation {
+            Correlation::Annand { a, b } => (a * reynolds(mean_piston_speed).powf(b), bore),
+            Correlation::Woschni { c1 } => {
+                (0.035 * reynolds(c1 * mean_piston_speed).powf(0.8), bore)
+            }
+            Correlation::KornhauserSmith => {
+                let hydraulic_diameter = 4. * space.vol / wall_area;
+                let diffusivity = self.conductivity / (space.dens * space.cp);
+                let peclet = self.omega * hydraulic_diameter.powi(2) / (4. * diffusivity);
+                (0.56 * peclet.powf(0.69), hydraulic_diameter)
+            }
+        };
+        nusselt * self.conductivity / length * wall_area
+    }
+}
+
+impl WallConductance for WallHeat {
+    fn conductances(&self, comp: SpaceConditions, exp: SpaceConditions) -> (f64, f64) {
+        (
+            self.conductance(self.comp, comp),
+            self.conductance(self.exp, exp),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+
+    fn wall_heat(correlation: Correlation) -> WallHeat {
+        let config = Config {
+            correlation,
+            k_gas: 0.2,
+            mu_gas: 1e-5,
+            bore_c: Some(0.1),
+            bore_e: Some(0.1),
+        };
+        let (comp, exp) = config
+            .cylinders(PI * 0.01 / 4. * 0.02, PI * 0.01 / 4. * 0.02)
+            .expect("bores are configured");
+        WallHeat::new(&config, 50., comp, exp)
+    }
+
+    fn space(vol_deriv: f64) -> SpaceConditions {
+        SpaceConditions {
+            vol: PI * 0.01 / 4. * 0.05,
+            dV_dt: vol_deriv,
+            dens: 4.,
+            cp: 14_300.,
+        }
+    }
+
+    #[test]
+    fn annand_follows_mean_piston_speed() {
+        let wall_heat = wall_heat(Correlation::Annand { a: 0.5, b: 0.7 });
+        assert_relative_eq!(wall_heat.comp.stroke, 0.02, max_relative = 1e-12);
+
+        // A 2 cm stroke at 50 Hz is a mean piston speed of 2 m/s, which gives
+        // Re = 4 * 2 * 0.1 / 1e-5 = 80,000 over a wall area of two faces and
+        // 5 cm of cylinder, however fast the piston is moving
+        let wall_area = 2. * PI * 0.01 / 4. + PI * 0.1 * 0.05;
+        let expected = 0.5 * 80_000f64.powf(0.7) * 0.2 / 0.1 * wall_area;
+        for vol_deriv in [0., -0.01, 0.03] {
+            assert_relative_eq!(
+                wall_heat.conductance(wall_heat.comp, space(vol_deriv)),
+                expected,
+                max_relative = 1e-12
+            );
+        }
+    }
+
+    #[test]
+    fn woschni_scales_the_mean_piston_speed() {
+        let wall_heat = wall_heat(Correlation::Woschni { c1: 2.28 });
+        let wall_area = 2. * PI * 0.01 / 4. + PI * 0.1 * 0.05;
+        let reynolds: f64 = 4. * 2.28 * 2. * 0.1 / 1e-5;
+        assert_relative_eq!(
+            wall_heat.conductance(wall_heat.exp, space(0.)),
+            0.035 * reynolds.powf(0.8) * 0.2 / 0.1 * wall_area,
+            max_relative = 1e-12
+        );
+    }
+
+    #[test]
+    fn kornhauser_smith_follows_hydraulic_diameter() {
+        let wall_heat = wall_heat(Correlation::KornhauserSmith);
+        let space = space(0.);
+        let wall_area = 2. * PI * 0.01 / 4. + PI * 0.1 * 0.05;
+        let hydraulic_diameter = 4. * space.vol / wall_area;
+        let peclet = 100. * PI * hydraulic_diameter.powi(2) * 4. * 14_300. / (4. * 0.2);
+        assert_relative_eq!(
+            wall_heat.conductance(wall_heat.comp, space),
+            0.56 * peclet.powf(0.69) * 0.2 / hydraulic_diameter * wall_area,
+            max_relative = 1e-12
+        );
+    }
+
+    #[test]
+    fn needs_positive_bores() {
+        let config = Config {
+            correlation: Correlation::KornhauserSmith,
+            k_gas: 0.2,
+            mu_gas: 1e-5,
+            bore_c: Some(0.1),
+            bore_e: None,
+        };
+        assert!(config.cylinders(1e-4, 1e-4).is_err());
+        let config = Config {
+            bore_e: Some(0.),
+            ..config
+        };
+        assert!(config.cylinders(1e-4, 1e-4).is_err());
+    }
+}